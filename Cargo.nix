@@ -414,6 +414,12 @@ rec {
             usesDefaultFeatures = false;
             features = [ "derive" ];
           }
+          {
+            name = "serde_json";
+            packageId = "serde_json";
+            usesDefaultFeatures = false;
+            features = [ "std" ];
+          }
           {
             name = "tokio";
             packageId = "tokio";
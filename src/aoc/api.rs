@@ -1,21 +1,27 @@
 use std::sync::LazyLock;
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context as _};
 use regex::Regex;
-use reqwest::Client;
+use reqwest::{Client, Url};
 
 use super::models::{AocWhoami, PrivateLeaderboard};
 
 static INVITE_CODE_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"<code>((\d+)-[\da-z]+)</code>").unwrap());
+static PUZZLE_TITLE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<h2>--- Day \d+: (.+?) ---</h2>").unwrap());
 
 pub struct AocApiClient {
     http: Client,
+    /// The url of the AoC server, whose path is prefixed to the paths of the endpoints and whose
+    /// query is kept.
+    base_url: Url,
 }
 
 impl AocApiClient {
-    pub fn new(session: &str) -> anyhow::Result<Self> {
+    pub fn new(session: &str, base_url: &str) -> anyhow::Result<Self> {
         Ok(Self {
+            base_url: Url::parse(base_url)?,
             http: Client::builder()
                 .default_headers(
                     [(
@@ -29,10 +35,19 @@ impl AocApiClient {
         })
     }
 
+    fn endpoint(&self, path: &str) -> Url {
+        let mut url = self.base_url.clone();
+        url.set_path(&format!(
+            "{}{path}",
+            self.base_url.path().trim_end_matches('/')
+        ));
+        url
+    }
+
     pub async fn whoami(&self) -> anyhow::Result<AocWhoami> {
         let response = self
             .http
-            .get("https://adventofcode.com/leaderboard/private")
+            .get(self.endpoint("/leaderboard/private"))
             .send()
             .await?
             .error_for_status()?
@@ -51,26 +66,113 @@ impl AocApiClient {
         })
     }
 
+    pub async fn get_puzzle_title(&self, year: i32, day: u32) -> anyhow::Result<String> {
+        let response = self
+            .http
+            .get(self.endpoint(&format!("/{year}/day/{day}")))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        parse_puzzle_title(&response)
+            .ok_or_else(|| anyhow!("Failed to find puzzle title in response"))
+    }
+
     pub async fn get_private_leaderboard(
         &self,
         year: i32,
         user_id: u64,
-    ) -> reqwest::Result<PrivateLeaderboard> {
-        self.http
-            .get(format!(
-                "https://adventofcode.com/{year}/leaderboard/private/view/{user_id}.json"
-            ))
+    ) -> anyhow::Result<(PrivateLeaderboard, String)> {
+        let body = self
+            .http
+            .get(self.endpoint(&format!("/{year}/leaderboard/private/view/{user_id}.json")))
             .send()
             .await?
             .error_for_status()?
-            .json()
-            .await
+            .text()
+            .await?;
+        let leaderboard = serde_json::from_str(&body).context("error decoding response body")?;
+        Ok((leaderboard, body))
     }
 }
 
+fn parse_puzzle_title(html: &str) -> Option<String> {
+    let title = PUZZLE_TITLE_REGEX.captures(html)?.get(1)?.as_str();
+    Some(
+        title
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::aoc::mock::{block_on, MockAoc, LEADERBOARD_2024};
+
+    #[test]
+    fn base_url() {
+        let (whoami, leaderboard, title) = block_on(async {
+            let aoc = MockAoc::start().await;
+            aoc.leaderboard(2024, 200, LEADERBOARD_2024).await;
+            aoc.route(
+                "/2024/day/7",
+                200,
+                "<article class=\"day-desc\"><h2>--- Day 7: Bridge Repair ---</h2>",
+            )
+            .await;
+
+            // a trailing slash is not doubled in the paths
+            let client = AocApiClient::new("session", &format!("{}/", aoc.url())).unwrap();
+            assert_eq!(
+                client.endpoint("/2024/day/7").as_str(),
+                format!("{}/2024/day/7", aoc.url())
+            );
+            (
+                client.whoami().await.unwrap(),
+                client.get_private_leaderboard(2024, 1).await.unwrap().0,
+                client.get_puzzle_title(2024, 7).await.unwrap(),
+            )
+        });
+        assert_eq!(whoami.user_id, 1);
+        assert_eq!(leaderboard.members.len(), 3);
+        assert_eq!(title, "Bridge Repair");
+    }
+
+    #[test]
+    fn endpoint() {
+        // mirrors may be served below a path and require query parameters
+        let client =
+            AocApiClient::new("session", "https://mirror.example.com/aoc?token=t0k3n").unwrap();
+        assert_eq!(
+            client
+                .endpoint("/2024/leaderboard/private/view/1.json")
+                .as_str(),
+            "https://mirror.example.com/aoc/2024/leaderboard/private/view/1.json?token=t0k3n"
+        );
+    }
+
+    #[test]
+    fn puzzle_title() {
+        assert_eq!(
+            parse_puzzle_title(
+                "<article class=\"day-desc\"><h2>--- Day 7: Camel Cards ---</h2><p>Your all-expenses-paid \
+                 trip"
+            )
+            .as_deref(),
+            Some("Camel Cards")
+        );
+        assert_eq!(
+            parse_puzzle_title("<h2>--- Day 13: Care Package &amp; Arcade ---</h2>").as_deref(),
+            Some("Care Package & Arcade")
+        );
+        assert_eq!(parse_puzzle_title("<h2>Advent of Code</h2>"), None);
+    }
 
     #[test]
     fn invite_code_regex() {
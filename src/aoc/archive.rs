@@ -0,0 +1,193 @@
+//! An archive of the fetched private leaderboards, so that the standings at earlier times can be
+//! looked up later. Snapshots are only archived if they differ from the previous fetch.
+//!
+//! The snapshots are kept in their own SQLite database next to the matrix store, in a table keyed
+//! by leaderboard, year and time of the fetch, so that lookups and evictions do not have to load
+//! any other snapshots.
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use tracing::trace;
+
+use super::models::{AocId, PrivateLeaderboard};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    leaderboard INTEGER NOT NULL,
+    year INTEGER NOT NULL,
+    -- milliseconds since the unix epoch
+    fetched_at INTEGER NOT NULL,
+    -- the leaderboard encoded using MessagePack
+    data BLOB NOT NULL,
+    PRIMARY KEY (leaderboard, year, fetched_at)
+) WITHOUT ROWID;
+";
+
+pub struct Archive {
+    connection: Arc<Mutex<Connection>>,
+    /// Snapshots kept per leaderboard and year, the oldest ones are removed first. `0` disables
+    /// the archive.
+    capacity: usize,
+}
+
+impl Archive {
+    pub fn open(path: &Path, capacity: usize) -> anyhow::Result<Self> {
+        Self::with_connection(Connection::open(path)?, capacity)
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    pub fn open_in_memory(capacity: usize) -> anyhow::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?, capacity)
+    }
+
+    fn with_connection(connection: Connection, capacity: usize) -> anyhow::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            capacity,
+        })
+    }
+
+    /// Run a query on a blocking thread, as SQLite blocks while accessing the disk.
+    async fn run<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&mut Connection) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let connection = Arc::clone(&self.connection);
+        tokio::task::spawn_blocking(move || query(&mut connection.lock().unwrap())).await?
+    }
+
+    pub async fn record(
+        &self,
+        leaderboard: AocId,
+        year: i32,
+        ts: DateTime<Utc>,
+        snapshot: &PrivateLeaderboard,
+    ) -> anyhow::Result<()> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        self.insert(leaderboard, year, ts, snapshot).await?;
+        trace!(leaderboard, year, "archived leaderboard");
+        Ok(())
+    }
+
+    async fn insert(
+        &self,
+        leaderboard: AocId,
+        year: i32,
+        ts: DateTime<Utc>,
+        snapshot: &PrivateLeaderboard,
+    ) -> anyhow::Result<()> {
+        let data = rmp_serde::to_vec_named(snapshot)?;
+        let capacity = self.capacity as i64;
+        self.run(move |connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute(
+                "INSERT OR REPLACE INTO snapshots (leaderboard, year, fetched_at, data)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![leaderboard, year, ts.timestamp_millis(), data],
+            )?;
+            transaction.execute(
+                "DELETE FROM snapshots WHERE leaderboard = ?1 AND year = ?2 AND fetched_at <= (
+                     SELECT fetched_at FROM snapshots WHERE leaderboard = ?1 AND year = ?2
+                     ORDER BY fetched_at DESC LIMIT 1 OFFSET ?3
+                 )",
+                params![leaderboard, year, capacity],
+            )?;
+            transaction.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// The latest archived leaderboard of the given year fetched at or before the given time,
+    /// with the time of its fetch.
+    pub async fn snapshot_at(
+        &self,
+        leaderboard: AocId,
+        year: i32,
+        ts: DateTime<Utc>,
+    ) -> anyhow::Result<Option<(PrivateLeaderboard, DateTime<Utc>)>> {
+        let row = self
+            .run(move |connection| {
+                let row = connection
+                    .query_row(
+                        "SELECT data, fetched_at FROM snapshots
+                         WHERE leaderboard = ?1 AND year = ?2 AND fetched_at <= ?3
+                         ORDER BY fetched_at DESC LIMIT 1",
+                        params![leaderboard, year, ts.timestamp_millis()],
+                        |row| Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, i64>(1)?)),
+                    )
+                    .optional()?;
+                Ok(row)
+            })
+            .await?;
+        let Some((data, fetched_at)) = row else {
+            return Ok(None);
+        };
+        let snapshot = rmp_serde::from_slice(&data)?;
+        let fetched_at = DateTime::from_timestamp_millis(fetched_at)
+            .ok_or_else(|| anyhow::anyhow!("Invalid archive time {fetched_at}"))?;
+        Ok(Some((snapshot, fetched_at)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::aoc::{
+        fixtures::{leaderboard, member},
+        mock::block_on,
+    };
+
+    #[test]
+    fn archive() {
+        block_on(async {
+            let dir = std::env::temp_dir().join(format!("aocbot-archive-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("archive.sqlite3");
+            let _ = std::fs::remove_file(&path);
+
+            let archive = Archive::open(&path, 2).unwrap();
+            let at = |minutes| DateTime::UNIX_EPOCH + TimeDelta::minutes(minutes);
+            async fn snapshot(archive: &Archive, minutes: i64) -> Option<(usize, DateTime<Utc>)> {
+                let at = DateTime::UNIX_EPOCH + TimeDelta::minutes(minutes);
+                archive
+                    .snapshot_at(1, 2024, at)
+                    .await
+                    .unwrap()
+                    .map(|(lb, ts)| (lb.members.len(), ts))
+            }
+
+            for (i, minutes) in [0, 10, 20].into_iter().enumerate() {
+                let members = (1..=i as u64 + 1).map(|id| member(id, "Alice", &[]));
+                archive
+                    .record(1, 2024, at(minutes), &leaderboard(members))
+                    .await
+                    .unwrap();
+            }
+            // the oldest snapshot was evicted
+            assert_eq!(snapshot(&archive, 5).await, None);
+            assert_eq!(snapshot(&archive, 10).await, Some((2, at(10))));
+            assert_eq!(snapshot(&archive, 19).await, Some((2, at(10))));
+            assert_eq!(snapshot(&archive, 60).await, Some((3, at(20))));
+            assert_eq!(archive.snapshot_at(1, 2023, at(60)).await.unwrap(), None);
+            // other leaderboards are archived separately
+            assert_eq!(archive.snapshot_at(2, 2024, at(60)).await.unwrap(), None);
+
+            // the snapshots survive a restart
+            drop(archive);
+            let archive = Archive::open(&path, 2).unwrap();
+            assert_eq!(snapshot(&archive, 15).await, Some((2, at(10))));
+            std::fs::remove_dir_all(&dir).unwrap();
+        });
+    }
+}
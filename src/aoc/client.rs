@@ -1,57 +1,161 @@
 use std::{
     collections::{BTreeMap, HashMap},
     ops::Bound,
+    str::FromStr,
+    sync::Arc,
     time::Duration,
 };
 
-use chrono::{DateTime, Utc};
-use tokio::sync::RwLock;
-use tracing::trace;
+use anyhow::anyhow;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures_util::future::join_all;
+use serde::{Deserialize, Deserializer};
+use tokio::sync::{Mutex, RwLock, Semaphore};
+use tracing::{trace, warn};
 
 use super::{
-    api::AocApiClient,
-    models::{AocWhoami, PrivateLeaderboard},
+    archive::Archive,
+    models::{AocId, AocWhoami, PrivateLeaderboard, Score, StarDiscrepancy},
+    sessions::Sessions,
 };
 use crate::{
     aoc::day::AocDay,
-    utils::{datetime::now, store::Store},
+    utils::{
+        datetime::now,
+        lru::{CacheStats, Lru},
+        single_flight::SingleFlight,
+        store::Store,
+    },
 };
 
+pub const FIRST_YEAR: i32 = 2015;
+
+const CONCURRENT_YEAR_FETCHES: usize = 3;
+
+/// How long a failed fetch of a puzzle title is remembered, so that commands shown meanwhile do
+/// not wait for the puzzle page again.
+const PUZZLE_TITLE_RETRY: TimeDelta = TimeDelta::minutes(5);
+
 const LEADERBOARD_CACHE_STORE_KEY: &[u8] = b"aoc_leaderboard";
+const PUZZLE_TITLES_STORE_KEY: &[u8] = b"aoc_puzzle_titles";
+const RAW_LEADERBOARDS_STORE_KEY: &[u8] = b"aoc_leaderboard_raw";
 
 pub type LeaderboardCache = HashMap<i32, (PrivateLeaderboard, DateTime<Utc>)>;
 
+pub type RawLeaderboards = HashMap<i32, String>;
+
+pub type OtherLeaderboardCache = Lru<(AocId, i32), (PrivateLeaderboard, DateTime<Utc>)>;
+
+/// Puzzle titles by year and day, as persisted in the store. Titles never change, so they are
+/// only evicted to bound the cache.
+pub type PuzzleTitles = HashMap<i32, HashMap<u32, String>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheScope {
+    pub board: Option<AocId>,
+    pub year: Option<i32>,
+}
+
+impl CacheScope {
+    /// Whether the scope includes an entry of the given board and year. Entries which do not
+    /// belong to a board (or year) are only included if the scope is not restricted to one.
+    pub fn includes(self, board: Option<AocId>, year: Option<i32>) -> bool {
+        (self.board.is_none() || self.board == board) && (self.year.is_none() || self.year == year)
+    }
+}
+
 pub struct AocClient {
-    api: AocApiClient,
+    sessions: Sessions,
     whoami: AocWhoami,
     default_cache_ttl: Duration,
     cache_ttl_rules: BTreeMap<i64, Duration>,
+    fresh_interval: Duration,
+    opt_out_marker: Option<String>,
+    /// Queues of forced leaderboard fetches by year.
+    refresh_locks: std::sync::Mutex<HashMap<i32, Arc<Mutex<()>>>>,
     leaderboard_cache: RwLock<LeaderboardCache>,
+    /// Updated together with `leaderboard_cache`, whose lock guards both.
+    raw_leaderboards: RwLock<RawLeaderboards>,
+    /// In-flight leaderboard fetches by year. Daily leaderboards are derived from the yearly one,
+    /// so concurrent requests for any day of a year share a single fetch.
+    leaderboard_fetches: SingleFlight<i32, (PrivateLeaderboard, DateTime<Utc>)>,
+    other_leaderboards: OtherLeaderboardCache,
+    other_leaderboard_fetches: SingleFlight<(AocId, i32), (PrivateLeaderboard, DateTime<Utc>)>,
+    puzzle_titles: Lru<(i32, u32), String>,
+    puzzle_title_fetches: SingleFlight<(i32, u32), String>,
+    puzzle_title_failures: Lru<(i32, u32), DateTime<Utc>>,
+    star_discrepancies: RwLock<HashMap<i32, Vec<StarDiscrepancy>>>,
+    archive: Archive,
     store: Store,
 }
 
 impl AocClient {
+    /// Create a client using the given sessions. The first session owns the private leaderboard,
+    /// the others are only used as fallbacks and must belong to members of that leaderboard.
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
-        session: &str,
+        sessions: Sessions,
         default_cache_ttl: Duration,
         cache_ttl_rules: BTreeMap<i64, Duration>,
+        fresh_interval: Duration,
+        other_leaderboards_capacity: usize,
+        puzzle_titles_capacity: usize,
+        opt_out_marker: Option<String>,
+        archive: Archive,
         store: Store,
     ) -> anyhow::Result<Self> {
-        let api = AocApiClient::new(session)?;
-
-        let whoami = api.whoami().await?;
+        let whoami = sessions.primary().whoami().await?;
 
-        let leaderboard_cache = store
+        let mut leaderboard_cache = store
             .get::<LeaderboardCache>(LEADERBOARD_CACHE_STORE_KEY)
             .await?
             .unwrap_or_default();
+        // caches stored by older versions were not checked
+        let star_discrepancies = leaderboard_cache
+            .iter_mut()
+            .map(|(&year, (leaderboard, _))| {
+                apply_opt_out(opt_out_marker.as_deref(), leaderboard);
+                (year, check_star_counts(year, leaderboard))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let raw_leaderboards = store
+            .get::<RawLeaderboards>(RAW_LEADERBOARDS_STORE_KEY)
+            .await?
+            .unwrap_or_default();
+
+        let puzzle_titles = Lru::new("puzzle_titles", puzzle_titles_capacity);
+        let mut stored = store
+            .get::<PuzzleTitles>(PUZZLE_TITLES_STORE_KEY)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|(year, titles)| titles.into_iter().map(move |(day, t)| ((year, day), t)))
+            .collect::<Vec<_>>();
+        // the most recent puzzles are kept if not all titles fit
+        stored.sort_unstable();
+        for (key, title) in stored {
+            puzzle_titles.insert(key, title);
+        }
 
         Ok(Self {
-            api,
+            sessions,
             whoami,
             default_cache_ttl,
             cache_ttl_rules,
+            fresh_interval,
+            opt_out_marker,
+            refresh_locks: Default::default(),
             leaderboard_cache: leaderboard_cache.into(),
+            raw_leaderboards: raw_leaderboards.into(),
+            leaderboard_fetches: SingleFlight::new(),
+            other_leaderboards: Lru::new("other_leaderboards", other_leaderboards_capacity),
+            other_leaderboard_fetches: SingleFlight::new(),
+            puzzle_titles,
+            puzzle_title_fetches: SingleFlight::new(),
+            puzzle_title_failures: Lru::new("puzzle_title_failures", puzzle_titles_capacity),
+            star_discrepancies: star_discrepancies.into(),
+            archive,
             store,
         })
     }
@@ -60,13 +164,115 @@ impl AocClient {
         &self.whoami
     }
 
-    pub async fn clear_leaderboard_cache(&self) -> anyhow::Result<()> {
+    pub fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Remove the cached entries in the given scope, including the persisted ones, and return
+    /// the number of removed entries per cache.
+    pub async fn clear_caches(
+        &self,
+        scope: CacheScope,
+    ) -> anyhow::Result<Vec<(&'static str, usize)>> {
+        let primary = Some(self.whoami.user_id);
+
         let mut guard = self.leaderboard_cache.write().await;
-        guard.clear();
+        let len = guard.len();
+        guard.retain(|&year, _| !scope.includes(primary, Some(year)));
+        let leaderboards = len - guard.len();
+        let mut raw = self.raw_leaderboards.write().await;
+        let len = raw.len();
+        raw.retain(|year, _| guard.contains_key(year));
+        let raw_leaderboards = len - raw.len();
         self.store
             .set::<LeaderboardCache>(LEADERBOARD_CACHE_STORE_KEY, &guard)
             .await?;
-        Ok(())
+        self.store
+            .set::<RawLeaderboards>(RAW_LEADERBOARDS_STORE_KEY, &raw)
+            .await?;
+        drop((guard, raw));
+
+        let other_leaderboards = self
+            .other_leaderboards
+            .retain(|&(board, year), _| !scope.includes(Some(board), Some(year)));
+        let puzzle_titles = self
+            .puzzle_titles
+            .retain(|&(year, _), _| !scope.includes(None, Some(year)));
+        if puzzle_titles > 0 {
+            self.store_puzzle_titles().await?;
+        }
+        let puzzle_title_failures = self
+            .puzzle_title_failures
+            .retain(|&(year, _), _| !scope.includes(None, Some(year)));
+
+        Ok(vec![
+            ("leaderboards", leaderboards),
+            ("raw_leaderboards", raw_leaderboards),
+            (self.other_leaderboards.name(), other_leaderboards),
+            (self.puzzle_titles.name(), puzzle_titles),
+            (self.puzzle_title_failures.name(), puzzle_title_failures),
+        ])
+    }
+
+    async fn store_puzzle_titles(&self) -> anyhow::Result<()> {
+        let mut titles = PuzzleTitles::new();
+        for ((year, day), title) in self.puzzle_titles.entries() {
+            titles.entry(year).or_default().insert(day, title);
+        }
+        self.store
+            .set::<PuzzleTitles>(PUZZLE_TITLES_STORE_KEY, &titles)
+            .await
+    }
+
+    pub fn cache_stats(&self) -> Vec<CacheStats> {
+        vec![
+            self.other_leaderboards.stats(),
+            self.puzzle_titles.stats(),
+            self.puzzle_title_failures.stats(),
+        ]
+    }
+
+    /// The title of the given puzzle, or `None` if it is not unlocked yet or could not be fetched.
+    /// Failed fetches are not retried for [`PUZZLE_TITLE_RETRY`].
+    pub async fn get_puzzle_title(&self, year: i32, day: u32) -> Option<String> {
+        let cached = || self.puzzle_titles.get(&(year, day));
+        if let Some(title) = cached() {
+            return Some(title);
+        }
+        let now = now();
+        if now < (AocDay { year, day }).unlock_datetime() {
+            return None;
+        }
+        let failed = self.puzzle_title_failures.get(&(year, day));
+        if failed.is_some_and(|ts| now < ts + PUZZLE_TITLE_RETRY) {
+            return None;
+        }
+
+        let result = self
+            .puzzle_title_fetches
+            .run((year, day), || async {
+                // a flight may have finished since the cache was checked
+                if let Some(title) = cached() {
+                    return Ok(title);
+                }
+
+                trace!(year, day, "fetching puzzle title");
+                let title = self.sessions.primary().get_puzzle_title(year, day).await?;
+                self.puzzle_titles.insert((year, day), title.clone());
+                if let Err(err) = self.store_puzzle_titles().await {
+                    warn!("Failed to store puzzle titles: {err}");
+                }
+                anyhow::Ok(title)
+            })
+            .await;
+        match result {
+            Ok(title) => Some(title),
+            Err(err) => {
+                warn!(year, day, "Failed to fetch puzzle title: {err}");
+                self.puzzle_title_failures.insert((year, day), now);
+                None
+            }
+        }
     }
 
     pub async fn get_private_leaderboard_cached(
@@ -93,39 +299,232 @@ impl AocClient {
             None => self.default_cache_ttl,
         };
 
-        let guard = self.leaderboard_cache.read().await;
-        if let Some(cached) = guard.get(&year).filter(|(_, ts)| now < *ts + ttl) {
+        let cached = || async {
+            let guard = self.leaderboard_cache.read().await;
+            let cached = guard.get(&year).filter(|(_, ts)| now < *ts + ttl)?;
             trace!(
                 year,
                 ttl_secs = (cached.1 + ttl - now).num_seconds(),
                 "leaderboard cached"
             );
-            return Ok(cached.clone());
+            Some(cached.clone())
+        };
+        if let Some(cached) = cached().await {
+            return Ok(cached);
         }
-        drop(guard);
 
+        self.leaderboard_fetches
+            .run(year, || async {
+                // a flight may have finished since the cache was checked
+                if let Some(cached) = cached().await {
+                    return Ok(cached);
+                }
+                self.fetch_private_leaderboard(year).await
+            })
+            .await
+    }
+
+    /// Fetch the leaderboard of the given year and cache it. The cache is only locked once the
+    /// response arrived, so readers of other years are not blocked by the request.
+    async fn fetch_private_leaderboard(
+        &self,
+        year: i32,
+    ) -> anyhow::Result<(PrivateLeaderboard, DateTime<Utc>)> {
+        trace!(year, "fetching leaderboard");
+        let (mut leaderboard, raw) = self
+            .sessions
+            .get_private_leaderboard(year, self.whoami.user_id)
+            .await?;
+        self.record_star_discrepancies(year, &mut leaderboard).await;
+
+        // the completion time is used, so that refreshes queued during the fetch reuse it
+        let entry = (leaderboard, now());
         let mut guard = self.leaderboard_cache.write().await;
-        if let Some(cached) = guard.get(&year).filter(|(_, ts)| now < *ts + ttl) {
+        let changed = is_changed(guard.get(&year), &entry);
+        self.cache_leaderboard(&mut guard, year, entry.clone(), raw)
+            .await?;
+        drop(guard);
+        if changed {
+            self.archive(year, &entry).await;
+        }
+        Ok(entry)
+    }
+
+    /// The leaderboard of the given year of another private leaderboard, which the primary
+    /// session has to be a member of. It is cached for `default_cache_ttl`.
+    pub async fn get_other_private_leaderboard(
+        &self,
+        owner_id: AocId,
+        year: i32,
+    ) -> anyhow::Result<(PrivateLeaderboard, DateTime<Utc>)> {
+        if owner_id == self.whoami.user_id {
+            return self.get_private_leaderboard(year).await;
+        }
+        let now = now();
+        let key = (owner_id, year);
+        let cached = || {
+            self.other_leaderboards
+                .get(&key)
+                .filter(|(_, ts)| now < *ts + self.default_cache_ttl)
+        };
+        if let Some(cached) = cached() {
+            return Ok(cached);
+        }
+
+        self.other_leaderboard_fetches
+            .run(key, || async {
+                // a flight may have finished since the cache was checked
+                if let Some(cached) = cached() {
+                    return Ok(cached);
+                }
+
+                // backup sessions are not necessarily members of other leaderboards
+                trace!(owner_id, year, "fetching other leaderboard");
+                let (mut leaderboard, _) = self
+                    .sessions
+                    .primary()
+                    .get_private_leaderboard(year, owner_id)
+                    .await?;
+                apply_opt_out(self.opt_out_marker.as_deref(), &mut leaderboard);
+                check_star_counts(year, &mut leaderboard);
+                let entry = (leaderboard, now);
+                self.other_leaderboards.insert(key, entry.clone());
+                Ok(entry)
+            })
+            .await
+    }
+
+    pub async fn get_all_private_leaderboards(&self) -> Vec<(i32, Option<PrivateLeaderboard>)> {
+        let semaphore = Semaphore::new(CONCURRENT_YEAR_FETCHES);
+        let years = FIRST_YEAR..=AocDay::most_recent().year;
+        join_all(years.map(|year| {
+            let semaphore = &semaphore;
+            async move {
+                let _permit = semaphore.acquire().await;
+                match self.get_private_leaderboard(year).await {
+                    Ok((leaderboard, _)) => (year, Some(leaderboard)),
+                    Err(err) => {
+                        warn!(year, "Failed to fetch leaderboard: {err}");
+                        (year, None)
+                    }
+                }
+            }
+        }))
+        .await
+    }
+
+    pub async fn refresh_wait(&self, year: i32) -> Duration {
+        let last_fetch = self.leaderboard_cache.read().await.get(&year).map(|c| c.1);
+        refresh_wait(last_fetch, self.fresh_interval, now())
+    }
+
+    /// Fetch the leaderboard of the given year bypassing the cache, but waiting until at least
+    /// `fresh_interval` passed since the previous fetch. Concurrent refreshes of a year are queued
+    /// and share the result of the first one, which also joins a fetch already in flight.
+    pub async fn refresh_private_leaderboard(&self, year: i32) -> anyhow::Result<()> {
+        let requested = now();
+        let lock = Arc::clone(self.refresh_locks.lock().unwrap().entry(year).or_default());
+        let _refresh = lock.lock().await;
+
+        let last_fetch = self.leaderboard_cache.read().await.get(&year).map(|c| c.1);
+        // another refresh finished while this one was queued
+        if last_fetch.is_some_and(|ts| ts >= requested) {
+            return Ok(());
+        }
+        let wait = refresh_wait(last_fetch, self.fresh_interval, now());
+        if !wait.is_zero() {
             trace!(
                 year,
-                ttl_secs = (cached.1 + ttl - now).num_seconds(),
-                "leaderboard cached"
+                wait_secs = wait.as_secs(),
+                "waiting to refresh leaderboard"
             );
-            return Ok(cached.clone());
+            tokio::time::sleep(wait).await;
         }
 
-        trace!(year, "fetching leaderboard");
-        let leaderboard = self
-            .api
-            .get_private_leaderboard(year, self.whoami.user_id)
+        trace!(year, "refreshing leaderboard");
+        self.leaderboard_fetches
+            .run(year, || self.fetch_private_leaderboard(year))
             .await?;
+        Ok(())
+    }
 
-        let entry = (leaderboard, now);
-        guard.insert(year, entry.clone());
+    async fn cache_leaderboard(
+        &self,
+        guard: &mut LeaderboardCache,
+        year: i32,
+        entry: (PrivateLeaderboard, DateTime<Utc>),
+        raw: String,
+    ) -> anyhow::Result<()> {
+        guard.insert(year, entry);
         self.store
-            .set::<LeaderboardCache>(LEADERBOARD_CACHE_STORE_KEY, &guard)
+            .set::<LeaderboardCache>(LEADERBOARD_CACHE_STORE_KEY, guard)
             .await?;
-        Ok(entry)
+        let mut raw_leaderboards = self.raw_leaderboards.write().await;
+        raw_leaderboards.insert(year, raw);
+        self.store
+            .set::<RawLeaderboards>(RAW_LEADERBOARDS_STORE_KEY, &raw_leaderboards)
+            .await
+    }
+
+    /// The response body of the cached leaderboard of the given year, exactly as AoC sent it,
+    /// with the time of its fetch. The leaderboard is fetched if it is not cached, or refreshed if
+    /// the cache was written by a version not keeping response bodies.
+    pub async fn get_raw_private_leaderboard(
+        &self,
+        year: i32,
+    ) -> anyhow::Result<(String, DateTime<Utc>)> {
+        self.get_private_leaderboard(year).await?;
+        let cached = || async {
+            let guard = self.leaderboard_cache.read().await;
+            let raw = self.raw_leaderboards.read().await.get(&year).cloned()?;
+            Some((raw, guard.get(&year)?.1))
+        };
+        if let Some(cached) = cached().await {
+            return Ok(cached);
+        }
+        self.refresh_private_leaderboard(year).await?;
+        cached()
+            .await
+            .ok_or_else(|| anyhow!("The leaderboard of {year} was not cached"))
+    }
+
+    pub async fn archived_leaderboard(
+        &self,
+        year: i32,
+        ts: DateTime<Utc>,
+    ) -> anyhow::Result<Option<(PrivateLeaderboard, DateTime<Utc>)>> {
+        self.archive
+            .snapshot_at(self.whoami.user_id, year, ts)
+            .await
+    }
+
+    /// Archive a fetched leaderboard. This is done after the cache is unlocked, so that readers
+    /// do not wait for the archive. Failures are only logged, the fetch itself succeeded.
+    async fn archive(&self, year: i32, (leaderboard, ts): &(PrivateLeaderboard, DateTime<Utc>)) {
+        let id = self.whoami.user_id;
+        if let Err(err) = self.archive.record(id, year, *ts, leaderboard).await {
+            warn!(year, "Failed to archive leaderboard: {err}");
+        }
+    }
+
+    /// The members whose star count disagreed with their completed days in the latest fetch of
+    /// the given year. Their star count was replaced by the derived one.
+    pub async fn star_discrepancies(&self, year: i32) -> Vec<StarDiscrepancy> {
+        self.star_discrepancies
+            .read()
+            .await
+            .get(&year)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    async fn record_star_discrepancies(&self, year: i32, leaderboard: &mut PrivateLeaderboard) {
+        apply_opt_out(self.opt_out_marker.as_deref(), leaderboard);
+        let discrepancies = check_star_counts(year, leaderboard);
+        self.star_discrepancies
+            .write()
+            .await
+            .insert(year, discrepancies);
     }
 
     pub async fn get_daily_private_leaderboard(
@@ -133,74 +532,399 @@ impl AocClient {
         year: i32,
         day: u32,
         parts: Parts,
+        part2_start: Part2Start,
+        split_ties: bool,
     ) -> anyhow::Result<(PrivateLeaderboard, DateTime<Utc>)> {
         let (mut leaderboard, last_update) = self.get_private_leaderboard(year).await?;
+        score_day(&mut leaderboard, day, parts, part2_start, split_ties);
+        Ok((leaderboard, last_update))
+    }
+}
 
-        let mut members_by_p1 = leaderboard
+/// Replace the scores of the leaderboard with those of the given day. The first member to finish
+/// a part gets one point per member of the leaderboard, the second one point less and so on. If
+/// `split_ties` is set, members finishing a part at the same time split the points of their places
+/// evenly instead.
+///
+/// When only part 2 is scored and `part2_start` is [`Part2Start::Part1`], part 2 is ranked by the
+/// time between the completions of both parts instead of the completion of part 2.
+pub fn score_day(
+    leaderboard: &mut PrivateLeaderboard,
+    day: u32,
+    parts: Parts,
+    part2_start: Part2Start,
+    split_ties: bool,
+) {
+    let from_part1 = parts == Parts::P2 && part2_start == Part2Start::Part1;
+    // the completions by id with the time they are ranked by and the time of the star
+    let completions = |part2: bool| {
+        let mut completions = leaderboard
             .members
             .iter()
             .filter_map(|(&id, m)| {
-                m.completion_day_level
-                    .get(&day)
-                    .map(|c| (id, m, c.fst.get_star_ts))
+                let completion = m.completion_day_level.get(&day)?;
+                let p1 = completion.fst.get_star_ts;
+                let (key, ts) = match (part2, from_part1) {
+                    (false, _) => (p1.timestamp(), p1),
+                    (true, false) => {
+                        let p2 = completion.snd.as_ref()?.get_star_ts;
+                        (p2.timestamp(), p2)
+                    }
+                    (true, true) => {
+                        let p2 = completion.snd.as_ref()?.get_star_ts;
+                        ((p2 - p1).num_seconds(), p2)
+                    }
+                };
+                Some((id, m, key, ts))
             })
             .collect::<Vec<_>>();
-        members_by_p1.sort_unstable_by_key(|&(_, m, ts)| (ts, m));
-        let member_ids_and_completion_ts_by_p1 = members_by_p1
+        completions.sort_unstable_by_key(|&(_, m, key, _)| (key, m));
+        completions
             .into_iter()
-            .map(|(id, _, c)| (id, c))
-            .collect::<Vec<_>>();
+            .map(|(id, _, key, ts)| (id, key, ts))
+            .collect::<Vec<_>>()
+    };
+    let mut scored_parts = Vec::new();
+    if matches!(parts, Parts::P1 | Parts::Both) {
+        scored_parts.push(completions(false));
+    }
+    if matches!(parts, Parts::P2 | Parts::Both) {
+        scored_parts.push(completions(true));
+    }
 
-        let mut members_by_p2 = leaderboard
-            .members
-            .iter()
-            .filter_map(|(&id, m)| {
-                m.completion_day_level
-                    .get(&day)
-                    .and_then(|c| c.snd.as_ref())
-                    .map(|c| (id, m, c.get_star_ts))
-            })
-            .collect::<Vec<_>>();
-        members_by_p2.sort_unstable_by_key(|&(_, m, ts)| (ts, m));
-        let member_ids_and_completion_ts_by_p2 = members_by_p2
-            .into_iter()
-            .map(|(id, _, c)| (id, c))
-            .collect::<Vec<_>>();
+    for m in leaderboard.members.values_mut() {
+        m.global_score = 0;
+        m.local_score = 0;
+        m.split_score = split_ties.then_some(Score(0.0));
+        m.stars = 0;
+        m.last_star_ts = Default::default();
+    }
 
-        for m in leaderboard.members.values_mut() {
-            m.global_score = 0;
-            m.local_score = 0;
-            m.stars = 0;
-            m.last_star_ts = Default::default();
-        }
+    let total = leaderboard.members.len();
+    for completions in scored_parts {
+        for (i, &(id, key, ts)) in completions.iter().enumerate() {
+            let member = leaderboard.members.get_mut(&id).unwrap();
+            member.local_score += (total - i) as u32;
+            member.stars += 1;
+            member.last_star_ts = member.last_star_ts.max(ts);
 
-        if matches!(parts, Parts::P1 | Parts::Both) {
-            for (i, (id, ts)) in member_ids_and_completion_ts_by_p1.into_iter().enumerate() {
-                let score = leaderboard.members.len() - i;
-                let member = leaderboard.members.get_mut(&id).unwrap();
-                member.local_score += score as u32;
-                member.stars += 1;
-                member.last_star_ts = member.last_star_ts.max(ts);
+            if let Some(split_score) = &mut member.split_score {
+                let (points, tied) = completions
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &(_, other, _))| other == key)
+                    .fold((0, 0), |(points, tied), (j, _)| {
+                        (points + total - j, tied + 1)
+                    });
+                split_score.0 += points as f64 / tied as f64;
             }
         }
+    }
+}
 
-        if matches!(parts, Parts::P2 | Parts::Both) {
-            for (i, (id, ts)) in member_ids_and_completion_ts_by_p2.into_iter().enumerate() {
-                let score = leaderboard.members.len() - i;
-                let member = leaderboard.members.get_mut(&id).unwrap();
-                member.local_score += score as u32;
-                member.stars += 1;
-                member.last_star_ts = member.last_star_ts.max(ts);
-            }
-        }
+fn check_star_counts(year: i32, leaderboard: &mut PrivateLeaderboard) -> Vec<StarDiscrepancy> {
+    let discrepancies = leaderboard.fix_star_counts();
+    for d in &discrepancies {
+        warn!(
+            year,
+            id = d.id,
+            stars = d.stars,
+            completed = d.completed,
+            "Star count of member disagrees with their completed days"
+        );
+    }
+    discrepancies
+}
 
-        Ok((leaderboard, last_update))
+fn is_changed(
+    previous: Option<&(PrivateLeaderboard, DateTime<Utc>)>,
+    (leaderboard, _): &(PrivateLeaderboard, DateTime<Utc>),
+) -> bool {
+    previous.is_none_or(|(previous, _)| previous != leaderboard)
+}
+
+fn apply_opt_out(marker: Option<&str>, leaderboard: &mut PrivateLeaderboard) {
+    if let Some(marker) = marker {
+        leaderboard.apply_opt_out(marker);
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+fn refresh_wait(
+    last_fetch: Option<DateTime<Utc>>,
+    interval: Duration,
+    now: DateTime<Utc>,
+) -> Duration {
+    let Some(last_fetch) = last_fetch else {
+        return Duration::ZERO;
+    };
+    let elapsed = (now - last_fetch).to_std().unwrap_or_default();
+    interval.saturating_sub(elapsed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Parts {
     P1,
     P2,
     Both,
 }
+
+impl Parts {
+    pub const NAMES: &[&str] = &["1", "2", "both", "1,2"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::P1 => "1",
+            Self::P2 => "2",
+            Self::Both => "both",
+        }
+    }
+}
+
+impl FromStr for Parts {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "1" => Ok(Self::P1),
+            "2" => Ok(Self::P2),
+            "both" | "1,2" | "2,1" => Ok(Self::Both),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The start of the part 2 completion time, as communities rank part 2 differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Part2Start {
+    #[default]
+    Part1,
+    Unlock,
+}
+
+impl Part2Start {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Part1 => "part1",
+            Self::Unlock => "unlock",
+        }
+    }
+
+    pub fn completion_header(self, parts: Parts) -> &'static str {
+        match (parts, self) {
+            (Parts::P2, Self::Part1) => "Completion (from part 1)",
+            (Parts::P2, Self::Unlock) => "Completion (from unlock)",
+            (Parts::P1 | Parts::Both, _) => "Completion",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Parts {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(|()| {
+            serde::de::Error::custom(format!("invalid parts '{value}' (expected 1, 2 or both)"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::{
+        aoc::{
+            fixtures::{leaderboard, member},
+            mock::{block_on, MockAoc, LEADERBOARD_2024},
+            models::ranked_members,
+        },
+        utils::datetime::set_now,
+    };
+
+    #[test]
+    fn puzzle_title() {
+        let _guard = set_now(AocDay { year: 2024, day: 3 }.unlock_datetime());
+        block_on(async {
+            let aoc = MockAoc::start().await;
+            let client = aoc.client().await;
+            assert_eq!(client.get_puzzle_title(2024, 4).await, None);
+
+            aoc.route(
+                "/2024/day/1",
+                200,
+                "<h2>--- Day 1: Historian Hysteria ---</h2>",
+            )
+            .await;
+            aoc.route("/2024/day/2", 500, "").await;
+            assert_eq!(
+                client.get_puzzle_title(2024, 1).await.as_deref(),
+                Some("Historian Hysteria")
+            );
+            assert_eq!(client.get_puzzle_title(2024, 2).await, None);
+
+            // titles are cached forever, failures for a while
+            aoc.route("/2024/day/1", 500, "").await;
+            aoc.route(
+                "/2024/day/2",
+                200,
+                "<h2>--- Day 2: Red-Nosed Reports ---</h2>",
+            )
+            .await;
+            assert_eq!(
+                client.get_puzzle_title(2024, 1).await.as_deref(),
+                Some("Historian Hysteria")
+            );
+            assert_eq!(client.get_puzzle_title(2024, 2).await, None);
+        });
+    }
+
+    #[test]
+    fn clear_caches() {
+        let _guard = set_now(AocDay { year: 2024, day: 3 }.unlock_datetime());
+        block_on(async {
+            let aoc = MockAoc::start().await;
+            aoc.leaderboard(2024, 200, LEADERBOARD_2024).await;
+            aoc.route(
+                "/2024/leaderboard/private/view/2.json",
+                200,
+                LEADERBOARD_2024,
+            )
+            .await;
+            aoc.route(
+                "/2024/day/1",
+                200,
+                "<h2>--- Day 1: Historian Hysteria ---</h2>",
+            )
+            .await;
+            let client = aoc.client().await;
+            client.get_private_leaderboard(2024).await.unwrap();
+            client.get_other_private_leaderboard(2, 2024).await.unwrap();
+            client.get_puzzle_title(2024, 1).await.unwrap();
+            let cleared = |board, year| {
+                let client = &client;
+                async move {
+                    let cleared = client
+                        .clear_caches(CacheScope { board, year })
+                        .await
+                        .unwrap();
+                    cleared
+                        .into_iter()
+                        .filter(|&(_, n)| n > 0)
+                        .collect::<Vec<_>>()
+                }
+            };
+
+            assert_eq!(cleared(Some(1), Some(2023)).await, []);
+            // puzzle titles belong to no leaderboard
+            assert_eq!(cleared(Some(2), None).await, [("other_leaderboards", 1)]);
+            assert_eq!(
+                cleared(None, Some(2024)).await,
+                [
+                    ("leaderboards", 1),
+                    ("raw_leaderboards", 1),
+                    ("puzzle_titles", 1)
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn single_flight() {
+        let _guard = set_now(AocDay { year: 2024, day: 3 }.unlock_datetime());
+        block_on(async {
+            let aoc = MockAoc::start().await;
+            aoc.expect("/2024/leaderboard/private/view/1.json", LEADERBOARD_2024, 1)
+                .await;
+            aoc.expect("/2024/leaderboard/private/view/2.json", LEADERBOARD_2024, 1)
+                .await;
+            let client = aoc.client().await;
+
+            let days = join_all((1..=5).map(|day| {
+                client.get_daily_private_leaderboard(
+                    2024,
+                    day,
+                    Parts::Both,
+                    Part2Start::Part1,
+                    false,
+                )
+            }));
+            let others = join_all((0..5).map(|_| client.get_other_private_leaderboard(2, 2024)));
+            let (days, others) = tokio::join!(days, others);
+            assert!(days.iter().chain(&others).all(Result::is_ok));
+            // the expected number of requests is verified when the server is dropped
+        });
+    }
+
+    #[test]
+    fn refresh_wait() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let interval = Duration::from_secs(60);
+        let wait = |last_fetch| super::refresh_wait(last_fetch, interval, now);
+
+        assert_eq!(wait(None), Duration::ZERO);
+        assert_eq!(
+            wait(Some(now - TimeDelta::seconds(20))),
+            Duration::from_secs(40)
+        );
+        assert_eq!(wait(Some(now - TimeDelta::minutes(5))), Duration::ZERO);
+        // fetched after the request started
+        assert_eq!(wait(Some(now + TimeDelta::seconds(1))), interval);
+    }
+
+    #[test]
+    fn split_ties() {
+        let mut leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200))]),
+            member(2, "Bob", &[(1, 100, Some(300))]),
+            member(3, "Carol", &[(1, 100, Some(300))]),
+            member(4, "Dave", &[(1, 400, None)]),
+        ]);
+        let scores = |leaderboard: &PrivateLeaderboard| {
+            let (ranked, _) =
+                ranked_members(leaderboard.members.values().cloned().collect(), 0, true);
+            ranked
+                .into_iter()
+                .map(|(rank, m)| (rank, m.id, m.score().to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        // the three members tied on part 1 split 4 + 3 + 2 points, two of them are tied on part 2
+        // and split 3 + 2 points
+        score_day(&mut leaderboard, 1, Parts::Both, Part2Start::Part1, true);
+        assert_eq!(
+            scores(&leaderboard),
+            [
+                (1, 1, "7".into()),
+                (2, 2, "5.5".into()),
+                (2, 3, "5.5".into()),
+                (4, 4, "1".into())
+            ]
+        );
+        score_day(&mut leaderboard, 1, Parts::P1, Part2Start::Part1, true);
+        assert_eq!(
+            scores(&leaderboard),
+            [
+                (1, 1, "3".into()),
+                (1, 2, "3".into()),
+                (1, 3, "3".into()),
+                (4, 4, "1".into())
+            ]
+        );
+
+        // without splitting, ties are broken by the member order
+        score_day(&mut leaderboard, 1, Parts::Both, Part2Start::Part1, false);
+        assert_eq!(
+            scores(&leaderboard),
+            [
+                (1, 1, "8".into()),
+                (2, 2, "6".into()),
+                (3, 3, "4".into()),
+                (4, 4, "1".into())
+            ]
+        );
+    }
+}
@@ -1,8 +1,9 @@
-use chrono::{DateTime, Datelike, TimeZone, Utc};
+use chrono::{DateTime, Datelike, FixedOffset, TimeDelta, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 
 use crate::utils::datetime::{now_est, EST};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AocDay {
     pub year: i32,
     pub day: u32,
@@ -20,7 +21,17 @@ impl AocDay {
     }
 
     pub fn current() -> Option<Self> {
-        let now = now_est();
+        Self::current_at(now_est())
+    }
+
+    /// The current day, except during the first `grace` after a puzzle unlocks, when it is still
+    /// the previous day. On the first of December there is no previous day, so it is returned
+    /// right away.
+    pub fn current_with_grace(grace: TimeDelta) -> Option<Self> {
+        Self::current_at(now_est() - grace).or_else(Self::current)
+    }
+
+    fn current_at(now: DateTime<FixedOffset>) -> Option<Self> {
         (now.month() == 12 && now.day() <= 25).then_some(Self {
             year: now.year(),
             day: now.day(),
@@ -120,6 +131,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn current_with_grace() {
+        let grace = TimeDelta::hours(6);
+        for (now, expected) in [
+            ("2024-11-30T23:00:00+01:00", None),
+            ("2024-12-01T06:00:13+01:00", Some((2024, 1))),
+            ("2024-12-15T05:59:07+01:00", Some((2024, 14))),
+            ("2024-12-15T06:00:13+01:00", Some((2024, 14))),
+            ("2024-12-15T11:59:07+01:00", Some((2024, 14))),
+            ("2024-12-15T12:00:13+01:00", Some((2024, 15))),
+            ("2024-12-26T11:59:07+01:00", Some((2024, 25))),
+            ("2024-12-26T12:00:13+01:00", None),
+        ] {
+            let _guard = set_now(now.parse().unwrap());
+            let expected = expected.map(|(year, day)| AocDay { year, day });
+            assert_eq!(AocDay::current_with_grace(grace), expected, "{now}");
+        }
+
+        let _guard = set_now("2024-12-15T06:00:13+01:00".parse().unwrap());
+        assert_eq!(
+            AocDay::current_with_grace(TimeDelta::zero()),
+            AocDay::current()
+        );
+    }
+
     #[test]
     fn most_recent() {
         for (now, year, day) in [
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use chrono::TimeDelta;
+
+use super::{
+    day::AocDay,
+    models::{
+        AocId, PrivateLeaderboard, PrivateLeaderboardMember, PrivateLeaderboardMemberCompletionDay,
+        PrivateLeaderboardMemberCompletionDayPart,
+    },
+};
+use crate::utils::serde::via_string::ViaString;
+
+pub const YEAR: i32 = 2024;
+
+/// Construct a member of [`YEAR`] from a list of `(day, part 1, part 2)` completion times, given
+/// as seconds after the respective puzzle unlock.
+pub fn member(id: AocId, name: &str, days: &[(u32, i64, Option<i64>)]) -> PrivateLeaderboardMember {
+    let part = |day, secs| PrivateLeaderboardMemberCompletionDayPart {
+        get_star_ts: AocDay { year: YEAR, day }.unlock_datetime() + TimeDelta::seconds(secs),
+        star_index: 0,
+    };
+    let completion_day_level = days
+        .iter()
+        .map(|&(day, p1, p2)| {
+            let completion = PrivateLeaderboardMemberCompletionDay {
+                fst: part(day, p1),
+                snd: p2.map(|p2| part(day, p2)),
+            };
+            (day, completion)
+        })
+        .collect::<HashMap<_, _>>();
+
+    PrivateLeaderboardMember {
+        id,
+        name: (!name.is_empty()).then(|| name.into()),
+        global_score: 0,
+        local_score: 0,
+        split_score: None,
+        stars: completion_day_level
+            .values()
+            .map(|c| 1 + c.snd.is_some() as u32)
+            .sum(),
+        last_star_ts: completion_day_level
+            .values()
+            .map(|c| c.snd.as_ref().unwrap_or(&c.fst).get_star_ts)
+            .max()
+            .unwrap_or_default(),
+        completion_day_level,
+        no_ping: false,
+    }
+}
+
+pub fn leaderboard(
+    members: impl IntoIterator<Item = PrivateLeaderboardMember>,
+) -> PrivateLeaderboard {
+    PrivateLeaderboard {
+        event: YEAR.to_string(),
+        owner_id: 1,
+        members: members.into_iter().map(|m| (ViaString(m.id), m)).collect(),
+    }
+}
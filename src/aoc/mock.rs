@@ -0,0 +1,117 @@
+//! A mock AoC server serving canned responses on localhost, so that tests can exercise the whole
+//! path from fetching a leaderboard to rendering it. The integration tests in `tests/` use the
+//! same canned responses.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use super::{archive::Archive, client::AocClient, sessions::Sessions};
+use crate::utils::store::Store;
+
+/// The private leaderboard page of the session, whose invite code identifies the owner.
+const WHOAMI: &str = "<p>Others can join it using the code <code>1-42ff1337</code>.</p>";
+
+/// A private leaderboard of 2024 as returned by AoC. Bob's star count disagrees with his
+/// completed days and the third member is anonymous.
+pub const LEADERBOARD_2024: &str = include_str!("../../tests/fixtures/leaderboard_2024.json");
+
+pub struct MockAoc {
+    server: MockServer,
+    priority: AtomicU8,
+}
+
+impl MockAoc {
+    pub async fn start() -> Self {
+        let mock = Self {
+            server: MockServer::start().await,
+            priority: AtomicU8::new(u8::MAX),
+        };
+        mock.route("/leaderboard/private", 200, WHOAMI).await;
+        mock
+    }
+
+    pub fn url(&self) -> String {
+        self.server.uri()
+    }
+
+    /// Answer requests of the given path with the given status and body, replacing previous
+    /// answers of the path.
+    pub async fn route(&self, route: &str, status: u16, body: &str) {
+        // among matching mocks, wiremock answers with the one of the highest priority
+        let priority = self.priority.fetch_sub(1, Ordering::Relaxed);
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(ResponseTemplate::new(status).set_body_string(body))
+            .with_priority(priority)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answer requests of the given path slowly, so that concurrent requests overlap, and expect
+    /// the given number of them when the server is dropped.
+    pub async fn expect(&self, route: &str, body: &str, requests: u64) {
+        let priority = self.priority.fetch_sub(1, Ordering::Relaxed);
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(body)
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .with_priority(priority)
+            .expect(requests)
+            .mount(&self.server)
+            .await;
+    }
+
+    pub async fn leaderboard(&self, year: i32, status: u16, body: &str) {
+        let path = format!("/{year}/leaderboard/private/view/1.json");
+        self.route(&path, status, body).await;
+    }
+
+    pub async fn store(&self) -> Store {
+        let matrix = matrix_sdk::Client::builder()
+            .homeserver_url(self.url())
+            .build()
+            .await
+            .unwrap();
+        Store::new(matrix)
+    }
+
+    pub async fn client(&self) -> AocClient {
+        let sessions = Sessions::new(&["session".into()], &self.url()).unwrap();
+        AocClient::new(
+            sessions,
+            Duration::from_secs(900),
+            BTreeMap::new(),
+            Duration::from_secs(60),
+            50,
+            300,
+            None,
+            Archive::open_in_memory(10).unwrap(),
+            self.store().await,
+        )
+        .await
+        .unwrap()
+    }
+}
+
+/// Run the given future to completion, so that tests can hold a [`set_now`] guard meanwhile.
+///
+/// [`set_now`]: crate::utils::datetime::set_now
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
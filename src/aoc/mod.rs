@@ -1,4 +1,10 @@
 mod api;
+pub mod archive;
 pub mod client;
 pub mod day;
+#[cfg(test)]
+pub mod fixtures;
+#[cfg(test)]
+pub mod mock;
 pub mod models;
+pub mod sessions;
@@ -1,10 +1,18 @@
-use std::{cmp::Reverse, collections::HashMap};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 use chrono::{DateTime, Utc};
 use matrix_sdk::ruma::UserId;
 use serde::{Deserialize, Serialize};
 
-use crate::utils::{self, serde::via_string::ViaString};
+use super::day::AocDay;
+use crate::{
+    config::GridConfig,
+    utils::{self, datetime::now, fmt::escape_html, serde::via_string::ViaString},
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AocWhoami {
@@ -22,19 +30,162 @@ pub struct PrivateLeaderboard {
     pub members: PrivateLeaderboardMembers,
 }
 
+impl PrivateLeaderboard {
+    pub fn star_events(&self, day: u32) -> Vec<StarEvent<'_>> {
+        let mut events = self
+            .members
+            .values()
+            .filter_map(|m| Some((m, m.completion_day_level.get(&day)?)))
+            .flat_map(|(member, c)| {
+                let p1 = StarEvent {
+                    member,
+                    part2: false,
+                    ts: c.fst.get_star_ts,
+                };
+                let p2 = c.snd.as_ref().map(|p2| StarEvent {
+                    member,
+                    part2: true,
+                    ts: p2.get_star_ts,
+                });
+                [Some(p1), p2].into_iter().flatten()
+            })
+            .collect::<Vec<_>>();
+        events.sort_unstable_by_key(|e| (e.ts, e.part2, e.member.id));
+        events
+    }
+
+    /// Replace the star counts which disagree with the completed days, which are the ground
+    /// truth, and return the corrected members ordered by id. A discrepancy indicates stale or
+    /// misparsed data.
+    pub fn fix_star_counts(&mut self) -> Vec<StarDiscrepancy> {
+        let mut discrepancies = self
+            .members
+            .values_mut()
+            .filter_map(|m| {
+                let completed = m.completed_stars();
+                let discrepancy = (m.stars != completed).then(|| StarDiscrepancy {
+                    id: m.id,
+                    name: m.display_name(),
+                    stars: m.stars,
+                    completed,
+                });
+                m.stars = completed;
+                discrepancy
+            })
+            .collect::<Vec<_>>();
+        discrepancies.sort_unstable_by_key(|d| d.id);
+        discrepancies
+    }
+
+    pub fn apply_opt_out(&mut self, marker: &str) {
+        for member in self.members.values_mut() {
+            let Some(name) = member.name.as_mut().filter(|n| n.contains(marker)) else {
+                continue;
+            };
+            *name = name.replace(marker, "").trim().into();
+            if name.is_empty() {
+                member.name = None;
+            }
+            member.no_ping = true;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StarDiscrepancy {
+    pub id: AocId,
+    pub name: String,
+    pub stars: u32,
+    pub completed: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StarEvent<'a> {
+    pub member: &'a PrivateLeaderboardMember,
+    pub part2: bool,
+    pub ts: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PrivateLeaderboardMember {
     pub id: AocId,
     pub name: Option<String>,
     pub global_score: u32,
     pub local_score: u32,
+    /// The local score with the points of tied members split among them, which replaces
+    /// `local_score` if set. Only computed for daily leaderboards with `aoc.split_ties`.
+    #[serde(skip)]
+    pub split_score: Option<Score>,
     pub stars: u32,
     #[serde(with = "utils::serde::timestamp")]
     pub last_star_ts: DateTime<Utc>,
     pub completion_day_level: HashMap<u32, PrivateLeaderboardMemberCompletionDay>,
+    /// Whether the member opted out of mentions using `aoc.opt_out_marker`. Stored, since the
+    /// marker is already removed from the name of stored leaderboards.
+    #[serde(default)]
+    pub no_ping: bool,
+}
+
+/// A possibly fractional local score. Scores are compared and shown rounded to one decimal, so
+/// that sums of split points are not ordered by floating point noise.
+#[derive(Debug, Clone, Copy)]
+pub struct Score(pub f64);
+
+impl Score {
+    fn tenths(self) -> i64 {
+        (self.0 * 10.0).round() as i64
+    }
+}
+
+impl From<u32> for Score {
+    fn from(score: u32) -> Self {
+        Self(score.into())
+    }
+}
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.tenths() == other.tenths()
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.tenths().cmp(&other.tenths())
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tenths = self.tenths();
+        if tenths % 10 == 0 {
+            write!(f, "{}", tenths / 10)
+        } else {
+            write!(f, "{}.{}", tenths / 10, tenths % 10)
+        }
+    }
 }
 
 impl PrivateLeaderboardMember {
+    pub fn score(&self) -> Score {
+        self.split_score.unwrap_or(self.local_score.into())
+    }
+
+    pub fn completed_stars(&self) -> u32 {
+        self.completion_day_level
+            .values()
+            .map(|c| 1 + c.snd.is_some() as u32)
+            .sum()
+    }
+
     pub fn display_name(&self) -> String {
         match self.name.clone() {
             Some(name) => name,
@@ -43,18 +194,137 @@ impl PrivateLeaderboardMember {
     }
 
     pub fn matrix_mention_or_display_name(&self, matrix: Option<&UserId>) -> String {
-        match matrix {
-            Some(matrix) => format!("{} ({})", matrix.matrix_to_uri(), self.display_name()),
-            None => format!("**{}**", self.display_name()),
+        match matrix.filter(|_| !self.no_ping) {
+            Some(matrix) => format!(
+                "{} ({})",
+                matrix.matrix_to_uri(),
+                escape_html(&self.display_name())
+            ),
+            None => format!("**{}**", escape_html(&self.display_name())),
         }
     }
 
     pub fn matrix_mention_or_display_name_html(&self, matrix: Option<&UserId>) -> String {
-        match matrix {
-            Some(matrix) => format!("{} ({})", matrix.matrix_to_uri(), self.display_name()),
-            None => format!("<b>{}</b>", self.display_name()),
+        match matrix.filter(|_| !self.no_ping) {
+            Some(matrix) => format!(
+                "{} ({})",
+                matrix.matrix_to_uri(),
+                escape_html(&self.display_name())
+            ),
+            None => format!("<b>{}</b>", escape_html(&self.display_name())),
+        }
+    }
+
+    pub fn day_status(&self, day: AocDay) -> DayStatus {
+        match self.completion_day_level.get(&day.day) {
+            Some(c) if c.snd.is_some() => DayStatus::Solved,
+            Some(_) => DayStatus::PartOne,
+            None if day.unlock_datetime() > now() => DayStatus::Locked,
+            None => DayStatus::Unsolved,
         }
     }
+
+    /// One glyph per day of the given year, in groups of five days. In descending order, the grid
+    /// starts with the most recent unlocked day and locked days are left out.
+    pub fn star_grid(&self, year: i32, order: DayOrder, grid: &GridConfig) -> String {
+        let statuses = (1..=25).map(|day| self.day_status(AocDay { year, day }));
+        let glyphs = match order {
+            DayOrder::Ascending => statuses.map(|s| grid.glyph(s)).collect::<Vec<_>>(),
+            DayOrder::Descending => statuses
+                .rev()
+                .filter(|&status| status != DayStatus::Locked)
+                .map(|s| grid.glyph(s))
+                .collect(),
+        };
+        glyphs
+            .chunks(5)
+            .map(|chunk| chunk.concat())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DayOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayStatus {
+    Locked,
+    Unsolved,
+    PartOne,
+    Solved,
+}
+
+/// Display names of the given members, with a short id suffix appended to names that occur more
+/// than once, so members sharing a name can be told apart.
+pub fn disambiguated_display_names<'a>(
+    members: impl IntoIterator<Item = &'a PrivateLeaderboardMember>,
+) -> HashMap<AocId, String> {
+    let names = members
+        .into_iter()
+        .map(|m| (m.id, m.display_name()))
+        .collect::<Vec<_>>();
+
+    let mut seen = HashSet::new();
+    let duplicates = names
+        .iter()
+        .filter(|(_, name)| !seen.insert(name))
+        .map(|(_, name)| name.clone())
+        .collect::<HashSet<_>>();
+
+    names
+        .into_iter()
+        .map(|(id, name)| {
+            if duplicates.contains(&name) {
+                (id, format!("{name} #{:04}", id % 10000))
+            } else {
+                (id, name)
+            }
+        })
+        .collect()
+}
+
+/// Sort members and assign ranks by local score (members with equal scores share a rank), then
+/// drop members with fewer than `min_stars` stars.
+///
+/// If `rank_before_filter` is set, ranks refer to the position on the full leaderboard, otherwise
+/// only the remaining members are ranked. Also returns the number of dropped members.
+pub fn ranked_members(
+    mut members: Vec<PrivateLeaderboardMember>,
+    min_stars: u32,
+    rank_before_filter: bool,
+) -> (Vec<(usize, PrivateLeaderboardMember)>, usize) {
+    fn rank(
+        members: impl IntoIterator<Item = PrivateLeaderboardMember>,
+    ) -> impl Iterator<Item = (usize, PrivateLeaderboardMember)> {
+        let mut last_score = None;
+        let mut rank = 0;
+        members.into_iter().enumerate().map(move |(i, member)| {
+            if last_score != Some(member.score()) {
+                last_score = Some(member.score());
+                rank = i + 1;
+            }
+            (rank, member)
+        })
+    }
+
+    members.sort_unstable();
+    let total = members.len();
+
+    let ranked = if rank_before_filter {
+        rank(members)
+            .filter(|(_, m)| m.stars >= min_stars)
+            .collect::<Vec<_>>()
+    } else {
+        rank(members.into_iter().filter(|m| m.stars >= min_stars)).collect()
+    };
+
+    let hidden = total - ranked.len();
+    (ranked, hidden)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -73,21 +343,199 @@ pub struct PrivateLeaderboardMemberCompletionDayPart {
 }
 
 impl PartialOrd for PrivateLeaderboardMember {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
 impl Ord for PrivateLeaderboardMember {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let key = |m: &Self| {
-            (
-                Reverse(m.local_score),
-                Reverse(m.stars),
-                m.last_star_ts,
-                m.id,
-            )
-        };
+    fn cmp(&self, other: &Self) -> Ordering {
+        let key = |m: &Self| (Reverse(m.score()), Reverse(m.stars), m.last_star_ts, m.id);
         key(self).cmp(&key(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aoc::fixtures::{leaderboard, member, YEAR},
+        config::tests::load_with_overrides,
+        utils::datetime::set_now,
+    };
+
+    #[test]
+    fn disambiguated_names() {
+        let members = [
+            member(1234567, "Alice", &[]),
+            member(7654321, "Alice", &[]),
+            member(42, "Bob", &[]),
+            member(1337, "", &[]),
+        ];
+        let names = disambiguated_display_names(&members);
+        assert_eq!(names.len(), 4);
+        assert_eq!(names[&1234567], "Alice #4567");
+        assert_eq!(names[&7654321], "Alice #4321");
+        assert_eq!(names[&42], "Bob");
+        assert_eq!(names[&1337], "[anonymous user #1337]");
+    }
+
+    #[test]
+    fn star_events() {
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(7, 250, Some(570)), (8, 10, None)]),
+            member(2, "Bob", &[(7, 120, None)]),
+            member(3, "Charlie", &[(7, 250, Some(300))]),
+            member(4, "Dave", &[(6, 20, Some(30))]),
+        ]);
+
+        let unlock = AocDay { year: YEAR, day: 7 }.unlock_datetime();
+        let events = leaderboard
+            .star_events(7)
+            .into_iter()
+            .map(|e| (e.member.id, e.part2, (e.ts - unlock).num_seconds()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                (2, false, 120),
+                (1, false, 250),
+                (3, false, 250),
+                (3, true, 300),
+                (1, true, 570),
+            ]
+        );
+
+        assert!(leaderboard.star_events(9).is_empty());
+    }
+
+    #[test]
+    fn fix_star_counts() {
+        let mut leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200)), (2, 300, None)]),
+            PrivateLeaderboardMember {
+                stars: 5,
+                ..member(2, "Bob", &[(1, 100, Some(200))])
+            },
+            PrivateLeaderboardMember {
+                stars: 0,
+                ..member(3, "", &[(1, 100, None)])
+            },
+        ]);
+        assert_eq!(
+            leaderboard.fix_star_counts(),
+            [
+                StarDiscrepancy {
+                    id: 2,
+                    name: "Bob".into(),
+                    stars: 5,
+                    completed: 2
+                },
+                StarDiscrepancy {
+                    id: 3,
+                    name: "[anonymous user #3]".into(),
+                    stars: 0,
+                    completed: 1
+                },
+            ]
+        );
+        assert_eq!(leaderboard.fix_star_counts(), []);
+        let stars = |id| leaderboard.members[&ViaString(id)].stars;
+        assert_eq!((stars(1), stars(2), stars(3)), (3, 2, 1));
+    }
+
+    #[test]
+    fn ranked() {
+        let scored = |id, local_score, days: &[_]| PrivateLeaderboardMember {
+            local_score,
+            ..member(id, "", days)
+        };
+        let members = vec![
+            scored(1, 10, &[(1, 10, None)]),
+            scored(2, 30, &[(1, 10, Some(20)), (2, 10, Some(20))]),
+            scored(3, 20, &[(1, 10, Some(20))]),
+            scored(4, 20, &[(1, 10, Some(30))]),
+            scored(5, 5, &[(1, 10, Some(20)), (2, 10, None)]),
+        ];
+        let ranks = |(ranked, hidden): (Vec<(usize, PrivateLeaderboardMember)>, usize)| {
+            let ranked = ranked
+                .into_iter()
+                .map(|(r, m)| (r, m.id))
+                .collect::<Vec<_>>();
+            (ranked, hidden)
+        };
+
+        assert_eq!(
+            ranks(ranked_members(members.clone(), 0, true)),
+            (vec![(1, 2), (2, 3), (2, 4), (4, 1), (5, 5)], 0)
+        );
+        assert_eq!(
+            ranks(ranked_members(members.clone(), 2, true)),
+            (vec![(1, 2), (2, 3), (2, 4), (5, 5)], 1)
+        );
+        assert_eq!(
+            ranks(ranked_members(members.clone(), 2, false)),
+            (vec![(1, 2), (2, 3), (2, 4), (4, 5)], 1)
+        );
+        assert_eq!(
+            ranks(ranked_members(members, 3, false)),
+            (vec![(1, 2), (2, 5)], 3)
+        );
+    }
+
+    #[test]
+    fn mention_html() {
+        let member = member(1, "<b>Alice & Bob", &[]);
+        let matrix = UserId::parse("@alice:example.com").unwrap();
+        assert_eq!(
+            member.matrix_mention_or_display_name_html(Some(&matrix)),
+            "https://matrix.to/#/@alice:example.com (&lt;b&gt;Alice &amp; Bob)"
+        );
+        assert_eq!(
+            member.matrix_mention_or_display_name_html(None),
+            "<b>&lt;b&gt;Alice &amp; Bob</b>"
+        );
+    }
+
+    #[test]
+    fn star_grid() {
+        let grid = load_with_overrides([]).aoc.grid;
+        let guard = set_now("2024-12-04T12:00:00Z".parse().unwrap());
+        let member = member(1, "Alice", &[(1, 10, Some(20)), (3, 10, None)]);
+
+        assert_eq!(
+            member.day_status(AocDay { year: YEAR, day: 2 }),
+            DayStatus::Unsolved
+        );
+        assert_eq!(
+            member.day_status(AocDay { year: YEAR, day: 5 }),
+            DayStatus::Locked
+        );
+        assert_eq!(
+            member.star_grid(YEAR, DayOrder::Ascending, &grid),
+            "★○☆○· ····· ····· ····· ·····"
+        );
+        assert_eq!(member.star_grid(YEAR, DayOrder::Descending, &grid), "○☆○★");
+        drop(guard);
+
+        let _guard = set_now("2025-01-01T00:00:00Z".parse().unwrap());
+        assert_eq!(
+            member.star_grid(YEAR, DayOrder::Ascending, &grid),
+            "★○☆○○ ○○○○○ ○○○○○ ○○○○○ ○○○○○"
+        );
+        assert_eq!(
+            member.star_grid(YEAR, DayOrder::Descending, &grid),
+            "○○○○○ ○○○○○ ○○○○○ ○○○○○ ○○☆○★"
+        );
+
+        let grid = load_with_overrides([
+            r#"aoc.grid = { solved = "✅", part_one = "🟨", unsolved = "⬜", locked = "·" }"#,
+        ])
+        .aoc
+        .grid;
+        assert_eq!(
+            member.star_grid(YEAR, DayOrder::Descending, &grid),
+            "⬜⬜⬜⬜⬜ ⬜⬜⬜⬜⬜ ⬜⬜⬜⬜⬜ ⬜⬜⬜⬜⬜ ⬜⬜🟨⬜✅"
+        );
+    }
+}
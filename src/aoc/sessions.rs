@@ -0,0 +1,160 @@
+use std::{future::Future, sync::Mutex};
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::StatusCode;
+use tracing::{info, warn};
+
+use super::{api::AocApiClient, models::PrivateLeaderboard};
+use crate::utils::datetime::now;
+
+/// How long a session is skipped after it has been rejected or rate-limited.
+const SESSION_COOLDOWN: TimeDelta = TimeDelta::minutes(15);
+
+/// The configured AoC sessions. Requests use the active session and rotate to the next usable one
+/// if it is rejected or rate-limited.
+pub struct Sessions {
+    clients: Vec<AocApiClient>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    active: usize,
+    unusable_until: Vec<Option<DateTime<Utc>>>,
+}
+
+impl Sessions {
+    pub fn new(sessions: &[String], base_url: &str) -> anyhow::Result<Self> {
+        if sessions.is_empty() {
+            bail!("At least one AoC session is required");
+        }
+        Ok(Self {
+            clients: sessions
+                .iter()
+                .map(|session| AocApiClient::new(session, base_url))
+                .collect::<anyhow::Result<_>>()?,
+            state: Mutex::new(State {
+                active: 0,
+                unusable_until: vec![None; sessions.len()],
+            }),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn primary(&self) -> &AocApiClient {
+        &self.clients[0]
+    }
+
+    pub async fn get_private_leaderboard(
+        &self,
+        year: i32,
+        user_id: u64,
+    ) -> anyhow::Result<(PrivateLeaderboard, String)> {
+        self.request(|client| client.get_private_leaderboard(year, user_id))
+            .await
+    }
+
+    async fn request<'a, T, F, Fut>(&'a self, f: F) -> anyhow::Result<T>
+    where
+        F: Fn(&'a AocApiClient) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut last_err = None;
+        for _ in 0..self.clients.len() {
+            let Some(index) = self.state.lock().unwrap().select(now()) else {
+                break;
+            };
+
+            match f(&self.clients[index]).await {
+                Ok(response) => return Ok(response),
+                Err(err) if is_session_error(&err) => {
+                    warn!(session = index, %err, "AoC session failed, marking it as unusable");
+                    self.state
+                        .lock()
+                        .unwrap()
+                        .mark_unusable(index, now() + SESSION_COOLDOWN);
+                    last_err = Some(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(match last_err {
+            Some(err) => err.context("All AoC sessions failed"),
+            None => anyhow!("All AoC sessions are temporarily unusable"),
+        })
+    }
+}
+
+impl State {
+    fn select(&mut self, now: DateTime<Utc>) -> Option<usize> {
+        let len = self.unusable_until.len();
+        let index = (0..len)
+            .map(|i| (self.active + i) % len)
+            .find(|&i| self.unusable_until[i].is_none_or(|until| until <= now))?;
+        if index != self.active {
+            info!(session = index, "switching to another AoC session");
+            self.active = index;
+        }
+        self.unusable_until[index] = None;
+        Some(index)
+    }
+
+    fn mark_unusable(&mut self, index: usize, until: DateTime<Utc>) {
+        self.unusable_until[index] = Some(until);
+    }
+}
+
+/// Whether the error indicates an expired or rate-limited session rather than an AoC outage.
+/// AoC redirects requests with an invalid session to an html page, which fails to decode.
+fn is_session_error(err: &anyhow::Error) -> bool {
+    if err.is::<serde_json::Error>() {
+        return true;
+    }
+    let status = err
+        .downcast_ref::<reqwest::Error>()
+        .and_then(|err| err.status());
+    status.is_some_and(|status| {
+        matches!(
+            status,
+            StatusCode::BAD_REQUEST
+                | StatusCode::UNAUTHORIZED
+                | StatusCode::FORBIDDEN
+                | StatusCode::TOO_MANY_REQUESTS
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut state = State {
+            active: 0,
+            unusable_until: vec![None; 3],
+        };
+        assert_eq!(state.select(now), Some(0));
+
+        state.mark_unusable(0, now + SESSION_COOLDOWN);
+        assert_eq!(state.select(now), Some(1));
+
+        state.mark_unusable(1, now + SESSION_COOLDOWN);
+        state.mark_unusable(2, now + TimeDelta::minutes(1));
+        assert_eq!(state.select(now), None);
+
+        // the session with the shortest cooldown becomes usable again first
+        assert_eq!(state.select(now + TimeDelta::minutes(1)), Some(2));
+        // sessions stay active until they fail
+        assert_eq!(state.select(now + SESSION_COOLDOWN), Some(2));
+
+        state.mark_unusable(2, now + SESSION_COOLDOWN * 2);
+        assert_eq!(state.select(now + SESSION_COOLDOWN), Some(0));
+    }
+}
@@ -0,0 +1,146 @@
+//! A bounded, persisted log of the commands run by users (and optionally the bot's own actions),
+//! queryable using the `audit` command.
+
+use std::{cell::Cell, collections::VecDeque, fmt, future::Future};
+
+use chrono::{DateTime, Utc};
+use matrix_sdk::ruma::{OwnedRoomId, OwnedUserId, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::utils::{serde::timestamp, store::Store};
+
+const AUDIT_LOG_STORE_KEY: &[u8] = b"audit_log";
+
+tokio::task_local! {
+    static OUTCOME: Cell<Outcome>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    Success,
+    Error,
+    TooLarge,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Success => "success",
+            Self::Error => "error",
+            Self::TooLarge => "too large",
+        })
+    }
+}
+
+/// Run `future`, collecting the outcome reported using [`set_outcome`]. The outcome is
+/// [`Outcome::Success`] unless reported otherwise.
+pub async fn with_outcome<F: Future>(future: F) -> (Outcome, F::Output) {
+    OUTCOME
+        .scope(Cell::new(Outcome::Success), async {
+            let output = future.await;
+            (OUTCOME.with(Cell::get), output)
+        })
+        .await
+}
+
+/// Report the outcome of the currently running command. Does nothing outside of
+/// [`with_outcome`].
+pub fn set_outcome(outcome: Outcome) {
+    let _ = OUTCOME.try_with(|o| o.set(outcome));
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(with = "timestamp")]
+    pub ts: DateTime<Utc>,
+    pub room_id: OwnedRoomId,
+    pub sender: OwnedUserId,
+    pub command: String,
+    /// Parsed arguments of the command, the message itself is not recorded.
+    pub params: Vec<String>,
+    pub outcome: Outcome,
+}
+
+pub struct AuditLog {
+    store: Store,
+    capacity: usize,
+    entries: RwLock<VecDeque<AuditEntry>>,
+}
+
+impl AuditLog {
+    pub async fn load(store: Store, capacity: usize) -> anyhow::Result<Self> {
+        let mut entries = store
+            .get::<VecDeque<AuditEntry>>(AUDIT_LOG_STORE_KEY)
+            .await?
+            .unwrap_or_default();
+        truncate(&mut entries, capacity);
+        Ok(Self {
+            store,
+            capacity,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    pub async fn record(&self, entry: AuditEntry) -> anyhow::Result<()> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut guard = self.entries.write().await;
+        guard.push_back(entry);
+        truncate(&mut guard, self.capacity);
+        self.store.set(AUDIT_LOG_STORE_KEY, &*guard).await?;
+        Ok(())
+    }
+
+    pub async fn recent(&self, sender: Option<&UserId>, n: usize) -> Vec<AuditEntry> {
+        let guard = self.entries.read().await;
+        let mut entries = guard
+            .iter()
+            .rev()
+            .filter(|e| sender.is_none_or(|s| e.sender == s))
+            .take(n)
+            .cloned()
+            .collect::<Vec<_>>();
+        entries.reverse();
+        entries
+    }
+}
+
+fn truncate(entries: &mut VecDeque<AuditEntry>, capacity: usize) {
+    let excess = entries.len().saturating_sub(capacity);
+    entries.drain(..excess);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn outcome() {
+        assert_eq!(with_outcome(async {}).await.0, Outcome::Success);
+        assert_eq!(
+            with_outcome(async { set_outcome(Outcome::TooLarge) })
+                .await
+                .0,
+            Outcome::TooLarge
+        );
+        // outside of a scope
+        set_outcome(Outcome::Error);
+    }
+
+    #[test]
+    fn truncate() {
+        let entry = |n: u32| AuditEntry {
+            ts: DateTime::from_timestamp(n.into(), 0).unwrap(),
+            room_id: "!xoXcjSEJPUfQmzETtS:matrix.example.com".try_into().unwrap(),
+            sender: "@alice:example.com".try_into().unwrap(),
+            command: "ping".into(),
+            params: Vec::new(),
+            outcome: Outcome::Success,
+        };
+        let mut entries = (0..5).map(entry).collect();
+        super::truncate(&mut entries, 3);
+        assert_eq!(entries, (2..5).map(entry).collect::<VecDeque<_>>());
+    }
+}
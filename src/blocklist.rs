@@ -0,0 +1,83 @@
+//! Matrix users whose commands and reactions are ignored, managed by the admins using the `block`
+//! and `unblock` commands.
+
+use chrono::{DateTime, Utc};
+use matrix_sdk::ruma::{OwnedUserId, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::utils::{datetime::now, serde::timestamp, store::Store};
+
+const BLOCKLIST_STORE_KEY: &[u8] = b"blocklist";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockedUser {
+    pub user_id: OwnedUserId,
+    pub reason: Option<String>,
+    pub blocked_by: OwnedUserId,
+    #[serde(with = "timestamp")]
+    pub ts: DateTime<Utc>,
+}
+
+pub struct Blocklist {
+    store: Store,
+    entries: RwLock<Vec<BlockedUser>>,
+}
+
+impl Blocklist {
+    pub async fn load(store: Store) -> anyhow::Result<Self> {
+        let entries = store
+            .get::<Vec<BlockedUser>>(BLOCKLIST_STORE_KEY)
+            .await?
+            .unwrap_or_default();
+        Ok(Self {
+            store,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    pub async fn all(&self) -> Vec<BlockedUser> {
+        self.entries.read().await.clone()
+    }
+
+    pub async fn is_blocked(&self, user_id: &UserId) -> bool {
+        self.entries
+            .read()
+            .await
+            .iter()
+            .any(|b| b.user_id == user_id)
+    }
+
+    /// Block the given user. Returns `false` if they were already blocked, keeping the original
+    /// reason.
+    pub async fn block(
+        &self,
+        user_id: &UserId,
+        reason: Option<String>,
+        blocked_by: &UserId,
+    ) -> anyhow::Result<bool> {
+        let mut guard = self.entries.write().await;
+        if guard.iter().any(|b| b.user_id == user_id) {
+            return Ok(false);
+        }
+        guard.push(BlockedUser {
+            user_id: user_id.to_owned(),
+            reason,
+            blocked_by: blocked_by.to_owned(),
+            ts: now(),
+        });
+        self.store.set(BLOCKLIST_STORE_KEY, &*guard).await?;
+        Ok(true)
+    }
+
+    pub async fn unblock(&self, user_id: &UserId) -> anyhow::Result<bool> {
+        let mut guard = self.entries.write().await;
+        let len = guard.len();
+        guard.retain(|b| b.user_id != user_id);
+        if guard.len() == len {
+            return Ok(false);
+        }
+        self.store.set(BLOCKLIST_STORE_KEY, &*guard).await?;
+        Ok(true)
+    }
+}
@@ -1,21 +1,46 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::Duration,
+};
 
-use chrono::FixedOffset;
+use anyhow::bail;
+use chrono::{FixedOffset, NaiveTime, TimeDelta};
 use config::{File, FileFormat};
-use matrix_sdk::ruma::{OwnedRoomId, OwnedUserId};
+use matrix_sdk::ruma::{OwnedRoomId, OwnedUserId, RoomId};
 use regex::Regex;
+use reqwest::Url;
 use serde::{Deserialize, Deserializer};
 
 use crate::{
-    aoc::models::AocId,
-    utils::{self, regex_set_replacer::RegexSetReplacer},
+    aoc::{
+        client::{Part2Start, Parts},
+        models::{AocId, DayStatus},
+    },
+    i18n::Locale,
+    matrix::{
+        commands::{aoc::enrich::EnricherKind, is_command_name},
+        quiet::QuietPolicy,
+    },
+    utils::{self, fmt::truncate_url, regex_set_replacer::RegexSetReplacer},
 };
 
 pub fn load<'a>(config_path: impl Iterator<Item = &'a str>) -> anyhow::Result<Config> {
     load_with_defaults(std::iter::empty(), config_path)
 }
 
-fn load_with_defaults<'a>(
+/// Placeholders for the options without a default, for tests which neither connect to matrix nor
+/// read a session file.
+#[cfg(any(test, feature = "testing"))]
+pub(crate) const REQUIRED: [&str; 5] = [
+    "matrix.homeserver = \"https://matrix.example.com\"",
+    "matrix.store_path = \".store\"",
+    "matrix.admin_ids = []",
+    "matrix.room_id = \"!xoXcjSEJPUfQmzETtS:matrix.example.com\"",
+    "aoc.session_file = \".session\"",
+];
+
+pub(crate) fn load_with_defaults<'a>(
     defaults: impl Iterator<Item = &'a str>,
     config_path: impl Iterator<Item = &'a str>,
 ) -> anyhow::Result<Config> {
@@ -30,7 +55,9 @@ fn load_with_defaults<'a>(
         builder = builder.add_source(File::with_name(path.trim()));
     }
 
-    builder.build()?.try_deserialize().map_err(Into::into)
+    let config = builder.build()?.try_deserialize::<Config>()?;
+    config.validate()?;
+    Ok(config)
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,9 +68,179 @@ pub struct Config {
     pub matrix: MatrixConfig,
     pub aoc: AocConfig,
     pub garygrady: GarygradyConfig,
+    pub solves: SolvesConfig,
+    pub digest: DigestConfig,
+    pub watchdog: WatchdogConfig,
+    pub caches: CachesConfig,
     pub users: Vec<User>,
 }
 
+impl Config {
+    pub fn default_parts(&self, room_id: &RoomId) -> Parts {
+        self.matrix
+            .room(room_id)
+            .and_then(|r| r.default_parts)
+            .unwrap_or(self.aoc.default_parts)
+    }
+
+    /// Check the constraints that are not covered by deserialization, e.g. ranges and references
+    /// between fields. All problems are reported in a single error.
+    fn validate(&self) -> anyhow::Result<()> {
+        let mut problems = Vec::new();
+        let mut check = |valid: bool, problem: String| {
+            if !valid {
+                problems.push(problem);
+            }
+        };
+
+        let matrix = &self.matrix;
+        check(
+            matrix.max_concurrent_commands > 0,
+            "matrix.max_concurrent_commands must be at least 1".into(),
+        );
+        let commands = matrix
+            .commands
+            .keys()
+            .map(|name| ("matrix.commands", name))
+            .chain(matrix.rooms.iter().flat_map(|room| {
+                room.commands
+                    .keys()
+                    .map(|name| ("matrix.rooms.commands", name))
+            }));
+        for (field, name) in commands {
+            check(
+                is_command_name(name),
+                format!(
+                    "{field}: unknown command '{name}' (commands are named by their full name)"
+                ),
+            );
+        }
+        for name in matrix.ephemeral.keys() {
+            check(
+                name == "debug" || is_command_name(name),
+                format!("matrix.ephemeral: unknown command '{name}'"),
+            );
+        }
+        let mut rooms = HashSet::new();
+        for room in &matrix.rooms {
+            check(
+                rooms.insert(&room.id),
+                format!(
+                    "matrix.rooms: room {} is configured more than once",
+                    room.id
+                ),
+            );
+            check(
+                room.post.rows.is_none_or(|rows| rows >= self.aoc.min_rows),
+                format!(
+                    "matrix.rooms: post.rows of room {} must be at least aoc.min_rows ({})",
+                    room.id, self.aoc.min_rows
+                ),
+            );
+        }
+
+        let aoc = &self.aoc;
+        check(
+            Url::parse(&aoc.base_url).is_ok(),
+            format!("aoc.base_url: invalid url '{}'", aoc.base_url),
+        );
+        check(aoc.min_rows > 0, "aoc.min_rows must be at least 1".into());
+        check(
+            aoc.leaderboard_rows >= aoc.min_rows,
+            format!(
+                "aoc.leaderboard_rows must be at least aoc.min_rows ({})",
+                aoc.min_rows
+            ),
+        );
+        for (field, rows, max_rows) in [
+            ("aoc.boards", aoc.boards.rows, aoc.boards.max_rows),
+            ("aoc.history", aoc.history.rows, aoc.history.max_rows),
+        ] {
+            check(
+                (aoc.min_rows..=max_rows).contains(&rows),
+                format!(
+                    "{field}.rows must be between aoc.min_rows ({}) and {field}.max_rows \
+                     ({max_rows})",
+                    aoc.min_rows
+                ),
+            );
+        }
+        let mut par_days = HashSet::new();
+        for par in &aoc.par_times {
+            check(
+                (1..=25).contains(&par.day),
+                format!("aoc.par_times: invalid day {}", par.day),
+            );
+            check(
+                par_days.insert((par.year, par.day)),
+                format!(
+                    "aoc.par_times: day {} is configured more than once",
+                    par.day
+                ),
+            );
+        }
+        check(
+            aoc.opt_out_marker
+                .as_ref()
+                .is_none_or(|m| !m.trim().is_empty()),
+            "aoc.opt_out_marker must not be empty".into(),
+        );
+        let (mut names, mut ids) = (HashSet::new(), HashSet::new());
+        for board in &aoc.boards.others {
+            check(
+                !board.name.trim().is_empty(),
+                format!(
+                    "aoc.boards.others: leaderboard {} has an empty name",
+                    board.id
+                ),
+            );
+            check(
+                names.insert(&board.name),
+                format!("aoc.boards.others: duplicate name '{}'", board.name),
+            );
+            check(
+                ids.insert(board.id),
+                format!("aoc.boards.others: duplicate id {}", board.id),
+            );
+        }
+
+        check(
+            self.garygrady.interval > 0,
+            "garygrady.interval must be at least 1".into(),
+        );
+        check(
+            self.solves.interval > 0,
+            "solves.interval must be at least 1".into(),
+        );
+        check(
+            self.digest.interval > 0,
+            "digest.interval must be at least 1".into(),
+        );
+
+        let (mut aoc_ids, mut matrix_ids) = (HashSet::new(), HashSet::new());
+        for user in &self.users {
+            if let Some(id) = user.aoc {
+                check(
+                    aoc_ids.insert(id),
+                    format!("users: AoC id {id} is linked more than once"),
+                );
+            }
+            if let Some(id) = &user.matrix {
+                check(
+                    matrix_ids.insert(id),
+                    format!("users: matrix user {id} is linked more than once"),
+                );
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            bail!("Invalid config:\n- {}", problems.join("\n- "))
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct MatrixConfig {
@@ -51,19 +248,240 @@ pub struct MatrixConfig {
     pub store_path: PathBuf,
     pub admin_ids: Vec<OwnedUserId>,
     pub room_id: OwnedRoomId,
+    pub admin_room_id: Option<OwnedRoomId>,
     pub command_prefix: String,
     pub link_prefix: String,
+    pub image_format: bool,
+    pub reactions: bool,
+    pub max_concurrent_commands: usize,
+    pub max_mentions: usize,
+    /// Delay in seconds between two scheduled posts triggered at the same time.
+    pub broadcast_delay: u64,
+    pub dedupe_posts: bool,
+    /// Local time at which scheduled posts are sent even if nothing changed.
+    #[serde(
+        default,
+        deserialize_with = "utils::serde::via_string::deserialize_option"
+    )]
+    pub always_post: Option<NaiveTime>,
+    pub pin_posts: bool,
+    pub collapse: bool,
+    pub mute: bool,
+    pub quiet_hours: Option<TimeRange>,
+    pub quiet_policy: QuietPolicy,
+    pub audit: AuditConfig,
+    pub limits: LimitsConfig,
+    pub locale: Locale,
+    pub commands: HashMap<String, bool>,
+    /// Seconds after which the responses of a command are redacted, by command name (or `debug`
+    /// for commands run with `debug=yes`).
+    pub ephemeral: HashMap<String, u64>,
+    #[serde(deserialize_with = "deserialize_aliases")]
+    pub aliases: HashMap<String, String>,
+    pub rooms: Vec<RoomConfig>,
+}
+
+impl MatrixConfig {
+    pub fn room(&self, room_id: &RoomId) -> Option<&RoomConfig> {
+        self.rooms.iter().find(|r| r.id == room_id)
+    }
+
+    pub fn command_enabled(&self, room_id: &RoomId, command: &str) -> bool {
+        self.room(room_id)
+            .and_then(|r| r.commands.get(command))
+            .or_else(|| self.commands.get(command))
+            .copied()
+            .unwrap_or(true)
+    }
+
+    pub fn reactions_enabled(&self, room_id: &RoomId) -> bool {
+        self.room(room_id)
+            .and_then(|r| r.reactions)
+            .unwrap_or(self.reactions)
+    }
+
+    pub fn dedupe_posts_enabled(&self, room_id: &RoomId) -> bool {
+        self.room(room_id)
+            .and_then(|r| r.dedupe_posts)
+            .unwrap_or(self.dedupe_posts)
+    }
+
+    pub fn pin_posts_enabled(&self, room_id: &RoomId) -> bool {
+        self.room(room_id)
+            .and_then(|r| r.pin_posts)
+            .unwrap_or(self.pin_posts)
+    }
+
+    pub fn collapse_enabled(&self, room_id: &RoomId) -> bool {
+        self.room(room_id)
+            .and_then(|r| r.collapse)
+            .unwrap_or(self.collapse)
+    }
+
+    pub fn muted(&self, room_id: &RoomId) -> bool {
+        self.room(room_id).and_then(|r| r.mute).unwrap_or(self.mute)
+    }
+
+    pub fn quiet_hours(&self, room_id: &RoomId) -> Option<TimeRange> {
+        self.room(room_id)
+            .and_then(|r| r.quiet_hours)
+            .or(self.quiet_hours)
+    }
+
+    pub fn post(&self, room_id: &RoomId) -> Option<&PostConfig> {
+        self.room(room_id).map(|r| &r.post)
+    }
+
+    pub fn locale(&self, room_id: &RoomId) -> Locale {
+        self.room(room_id)
+            .and_then(|r| r.locale)
+            .unwrap_or(self.locale)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditConfig {
+    pub capacity: usize,
+    pub bot_actions: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Maximum length of a command (after expanding aliases) in bytes.
+    pub max_length: usize,
+    pub max_args: usize,
+    /// Maximum length of a single (quoted) argument or keyword argument value in bytes.
+    pub max_arg_length: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoomConfig {
+    pub id: OwnedRoomId,
+    #[serde(default)]
+    pub commands: HashMap<String, bool>,
+    pub reactions: Option<bool>,
+    pub dedupe_posts: Option<bool>,
+    pub pin_posts: Option<bool>,
+    pub collapse: Option<bool>,
+    pub default_parts: Option<Parts>,
+    pub mute: Option<bool>,
+    pub quiet_hours: Option<TimeRange>,
+    pub locale: Option<Locale>,
+    #[serde(default)]
+    pub post: PostConfig,
+}
+
+/// The daily leaderboard posted to a room. The slice also applies to the posts of schedules added
+/// using the `schedule` command.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostConfig {
+    /// Local time of a daily post during the event, in addition to the added schedules.
+    #[serde(
+        default,
+        deserialize_with = "utils::serde::via_string::deserialize_option"
+    )]
+    pub time: Option<NaiveTime>,
+    pub rows: Option<usize>,
+    /// Falls back to the default parts of the room.
+    pub parts: Option<Parts>,
+    pub offset: Option<usize>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct AocConfig {
+    pub base_url: String,
     pub session_file: PathBuf,
+    pub backup_session_files: Vec<PathBuf>,
     pub leaderboard_rows: usize,
+    pub rollup_rows: usize,
+    pub min_rows: usize,
     pub default_cache_ttl: u64,
     pub cache_ttl_rules: Vec<CacheTtlRule>,
+    /// Minimum time in seconds between two fetches of a leaderboard forced using `fresh=yes`.
+    pub fresh_interval: u64,
+    pub staleness: StalenessConfig,
+    pub participation: ParticipationConfig,
+    pub day_summary: bool,
+    pub default_parts: Parts,
+    pub part2_start: Part2Start,
+    pub par_times: Vec<ParTime>,
+    pub day_compare: bool,
+    pub compact_threshold: usize,
+    /// Seconds after a puzzle unlocks during which the previous day is still the default day.
+    pub default_day_grace: u64,
+    pub split_ties: bool,
+    pub room_members_only: bool,
+    /// Members whose AoC name contains this marker are never mentioned. The marker is removed
+    /// from their name.
+    pub opt_out_marker: Option<String>,
+    /// Fetched leaderboards kept per year in the archive, the oldest are removed first. Unchanged
+    /// leaderboards are not archived again, `0` disables the archive. The archive is stored in
+    /// `archive.sqlite3` in the `matrix.store_path` directory.
+    pub archive_snapshots: usize,
     #[serde(deserialize_with = "deserialize_repo_rules")]
     pub repo_rules: RegexSetReplacer,
+    pub repo_title_max_length: usize,
+    pub enrichers: Vec<EnricherKind>,
+    /// Milliseconds an enricher may take for all members of a leaderboard, unless overridden in
+    /// `enricher_timeouts`.
+    pub enricher_timeout: u64,
+    pub enricher_timeouts: HashMap<EnricherKind, u64>,
+    pub branding: BrandingConfig,
+    pub boards: BoardsConfig,
+    pub history: HistoryConfig,
+    pub streak: StreakConfig,
+    pub newcomers: NewcomersConfig,
+    pub grid: GridConfig,
+}
+
+impl AocConfig {
+    pub fn default_day_grace(&self) -> TimeDelta {
+        TimeDelta::seconds(self.default_day_grace as i64)
+    }
+
+    pub fn enricher_timeout(&self, kind: EnricherKind) -> Duration {
+        let millis = self
+            .enricher_timeouts
+            .get(&kind)
+            .copied()
+            .unwrap_or(self.enricher_timeout);
+        Duration::from_millis(millis)
+    }
+
+    /// The par time of the given day, preferring an entry for its year.
+    pub fn par_time(&self, year: i32, day: u32) -> Option<TimeDelta> {
+        self.par_times
+            .iter()
+            .filter(|p| p.day == day && p.year.is_none_or(|y| y == year))
+            .max_by_key(|p| p.year.is_some())
+            .map(|p| TimeDelta::seconds(p.seconds as i64))
+    }
+
+    /// The title shown for a repository url: the replacement of the first matching repo rule (or
+    /// the url itself), truncated to `repo_title_max_length`.
+    pub fn repo_title(&self, repo: &str) -> String {
+        let title = self
+            .repo_rules
+            .match_and_replace(repo)
+            .map(|m| m.replacement);
+        let title = title.as_deref().unwrap_or(repo);
+        truncate_url(title, self.repo_title_max_length).into_owned()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParTime {
+    /// The par time applies to the day of every year if no year is given.
+    #[serde(default)]
+    pub year: Option<i32>,
+    pub day: u32,
+    pub seconds: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -73,6 +491,92 @@ pub struct CacheTtlRule {
     pub ttl: u64,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StalenessConfig {
+    pub fresh: u64,
+    pub stale: u64,
+}
+
+/// The display name of the private leaderboard, shown in the headers of its leaderboards instead
+/// of "Private Leaderboard".
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BrandingConfig {
+    pub name: Option<String>,
+    pub tagline: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoardsConfig {
+    pub rows: usize,
+    pub max_rows: usize,
+    pub others: Vec<BoardConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub rows: usize,
+    pub max_rows: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StreakConfig {
+    pub badge: String,
+    pub badge_years: u32,
+}
+
+/// Members who joined a private leaderboard are shown with a badge in the next `renders`
+/// renderings of its leaderboards, for at most `hours` hours.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewcomersConfig {
+    pub badge: String,
+    pub renders: u32,
+    pub hours: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoardConfig {
+    pub name: String,
+    pub id: AocId,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GridConfig {
+    #[serde(deserialize_with = "deserialize_glyph")]
+    pub solved: String,
+    #[serde(deserialize_with = "deserialize_glyph")]
+    pub part_one: String,
+    #[serde(deserialize_with = "deserialize_glyph")]
+    pub unsolved: String,
+    #[serde(deserialize_with = "deserialize_glyph")]
+    pub locked: String,
+}
+
+impl GridConfig {
+    pub fn glyph(&self, status: DayStatus) -> &str {
+        match status {
+            DayStatus::Locked => &self.locked,
+            DayStatus::Unsolved => &self.unsolved,
+            DayStatus::PartOne => &self.part_one,
+            DayStatus::Solved => &self.solved,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParticipationConfig {
+    pub min_stars: u32,
+    pub rank_before_filter: bool,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct GarygradyConfig {
@@ -80,12 +584,75 @@ pub struct GarygradyConfig {
     pub max_age: u64,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SolvesConfig {
+    /// Seconds between two checks for new stars.
+    pub interval: u64,
+    pub room_id: Option<OwnedRoomId>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DigestConfig {
+    pub interval: u64,
+    pub quiet_hours: TimeRange,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchdogConfig {
+    /// Seconds without sync responses or handled commands after which the bot is considered to
+    /// be stuck, or 0 to disable the watchdog.
+    pub timeout: u64,
+    pub active_hours: TimeRange,
+    pub reconnect: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CachesConfig {
+    pub other_leaderboards: usize,
+    pub day_ranks: usize,
+    pub room_members: usize,
+    pub puzzle_titles: usize,
+    pub views: usize,
+}
+
+/// A range of local times, possibly wrapping around midnight.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeRange {
+    #[serde(with = "utils::serde::via_string")]
+    pub start: NaiveTime,
+    #[serde(with = "utils::serde::via_string")]
+    pub end: NaiveTime,
+}
+
+impl TimeRange {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct User {
     pub aoc: Option<AocId>,
     pub matrix: Option<OwnedUserId>,
     pub repo: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "utils::serde::via_string::deserialize_option"
+    )]
+    pub timezone: Option<FixedOffset>,
+    /// Previous AoC names of the member, which are still accepted by commands taking a name.
+    #[serde(default)]
+    pub aliases: Vec<String>,
 }
 
 fn deserialize_repo_rules<'de, D>(deserializer: D) -> Result<RegexSetReplacer, D::Error>
@@ -107,23 +674,245 @@ where
     Ok(RegexSetReplacer::new(rules))
 }
 
+/// Deserialize a grid glyph, which has to be a single character (optionally followed by a
+/// variation selector) to keep the grids aligned.
+fn deserialize_glyph<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let glyph = String::deserialize(deserializer)?;
+    let mut chars = glyph.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| !c.is_whitespace() && !c.is_control())
+        && matches!(chars.next(), None | Some('\u{fe0e}' | '\u{fe0f}'))
+        && chars.next().is_none();
+    if !valid {
+        return Err(serde::de::Error::custom(format!(
+            "invalid grid glyph '{glyph}' (expected a single character)"
+        )));
+    }
+    Ok(glyph)
+}
+
+fn deserialize_aliases<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let aliases = HashMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, expansion)| (name.to_lowercase(), expansion))
+        .collect::<HashMap<_, _>>();
+
+    for (name, expansion) in &aliases {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(serde::de::Error::custom(format!(
+                "invalid alias name '{name}'"
+            )));
+        }
+        if expansion.trim().is_empty() {
+            return Err(serde::de::Error::custom(format!(
+                "alias '{name}' has an empty expansion"
+            )));
+        }
+
+        let mut seen = HashSet::from([name.as_str()]);
+        let mut current = expansion.as_str();
+        loop {
+            let target = current.split_whitespace().next().unwrap_or_default();
+            let Some((target, expansion)) = aliases.get_key_value(&target.to_lowercase()) else {
+                break;
+            };
+            if !seen.insert(target) {
+                return Err(serde::de::Error::custom(format!(
+                    "alias '{name}' expands to itself"
+                )));
+            }
+            current = expansion;
+        }
+    }
+
+    Ok(aliases)
+}
+
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use super::*;
 
+    pub fn load_with_overrides<'a>(overrides: impl IntoIterator<Item = &'a str>) -> Config {
+        try_load_with_overrides(overrides).unwrap()
+    }
+
+    fn try_load_with_overrides<'a>(
+        overrides: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Config, config::ConfigError> {
+        let mut builder = config::Config::builder();
+        for content in REQUIRED
+            .into_iter()
+            .chain([include_str!("../config.toml")])
+            .chain(overrides)
+        {
+            builder = builder.add_source(File::from_str(content, FileFormat::Toml));
+        }
+        builder.build()?.try_deserialize()
+    }
+
     #[test]
     fn load() {
         load_with_defaults(
-            [
-                "matrix.homeserver = \"https://matrix.example.com\"",
-                "matrix.store_path = \".store\"",
-                "matrix.admin_ids = []",
-                "matrix.room_id = \"!xoXcjSEJPUfQmzETtS:matrix.example.com\"",
-                "aoc.session_file = \".session\"",
-            ]
-            .into_iter(),
+            REQUIRED.into_iter(),
             [concat!(env!("CARGO_MANIFEST_DIR"), "/users.toml")].into_iter(),
         )
         .unwrap();
     }
+
+    #[test]
+    fn command_enabled() {
+        let config = load_with_overrides([
+            "matrix.commands = { leaderboard = false, user = false }",
+            "matrix.rooms = [{ id = \"!xoXcjSEJPUfQmzETtS:matrix.example.com\", commands = { \
+             leaderboard = true, day = false } }]",
+        ]);
+
+        let room: &RoomId = "!xoXcjSEJPUfQmzETtS:matrix.example.com".try_into().unwrap();
+        let other: &RoomId = "!EHmSdCZOSsmMjdKlDb:matrix.example.com".try_into().unwrap();
+
+        assert!(config.matrix.command_enabled(room, "leaderboard"));
+        assert!(!config.matrix.command_enabled(room, "day"));
+        assert!(!config.matrix.command_enabled(room, "user"));
+        assert!(config.matrix.command_enabled(room, "ping"));
+
+        assert!(!config.matrix.command_enabled(other, "leaderboard"));
+        assert!(config.matrix.command_enabled(other, "day"));
+        assert!(!config.matrix.command_enabled(other, "user"));
+        assert!(config.matrix.command_enabled(other, "ping"));
+    }
+
+    #[test]
+    fn reactions_enabled() {
+        let config = load_with_overrides([
+            "matrix.rooms = [{ id = \"!xoXcjSEJPUfQmzETtS:matrix.example.com\", reactions = true }]",
+        ]);
+
+        let room: &RoomId = "!xoXcjSEJPUfQmzETtS:matrix.example.com".try_into().unwrap();
+        let other: &RoomId = "!EHmSdCZOSsmMjdKlDb:matrix.example.com".try_into().unwrap();
+
+        assert!(config.matrix.reactions_enabled(room));
+        assert!(!config.matrix.reactions_enabled(other));
+    }
+
+    #[test]
+    fn default_parts() {
+        let config = load_with_overrides([
+            r#"aoc.default_parts = "2""#,
+            "matrix.rooms = [{ id = \"!xoXcjSEJPUfQmzETtS:matrix.example.com\", default_parts = \
+             \"both\" }]",
+        ]);
+
+        let room: &RoomId = "!xoXcjSEJPUfQmzETtS:matrix.example.com".try_into().unwrap();
+        let other: &RoomId = "!EHmSdCZOSsmMjdKlDb:matrix.example.com".try_into().unwrap();
+
+        assert_eq!(config.default_parts(room), Parts::Both);
+        assert_eq!(config.default_parts(other), Parts::P2);
+
+        let err = try_load_with_overrides([r#"aoc.default_parts = "3""#]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("invalid parts '3' (expected 1, 2 or both)"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn quiet_hours() {
+        let config = load_with_overrides([]);
+        let time = |t| NaiveTime::parse_from_str(t, "%H:%M").unwrap();
+        let quiet_hours = config.digest.quiet_hours;
+        assert!(quiet_hours.contains(time("23:00")));
+        assert!(quiet_hours.contains(time("03:00")));
+        assert!(!quiet_hours.contains(time("12:00")));
+
+        let quiet_hours = TimeRange {
+            start: time("01:00"),
+            end: time("05:00"),
+        };
+        assert!(quiet_hours.contains(time("03:00")));
+        assert!(!quiet_hours.contains(time("05:00")));
+        assert!(!quiet_hours.contains(time("23:00")));
+    }
+
+    #[test]
+    fn aliases() {
+        let config = load_with_overrides([
+            r#"matrix.aliases = { top10 = "lb rows=10", Top = "top10 offset=0" }"#,
+        ]);
+        assert_eq!(config.matrix.aliases["top"], "top10 offset=0");
+
+        for aliases in [
+            r#"matrix.aliases = { a = "a" }"#,
+            r#"matrix.aliases = { a = "b x", b = "c", c = "A y" }"#,
+            r#"matrix.aliases = { a = " " }"#,
+        ] {
+            assert!(try_load_with_overrides([aliases]).is_err(), "{aliases}");
+        }
+    }
+
+    #[test]
+    fn validate() {
+        load_with_overrides([]).validate().unwrap();
+
+        let err = load_with_overrides([
+            "aoc.leaderboard_rows = 0",
+            "aoc.history = { rows = 30, max_rows = 20 }",
+            "matrix.commands = { lb = false, day = false }",
+            r#"aoc.boards.others = [{ name = "Work", id = 1 }, { name = "Work", id = 2 }]"#,
+        ])
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid config:\n- matrix.commands: unknown command 'lb' (commands are named by their \
+             full name)\n- aoc.leaderboard_rows must be at least aoc.min_rows (1)\n- aoc.history.rows \
+             must be between aoc.min_rows (1) and aoc.history.max_rows (20)\n- aoc.boards.others: \
+             duplicate name 'Work'"
+        );
+    }
+
+    #[test]
+    fn par_time() {
+        let config = load_with_overrides([
+            "aoc.par_times = [{ day = 1, seconds = 600 }, { year = 2024, day = 1, seconds = 300 }]",
+        ]);
+        assert_eq!(config.aoc.par_time(2024, 1), Some(TimeDelta::minutes(5)));
+        assert_eq!(config.aoc.par_time(2023, 1), Some(TimeDelta::minutes(10)));
+        assert_eq!(config.aoc.par_time(2024, 2), None);
+
+        let err = load_with_overrides([
+            "aoc.par_times = [{ day = 26, seconds = 1 }, { day = 2, seconds = 1 }, { day = 2, \
+             seconds = 2 }]",
+        ])
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid config:\n- aoc.par_times: invalid day 26\n- aoc.par_times: day 2 is configured \
+             more than once"
+        );
+    }
+
+    #[test]
+    fn grid() {
+        let config = load_with_overrides([
+            r#"aoc.grid = { solved = "✅", part_one = "🟨", unsolved = "⬜", locked = "⭐️" }"#,
+        ]);
+        assert_eq!(config.aoc.grid.glyph(DayStatus::PartOne), "🟨");
+        assert_eq!(config.aoc.grid.glyph(DayStatus::Locked), "⭐️");
+
+        for glyph in ["", " ", "**", "ab"] {
+            let grid = format!(
+                r#"aoc.grid = {{ solved = "{glyph}", part_one = "☆", unsolved = "○", locked = "·" }}"#
+            );
+            assert!(try_load_with_overrides([grid.as_str()]).is_err(), "{glyph}");
+        }
+    }
 }
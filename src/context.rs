@@ -1,27 +1,79 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{self, Arc},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
 
-use matrix_sdk::{ruma::OwnedUserId, Room};
+use matrix_sdk::{
+    ruma::{OwnedRoomId, OwnedUserId, RoomId, UserId},
+    Room, RoomMemberships,
+};
 use reqwest::Url;
+use tokio::sync::{Mutex, Notify, OwnedSemaphorePermit, RwLock, Semaphore};
 
 use crate::{
-    aoc::client::AocClient,
+    aoc::{
+        client::{AocClient, CacheScope, Part2Start, Parts},
+        models::AocId,
+    },
+    audit::AuditLog,
+    blocklist::Blocklist,
     config::{Config, User},
+    digest::Subscriptions,
+    links::{Link, Links, Request},
     mastodon,
-    utils::store::Store,
+    matrix::commands::aoc::{day::Ranks, enrich::Repo, reactions::Views},
+    newcomers::Newcomers,
+    schedule::{Schedule, Schedules},
+    utils::{
+        datetime::now,
+        lru::{CacheStats, Lru},
+        store::Store,
+    },
 };
 
+const ROOM_MEMBERS_TTL: TimeDelta = TimeDelta::minutes(1);
+
+type RoomMembers = Arc<HashSet<OwnedUserId>>;
+
+/// The room, year, day, parts and start of the part 2 completion time of a daily leaderboard,
+/// which all change its ranks.
+pub type DayRanksKey = (OwnedRoomId, i32, u32, Parts, Part2Start);
+
 pub struct Context {
     pub config: Config,
     pub store: Store,
     pub room: Room,
     pub aoc_client: AocClient,
     pub garygrady: ContextGarygrady,
-    pub users: ContextUsers,
+    /// Rebuilt when users link their account using the `link` command.
+    pub users: RwLock<ContextUsers>,
+    pub views: Views,
+    pub schedules: Schedules,
+    pub audit_log: AuditLog,
+    pub digests: Subscriptions,
+    pub blocklist: Blocklist,
+    pub links: Links,
+    pub newcomers: Newcomers,
+    pub day_ranks: Lru<DayRanksKey, Ranks>,
+    pub reconnect: Notify,
+    command_semaphores: Mutex<HashMap<OwnedRoomId, Arc<Semaphore>>>,
+    room_members: Lru<OwnedRoomId, (DateTime<Utc>, RoomMembers)>,
+    last_activity: sync::Mutex<DateTime<Utc>>,
 }
 
 pub struct ContextUsers {
     pub by_aoc: HashMap<u64, User>,
     pub by_matrix: HashMap<OwnedUserId, User>,
+    /// The links of the users with an AoC id, resolved once as the repo titles may run regexes.
+    pub resolved: HashMap<AocId, ResolvedUser>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedUser {
+    pub matrix_uri: Option<String>,
+    pub repo: Option<Repo>,
 }
 
 pub struct ContextGarygrady {
@@ -30,39 +82,192 @@ pub struct ContextGarygrady {
 }
 
 impl Context {
-    pub fn new(
+    pub async fn new(
         config: Config,
         store: Store,
         room: Room,
         aoc_client: AocClient,
         garygrady: ContextGarygrady,
-    ) -> Self {
-        let users = ContextUsers::from_config(&config);
+    ) -> anyhow::Result<Self> {
+        let schedules =
+            Schedules::load(store.clone(), Schedule::configured(&config.matrix)).await?;
+        let audit_log = AuditLog::load(store.clone(), config.matrix.audit.capacity).await?;
+        let digests = Subscriptions::load(store.clone()).await?;
+        let blocklist = Blocklist::load(store.clone()).await?;
+        let links = Links::load(store.clone()).await?;
+        let users = ContextUsers::new(&config, &links.all().await);
+        let newcomers = Newcomers::load(store.clone()).await?;
+        let views = Views::new(config.caches.views);
+        let day_ranks = Lru::new("day_ranks", config.caches.day_ranks);
+        let room_members = Lru::new("room_members", config.caches.room_members);
 
-        Self {
+        Ok(Self {
             config,
             store,
             room,
             aoc_client,
             garygrady,
-            users,
+            users: RwLock::new(users),
+            views,
+            schedules,
+            audit_log,
+            digests,
+            blocklist,
+            links,
+            newcomers,
+            day_ranks,
+            reconnect: Notify::new(),
+            command_semaphores: Default::default(),
+            room_members,
+            last_activity: sync::Mutex::new(now()),
+        })
+    }
+
+    pub async fn link(&self, request: &Request) -> anyhow::Result<()> {
+        self.links.confirm(request).await?;
+        self.reload_users().await;
+        Ok(())
+    }
+
+    pub async fn unlink(&self, matrix: &UserId) -> anyhow::Result<bool> {
+        let unlinked = self.links.unlink(matrix).await?;
+        if unlinked {
+            self.reload_users().await;
+        }
+        Ok(unlinked)
+    }
+
+    async fn reload_users(&self) {
+        *self.users.write().await = ContextUsers::new(&self.config, &self.links.all().await);
+    }
+
+    pub fn cache_stats(&self) -> Vec<CacheStats> {
+        let mut stats = self.aoc_client.cache_stats();
+        stats.extend([
+            self.views.stats(),
+            self.day_ranks.stats(),
+            self.room_members.stats(),
+        ]);
+        stats
+    }
+
+    /// Remove the entries of the in-memory caches in the given scope, as well as the persisted
+    /// leaderboards, and return the number of removed entries per cache. The views and ranks are
+    /// all of the primary leaderboard, room members belong to no leaderboard or year.
+    pub async fn clear_caches(
+        &self,
+        scope: CacheScope,
+    ) -> anyhow::Result<Vec<(&'static str, usize)>> {
+        let primary = Some(self.aoc_client.whoami().user_id);
+        let mut cleared = self.aoc_client.clear_caches(scope).await?;
+        cleared.extend([
+            (
+                self.views.name(),
+                self.views
+                    .retain(|view| !scope.includes(primary, Some(view.year()))),
+            ),
+            (
+                self.day_ranks.name(),
+                self.day_ranks
+                    .retain(|(_, year, ..), _| !scope.includes(primary, Some(*year))),
+            ),
+            (
+                self.room_members.name(),
+                self.room_members.retain(|_, _| !scope.includes(None, None)),
+            ),
+        ]);
+        Ok(cleared)
+    }
+
+    pub fn record_activity(&self) {
+        *self.last_activity.lock().unwrap() = now();
+    }
+
+    pub fn last_activity(&self) -> DateTime<Utc> {
+        *self.last_activity.lock().unwrap()
+    }
+
+    pub async fn room_members(&self, room: &Room) -> anyhow::Result<RoomMembers> {
+        if let Some((ts, members)) = self.room_members.get(room.room_id()) {
+            if now() < ts + ROOM_MEMBERS_TTL {
+                return Ok(members);
+            }
         }
+
+        let members = Arc::new(
+            room.members(RoomMemberships::JOIN)
+                .await?
+                .into_iter()
+                .map(|m| m.user_id().to_owned())
+                .collect::<HashSet<_>>(),
+        );
+        self.room_members
+            .insert(room.room_id().to_owned(), (now(), Arc::clone(&members)));
+        Ok(members)
+    }
+
+    /// Try to reserve one of the command execution slots of the given room. The slot is released
+    /// again when the returned permit is dropped.
+    pub async fn try_acquire_command_permit(
+        &self,
+        room_id: &RoomId,
+    ) -> Option<OwnedSemaphorePermit> {
+        let semaphore = self
+            .command_semaphores
+            .lock()
+            .await
+            .entry(room_id.to_owned())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.config.matrix.max_concurrent_commands)))
+            .clone();
+        semaphore.try_acquire_owned().ok()
     }
 }
 
 impl ContextUsers {
-    fn from_config(config: &Config) -> Self {
-        let by_aoc = config
-            .users
+    /// The users of the config and the links added by the users themselves. Links of matrix users
+    /// or AoC members which are already in the config are ignored.
+    pub fn new(config: &Config, links: &[Link]) -> Self {
+        let linked = links
             .iter()
+            .filter(|link| {
+                !config
+                    .users
+                    .iter()
+                    .any(|u| u.matrix.as_ref() == Some(&link.matrix) || u.aoc == Some(link.aoc))
+            })
+            .map(|link| User {
+                aoc: Some(link.aoc),
+                matrix: Some(link.matrix.clone()),
+                repo: link.repo.clone(),
+                timezone: None,
+                aliases: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        let users = || config.users.iter().chain(&linked);
+
+        let by_aoc = users()
             .flat_map(|user| Some((user.aoc?, user.clone())))
             .collect();
-        let by_matrix = config
-            .users
-            .iter()
+        let by_matrix = users()
             .flat_map(|user| Some((user.matrix.clone()?, user.clone())))
             .collect();
+        let resolved = users()
+            .flat_map(|user| {
+                let resolved = ResolvedUser {
+                    matrix_uri: user.matrix.as_ref().map(|m| m.matrix_to_uri().to_string()),
+                    repo: user.repo.as_ref().map(|url| Repo {
+                        title: config.aoc.repo_title(url),
+                        url: url.clone(),
+                    }),
+                };
+                Some((user.aoc?, resolved))
+            })
+            .collect();
 
-        Self { by_aoc, by_matrix }
+        Self {
+            by_aoc,
+            by_matrix,
+            resolved,
+        }
     }
 }
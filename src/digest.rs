@@ -0,0 +1,240 @@
+//! Private daily progress digests that users subscribe to using the `digest` command.
+
+use std::fmt::Write;
+
+use chrono::Datelike;
+use matrix_sdk::ruma::{OwnedUserId, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    aoc::{
+        day::AocDay,
+        models::{AocId, PrivateLeaderboard},
+    },
+    utils::{
+        datetime::now_est,
+        fmt::{fmt_rank, fmt_timedelta},
+        stats::median,
+        store::Store,
+    },
+};
+
+const DIGEST_SUBSCRIPTIONS_STORE_KEY: &[u8] = b"digest_subscriptions";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub user_id: OwnedUserId,
+    pub last_sent: Option<AocDay>,
+}
+
+pub struct Subscriptions {
+    store: Store,
+    entries: RwLock<Vec<Subscription>>,
+}
+
+impl Subscriptions {
+    pub async fn load(store: Store) -> anyhow::Result<Self> {
+        let entries = store
+            .get::<Vec<Subscription>>(DIGEST_SUBSCRIPTIONS_STORE_KEY)
+            .await?
+            .unwrap_or_default();
+        Ok(Self {
+            store,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    pub async fn all(&self) -> Vec<Subscription> {
+        self.entries.read().await.clone()
+    }
+
+    pub async fn is_subscribed(&self, user_id: &UserId) -> bool {
+        self.entries
+            .read()
+            .await
+            .iter()
+            .any(|s| s.user_id == user_id)
+    }
+
+    pub async fn set(&self, user_id: &UserId, subscribed: bool) -> anyhow::Result<bool> {
+        let mut guard = self.entries.write().await;
+        let len = guard.len();
+        if subscribed {
+            if guard.iter().any(|s| s.user_id == user_id) {
+                return Ok(false);
+            }
+            // digests of the days before subscribing are not owed
+            let last_sent = is_december().then(AocDay::most_recent).and_then(|today| {
+                let day = today.day.checked_sub(1).filter(|&day| day > 0)?;
+                Some(AocDay { day, ..today })
+            });
+            guard.push(Subscription {
+                user_id: user_id.to_owned(),
+                last_sent,
+            });
+        } else {
+            guard.retain(|s| s.user_id != user_id);
+            if guard.len() == len {
+                return Ok(false);
+            }
+        }
+        self.store
+            .set(DIGEST_SUBSCRIPTIONS_STORE_KEY, &*guard)
+            .await?;
+        Ok(true)
+    }
+
+    pub async fn mark_sent(&self, user_id: &UserId, day: AocDay) -> anyhow::Result<()> {
+        let mut guard = self.entries.write().await;
+        for subscription in guard.iter_mut().filter(|s| s.user_id == user_id) {
+            subscription.last_sent = Some(day);
+        }
+        self.store
+            .set(DIGEST_SUBSCRIPTIONS_STORE_KEY, &*guard)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Whether digests are sent, which is during december. Days finished after the 25th are still
+/// sent until the end of the month.
+pub fn is_december() -> bool {
+    now_est().month() == 12
+}
+
+/// The days of the event up to `today` whose digests were not sent yet, in order. Digests of
+/// previous events are not owed.
+pub fn owed_days(last_sent: Option<AocDay>, today: AocDay) -> impl Iterator<Item = AocDay> {
+    let first = match last_sent {
+        Some(last_sent) if last_sent.year == today.year => last_sent.day + 1,
+        _ => 1,
+    };
+    (first..=today.day).map(move |day| AocDay { day, ..today })
+}
+
+pub fn render(leaderboard: &PrivateLeaderboard, member_id: AocId, day: AocDay) -> Option<String> {
+    let AocDay { year, day } = day;
+    let unlock = AocDay { year, day }.unlock_datetime();
+
+    let finish = |id: AocId| {
+        let member = leaderboard.members.values().find(|m| m.id == id)?;
+        let ts = member
+            .completion_day_level
+            .get(&day)?
+            .snd
+            .as_ref()?
+            .get_star_ts;
+        Some(ts)
+    };
+    let member = leaderboard.members.values().find(|m| m.id == member_id)?;
+    let finished = finish(member_id)?;
+
+    let mut finishers = leaderboard
+        .members
+        .values()
+        .filter_map(|m| finish(m.id).map(|ts| (ts, m.id)))
+        .collect::<Vec<_>>();
+    finishers.sort_unstable();
+    let day_rank = finishers.iter().filter(|&&(ts, _)| ts < finished).count() + 1;
+    let mut deltas = finishers
+        .iter()
+        .map(|&(ts, _)| ts - unlock)
+        .collect::<Vec<_>>();
+    let board_median = median(&mut deltas)?;
+
+    let delta = finished - unlock;
+    let comparison = if delta <= board_median {
+        format!(
+            "{} faster than the median",
+            fmt_timedelta(board_median - delta)
+        )
+    } else {
+        format!(
+            "{} slower than the median",
+            fmt_timedelta(delta - board_median)
+        )
+    };
+
+    let overall_rank = leaderboard
+        .members
+        .values()
+        .filter(|&o| o <= member)
+        .count();
+
+    let mut out = format!("### Your Advent of Code {year}/{day:02} digest\n");
+    writeln!(
+        &mut out,
+        "- Finished in **{}**, {} of {} finishers",
+        fmt_timedelta(delta),
+        fmt_rank(day_rank),
+        finishers.len()
+    )
+    .unwrap();
+    writeln!(
+        &mut out,
+        "- Board median: {} ({comparison})",
+        fmt_timedelta(board_median)
+    )
+    .unwrap();
+    writeln!(
+        &mut out,
+        "- Overall: {} with {} points and {} stars",
+        fmt_rank(overall_rank),
+        member.local_score,
+        member.stars
+    )
+    .unwrap();
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::{
+        fixtures::{leaderboard, member, YEAR},
+        models::PrivateLeaderboardMember,
+    };
+
+    #[test]
+    fn render() {
+        let leaderboard = leaderboard([
+            PrivateLeaderboardMember {
+                local_score: 20,
+                ..member(1, "Alice", &[(1, 100, Some(200))])
+            },
+            PrivateLeaderboardMember {
+                local_score: 10,
+                ..member(2, "Bob", &[(1, 300, Some(600))])
+            },
+            member(3, "Carol", &[(1, 50, None)]),
+        ]);
+        let day = AocDay { year: YEAR, day: 1 };
+
+        let digest = super::render(&leaderboard, 2, day).unwrap();
+        assert_eq!(
+            digest,
+            "### Your Advent of Code 2024/01 digest\n- Finished in **10m 0s**, 🥈 2nd of 2 \
+             finishers\n- Board median: 6m 40s (3m 20s slower than the median)\n- Overall: 🥈 \
+             2nd with 10 points and 2 stars\n"
+        );
+
+        // part 2 is not finished yet
+        assert_eq!(super::render(&leaderboard, 3, day), None);
+        assert_eq!(super::render(&leaderboard, 4, day), None);
+    }
+
+    #[test]
+    fn owed_days() {
+        let day = |year, day| AocDay { year, day };
+        let owed = |last_sent, today| super::owed_days(last_sent, today).collect::<Vec<_>>();
+
+        assert_eq!(owed(Some(day(YEAR, 3)), day(YEAR, 3)), []);
+        assert_eq!(
+            owed(Some(day(YEAR, 3)), day(YEAR, 5)),
+            [day(YEAR, 4), day(YEAR, 5)]
+        );
+        assert_eq!(owed(None, day(YEAR, 2)), [day(YEAR, 1), day(YEAR, 2)]);
+        assert_eq!(owed(Some(day(YEAR - 1, 25)), day(YEAR, 1)), [day(YEAR, 1)]);
+    }
+}
@@ -0,0 +1,263 @@
+//! Translations of the bot's own messages. Leaderboard data is never translated.
+//!
+//! Translatable messages are [`Message`] keys, which are rendered in the locale of the current
+//! [`with_locale`] scope when they are formatted. Other messages are sent in English.
+
+use std::{fmt, future::Future};
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+tokio::task_local! {
+    static LOCALE: Locale;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+pub async fn with_locale<F: Future>(locale: Locale, future: F) -> F::Output {
+    LOCALE.scope(locale, future).await
+}
+
+/// The locale of the current [`with_locale`] scope, English outside of a scope.
+fn current_locale() -> Locale {
+    LOCALE.try_with(|&l| l).unwrap_or_default()
+}
+
+/// A translatable message. Its arguments are inserted as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message<'a> {
+    Error,
+    PermissionDenied,
+    InternalError,
+    CommandDisabled,
+    UnknownCommand {
+        prefix: &'a str,
+    },
+    UserNotFound,
+    InvalidArgument(&'a str),
+    InvalidArgumentExpected {
+        name: &'a str,
+        expected: &'a str,
+    },
+    InvalidArgumentSuggestion {
+        name: &'a str,
+        suggestion: &'a str,
+    },
+    MissingArgument(&'a str),
+    IncompatibleArguments {
+        key: &'a str,
+        other: &'a str,
+        reason: &'a str,
+    },
+    ImageDisabled,
+    CommandTooLong {
+        max: usize,
+    },
+    TooManyArguments {
+        max: usize,
+    },
+    ArgumentTooLong {
+        max: usize,
+    },
+    FetchFailed {
+        year: i32,
+        status: Option<StatusCode>,
+    },
+    ReadFailed {
+        year: i32,
+    },
+    NoSchedule(u32),
+    SliceTooLarge,
+}
+
+impl Message<'_> {
+    fn write_to(&self, locale: Locale, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Locale::{De, En};
+
+        match (*self, locale) {
+            (Self::Error, En) => f.write_str("Error"),
+            (Self::Error, De) => f.write_str("Fehler"),
+            (Self::PermissionDenied, En) => f.write_str("Permission denied"),
+            (Self::PermissionDenied, De) => f.write_str("Zugriff verweigert"),
+            (Self::InternalError, En) => f.write_str("An internal error occurred"),
+            (Self::InternalError, De) => f.write_str("Ein interner Fehler ist aufgetreten"),
+            (Self::CommandDisabled, En) => f.write_str("That command is disabled here"),
+            (Self::CommandDisabled, De) => f.write_str("Dieser Befehl ist hier deaktiviert"),
+            (Self::UnknownCommand { prefix }, En) => write!(
+                f,
+                "Unknown command. Send `{prefix}help` for a list of available commands."
+            ),
+            (Self::UnknownCommand { prefix }, De) => write!(
+                f,
+                "Unbekannter Befehl. Sende `{prefix}help` für eine Liste der verfügbaren Befehle."
+            ),
+            (Self::UserNotFound, En) => f.write_str("User not found"),
+            (Self::UserNotFound, De) => f.write_str("Benutzer nicht gefunden"),
+            (Self::InvalidArgument(name), En) => write!(f, "Failed to parse argument '{name}'"),
+            (Self::InvalidArgument(name), De) => write!(f, "Ungültiges Argument '{name}'"),
+            (Self::InvalidArgumentExpected { name, expected }, En) => {
+                write!(f, "Failed to parse argument '{name}' (expected {expected})")
+            }
+            (Self::InvalidArgumentExpected { name, expected }, De) => {
+                write!(f, "Ungültiges Argument '{name}' (erwartet: {expected})")
+            }
+            (Self::InvalidArgumentSuggestion { name, suggestion }, En) => write!(
+                f,
+                "Failed to parse argument '{name}' (did you mean '{suggestion}'?)"
+            ),
+            (Self::InvalidArgumentSuggestion { name, suggestion }, De) => {
+                write!(
+                    f,
+                    "Ungültiges Argument '{name}' (meintest du '{suggestion}'?)"
+                )
+            }
+            (Self::MissingArgument(name), En) => write!(f, "Argument '{name}' is required"),
+            (Self::MissingArgument(name), De) => write!(f, "Argument '{name}' fehlt"),
+            (Self::IncompatibleArguments { key, other, reason }, En) => write!(
+                f,
+                "Arguments '{key}' and '{other}' cannot be combined ({reason})"
+            ),
+            (Self::IncompatibleArguments { key, other, reason }, De) => write!(
+                f,
+                "Die Argumente '{key}' und '{other}' können nicht kombiniert werden ({reason})"
+            ),
+            (Self::ImageDisabled, En) => f.write_str("Image output is disabled"),
+            (Self::ImageDisabled, De) => f.write_str("Die Ausgabe als Bild ist deaktiviert"),
+            (Self::CommandTooLong { max }, En) => write!(
+                f,
+                "Command is too long (at most {max} characters are allowed)"
+            ),
+            (Self::CommandTooLong { max }, De) => write!(
+                f,
+                "Der Befehl ist zu lang (höchstens {max} Zeichen sind erlaubt)"
+            ),
+            (Self::TooManyArguments { max }, En) => {
+                write!(f, "Too many arguments (at most {max} are allowed)")
+            }
+            (Self::TooManyArguments { max }, De) => {
+                write!(f, "Zu viele Argumente (höchstens {max} sind erlaubt)")
+            }
+            (Self::ArgumentTooLong { max }, En) => write!(
+                f,
+                "Argument is too long (at most {max} characters are allowed)"
+            ),
+            (Self::ArgumentTooLong { max }, De) => write!(
+                f,
+                "Ein Argument ist zu lang (höchstens {max} Zeichen sind erlaubt)"
+            ),
+            (Self::FetchFailed { year, status }, En) => {
+                write!(f, "Failed to fetch private leaderboard for {year}")?;
+                status.map_or(Ok(()), |status| write!(f, " ({status})"))
+            }
+            (Self::FetchFailed { year, status }, De) => {
+                write!(
+                    f,
+                    "Das private Leaderboard für {year} konnte nicht abgerufen werden"
+                )?;
+                status.map_or(Ok(()), |status| write!(f, " ({status})"))
+            }
+            (Self::ReadFailed { year }, En) => write!(
+                f,
+                "Failed to read private leaderboard for {year} (the AoC session may have expired)"
+            ),
+            (Self::ReadFailed { year }, De) => write!(
+                f,
+                "Das private Leaderboard für {year} konnte nicht gelesen werden (die AoC-Sitzung \
+                 ist möglicherweise abgelaufen)"
+            ),
+            (Self::NoSchedule(id), En) => write!(f, "There is no schedule #{id}"),
+            (Self::NoSchedule(id), De) => write!(f, "Es gibt keinen Zeitplan #{id}"),
+            (Self::SliceTooLarge, En) => f.write_str(
+                "The requested leaderboard slice would be too large to fit in a matrix message. \
+                 Try to reduce the number of rows.",
+            ),
+            (Self::SliceTooLarge, De) => f.write_str(
+                "Der angeforderte Ausschnitt des Leaderboards passt nicht in eine \
+                 Matrix-Nachricht. Versuche, die Anzahl der Zeilen zu verringern.",
+            ),
+        }
+    }
+
+    pub fn to_locale(self, locale: Locale) -> String {
+        struct Localized<'a>(Message<'a>, Locale);
+
+        impl fmt::Display for Localized<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.write_to(self.1, f)
+            }
+        }
+
+        Localized(self, locale).to_string()
+    }
+}
+
+impl fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_to(current_locale(), f)
+    }
+}
+
+impl From<Message<'_>> for String {
+    fn from(message: Message<'_>) -> Self {
+        message.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate() {
+        let de = |message: Message| message.to_locale(Locale::De);
+        assert_eq!(de(Message::PermissionDenied), "Zugriff verweigert");
+        assert_eq!(de(Message::MissingArgument("day")), "Argument 'day' fehlt");
+        assert_eq!(
+            de(Message::InvalidArgumentSuggestion {
+                name: "p",
+                suggestion: "1,2"
+            }),
+            "Ungültiges Argument 'p' (meintest du '1,2'?)"
+        );
+        assert_eq!(
+            de(Message::InvalidArgumentExpected {
+                name: "format",
+                expected: "table, compact or image"
+            }),
+            "Ungültiges Argument 'format' (erwartet: table, compact or image)"
+        );
+        assert_eq!(
+            de(Message::InvalidArgument("year")),
+            "Ungültiges Argument 'year'"
+        );
+        assert_eq!(
+            de(Message::FetchFailed {
+                year: 2024,
+                status: Some(StatusCode::INTERNAL_SERVER_ERROR)
+            }),
+            "Das private Leaderboard für 2024 konnte nicht abgerufen werden (500 Internal Server \
+             Error)"
+        );
+        assert_eq!(
+            Message::FetchFailed {
+                year: 2024,
+                status: None
+            }
+            .to_locale(Locale::En),
+            "Failed to fetch private leaderboard for 2024"
+        );
+    }
+
+    #[tokio::test]
+    async fn scope() {
+        assert_eq!(Message::UserNotFound.to_string(), "User not found");
+        let translated = with_locale(Locale::De, async { Message::UserNotFound.to_string() }).await;
+        assert_eq!(translated, "Benutzer nicht gefunden");
+    }
+}
@@ -13,18 +13,27 @@ use reqwest::Url;
 use tracing::{error, info};
 
 use crate::{
-    aoc::client::AocClient,
+    aoc::{archive::Archive, client::AocClient, sessions::Sessions},
     context::{Context, ContextGarygrady},
     matrix::create_client,
     utils::store::Store,
 };
 
 mod aoc;
+mod audit;
+mod blocklist;
 mod config;
 mod context;
+mod digest;
+mod i18n;
+mod links;
 mod mastodon;
 mod matrix;
+mod newcomers;
+mod schedule;
 mod tasks;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod utils;
 
 const SESSION_STORE_KEY: &[u8] = b"session";
@@ -136,9 +145,12 @@ pub async fn run(config_path: impl Iterator<Item = &str>) -> anyhow::Result<()>
     info!(user_id = %response.user_id, devicd_id = %response.device_id.unwrap(), "Matrix login successful");
 
     // Advent of Code Login
-    let aoc_session = std::fs::read_to_string(&config.aoc.session_file)?;
+    let aoc_sessions = std::iter::once(&config.aoc.session_file)
+        .chain(&config.aoc.backup_session_files)
+        .map(|path| Ok(std::fs::read_to_string(path)?.trim().to_owned()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
     let aoc_client = AocClient::new(
-        aoc_session.trim(),
+        Sessions::new(&aoc_sessions, &config.aoc.base_url)?,
         Duration::from_secs(config.aoc.default_cache_ttl),
         config
             .aoc
@@ -146,12 +158,22 @@ pub async fn run(config_path: impl Iterator<Item = &str>) -> anyhow::Result<()>
             .iter()
             .map(|r| (r.minutes_after_unlock, Duration::from_secs(r.ttl)))
             .collect(),
+        Duration::from_secs(config.aoc.fresh_interval),
+        config.caches.other_leaderboards,
+        config.caches.puzzle_titles,
+        config.aoc.opt_out_marker.clone(),
+        Archive::open(
+            &config.matrix.store_path.join("archive.sqlite3"),
+            config.aoc.archive_snapshots,
+        )
+        .context("Failed to open the leaderboard archive")?,
         store.clone(),
     )
     .await?;
     info!(
         user_id = aoc_client.whoami().user_id,
         invite_code = aoc_client.whoami().invite_code,
+        sessions = aoc_client.session_count(),
         "AoC login successful"
     );
 
@@ -178,7 +200,7 @@ pub async fn run(config_path: impl Iterator<Item = &str>) -> anyhow::Result<()>
         room.join().await?;
     }
 
-    let context = Arc::new(Context::new(config, store, room, aoc_client, garygrady));
+    let context = Arc::new(Context::new(config, store, room, aoc_client, garygrady).await?);
 
     tasks::start(Arc::clone(&context));
 
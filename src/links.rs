@@ -0,0 +1,203 @@
+//! Links between matrix users and AoC members added by the users themselves using the `link`
+//! command, in addition to the users of the config.
+//!
+//! A link is only added once the user proved that they own the AoC account, by putting the code
+//! of their link request into the name of the AoC member, or once an admin approved the request.
+
+use std::hash::{BuildHasher, RandomState};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use matrix_sdk::ruma::{OwnedUserId, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    aoc::models::AocId,
+    utils::{datetime::now, serde::timestamp, store::Store},
+};
+
+const LINKS_STORE_KEY: &[u8] = b"links";
+const REQUESTS_STORE_KEY: &[u8] = b"link_requests";
+
+pub const REQUEST_TTL: TimeDelta = TimeDelta::days(1);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    pub matrix: OwnedUserId,
+    pub aoc: AocId,
+    pub repo: Option<String>,
+    #[serde(with = "timestamp")]
+    pub ts: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Request {
+    pub matrix: OwnedUserId,
+    pub aoc: AocId,
+    pub repo: Option<String>,
+    pub code: String,
+    #[serde(with = "timestamp")]
+    pub ts: DateTime<Utc>,
+}
+
+impl Request {
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.ts + REQUEST_TTL
+    }
+}
+
+/// The persisted list of links, at most one per matrix user and AoC member, and of the pending
+/// link requests, at most one per matrix user.
+pub struct Links {
+    store: Store,
+    entries: RwLock<Vec<Link>>,
+    requests: RwLock<Vec<Request>>,
+}
+
+impl Links {
+    pub async fn load(store: Store) -> anyhow::Result<Self> {
+        let entries = store
+            .get::<Vec<Link>>(LINKS_STORE_KEY)
+            .await?
+            .unwrap_or_default();
+        let requests = store
+            .get::<Vec<Request>>(REQUESTS_STORE_KEY)
+            .await?
+            .unwrap_or_default();
+        Ok(Self {
+            store,
+            entries: RwLock::new(entries),
+            requests: RwLock::new(requests),
+        })
+    }
+
+    pub async fn all(&self) -> Vec<Link> {
+        self.entries.read().await.clone()
+    }
+
+    pub async fn request(&self, matrix: &UserId) -> Option<Request> {
+        let now = now();
+        self.requests
+            .read()
+            .await
+            .iter()
+            .find(|r| r.matrix == matrix && !r.is_expired(now))
+            .cloned()
+    }
+
+    /// Request a link of the given matrix user to the AoC member, replacing their previous
+    /// request. The code of an unexpired request of the same member is kept, so that users who
+    /// already put it into their name do not have to change it again.
+    pub async fn add_request(
+        &self,
+        matrix: &UserId,
+        aoc: AocId,
+        repo: Option<String>,
+    ) -> anyhow::Result<Request> {
+        let now = now();
+        let mut guard = self.requests.write().await;
+        let previous = guard
+            .iter()
+            .find(|r| r.matrix == matrix && r.aoc == aoc && !r.is_expired(now));
+        let request = Request {
+            matrix: matrix.to_owned(),
+            aoc,
+            repo,
+            code: match previous {
+                Some(previous) => previous.code.clone(),
+                None => generate_code(matrix, aoc),
+            },
+            ts: previous.map_or(now, |p| p.ts),
+        };
+        guard.retain(|r| r.matrix != matrix && !r.is_expired(now));
+        guard.push(request.clone());
+        self.store.set(REQUESTS_STORE_KEY, &*guard).await?;
+        Ok(request)
+    }
+
+    /// Add the link of the given request, replacing the previous link of the matrix user. The
+    /// request proved the ownership of the AoC member, so links of other users to the member are
+    /// removed.
+    pub async fn confirm(&self, request: &Request) -> anyhow::Result<Link> {
+        let mut guard = self.entries.write().await;
+        guard.retain(|l| l.matrix != request.matrix && l.aoc != request.aoc);
+        let link = Link {
+            matrix: request.matrix.clone(),
+            aoc: request.aoc,
+            repo: request.repo.clone(),
+            ts: now(),
+        };
+        guard.push(link.clone());
+        self.store.set(LINKS_STORE_KEY, &*guard).await?;
+        drop(guard);
+
+        let mut guard = self.requests.write().await;
+        guard.retain(|r| r.matrix != request.matrix);
+        self.store.set(REQUESTS_STORE_KEY, &*guard).await?;
+        Ok(link)
+    }
+
+    pub async fn unlink(&self, matrix: &UserId) -> anyhow::Result<bool> {
+        let mut guard = self.entries.write().await;
+        let len = guard.len();
+        guard.retain(|l| l.matrix != matrix);
+        if guard.len() == len {
+            return Ok(false);
+        }
+        self.store.set(LINKS_STORE_KEY, &*guard).await?;
+        Ok(true)
+    }
+}
+
+fn generate_code(matrix: &UserId, aoc: AocId) -> String {
+    // a fresh random state is seeded randomly, so every request gets a new code
+    let hash = RandomState::new().hash_one((matrix, aoc));
+    format!("aocbot-{:08x}", hash as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aoc::mock::{block_on, MockAoc},
+        utils::datetime::set_now,
+    };
+
+    #[test]
+    fn requests() {
+        let _guard = set_now("2024-12-05T12:00:00Z".parse().unwrap());
+        let bob = <&UserId>::try_from("@bob:example.com").unwrap();
+        let mallory = <&UserId>::try_from("@mallory:example.com").unwrap();
+        block_on(async {
+            let links = Links::load(MockAoc::start().await.store().await)
+                .await
+                .unwrap();
+
+            let claim = links.add_request(mallory, 2, None).await.unwrap();
+            let request = links.add_request(bob, 2, None).await.unwrap();
+            assert_ne!(claim.code, request.code);
+            // requesting the same member again keeps the code
+            let repo = Some("https://github.com/bob/aoc".to_owned());
+            let again = links.add_request(bob, 2, repo.clone()).await.unwrap();
+            assert_eq!((&again.code, &again.repo), (&request.code, &repo));
+            assert_eq!(links.request(bob).await, Some(again.clone()));
+
+            // a confirmed request replaces the links of other users to the member
+            links.confirm(&claim).await.unwrap();
+            links.confirm(&again).await.unwrap();
+            let all = links.all().await;
+            assert_eq!(all.len(), 1);
+            assert_eq!((&*all[0].matrix, all[0].aoc, &all[0].repo), (bob, 2, &repo));
+            assert_eq!(links.request(bob).await, None);
+
+            // expired requests are ignored and get a new code
+            let expired = links.add_request(bob, 3, None).await.unwrap();
+            links.requests.write().await[0].ts = now() - REQUEST_TTL;
+            assert_eq!(links.request(bob).await, None);
+            let renewed = links.add_request(bob, 3, None).await.unwrap();
+            assert_eq!(renewed.ts, now());
+            assert_ne!(renewed.code, expired.code);
+            assert_eq!(links.requests.read().await.len(), 1);
+        });
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::{
+    aoc::models::{disambiguated_display_names, ranked_members, PrivateLeaderboard},
+    config::Config,
+    context::Context,
+    matrix::{
+        commands::{
+            aoc::fmt_last_update,
+            args::{parse_rows, parse_year, Rows},
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{html_message, RoomExt},
+    },
+    utils::fmt::{escape_html, fmt_rank},
+};
+
+const CONCURRENT_FETCHES: usize = 3;
+
+type Board<'a> = (&'a str, Option<(PrivateLeaderboard, DateTime<Utc>)>);
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let config = &context.config.aoc.boards;
+    if config.others.is_empty() {
+        return send_error(&room, event, "No other leaderboards are configured").await;
+    }
+
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let rows = match parse_rows(
+        cmd.get_from_kwargs("rows"),
+        config.rows,
+        context.config.aoc.min_rows,
+    ) {
+        Ok(Rows::Count(rows)) => rows.min(config.max_rows),
+        Ok(Rows::All) => config.max_rows,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    let owner = context.aoc_client.whoami().user_id;
+    let own = context.config.aoc.branding.name.as_deref();
+    let boards = [(own.unwrap_or("This leaderboard"), owner)]
+        .into_iter()
+        .chain(config.others.iter().map(|b| (b.name.as_str(), b.id)));
+    let semaphore = Semaphore::new(CONCURRENT_FETCHES);
+    let fetched = join_all(boards.map(|(name, id)| {
+        let semaphore = &semaphore;
+        async move {
+            let _permit = semaphore.acquire().await;
+            let result = context
+                .aoc_client
+                .get_other_private_leaderboard(id, year)
+                .await;
+            if let Err(err) = &result {
+                warn!(name, id, year, "Failed to fetch leaderboard: {err}");
+            }
+            (name, result.ok())
+        }
+    }))
+    .await;
+
+    let html = render(&context.config, year, rows, fetched);
+    room.reply_to(event, html_message(html)).await?;
+
+    Ok(())
+}
+
+pub(super) fn render(config: &Config, year: i32, rows: usize, boards: Vec<Board<'_>>) -> String {
+    let mut out = format!("<h3>Advent of Code {year}: Leaderboards</h3>\n");
+    let mut oldest_update = None::<DateTime<Utc>>;
+    for (name, board) in boards {
+        let Some((leaderboard, last_update)) = board else {
+            writeln!(
+                &mut out,
+                "<p><i>Failed to fetch the leaderboard {name}</i></p>"
+            )
+            .unwrap();
+            continue;
+        };
+        oldest_update = Some(oldest_update.map_or(last_update, |o| o.min(last_update)));
+
+        let (ranked, _) = ranked_members(leaderboard.members.into_values().collect(), 1, true);
+        let total = ranked.len();
+        let shown = ranked.into_iter().take(rows).collect::<Vec<_>>();
+        let names = disambiguated_display_names(shown.iter().map(|(_, m)| m));
+
+        write!(
+            &mut out,
+            "<h4>{name}</h4>\n<table>\n<tr> <th>Rank</th> <th>AoC Name</th> <th>Local Score</th> \
+             <th>Stars</th> </tr>\n"
+        )
+        .unwrap();
+        for (rank, member) in &shown {
+            writeln!(
+                &mut out,
+                "<tr> <td>{}</td> <td>{}</td> <td>{}</td> <td>{}</td> </tr>",
+                fmt_rank(*rank),
+                escape_html(&names[&member.id]),
+                member.local_score,
+                member.stars
+            )
+            .unwrap();
+        }
+        out.push_str("</table>\n");
+        match total - shown.len() {
+            0 => {}
+            1 => out.push_str("<sup>1 more member</sup>\n"),
+            n => writeln!(&mut out, "<sup>{n} more members</sup>").unwrap(),
+        }
+    }
+    if let Some(last_update) = oldest_update {
+        writeln!(
+            &mut out,
+            "<sup>Last update: {}</sup>",
+            fmt_last_update(config, last_update)
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use crate::{
+        aoc::fixtures::{leaderboard, member, YEAR},
+        config::tests::load_with_overrides,
+        utils::datetime::{now, set_now},
+    };
+
+    #[test]
+    fn render() {
+        let _guard = set_now("2024-12-05T12:00:00Z".parse().unwrap());
+        let config = load_with_overrides([]);
+        let ours = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200))]),
+            member(2, "Bob", &[(1, 150, None)]),
+            member(3, "Carol", &[(1, 300, None)]),
+        ]);
+        let theirs = leaderboard([member(4, "Dave", &[(1, 100, None)])]);
+
+        let html = super::render(
+            &config,
+            YEAR,
+            2,
+            vec![
+                ("This leaderboard", Some((ours, now()))),
+                ("Work", None),
+                ("Friends", Some((theirs, now() - TimeDelta::minutes(10)))),
+            ],
+        );
+        assert!(html.contains("<h4>This leaderboard</h4>"));
+        assert!(html.contains("<td>Bob</td>"));
+        assert!(!html.contains("Carol"));
+        assert!(html.contains("<sup>1 more member</sup>"));
+        assert!(html.contains("<p><i>Failed to fetch the leaderboard Work</i></p>"));
+        assert!(html.contains("<h4>Friends</h4>"));
+        assert!(html.contains("<td>Dave</td>"));
+        // the age of the oldest leaderboard is shown
+        assert!(html.contains("Last update: 2024-12-05 11:50:00 +00:00 (🟡 10m old)"));
+    }
+}
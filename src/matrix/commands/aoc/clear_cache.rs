@@ -1,29 +1,93 @@
-use matrix_sdk::{
-    ruma::events::{
-        reaction::ReactionEventContent, relation::Annotation,
-        room::message::OriginalRoomMessageEvent,
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{
+        client::{CacheScope, FIRST_YEAR},
+        day::AocDay,
+    },
+    context::Context,
+    i18n::Message,
+    matrix::{
+        commands::{parser::ParsedCommand, send_error},
+        utils::{notice, RoomExt},
     },
-    Room,
 };
 
-use crate::{context::Context, matrix::commands::send_error};
-
 pub async fn invoke(
     event: &OriginalRoomMessageEvent,
     room: Room,
     context: &Context,
+    cmd: ParsedCommand<'_>,
 ) -> anyhow::Result<()> {
     if !context.config.matrix.admin_ids.contains(&event.sender) {
-        return send_error(&room, event, "Permission denied").await;
+        return send_error(&room, event, Message::PermissionDenied).await;
     }
 
-    context.aoc_client.clear_leaderboard_cache().await?;
+    let board = match cmd.get_from_kwargs("board") {
+        Some("all") | None => None,
+        Some(board) => match board.parse() {
+            Ok(board) => Some(board),
+            Err(_) => return send_error(&room, event, Message::InvalidArgument("board")).await,
+        },
+    };
+    let most_recent_year = AocDay::most_recent().year;
+    let year = match cmd.get_from_kwargs("year") {
+        Some("all") | None => None,
+        Some(y) => match y
+            .parse()
+            .ok()
+            .filter(|y| (FIRST_YEAR..=most_recent_year).contains(y))
+        {
+            Some(y) => Some(y),
+            None => return send_error(&room, event, Message::InvalidArgument("year")).await,
+        },
+    };
 
-    room.send(ReactionEventContent::new(Annotation::new(
-        event.event_id.clone(),
-        "✅️".into(),
-    )))
-    .await?;
+    let scope = CacheScope { board, year };
+    let cleared = context.clear_caches(scope).await?;
+    room.reply_to(event, notice(render(scope, &cleared)))
+        .await?;
 
     Ok(())
 }
+
+fn render(scope: CacheScope, cleared: &[(&str, usize)]) -> String {
+    let board = match scope.board {
+        Some(board) => format!("private leaderboard {board}"),
+        None => "all private leaderboards".into(),
+    };
+    let year = match scope.year {
+        Some(year) => format!("Advent of Code {year}"),
+        None => "all years".into(),
+    };
+    let removed = cleared.iter().map(|(_, n)| n).sum::<usize>();
+    let counts = cleared
+        .iter()
+        .map(|(name, n)| format!("{name} {n}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let entries = if removed == 1 { "entry" } else { "entries" };
+    format!("✅️ Removed {removed} cached {entries} of {board} for {year} ({counts})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let scope = CacheScope {
+            board: Some(42),
+            year: Some(2024),
+        };
+        assert_eq!(
+            super::render(scope, &[("leaderboards", 0), ("other_leaderboards", 2)]),
+            "✅️ Removed 2 cached entries of private leaderboard 42 for Advent of Code 2024 \
+             (leaderboards 0, other_leaderboards 2)"
+        );
+        assert_eq!(
+            super::render(CacheScope::default(), &[("views", 1)]),
+            "✅️ Removed 1 cached entry of all private leaderboards for all years (views 1)"
+        );
+    }
+}
@@ -1,196 +1,572 @@
-use std::fmt::Write;
+use std::{cmp::Ordering, collections::HashMap, fmt::Write};
 
-use chrono::TimeZone;
-use matrix_sdk::{
-    ruma::{api::client::error::ErrorKind, events::room::message::OriginalRoomMessageEvent},
-    Room,
-};
+use chrono::{DateTime, TimeDelta, Utc};
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
 
 use crate::{
-    aoc::{client::Parts, day::AocDay, models::PrivateLeaderboardMember},
-    context::Context,
-    matrix::{
-        commands::{parser::ParsedCommand, send_error},
-        utils::{error_message, html_message, RoomExt},
+    aoc::{
+        client::{Part2Start, Parts, FIRST_YEAR},
+        day::AocDay,
+        models::{
+            disambiguated_display_names, ranked_members, AocId, PrivateLeaderboard,
+            PrivateLeaderboardMember,
+        },
+    },
+    config::Config,
+    context::{Context, ContextUsers},
+    matrix::commands::{
+        aoc::{
+            auto_format,
+            enrich::{mark_newcomers, Enrichment, Enrichments, Pipeline},
+            fit_rows, fmt_last_update, fmt_tagline, leaderboard_heading, prefetch, send_debug,
+            send_leaderboard, Rendered,
+        },
+        args::{
+            check_incompatible, parse_bool, parse_columns, parse_day, parse_format, parse_offset,
+            parse_part2_start, parse_parts, parse_rows, parse_year, Incompatible, Rows,
+            FORMAT_COLLAPSE, FORMAT_COLUMNS, PARTS_PART2_START,
+        },
+        error::{CommandError, CommandResult},
+        parser::ParsedCommand,
     },
+    matrix::utils::Mentions,
     utils::{
-        datetime::DateTimeExt,
-        fmt::{fmt_rank, fmt_timedelta},
+        datetime::{now, DateTimeExt},
+        fmt::{escape_html, fmt_rank, fmt_timedelta},
+        image::TextTable,
+        stats::{mean, median},
     },
 };
 
+const INCOMPATIBLE: &[Incompatible] = &[PARTS_PART2_START, FORMAT_COLUMNS, FORMAT_COLLAPSE];
+
+pub type Ranks = HashMap<AocId, usize>;
+
 pub async fn invoke(
     event: &OriginalRoomMessageEvent,
     room: Room,
     context: &Context,
-    mut cmd: ParsedCommand<'_>,
-) -> anyhow::Result<()> {
-    let day = match cmd
-        .get_from_kwargs_or_args("day")
-        .map(|d| d.parse().ok().filter(|d| (1..=25).contains(d)))
-        .or_else(|| AocDay::current().map(|d| Some(d.day)))
-    {
-        Some(Some(d)) => d,
-        Some(None) => return send_error(&room, event, "Failed to parse argument 'day'").await,
-        None => return send_error(&room, event, "Argument 'day' is required").await,
-    };
+    cmd: ParsedCommand<'_>,
+) -> CommandResult {
+    check_incompatible(&cmd, INCOMPATIBLE)?;
 
-    let most_recent_year = AocDay::most_recent().year;
-    let year = match cmd.get_from_kwargs_or_args("year").map(|y| {
-        y.parse()
-            .ok()
-            .filter(|y| (2015..=most_recent_year).contains(y))
-    }) {
-        Some(Some(y)) => y,
-        Some(None) => return send_error(&room, event, "Failed to parse argument 'year'").await,
-        None => most_recent_year,
+    let day = parse_day(
+        cmd.get_from_kwargs("day"),
+        context.config.aoc.default_day_grace(),
+    )?;
+    let year = parse_year(cmd.get_from_kwargs("year"))?;
+    let parts = parse_parts(
+        cmd.get_from_kwargs("p"),
+        context.config.default_parts(room.room_id()),
+    )?;
+
+    let rows = parse_rows(
+        cmd.get_from_kwargs("rows"),
+        context.config.aoc.leaderboard_rows,
+        context.config.aoc.min_rows,
+    )?;
+
+    let offset = parse_offset(cmd.get_from_kwargs("offset"))?;
+    // columns select the cells of the plain text formats, which is tsv unless requested otherwise
+    let columns = parse_columns(cmd.get_from_kwargs("columns"));
+    let requested_format = cmd
+        .get_from_kwargs("format")
+        .or(columns.as_ref().map(|_| "tsv"));
+    let format = parse_format(requested_format, context.config.matrix.image_format)?;
+
+    let summary = parse_bool(
+        cmd.get_from_kwargs("summary"),
+        "summary",
+        context.config.aoc.day_summary,
+    )?;
+
+    let part2_start = parse_part2_start(
+        cmd.get_from_kwargs("p2_from"),
+        context.config.aoc.part2_start,
+    )?;
+
+    let params = Params {
+        day,
+        year,
+        parts,
+        part2_start,
+        rows,
+        offset,
+        summary,
     };
+    let collapse = parse_bool(
+        cmd.get_from_kwargs("collapse"),
+        "collapse",
+        context.config.matrix.collapse_enabled(room.room_id()),
+    )?;
+    let fresh = parse_bool(cmd.get_from_kwargs("fresh"), "fresh", false)?;
+    let debug = parse_bool(cmd.get_from_kwargs("debug"), "debug", false)?;
 
-    let parts = match cmd.get_from_kwargs_or_args("p") {
-        Some("1") => Parts::P1,
-        Some("2") => Parts::P2,
-        Some("both") | None => Parts::Both,
-        Some(_) => return send_error(&room, event, "Failed to parse argument 'p'").await,
+    // rank movement is only shown for interactive re-runs of today's full leaderboard
+    let tracked = parts == Parts::Both && AocDay::current() == Some(AocDay { year, day });
+    let key = (room.room_id().to_owned(), year, day, parts, part2_start);
+    let previous = if tracked {
+        context.day_ranks.get(&key)
+    } else {
+        None
     };
 
-    let rows = match cmd
-        .get_from_kwargs_or_args("rows")
-        .map(|y| y.parse().ok().filter(|x| (0..=200).contains(x)))
+    let started = now();
+    prefetch(&room, event, context, year, fresh)
+        .await
+        .map_err(|err| CommandError::fetch(year, err))?;
+    let (mut rendered, ranks) = render(context, params, previous.as_ref())
+        .await
+        .map_err(|err| CommandError::fetch(year, err))?;
+    if let Some(columns) = &columns {
+        rendered.table.select_columns(columns)?;
+    }
+    let last_update = rendered.last_update;
+    let format = auto_format(
+        &context.config,
+        format,
+        requested_format.is_some(),
+        &mut rendered,
+    );
+    if tracked {
+        context.day_ranks.insert(key, ranks);
+    }
+
+    send_leaderboard(
+        &room,
+        event,
+        context,
+        format,
+        rendered,
+        params.into(),
+        collapse,
+    )
+    .await?;
+
+    if debug {
+        send_debug(
+            &room,
+            event,
+            started,
+            last_update,
+            &context.aoc_client.star_discrepancies(year).await,
+            &[
+                ("day", day.to_string()),
+                ("year", year.to_string()),
+                ("p", parts.name().into()),
+                ("p2_from", part2_start.name().into()),
+                ("rows", rows.to_string()),
+                ("offset", offset.to_string()),
+                ("summary", summary.to_string()),
+                ("collapse", collapse.to_string()),
+                ("fresh", fresh.to_string()),
+                ("format", format!("{format:?}").to_lowercase()),
+            ],
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params {
+    pub day: u32,
+    pub year: i32,
+    pub parts: Parts,
+    pub part2_start: Part2Start,
+    pub rows: Rows,
+    pub offset: usize,
+    pub summary: bool,
+}
+
+/// Fetch and render the given slice of the daily private leaderboard. If the ranks of a previous
+/// render are given and differ from the current ones, the rank movement of each member is shown.
+/// Also returns the current ranks.
+pub async fn render(
+    context: &Context,
+    params: Params,
+    previous: Option<&Ranks>,
+) -> anyhow::Result<(Rendered, Ranks)> {
+    let (leaderboard, last_update) = context
+        .aoc_client
+        .get_daily_private_leaderboard(
+            params.year,
+            params.day,
+            params.parts,
+            params.part2_start,
+            context.config.aoc.split_ties,
+        )
+        .await?;
+    let title = context
+        .aoc_client
+        .get_puzzle_title(params.year, params.day)
+        .await;
+    // the comparison is optional, so failing to fetch the previous year is ignored
+    let last_year = if params.summary && context.config.aoc.day_compare && params.year > FIRST_YEAR
     {
-        Some(Some(x)) => x,
-        Some(None) => return send_error(&room, event, "Failed to parse argument 'rows'").await,
-        None => context.config.aoc.leaderboard_rows,
+        let day = AocDay {
+            year: params.year - 1,
+            day: params.day,
+        };
+        context
+            .aoc_client
+            .get_daily_private_leaderboard(
+                day.year,
+                day.day,
+                params.parts,
+                params.part2_start,
+                context.config.aoc.split_ties,
+            )
+            .await
+            .ok()
+            .and_then(|(leaderboard, _)| {
+                summary_stats(
+                    leaderboard.members.values(),
+                    day,
+                    params.parts,
+                    params.part2_start,
+                )
+            })
+    } else {
+        None
     };
+    let ranks = ranks(&leaderboard);
+    let previous = previous.filter(|&previous| *previous != ranks);
+    let users = context.users.read().await;
+    let mut enrichments = Pipeline::new(&context.config, &users)
+        .enrich_all(&slice(&leaderboard, params))
+        .await;
+    mark_newcomers(context, params.year, &mut enrichments).await;
+    let rendered = build(
+        &context.config,
+        &users,
+        &enrichments,
+        leaderboard,
+        title.as_deref(),
+        previous,
+        last_year,
+        last_update,
+        params,
+    );
+    Ok((rendered, ranks))
+}
 
-    let offset = match cmd
-        .get_from_kwargs_or_args("offset")
-        .map(|y| y.parse().ok().filter(|x| (0..=200).contains(x)))
-    {
-        Some(Some(x)) => x,
-        Some(None) => return send_error(&room, event, "Failed to parse argument 'offset'").await,
-        None => 0,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SummaryStats {
+    pub finishers: usize,
+    pub median: TimeDelta,
+    pub mean: TimeDelta,
+}
+
+fn summary_stats<'a>(
+    members: impl IntoIterator<Item = &'a PrivateLeaderboardMember>,
+    day: AocDay,
+    parts: Parts,
+    part2_start: Part2Start,
+) -> Option<SummaryStats> {
+    let unlock = day.unlock_datetime();
+    let mut deltas = members
+        .into_iter()
+        .filter(|m| m.stars > 0)
+        .filter(|m| parts != Parts::P2 || m.completion_day_level.contains_key(&day.day))
+        .filter(|m| !(parts == Parts::Both && solved_p1_only(m, day.day)))
+        .filter_map(|m| completion_delta(m, day.day, parts, part2_start, unlock))
+        .collect::<Vec<_>>();
+    Some(SummaryStats {
+        mean: mean(&deltas)?,
+        median: median(&mut deltas)?,
+        finishers: deltas.len(),
+    })
+}
+
+fn fmt_summary(year: i32, stats: SummaryStats, last_year: Option<SummaryStats>) -> String {
+    let noun = |n: i64| {
+        if n.abs() == 1 {
+            "finisher"
+        } else {
+            "finishers"
+        }
     };
+    let mut out = format!(
+        "{} {}, median {}, mean {}",
+        stats.finishers,
+        noun(stats.finishers as i64),
+        fmt_timedelta(stats.median),
+        fmt_timedelta(stats.mean)
+    );
+    if let Some(last_year) = last_year {
+        let finishers = stats.finishers as i64 - last_year.finishers as i64;
+        let median = stats.median - last_year.median;
+        let sign = if median < TimeDelta::zero() { "-" } else { "+" };
+        write!(
+            &mut out,
+            " ({finishers:+} {}, median {sign}{} vs {})",
+            noun(finishers),
+            fmt_timedelta(median.abs()),
+            year - 1
+        )
+        .unwrap();
+    }
+    out
+}
 
-    let (leaderboard, last_update) = match context
-        .aoc_client
-        .get_daily_private_leaderboard(year, day, parts)
-        .await
-    {
-        Ok(resp) => resp,
-        Err(err) => match err.downcast::<reqwest::Error>() {
-            Ok(err) => {
-                if let Some(status) = err.status() {
-                    room.reply_to(
-                        event,
-                        error_message(format!(
-                            "Failed to fetch private leaderboard for {year} ({status})"
-                        )),
-                    )
-                    .await?;
-                    return Ok(());
-                } else {
-                    return Err(err.into());
-                }
-            }
-            Err(err) => return Err(err),
-        },
+pub fn ranks(leaderboard: &PrivateLeaderboard) -> Ranks {
+    let (members, _) = ranked_members(leaderboard.members.values().cloned().collect(), 1, true);
+    members.into_iter().map(|(rank, m)| (m.id, rank)).collect()
+}
+
+/// Render the given slice of the daily private leaderboard as html and as a plain text table.
+/// The puzzle title is shown in the header if it is known, the rank movement of each member if
+/// the previous ranks are given, and the comparison to the same day of the previous year if its
+/// statistics are given.
+#[allow(clippy::too_many_arguments)]
+pub fn build(
+    config: &Config,
+    users: &ContextUsers,
+    enrichments: &Enrichments,
+    leaderboard: PrivateLeaderboard,
+    title: Option<&str>,
+    previous: Option<&Ranks>,
+    last_year: Option<SummaryStats>,
+    last_update: DateTime<Utc>,
+    params: Params,
+) -> Rendered {
+    let build_slice = |leaderboard, rows| {
+        build_slice(
+            config,
+            users,
+            enrichments,
+            leaderboard,
+            title,
+            previous,
+            last_year,
+            last_update,
+            params,
+            rows,
+        )
     };
-    let last_update = context
-        .config
-        .local_timezone
-        .from_utc_datetime(&last_update.naive_utc())
-        .format_ymd_hms_z();
-
-    let mut members = leaderboard.members.into_values().collect::<Vec<_>>();
-    members.sort_unstable();
-
-    let parts_title = match parts {
-        Parts::P1 => "/1",
-        Parts::P2 => "/2",
-        Parts::Both => "",
+    match params.rows {
+        Rows::Count(rows) => build_slice(leaderboard, rows),
+        Rows::All => fit_rows(params.offset, |rows| build_slice(leaderboard.clone(), rows)),
+    }
+}
+
+fn ranked(
+    leaderboard: PrivateLeaderboard,
+    day: u32,
+    parts: Parts,
+) -> Vec<(usize, PrivateLeaderboardMember)> {
+    // part 2 times may be measured from part 1, so members without part 1 data are left out
+    let members = leaderboard
+        .members
+        .into_values()
+        .filter(|m| parts != Parts::P2 || m.completion_day_level.contains_key(&day))
+        .collect();
+    // members who did not solve the day yet are not shown, but still count towards the ranks
+    let (members, _) = ranked_members(members, 1, true);
+    members
+}
+
+/// The members in the given slice of the daily leaderboard, which are the ones to enrich. With
+/// `rows=all`, all members from the offset on are included, as it is not known yet how many
+/// of them fit into the message.
+fn slice(leaderboard: &PrivateLeaderboard, params: Params) -> Vec<PrivateLeaderboardMember> {
+    ranked(leaderboard.clone(), params.day, params.parts)
+        .into_iter()
+        .skip(params.offset)
+        .take(params.rows.limit())
+        .map(|(_, m)| m)
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_slice(
+    config: &Config,
+    users: &ContextUsers,
+    enrichments: &Enrichments,
+    leaderboard: PrivateLeaderboard,
+    title: Option<&str>,
+    previous: Option<&Ranks>,
+    last_year: Option<SummaryStats>,
+    last_update: DateTime<Utc>,
+    params: Params,
+    rows: usize,
+) -> Rendered {
+    let Params {
+        day,
+        year,
+        parts,
+        part2_start,
+        offset,
+        summary,
+        ..
+    } = params;
+
+    let updated_at = last_update;
+    let last_update = fmt_last_update(config, last_update);
+
+    let members = ranked(leaderboard, day, parts);
+
+    let branding = &config.aoc.branding;
+    let heading = leaderboard_heading(branding, year, Some((day, parts)), title);
+    let tagline = fmt_tagline(branding);
+    let completion_header = part2_start.completion_header(parts);
+    let mut table = TextTable {
+        title: heading.clone(),
+        header: [
+            "Rank",
+            "Local Score",
+            "Stars",
+            completion_header,
+            "AoC Name",
+            "Matrix User",
+            "Repository",
+        ]
+        .map(Into::into)
+        .into(),
+        footer: branding
+            .tagline
+            .iter()
+            .cloned()
+            .chain([format!("Last update: {last_update}")])
+            .collect(),
+        ..Default::default()
     };
+
     let mut leaderboard = format!(
         r#"
-<h3>Private Leaderboard (Advent of Code {year}/{day:02}{parts_title})</h3>
-<table>
-<tr> <th>Rank</th> <th>Local Score</th> <th>Stars</th> <th>Completion</th> <th>AoC Name</th> <th>Matrix User</th> <th>Repository</th> </tr>
-"#
+<h3>{}</h3>
+{tagline}<table>
+<tr> <th>Rank</th> <th>Local Score</th> <th>Stars</th> <th>{completion_header}</th> <th>AoC Name</th> <th>Matrix User</th> <th>Repository</th> </tr>
+"#,
+        escape_html(&heading)
     );
 
     let unlock = AocDay { year, day }.unlock_datetime();
+    let par = config.aoc.par_time(year, day);
 
-    let mut last_score = u32::MAX;
-    let mut rank = 0;
-    for (rank, member) in members
-        .into_iter()
-        .enumerate()
-        .map(|(i, member)| {
-            if member.local_score != last_score {
-                last_score = member.local_score;
-                rank = i + 1;
-            }
-            (rank, member)
+    let summary = summary
+        .then(|| {
+            summary_stats(
+                members.iter().map(|(_, m)| m),
+                AocDay { year, day },
+                parts,
+                part2_start,
+            )
         })
-        .filter(|(_, m)| m.stars > 0)
+        .flatten()
+        .map(|stats| fmt_summary(year, stats, last_year));
+
+    let rows = members
+        .into_iter()
         .skip(offset)
         .take(rows)
-    {
-        let PrivateLeaderboardMember {
-            local_score, stars, ..
-        } = member;
-
-        let name = member.display_name();
-
-        let matrix_name = context
-            .users
-            .by_aoc
-            .get(&member.id)
-            .and_then(|u| u.matrix.as_ref())
-            .map(|m| m.matrix_to_uri().to_string())
-            .unwrap_or_default();
+        .collect::<Vec<_>>();
+
+    let names = disambiguated_display_names(rows.iter().map(|(_, m)| m));
+    let mut compact = Vec::with_capacity(rows.len());
+    let mut mentions = Mentions::new(config.matrix.max_mentions);
+    let mut newcomers = Vec::new();
+
+    for (rank, member) in rows {
+        let local_score = member.score();
+        let stars = member.stars;
 
-        let repo = context
-            .users
-            .by_aoc
-            .get(&member.id)
-            .and_then(|u| u.repo.as_deref())
+        let name = &names[&member.id];
+        let html_name = escape_html(name);
+        let id = member.id;
+
+        let Enrichment {
+            matrix_user,
+            repo,
+            newcomer,
+        } = enrichments.get(&member.id).cloned().unwrap_or_default();
+        let (name, html_name) = if newcomer {
+            newcomers.push(id);
+            let badge = &config.aoc.newcomers.badge;
+            (
+                format!("{badge} {name}"),
+                format!("{} {html_name}", escape_html(badge)),
+            )
+        } else {
+            (name.clone(), html_name.into())
+        };
+        let matrix_name = matrix_user
+            .as_ref()
+            .map(|m| mentions.mention_member(&member, m))
             .unwrap_or_default();
-        let repo_title = context
-            .config
-            .aoc
-            .repo_rules
-            .match_and_replace(repo)
-            .map(|m| m.replacement);
-        let repo_title = repo_title.as_deref().unwrap_or(repo);
-
-        let (m, m_) = if rank <= 3 {
+        let (repo, repo_title) = repo.map_or_else(Default::default, |r| (r.url, r.title));
+        let (html_repo, html_repo_title) = (escape_html(&repo), escape_html(&repo_title));
+
+        let highlight = rank <= 3;
+        let (m, m_) = if highlight {
             ("<b>", "</b>")
         } else {
             Default::default()
         };
 
-        let rank = fmt_rank(rank);
-
-        let completion = context
-            .config
-            .local_timezone
-            .from_utc_datetime(&member.last_star_ts.naive_utc())
-            .format_ymd_hms();
-
-        let start = match parts {
-            Parts::P1 | Parts::Both => unlock,
-            Parts::P2 => {
-                member
-                    .completion_day_level
-                    .get(&day)
-                    .unwrap()
-                    .fst
-                    .get_star_ts
-            }
-        };
-        let delta = fmt_timedelta(member.last_star_ts - start);
+        let position = rank;
+        let movement = previous
+            .map(|previous| format!(" {}", fmt_movement(previous.get(&member.id).copied(), rank)))
+            .unwrap_or_default();
+        let rank = format!("{}{movement}", fmt_rank(rank));
+
+        let local_timezone = config.local_timezone;
+        let completion = member.last_star_ts.format_ymd_hms_in(
+            users
+                .by_aoc
+                .get(&member.id)
+                .and_then(|u| u.timezone)
+                .unwrap_or(local_timezone),
+            local_timezone,
+        );
+
+        let time = completion_delta(&member, day, parts, part2_start, unlock);
+        let delta = time
+            .map(|d| fmt_timedelta(d).to_string())
+            .unwrap_or_default();
 
-        let link_prefix = &context.config.matrix.link_prefix;
+        // members who only solved part one so far are ranked among the others, but their
+        // completion time does not refer to the full puzzle
+        let p1_only = parts == Parts::Both && solved_p1_only(&member, day);
+        // par times refer to the full puzzle, they are not shown for single parts
+        let (par, html_par) = par
+            .zip(time)
+            .filter(|_| parts == Parts::Both && !p1_only)
+            .map(|(par, time)| {
+                let (par, color) = fmt_par(time, par);
+                (
+                    format!(", {par}"),
+                    format!(", <span data-mx-color=\"{color}\">{par}</span>"),
+                )
+            })
+            .unwrap_or_default();
+        let p1_only = if p1_only { ", P1 only" } else { "" };
+
+        compact.push(format!(
+            "{position}.{movement} {html_name} — {local_score} ({stars}★, {delta}{p1_only}{html_par})"
+        ));
+
+        table.rows.push((
+            highlight,
+            [
+                rank.to_string(),
+                local_score.to_string(),
+                stars.to_string(),
+                format!("{completion}({delta}{p1_only}{par})"),
+                name,
+                // the plain text formats cannot show ids without risking a notification
+                matrix_user
+                    .filter(|_| !member.no_ping)
+                    .map(|m| m.to_string())
+                    .unwrap_or_default(),
+                repo_title.clone(),
+            ]
+            .into(),
+        ));
+
+        let link_prefix = &config.matrix.link_prefix;
         write!(
             &mut leaderboard,
             r#"
@@ -198,44 +574,504 @@ pub async fn invoke(
     <td>{m}{rank}{m_}</td>
     <td>{m}{local_score}{m_}</td>
     <td>{m}{stars}{m_}</td>
-    <td>{completion}({m}{delta}{m_})</td>
-    <td>{m}{name}{m_}</td>
+    <td>{completion}({m}{delta}{m_}{p1_only}{html_par})</td>
+    <td>{m}<span title="AoC #{id}">{html_name}</span>{m_}</td>
     <td>{matrix_name}</td>
-    <td>{m}<a href="{link_prefix}{repo}">{repo_title}</a>{m_}</td>
+    <td>{m}<a href="{link_prefix}{html_repo}">{html_repo_title}</a>{m_}</td>
 </tr>
 "#
         )
         .unwrap();
     }
 
+    let summary = match summary {
+        Some(summary) => {
+            table.footer.insert(0, summary.clone());
+            format!("<p><i>{summary}</i></p>\n")
+        }
+        None => String::new(),
+    };
+
+    let not_mentioned = mentions
+        .note()
+        .map(|note| format!(", {note}"))
+        .unwrap_or_default();
     write!(
         &mut leaderboard,
         r#"
 </table>
-<sup>Last update: {last_update}</sup>
+{summary}<sup>Last update: {last_update}{not_mentioned}</sup>
 "#
     )
     .unwrap();
 
-    if let Err(err) = room.reply_to(event, html_message(leaderboard)).await {
-        if err
-            .as_client_api_error()
-            .and_then(|err| err.error_kind())
-            .is_some_and(|kind| matches!(kind, ErrorKind::TooLarge))
-        {
-            room.reply_to(
-                event,
-                error_message(
-                    "The requested leaderboard slice would be too large to fit in a matrix \
-                     message. Try to reduce the number of rows.",
-                ),
+    let compact = format!(
+        "\n<h3>{}</h3>\n{tagline}<pre>{}</pre>\n{summary}<sup>Last update: {last_update}</sup>\n",
+        escape_html(&table.title),
+        compact.join("\n"),
+    );
+
+    Rendered {
+        html: leaderboard,
+        compact,
+        table,
+        last_update: updated_at,
+        newcomers,
+    }
+}
+
+/// Format the change from the previous rank of a member: `▲3` (moved up 3 ranks), `▼1` or `—`.
+fn fmt_movement(previous: Option<usize>, rank: usize) -> String {
+    match previous {
+        None => "new".into(),
+        Some(previous) if previous > rank => format!("▲{}", previous - rank),
+        Some(previous) if previous < rank => format!("▼{}", rank - previous),
+        Some(_) => "—".into(),
+    }
+}
+
+fn fmt_par(delta: TimeDelta, par: TimeDelta) -> (String, &'static str) {
+    match delta.cmp(&par) {
+        Ordering::Less => (
+            format!("{} under par", fmt_timedelta(par - delta)),
+            "#00cc00",
+        ),
+        Ordering::Equal => ("at par".into(), "#00cc00"),
+        Ordering::Greater => (
+            format!("{} over par", fmt_timedelta(delta - par)),
+            "#ff6666",
+        ),
+    }
+}
+
+/// Time between the start of the selected parts (the puzzle unlock or, depending on
+/// `part2_start`, the completion of part 1) and the member's last star, which is the last star of
+/// the day after [`score_day`](crate::aoc::client::score_day).
+pub fn completion_delta(
+    member: &PrivateLeaderboardMember,
+    day: u32,
+    parts: Parts,
+    part2_start: Part2Start,
+    unlock: DateTime<Utc>,
+) -> Option<TimeDelta> {
+    let start = match (parts, part2_start) {
+        (Parts::P1 | Parts::Both, _) | (Parts::P2, Part2Start::Unlock) => unlock,
+        (Parts::P2, Part2Start::Part1) => member.completion_day_level.get(&day)?.fst.get_star_ts,
+    };
+    Some(member.last_star_ts - start)
+}
+
+fn solved_p1_only(member: &PrivateLeaderboardMember, day: u32) -> bool {
+    member
+        .completion_day_level
+        .get(&day)
+        .is_some_and(|c| c.snd.is_none())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::{
+        aoc::{
+            client::score_day,
+            fixtures::{leaderboard, member, YEAR},
+        },
+        config::tests::load_with_overrides,
+        matrix::commands::{aoc::enrich::enrich_blocking, parser::parse},
+        utils::{datetime::set_now, snapshot::assert_snapshot},
+    };
+
+    impl Params {
+        pub fn new(year: i32, day: u32) -> Self {
+            Self {
+                day,
+                year,
+                parts: Parts::Both,
+                part2_start: Part2Start::Part1,
+                rows: Rows::Count(20),
+                offset: 0,
+                summary: false,
+            }
+        }
+    }
+
+    pub fn render(config: &Config, leaderboard: &PrivateLeaderboard, params: Params) -> Rendered {
+        let users = ContextUsers::new(config, &[]);
+        let enrichments = enrich_blocking(config, &users, leaderboard);
+        build(
+            config,
+            &users,
+            &enrichments,
+            leaderboard.clone(),
+            None,
+            None,
+            None,
+            now(),
+            params,
+        )
+    }
+
+    fn scored(
+        id: u64,
+        name: &str,
+        local_score: u32,
+        days: &[(u32, i64, Option<i64>)],
+    ) -> PrivateLeaderboardMember {
+        PrivateLeaderboardMember {
+            local_score,
+            ..member(id, name, days)
+        }
+    }
+
+    #[test]
+    fn snapshot() {
+        let now = AocDay { year: YEAR, day: 2 }.unlock_datetime();
+        let _guard = set_now(now);
+
+        let config = load_with_overrides([
+            r#"local_timezone = "+01:00""#,
+            r#"users = [
+                { aoc = 1, matrix = "@alice:example.com", repo = "https://github.com/alice/aoc" },
+                { aoc = 2, matrix = "@bob:example.com", timezone = "-05:00" },
+            ]"#,
+        ]);
+        let users = ContextUsers::new(&config, &[]);
+
+        let leaderboard = leaderboard([
+            scored(1, "Alice", 40, &[(1, 100, Some(200))]),
+            // tied with the anonymous member
+            scored(2, "Bob", 30, &[(1, 150, Some(400))]),
+            scored(3, "", 30, &[(1, 160, Some(3700))]),
+            scored(4, "Dave", 20, &[(1, 300, None)]),
+            scored(5, "Eve", 10, &[(1, 900, Some(1000))]),
+            scored(6, "Mallory", 0, &[]),
+        ]);
+
+        let params = Params {
+            summary: true,
+            ..Params::new(YEAR, 1)
+        };
+        let rendered = build(
+            &config,
+            &users,
+            &enrich_blocking(&config, &users, &leaderboard),
+            leaderboard,
+            Some("Historian Hysteria"),
+            None,
+            None,
+            now - TimeDelta::minutes(2),
+            params,
+        );
+
+        assert_snapshot("src/matrix/commands/aoc/snapshots/day.html", &rendered.html);
+        assert_snapshot(
+            "src/matrix/commands/aoc/snapshots/day_compact.html",
+            &rendered.compact,
+        );
+    }
+
+    #[test]
+    fn part2_without_part1() {
+        let _guard = set_now(AocDay { year: YEAR, day: 2 }.unlock_datetime());
+        let config = load_with_overrides([]);
+
+        let leaderboard = leaderboard([
+            PrivateLeaderboardMember {
+                stars: 1,
+                ..scored(1, "Alice", 20, &[(1, 100, Some(200))])
+            },
+            // inconsistent data: counted for the day, but without any completion
+            PrivateLeaderboardMember {
+                stars: 1,
+                ..scored(2, "Bob", 30, &[])
+            },
+        ]);
+        let params = Params {
+            parts: Parts::P2,
+            summary: true,
+            ..Params::new(YEAR, 1)
+        };
+        let rendered = render(&config, &leaderboard, params);
+        assert!(rendered.compact.contains("1. Alice — 20 (1★, 1m 40s)"));
+        assert!(!rendered.compact.contains("Bob"));
+        assert!(rendered.compact.contains("1 finisher, median"));
+    }
+
+    #[test]
+    fn part2_start() {
+        let _guard = set_now(AocDay { year: YEAR, day: 2 }.unlock_datetime());
+        let config = load_with_overrides([]);
+        // Bob is faster on part 2, but Alice finished both parts first
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(400))]),
+            member(2, "Bob", &[(1, 500, Some(600))]),
+        ]);
+        let rendered = |part2_start| {
+            let mut leaderboard = leaderboard.clone();
+            score_day(&mut leaderboard, 1, Parts::P2, part2_start, false);
+            let params = Params {
+                parts: Parts::P2,
+                part2_start,
+                ..Params::new(YEAR, 1)
+            };
+            render(&config, &leaderboard, params)
+        };
+
+        // timed from part 1, Bob is ranked first
+        let part1 = rendered(Part2Start::Part1);
+        assert!(part1.html.contains("<th>Completion (from part 1)</th>"));
+        assert!(part1
+            .compact
+            .contains("1. Bob — 2 (1★, 1m 40s)\n2. Alice — 1 (1★, 5m 0s)"));
+
+        let unlock = rendered(Part2Start::Unlock);
+        assert!(unlock.html.contains("<th>Completion (from unlock)</th>"));
+        assert_eq!(unlock.table.header[3], "Completion (from unlock)");
+        assert!(unlock
+            .compact
+            .contains("1. Alice — 2 (1★, 6m 40s)\n2. Bob — 1 (1★, 10m 0s)"));
+    }
+
+    #[test]
+    fn incompatible() {
+        let check = |cmd| check_incompatible(&parse(cmd), INCOMPATIBLE);
+
+        assert_eq!(
+            check("day p=2 p2_from=part1 format=compact collapse"),
+            Ok(())
+        );
+        assert_eq!(
+            check("day p=1 p2_from=part1"),
+            Err(
+                "Arguments 'p=1' and 'p2_from' cannot be combined (the start of part 2 only \
+                 applies to the part 2 leaderboard)"
+                    .into()
             )
-            .await?;
-            return Ok(());
-        } else {
-            return Err(err.into());
+        );
+        assert_eq!(
+            check("day format=image collapse=true"),
+            Err(
+                "Arguments 'format=image' and 'collapse' cannot be combined (only html \
+                 leaderboards can be collapsed)"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn par_time() {
+        let _guard = set_now(AocDay { year: YEAR, day: 2 }.unlock_datetime());
+        let config = load_with_overrides(["aoc.par_times = [{ day = 1, seconds = 300 }]"]);
+        let leaderboard = leaderboard([
+            scored(1, "Alice", 30, &[(1, 100, Some(200))]),
+            scored(2, "Bob", 20, &[(1, 150, Some(300))]),
+            scored(3, "Carol", 10, &[(1, 400, Some(1000))]),
+            scored(4, "Dave", 5, &[(1, 500, None)]),
+        ]);
+        let build = |parts| {
+            let params = Params {
+                parts,
+                ..Params::new(YEAR, 1)
+            };
+            render(&config, &leaderboard, params)
+        };
+
+        let rendered = build(Parts::Both);
+        assert!(rendered.html.contains(
+            r##"(<b>3m 20s</b>, <span data-mx-color="#00cc00">1m 40s under par</span>)"##
+        ));
+        assert!(rendered
+            .compact
+            .contains(r##"5m 0s, <span data-mx-color="#00cc00">at par</span>"##));
+        assert!(rendered
+            .compact
+            .contains(r##"16m 40s, <span data-mx-color="#ff6666">11m 40s over par</span>"##));
+        let dave = &rendered.table.rows[3].1[3];
+        assert!(dave.ends_with("(8m 20s, P1 only)"), "{dave}");
+
+        // par times refer to both parts
+        assert!(!build(Parts::P1).html.contains("par"));
+    }
+
+    #[test]
+    fn collapse() {
+        let _guard = set_now(AocDay { year: YEAR, day: 2 }.unlock_datetime());
+        let config = load_with_overrides([]);
+        let users = ContextUsers::new(&config, &[]);
+        let leaderboard = leaderboard([
+            scored(1, "Alice", 20, &[(1, 100, Some(200))]),
+            scored(2, "Bob", 10, &[(1, 150, None)]),
+        ]);
+        let mut rendered = build(
+            &config,
+            &users,
+            &enrich_blocking(&config, &users, &leaderboard),
+            leaderboard,
+            Some("Historian <Hysteria>"),
+            None,
+            None,
+            now(),
+            Params::new(YEAR, 1),
+        );
+        rendered.collapse();
+
+        // the heading moves into the summary
+        for html in [&rendered.html, &rendered.compact] {
+            assert!(html.starts_with(
+                "<details><summary><b>Private Leaderboard (Advent of Code 2024/01: Historian \
+                 &lt;Hysteria&gt;)</b> (2 rows)</summary>\n\n"
+            ));
+            assert!(!html.contains("<h3>"));
+            assert!(html.ends_with("</sup>\n</details>\n"));
         }
     }
 
-    Ok(())
+    #[test]
+    fn branding() {
+        let _guard = set_now(AocDay { year: YEAR, day: 2 }.unlock_datetime());
+        let config = load_with_overrides([
+            r#"aoc.branding = { name = "Rustaceans", tagline = "Fearless puzzle solving" }"#,
+        ]);
+        let users = ContextUsers::new(&config, &[]);
+        let params = Params {
+            parts: Parts::P2,
+            ..Params::new(YEAR, 7)
+        };
+        let rendered = build(
+            &config,
+            &users,
+            &Enrichments::new(),
+            leaderboard([]),
+            Some("Bridge Repair"),
+            None,
+            None,
+            now(),
+            params,
+        );
+        assert_eq!(
+            rendered.table.title,
+            "Rustaceans AoC 2024 — Day 7, Part 2: Bridge Repair"
+        );
+        assert!(rendered
+            .html
+            .contains("<p><i>Fearless puzzle solving</i></p>\n<table>"));
+        assert_eq!(rendered.table.footer[0], "Fearless puzzle solving");
+
+        let rendered = render(&load_with_overrides([]), &leaderboard([]), params);
+        assert_eq!(
+            rendered.table.title,
+            "Private Leaderboard (Advent of Code 2024/07/2)"
+        );
+        assert!(!rendered.html.contains("<p><i>"));
+    }
+
+    #[test]
+    fn escaping() {
+        let _guard = set_now(AocDay { year: YEAR, day: 2 }.unlock_datetime());
+        let config = load_with_overrides([
+            r#"users = [{ aoc = 1, repo = "https://example.com/?a=1&b=\"<x>" }]"#,
+        ]);
+        let users = ContextUsers::new(&config, &[]);
+        let leaderboard =
+            leaderboard([scored(1, "<b>Tom</b> & Jerry", 10, &[(1, 100, Some(200))])]);
+        let rendered = build(
+            &config,
+            &users,
+            &enrich_blocking(&config, &users, &leaderboard),
+            leaderboard,
+            Some("A < B"),
+            None,
+            None,
+            now(),
+            Params::new(YEAR, 1),
+        );
+
+        let name = "&lt;b&gt;Tom&lt;/b&gt; &amp; Jerry";
+        assert!(rendered
+            .html
+            .contains(&format!(r#"<span title="AoC #1">{name}</span>"#)));
+        assert!(rendered
+            .html
+            .contains(r#"<a href="https://example.com/?a=1&amp;b=&quot;&lt;x&gt;">"#));
+        assert!(rendered.html.contains(": A &lt; B)</h3>"));
+        assert!(rendered.compact.contains(&format!("1. {name} — 10")));
+        assert!(!rendered.html.contains("<b>Tom") && !rendered.compact.contains("<b>Tom"));
+        // plain text formats are not escaped
+        assert_eq!(rendered.table.rows[0].1[4], "<b>Tom</b> & Jerry");
+    }
+
+    #[test]
+    fn last_year() {
+        let day = AocDay { year: YEAR, day: 1 };
+        let current = leaderboard([
+            scored(1, "Alice", 20, &[(1, 100, Some(200))]),
+            scored(2, "Bob", 10, &[(1, 150, Some(400))]),
+            scored(3, "Carol", 0, &[(1, 300, None)]),
+        ]);
+        // the fixtures use the same year, which does not matter for the comparison
+        let last_year = leaderboard([scored(1, "Alice", 10, &[(1, 100, Some(500))])]);
+
+        let stats = summary_stats(
+            current.members.values(),
+            day,
+            Parts::Both,
+            Part2Start::Part1,
+        )
+        .unwrap();
+        assert_eq!(stats.finishers, 2);
+        assert_eq!(stats.median, TimeDelta::seconds(300));
+        let last_year = summary_stats(
+            last_year.members.values(),
+            day,
+            Parts::Both,
+            Part2Start::Part1,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fmt_summary(YEAR, stats, Some(last_year)),
+            "2 finishers, median 5m 0s, mean 5m 0s (+1 finisher, median -3m 20s vs 2023)"
+        );
+        assert_eq!(
+            fmt_summary(YEAR, stats, None),
+            "2 finishers, median 5m 0s, mean 5m 0s"
+        );
+    }
+
+    #[test]
+    fn movement() {
+        let _guard = set_now(AocDay { year: YEAR, day: 2 }.unlock_datetime());
+        let config = load_with_overrides([]);
+        let users = ContextUsers::new(&config, &[]);
+
+        let leaderboard = leaderboard([
+            scored(1, "Alice", 30, &[(1, 100, Some(200))]),
+            scored(2, "Bob", 20, &[(1, 150, Some(400))]),
+            scored(3, "Carol", 10, &[(1, 300, Some(500))]),
+            scored(4, "Dave", 5, &[(1, 600, None)]),
+        ]);
+        let ranks = ranks(&leaderboard);
+        assert_eq!(ranks, Ranks::from([(1, 1), (2, 2), (3, 3), (4, 4)]));
+
+        let previous = Ranks::from([(1, 2), (2, 1), (4, 4)]);
+        let rendered = build(
+            &config,
+            &users,
+            &enrich_blocking(&config, &users, &leaderboard),
+            leaderboard,
+            None,
+            Some(&previous),
+            None,
+            now(),
+            Params::new(YEAR, 1),
+        );
+        let lines = rendered.compact.lines().collect::<Vec<_>>();
+        assert!(lines[2].starts_with("<pre>1. ▲1 Alice —"), "{}", lines[2]);
+        assert!(lines[3].starts_with("2. ▼1 Bob —"), "{}", lines[3]);
+        assert!(lines[4].starts_with("3. new Carol —"), "{}", lines[4]);
+        assert!(lines[5].starts_with("4. — Dave —"), "{}", lines[5]);
+        assert!(rendered.html.contains("<td><b>🥇 1st ▲1</b></td>"));
+    }
 }
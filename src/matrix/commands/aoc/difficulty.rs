@@ -0,0 +1,226 @@
+use std::{cmp::Reverse, fmt::Write};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{day::AocDay, models::PrivateLeaderboard},
+    config::Config,
+    context::Context,
+    i18n::Message,
+    matrix::{
+        commands::{
+            aoc::{fmt_last_update, send_fetch_error},
+            args::parse_year,
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{html_message, RoomExt},
+    },
+    utils::{fmt::fmt_timedelta, stats::median, suggest::closest},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// Median time from the unlock to the second star.
+    Time,
+    /// Median time from the first to the second star.
+    Part2,
+    /// Fraction of participating members who earned both stars.
+    Completion,
+}
+
+impl Metric {
+    const NAMES: &[&str] = &["time", "p2", "completion"];
+
+    fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(|v| v.to_lowercase()).as_deref() {
+            None | Some("time") => Ok(Self::Time),
+            Some("p2") => Ok(Self::Part2),
+            Some("completion") => Ok(Self::Completion),
+            Some(value) => Err(match closest(value, Self::NAMES) {
+                Some(suggestion) => Message::InvalidArgumentSuggestion {
+                    name: "by",
+                    suggestion,
+                }
+                .into(),
+                None => Message::InvalidArgumentExpected {
+                    name: "by",
+                    expected: "time, p2 or completion",
+                }
+                .into(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difficulty {
+    pub day: u32,
+    pub finishers: usize,
+    pub participants: usize,
+    pub median: Option<TimeDelta>,
+}
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let metric = match Metric::parse(cmd.get_from_kwargs("by")) {
+        Ok(metric) => metric,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    let (leaderboard, last_update) = match context.aoc_client.get_private_leaderboard(year).await {
+        Ok(resp) => resp,
+        Err(err) => return send_fetch_error(&room, event, year, err).await,
+    };
+
+    let most_recent = AocDay::most_recent();
+    let days = if year == most_recent.year {
+        most_recent.day
+    } else {
+        25
+    };
+    let difficulties = (1..=days)
+        .map(|day| difficulty(&leaderboard, AocDay { year, day }, metric))
+        .collect();
+
+    let html = render(&context.config, year, metric, difficulties, last_update);
+    room.reply_to(event, html_message(html)).await?;
+
+    Ok(())
+}
+
+pub fn difficulty(leaderboard: &PrivateLeaderboard, day: AocDay, metric: Metric) -> Difficulty {
+    let unlock = day.unlock_datetime();
+    let participants = leaderboard.members.values().filter(|m| m.stars > 0).count();
+    let mut deltas = leaderboard
+        .members
+        .values()
+        .filter_map(|m| {
+            let completion = m.completion_day_level.get(&day.day)?;
+            let p2 = completion.snd.as_ref()?.get_star_ts;
+            Some(match metric {
+                Metric::Part2 => p2 - completion.fst.get_star_ts,
+                Metric::Time | Metric::Completion => p2 - unlock,
+            })
+        })
+        .collect::<Vec<_>>();
+    Difficulty {
+        day: day.day,
+        finishers: deltas.len(),
+        participants,
+        median: median(&mut deltas),
+    }
+}
+
+/// Sort the days from hardest to easiest. Days nobody finished are the hardest, ties of the
+/// completion rate are broken by the median time.
+pub fn sort(difficulties: &mut [Difficulty], metric: Metric) {
+    match metric {
+        Metric::Time | Metric::Part2 => {
+            difficulties.sort_by_key(|d| (d.median.is_some(), Reverse(d.median), d.day))
+        }
+        Metric::Completion => difficulties.sort_by_key(|d| {
+            // compare finishers / participants without floating point numbers
+            let ratio = d.finishers * 1000 / d.participants.max(1);
+            (ratio, Reverse(d.median), d.day)
+        }),
+    }
+}
+
+fn render(
+    config: &Config,
+    year: i32,
+    metric: Metric,
+    mut difficulties: Vec<Difficulty>,
+    last_update: DateTime<Utc>,
+) -> String {
+    sort(&mut difficulties, metric);
+    let column = match metric {
+        Metric::Time => "Median Time",
+        Metric::Part2 => "Median Part 2",
+        Metric::Completion => "Completion",
+    };
+
+    let mut out = format!(
+        "<h3>Advent of Code {year}: Days by Difficulty</h3>\n<table>\n<tr> <th>Rank</th> \
+         <th>Day</th> <th>{column}</th> <th>Finishers</th> </tr>\n"
+    );
+    for (i, d) in difficulties.iter().enumerate() {
+        let median = d.median.map(|m| fmt_timedelta(m).to_string());
+        let value = match metric {
+            Metric::Completion => format!("{}%", d.finishers * 100 / d.participants.max(1)),
+            Metric::Time | Metric::Part2 => median.unwrap_or_else(|| "—".into()),
+        };
+        writeln!(
+            &mut out,
+            "<tr> <td>{}</td> <td>{}</td> <td>{value}</td> <td>{}/{}</td> </tr>",
+            i + 1,
+            d.day,
+            d.finishers,
+            d.participants
+        )
+        .unwrap();
+    }
+    out.push_str("</table>\n");
+    writeln!(
+        &mut out,
+        "<sup>Hardest days first, last update: {}</sup>",
+        fmt_last_update(config, last_update)
+    )
+    .unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::fixtures::{leaderboard, member, YEAR};
+
+    #[test]
+    fn difficulty() {
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200)), (2, 1000, Some(5000))]),
+            member(2, "Bob", &[(1, 300, Some(400)), (2, 2000, None)]),
+            member(3, "Carol", &[(1, 500, Some(2000))]),
+        ]);
+        let day = |day| AocDay { year: YEAR, day };
+
+        let d1 = super::difficulty(&leaderboard, day(1), Metric::Time);
+        assert_eq!(d1.median, Some(TimeDelta::seconds(400)));
+        assert_eq!((d1.finishers, d1.participants), (3, 3));
+        let d1 = super::difficulty(&leaderboard, day(1), Metric::Part2);
+        assert_eq!(d1.median, Some(TimeDelta::seconds(100)));
+
+        let d2 = super::difficulty(&leaderboard, day(2), Metric::Time);
+        let d3 = super::difficulty(&leaderboard, day(3), Metric::Time);
+        assert_eq!((d2.finishers, d3.finishers), (1, 0));
+        assert_eq!(d3.median, None);
+
+        let mut days = [d1, d2, d3];
+        sort(&mut days, Metric::Time);
+        assert_eq!(days.map(|d| d.day), [3, 2, 1]);
+        let mut days =
+            [1, 2, 3].map(|d| super::difficulty(&leaderboard, day(d), Metric::Completion));
+        sort(&mut days, Metric::Completion);
+        assert_eq!(days.map(|d| d.day), [3, 2, 1]);
+    }
+
+    #[test]
+    fn metric() {
+        assert_eq!(Metric::parse(None), Ok(Metric::Time));
+        assert_eq!(Metric::parse(Some("Completion")), Ok(Metric::Completion));
+        assert_eq!(
+            Metric::parse(Some("completoin")),
+            Err("Failed to parse argument 'by' (did you mean 'completion'?)".into())
+        );
+    }
+}
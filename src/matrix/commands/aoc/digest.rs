@@ -0,0 +1,60 @@
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    context::Context,
+    matrix::{
+        commands::{args::parse_bool, parser::ParsedCommand, send_error},
+        utils::{notice, RoomExt},
+    },
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let users = context.users.read().await;
+    let linked = users
+        .by_matrix
+        .get(&event.sender)
+        .is_some_and(|u| u.aoc.is_some());
+    if !linked {
+        return send_error(
+            &room,
+            event,
+            "Your matrix account is not linked to an AoC account",
+        )
+        .await;
+    }
+
+    let action = cmd.get_from_kwargs("action");
+    let message = match action {
+        None => match context.digests.is_subscribed(&event.sender).await {
+            true => "You are subscribed to the daily digest".into(),
+            false => "You are not subscribed to the daily digest".into(),
+        },
+        Some(action) => {
+            let subscribed = match parse_bool(Some(action), "action", false) {
+                Ok(subscribed) => subscribed,
+                Err(err) => return send_error(&room, event, err).await,
+            };
+            let changed = context.digests.set(&event.sender, subscribed).await?;
+            match (subscribed, changed) {
+                (true, true) => format!(
+                    "✅️ Subscribed to the daily digest. After finishing a day, you will receive a \
+                     private message summarizing your progress (except between {} and {}).",
+                    context.config.digest.quiet_hours.start.format("%H:%M"),
+                    context.config.digest.quiet_hours.end.format("%H:%M"),
+                ),
+                (true, false) => "You are already subscribed to the daily digest".into(),
+                (false, true) => "✅️ Unsubscribed from the daily digest".into(),
+                (false, false) => "You are not subscribed to the daily digest".into(),
+            }
+        }
+    };
+
+    room.reply_to(event, notice(message)).await?;
+
+    Ok(())
+}
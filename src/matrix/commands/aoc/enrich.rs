@@ -0,0 +1,318 @@
+//! Extra data about leaderboard members, which is derived once per member before rendering. The
+//! enrichers to run are configured using `aoc.enrichers`. Enrichers may be slow, so only the
+//! members being rendered are enriched, concurrently, and a member whose enricher exceeds its
+//! timeout is rendered without it.
+
+use std::{collections::HashMap, future::Future, pin::Pin, time::Duration};
+
+use futures_util::future::join_all;
+use matrix_sdk::ruma::OwnedUserId;
+use serde::Deserialize;
+use tokio::time::timeout;
+use tracing::warn;
+
+use crate::{
+    aoc::models::{AocId, PrivateLeaderboardMember},
+    config::Config,
+    context::{Context, ContextUsers},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnricherKind {
+    Matrix,
+    Repo,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Enrichment {
+    pub matrix_user: Option<OwnedUserId>,
+    pub repo: Option<Repo>,
+    pub newcomer: bool,
+}
+
+pub type Enrichments = HashMap<AocId, Enrichment>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repo {
+    pub url: String,
+    pub title: String,
+}
+
+pub type EnrichFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+pub trait Enricher: Send + Sync {
+    fn enrich<'a>(
+        &'a self,
+        member: &'a PrivateLeaderboardMember,
+        enrichment: &'a mut Enrichment,
+    ) -> EnrichFuture<'a>;
+}
+
+struct MatrixLink<'a> {
+    users: &'a ContextUsers,
+}
+
+impl Enricher for MatrixLink<'_> {
+    fn enrich<'a>(
+        &'a self,
+        member: &'a PrivateLeaderboardMember,
+        enrichment: &'a mut Enrichment,
+    ) -> EnrichFuture<'a> {
+        enrichment.matrix_user = self
+            .users
+            .by_aoc
+            .get(&member.id)
+            .and_then(|u| u.matrix.clone());
+        Box::pin(async {})
+    }
+}
+
+struct RepoLink<'a> {
+    users: &'a ContextUsers,
+}
+
+impl Enricher for RepoLink<'_> {
+    fn enrich<'a>(
+        &'a self,
+        member: &'a PrivateLeaderboardMember,
+        enrichment: &'a mut Enrichment,
+    ) -> EnrichFuture<'a> {
+        enrichment.repo = self
+            .users
+            .resolved
+            .get(&member.id)
+            .and_then(|u| u.repo.clone());
+        Box::pin(async {})
+    }
+}
+
+pub struct Pipeline<'a> {
+    enrichers: Vec<(EnricherKind, Duration, Box<dyn Enricher + 'a>)>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(config: &'a Config, users: &'a ContextUsers) -> Self {
+        let enrichers = config
+            .aoc
+            .enrichers
+            .iter()
+            .map(|&kind| {
+                let enricher: Box<dyn Enricher> = match kind {
+                    EnricherKind::Matrix => Box::new(MatrixLink { users }),
+                    EnricherKind::Repo => Box::new(RepoLink { users }),
+                };
+                (kind, config.aoc.enricher_timeout(kind), enricher)
+            })
+            .collect();
+        Self { enrichers }
+    }
+
+    /// Enrich the given members, which should only be the members being rendered. The members
+    /// are enriched concurrently and each enricher gets its timeout per member, members it did not
+    /// finish in time keep the values of the previous enrichers.
+    pub async fn enrich_all<'m>(
+        &self,
+        members: impl IntoIterator<Item = &'m PrivateLeaderboardMember>,
+    ) -> Enrichments {
+        let members = members.into_iter().collect::<Vec<_>>();
+        let mut enrichments = members
+            .iter()
+            .map(|m| (m.id, Enrichment::default()))
+            .collect::<Enrichments>();
+        for (kind, limit, enricher) in &self.enrichers {
+            let enrichments_ref = &enrichments;
+            let results = join_all(members.iter().map(|&member| async move {
+                // a timed out enricher must not leave a partial enrichment behind
+                let mut draft = enrichments_ref[&member.id].clone();
+                timeout(*limit, enricher.enrich(member, &mut draft))
+                    .await
+                    .ok()
+                    .map(|()| (member.id, draft))
+            }))
+            .await;
+            let skipped = results.iter().filter(|r| r.is_none()).count();
+            enrichments.extend(results.into_iter().flatten());
+            if skipped > 0 {
+                warn!(
+                    enricher = ?kind,
+                    timeout = ?limit,
+                    skipped,
+                    "Enricher timed out, rendering these members without it"
+                );
+            }
+        }
+        enrichments
+    }
+}
+
+/// Mark the enriched members of the given year who recently joined the leaderboard. The rendering
+/// only counts towards clearing their badge once it is sent, see [`Rendered::newcomers`].
+///
+/// [`Rendered::newcomers`]: super::Rendered::newcomers
+pub async fn mark_newcomers(context: &Context, year: i32, enrichments: &mut Enrichments) {
+    let newcomers = context
+        .newcomers
+        .badged(year, &context.config.aoc.newcomers)
+        .await;
+    for id in newcomers {
+        if let Some(enrichment) = enrichments.get_mut(&id) {
+            enrichment.newcomer = true;
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn enrich_blocking(
+    config: &Config,
+    users: &ContextUsers,
+    leaderboard: &crate::aoc::models::PrivateLeaderboard,
+) -> Enrichments {
+    crate::aoc::mock::block_on(
+        Pipeline::new(config, users).enrich_all(leaderboard.members.values()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::Instant;
+
+    use super::*;
+    use crate::{
+        aoc::{
+            fixtures::{leaderboard, member},
+            mock::block_on,
+        },
+        config::tests::load_with_overrides,
+    };
+
+    struct Slow(Duration);
+
+    impl Enricher for Slow {
+        fn enrich<'a>(
+            &'a self,
+            _member: &'a PrivateLeaderboardMember,
+            enrichment: &'a mut Enrichment,
+        ) -> EnrichFuture<'a> {
+            Box::pin(async move {
+                tokio::time::sleep(self.0).await;
+                enrichment.repo = Some(Repo {
+                    url: "https://example.com/slow".into(),
+                    title: "slow".into(),
+                });
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout() {
+        let config = load_with_overrides([
+            r#"users = [{ aoc = 1, matrix = "@alice:example.com" }]"#,
+            "aoc.enricher_timeouts = { repo = 500 }",
+        ]);
+        assert_eq!(
+            config.aoc.enricher_timeout(EnricherKind::Repo),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            config.aoc.enricher_timeout(EnricherKind::Matrix),
+            Duration::from_millis(config.aoc.enricher_timeout)
+        );
+        let users = ContextUsers::new(&config, &[]);
+        let timeout = Duration::from_millis(50);
+        let pipeline = Pipeline {
+            enrichers: vec![
+                (
+                    EnricherKind::Matrix,
+                    timeout,
+                    Box::new(MatrixLink { users: &users }),
+                ),
+                (
+                    EnricherKind::Repo,
+                    timeout,
+                    Box::new(Slow(Duration::from_secs(10))),
+                ),
+            ],
+        };
+        let leaderboard = leaderboard([member(1, "Alice", &[]), member(2, "Bob", &[])]);
+
+        let start = Instant::now();
+        let enrichments = pipeline.enrich_all(leaderboard.members.values()).await;
+        assert_eq!(start.elapsed(), timeout);
+        // the slow enricher is skipped, the results of the others are kept
+        assert_eq!(
+            enrichments[&1],
+            Enrichment {
+                matrix_user: Some("@alice:example.com".try_into().unwrap()),
+                repo: None,
+                newcomer: false,
+            }
+        );
+        assert_eq!(enrichments[&2], Enrichment::default());
+
+        // the members are enriched concurrently, each within its own timeout
+        let members = (1..=10).map(|id| member(id, "", &[])).collect::<Vec<_>>();
+        let pipeline = Pipeline {
+            enrichers: vec![(
+                EnricherKind::Repo,
+                timeout,
+                Box::new(Slow(Duration::from_millis(30))),
+            )],
+        };
+        let start = Instant::now();
+        let enrichments = pipeline.enrich_all(&members).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(30));
+        assert_eq!(enrichments.len(), 10);
+        assert!(enrichments
+            .values()
+            .all(|e| e.repo.as_ref().unwrap().title == "slow"));
+    }
+
+    #[test]
+    fn pipeline() {
+        let users_toml = r#"users = [
+            { aoc = 1, matrix = "@alice:example.com", repo = "https://github.com/alice/aoc" },
+            { aoc = 2, repo = "https://example.com/bob" },
+        ]"#;
+        let config = load_with_overrides([users_toml]);
+        let users = ContextUsers::new(&config, &[]);
+        let pipeline = Pipeline::new(&config, &users);
+        let enrich = |pipeline: &Pipeline<'_>, member: PrivateLeaderboardMember| {
+            block_on(pipeline.enrich_all([&member]))
+                .remove(&member.id)
+                .unwrap()
+        };
+
+        assert_eq!(
+            enrich(&pipeline, member(1, "Alice", &[])),
+            Enrichment {
+                matrix_user: Some("@alice:example.com".try_into().unwrap()),
+                repo: Some(Repo {
+                    url: "https://github.com/alice/aoc".into(),
+                    title: "alice/aoc".into(),
+                }),
+                newcomer: false,
+            }
+        );
+        assert_eq!(
+            enrich(&pipeline, member(2, "Bob", &[])).repo.unwrap().title,
+            "https://example.com/bob"
+        );
+        assert_eq!(enrich(&pipeline, member(3, "", &[])), Enrichment::default());
+
+        // the repo titles are resolved once, not per render
+        let mut cached = ContextUsers::new(&config, &[]);
+        if let Some(repo) = cached.resolved.get_mut(&2).and_then(|u| u.repo.as_mut()) {
+            repo.title = "cached".into();
+        }
+        let pipeline = Pipeline::new(&config, &cached);
+        assert_eq!(
+            enrich(&pipeline, member(2, "Bob", &[])).repo.unwrap().title,
+            "cached"
+        );
+
+        let config = load_with_overrides([users_toml, r#"aoc.enrichers = ["repo"]"#]);
+        let pipeline = Pipeline::new(&config, &users);
+        assert_eq!(enrich(&pipeline, member(1, "Alice", &[])).matrix_user, None);
+    }
+}
@@ -0,0 +1,123 @@
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::models::{ranked_members, AocId, PrivateLeaderboardMember},
+    context::Context,
+    i18n::Message,
+    matrix::{
+        commands::{aoc::send_fetch_error, args::parse_year, parser::ParsedCommand, send_error},
+        utils::{notice, RoomExt},
+    },
+    utils::fmt::{escape_html, fmt_rank},
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let users = context.users.read().await;
+    let Some(aoc_id) = users.by_matrix.get(&event.sender).and_then(|u| u.aoc) else {
+        return send_error(
+            &room,
+            event,
+            "Your matrix account is not linked to an AoC account",
+        )
+        .await;
+    };
+
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let leaderboard = match context.aoc_client.get_private_leaderboard(year).await {
+        Ok((leaderboard, _)) => leaderboard,
+        Err(err) => return send_fetch_error(&room, event, year, err).await,
+    };
+
+    let (ranked, _) = ranked_members(leaderboard.members.into_values().collect(), 0, true);
+    let Some(message) = render(&ranked, aoc_id) else {
+        return send_error(&room, event, Message::UserNotFound).await;
+    };
+
+    room.reply_to(event, notice(message)).await?;
+
+    Ok(())
+}
+
+/// Describe the local score difference of the given member to the members ranked directly above
+/// and below them, or `None` if the member is not on the leaderboard.
+pub(super) fn render(ranked: &[(usize, PrivateLeaderboardMember)], id: AocId) -> Option<String> {
+    let i = ranked.iter().position(|(_, m)| m.id == id)?;
+    let (rank, member) = &ranked[i];
+    let score = member.local_score;
+
+    let gap = |(rank, other): &(usize, PrivateLeaderboardMember), ahead: bool| {
+        let name = escape_html(&other.display_name()).into_owned();
+        let rank = fmt_rank(*rank);
+        let diff = score.abs_diff(other.local_score);
+        let points = if diff == 1 { "point" } else { "points" };
+        let direction = if ahead { "ahead of" } else { "behind" };
+        if diff == 0 {
+            format!("tied with **{name}** ({rank})")
+        } else {
+            format!("{diff} {points} {direction} **{name}** ({rank})")
+        }
+    };
+    let above = i.checked_sub(1).map(|j| gap(&ranked[j], false));
+    let below = ranked.get(i + 1).map(|next| gap(next, true));
+    let gaps = above.into_iter().chain(below).collect::<Vec<_>>();
+
+    let mut out = format!("You are {} with {score} points", fmt_rank(*rank));
+    if gaps.is_empty() {
+        out.push_str(", the only member of the leaderboard");
+    } else {
+        out.push_str(": ");
+        out.push_str(&gaps.join(" and "));
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::fixtures::member;
+
+    #[test]
+    fn render() {
+        let scored = |id, name, local_score| PrivateLeaderboardMember {
+            local_score,
+            ..member(id, name, &[])
+        };
+        let (ranked, _) = ranked_members(
+            vec![
+                scored(1, "Alice", 120),
+                scored(2, "Bob", 105),
+                scored(3, "Carol", 104),
+                scored(4, "Dave", 104),
+            ],
+            0,
+            true,
+        );
+
+        assert_eq!(
+            super::render(&ranked, 1).unwrap(),
+            "You are 🥇 1st with 120 points: 15 points ahead of **Bob** (🥈 2nd)"
+        );
+        assert_eq!(
+            super::render(&ranked, 2).unwrap(),
+            "You are 🥈 2nd with 105 points: 15 points behind **Alice** (🥇 1st) and 1 point \
+             ahead of **Carol** (🥉 3rd)"
+        );
+        assert_eq!(
+            super::render(&ranked, 4).unwrap(),
+            "You are 🥉 3rd with 104 points: tied with **Carol** (🥉 3rd)"
+        );
+        assert_eq!(super::render(&ranked, 5), None);
+        assert_eq!(
+            super::render(&ranked[..1], 1).unwrap(),
+            "You are 🥇 1st with 120 points, the only member of the leaderboard"
+        );
+    }
+}
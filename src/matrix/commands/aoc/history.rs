@@ -0,0 +1,189 @@
+use std::{collections::HashMap, fmt::Write};
+
+use chrono::{DateTime, Utc};
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{
+        client::{score_day, Part2Start, Parts},
+        day::AocDay,
+        models::{disambiguated_display_names, ranked_members, AocId, PrivateLeaderboard},
+    },
+    config::Config,
+    context::Context,
+    matrix::{
+        commands::{
+            aoc::{fmt_last_update, send_fetch_error},
+            args::{parse_rows, parse_year, Rows},
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{html_message, RoomExt},
+    },
+    utils::fmt::escape_html,
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let config = &context.config.aoc.history;
+
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let rows = match parse_rows(
+        cmd.get_from_kwargs("rows"),
+        config.rows,
+        context.config.aoc.min_rows,
+    ) {
+        Ok(Rows::Count(rows)) => rows.min(config.max_rows),
+        Ok(Rows::All) => config.max_rows,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    let (leaderboard, last_update) = match context.aoc_client.get_private_leaderboard(year).await {
+        Ok(resp) => resp,
+        Err(err) => return send_fetch_error(&room, event, year, err).await,
+    };
+
+    let most_recent = AocDay::most_recent();
+    let days = if year == most_recent.year {
+        most_recent.day
+    } else {
+        25
+    };
+
+    let html = render(&context.config, year, days, rows, leaderboard, last_update);
+    room.reply_to(event, html_message(html)).await?;
+
+    Ok(())
+}
+
+/// The rank of each member after each of the first `days` days, computed from the scores of the
+/// days up to and including it. Members without stars so far are not ranked.
+pub fn rank_history(
+    leaderboard: &PrivateLeaderboard,
+    days: u32,
+) -> HashMap<AocId, Vec<Option<usize>>> {
+    let mut totals = HashMap::<AocId, (u32, u32)>::new();
+    let mut history = HashMap::<AocId, Vec<Option<usize>>>::new();
+    for day in 1..=days {
+        let mut scored = leaderboard.clone();
+        score_day(&mut scored, day, Parts::Both, Part2Start::default(), false);
+        for member in scored.members.values() {
+            let (score, stars) = totals.entry(member.id).or_default();
+            *score += member.local_score;
+            *stars += member.stars;
+        }
+
+        for (&id, &(score, stars)) in &totals {
+            let rank = (stars > 0)
+                .then(|| 1 + totals.values().filter(|&&(other, _)| other > score).count());
+            history.entry(id).or_default().push(rank);
+        }
+    }
+    history
+}
+
+pub(super) fn render(
+    config: &Config,
+    year: i32,
+    days: u32,
+    rows: usize,
+    leaderboard: PrivateLeaderboard,
+    last_update: DateTime<Utc>,
+) -> String {
+    let history = rank_history(&leaderboard, days);
+    let (ranked, _) = ranked_members(leaderboard.members.into_values().collect(), 1, true);
+    let shown = ranked.into_iter().take(rows).collect::<Vec<_>>();
+    let names = disambiguated_display_names(shown.iter().map(|(_, m)| m));
+
+    let mut out =
+        format!("<h3>Advent of Code {year}: Rank History</h3>\n<table>\n<tr> <th>AoC Name</th>");
+    for day in 1..=days {
+        write!(&mut out, " <th>{day}</th>").unwrap();
+    }
+    out.push_str(" </tr>\n");
+
+    for (_, member) in &shown {
+        write!(
+            &mut out,
+            "<tr> <td>{}</td>",
+            escape_html(&names[&member.id])
+        )
+        .unwrap();
+        for rank in history.get(&member.id).into_iter().flatten() {
+            match rank {
+                Some(rank) => write!(&mut out, " <td>{rank}</td>").unwrap(),
+                None => out.push_str(" <td>·</td>"),
+            }
+        }
+        out.push_str(" </tr>\n");
+    }
+    out.push_str("</table>\n");
+
+    writeln!(
+        &mut out,
+        "<sup>Last update: {}</sup>",
+        fmt_last_update(config, last_update)
+    )
+    .unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        aoc::{
+            fixtures::{leaderboard, member, YEAR},
+            models::PrivateLeaderboardMember,
+        },
+        config::tests::load_with_overrides,
+        utils::datetime::{now, set_now},
+    };
+
+    #[test]
+    fn rank_history() {
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200)), (2, 500, None)]),
+            member(2, "Bob", &[(1, 150, Some(250)), (2, 100, Some(200))]),
+            member(3, "Carol", &[(2, 600, None)]),
+        ]);
+
+        let history = super::rank_history(&leaderboard, 3);
+        // Alice leads after day 1, Bob overtakes her on day 2, Carol starts on day 2
+        assert_eq!(history[&1], [Some(1), Some(2), Some(2)]);
+        assert_eq!(history[&2], [Some(2), Some(1), Some(1)]);
+        assert_eq!(history[&3], [None, Some(3), Some(3)]);
+    }
+
+    #[test]
+    fn render() {
+        let _guard = set_now("2024-12-03T12:00:00Z".parse().unwrap());
+        let config = load_with_overrides([]);
+        let leaderboard = leaderboard([
+            PrivateLeaderboardMember {
+                local_score: 8,
+                ..member(1, "Alice", &[(1, 100, Some(200)), (2, 500, None)])
+            },
+            PrivateLeaderboardMember {
+                local_score: 10,
+                ..member(2, "Bob", &[(1, 150, Some(250)), (2, 100, Some(200))])
+            },
+            PrivateLeaderboardMember {
+                local_score: 1,
+                ..member(3, "Carol", &[(2, 600, None)])
+            },
+        ]);
+
+        let html = super::render(&config, YEAR, 2, 2, leaderboard, now());
+        assert!(html.contains("<tr> <th>AoC Name</th> <th>1</th> <th>2</th> </tr>"));
+        assert!(html.contains("<tr> <td>Bob</td> <td>2</td> <td>1</td> </tr>"));
+        assert!(html.contains("<tr> <td>Alice</td> <td>1</td> <td>2</td> </tr>"));
+        assert!(!html.contains("Carol"));
+    }
+}
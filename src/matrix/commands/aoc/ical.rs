@@ -0,0 +1,150 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{day::AocDay, models::PrivateLeaderboardMember},
+    context::Context,
+    i18n::Message,
+    matrix::{
+        commands::{
+            aoc::{send_fetch_error, user::find_member},
+            args::parse_year,
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{file_message, RoomExt},
+    },
+    utils::datetime::now,
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let user = cmd.get_from_kwargs("user");
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    let leaderboard = match context.aoc_client.get_private_leaderboard(year).await {
+        Ok((leaderboard, _)) => leaderboard,
+        Err(err) => return send_fetch_error(&room, event, year, err).await,
+    };
+    let users = context.users.read().await;
+    let Some(member) = find_member(&leaderboard, &users, event, user) else {
+        return send_error(&room, event, Message::UserNotFound).await;
+    };
+
+    let timezone = users
+        .by_aoc
+        .get(&member.id)
+        .and_then(|u| u.timezone)
+        .unwrap_or(context.config.local_timezone);
+    let calendar = render(member, year, timezone, now());
+
+    let content = file_message(
+        &room,
+        &format!("aoc-{year}-{}.ics", member.id),
+        &mime_guess::from_ext("ics").first_or_octet_stream(),
+        calendar.into_bytes(),
+    )
+    .await?;
+    room.reply_to(event, content).await?;
+
+    Ok(())
+}
+
+fn render(
+    member: &PrivateLeaderboardMember,
+    year: i32,
+    timezone: FixedOffset,
+    now: DateTime<Utc>,
+) -> String {
+    let mut stars = member
+        .completion_day_level
+        .iter()
+        .flat_map(|(&day, c)| {
+            [(1, Some(&c.fst)), (2, c.snd.as_ref())]
+                .into_iter()
+                .filter_map(move |(part, c)| Some((c?.get_star_ts, day, part)))
+        })
+        .collect::<Vec<_>>();
+    stars.sort_unstable();
+
+    // fixed offsets have no daylight saving time, so the timezone has a single observance
+    let offset = timezone.to_string().replace(':', "");
+    let tzid = format!("UTC{timezone}");
+    let dtstamp = now.format("%Y%m%dT%H%M%SZ");
+
+    let mut out = String::new();
+    let mut line = |line: &str| {
+        out.push_str(line);
+        out.push_str("\r\n");
+    };
+    line("BEGIN:VCALENDAR");
+    line("VERSION:2.0");
+    line("PRODID:-//aocbot//Advent of Code solve times//EN");
+    line("BEGIN:VTIMEZONE");
+    line(&format!("TZID:{tzid}"));
+    line("BEGIN:STANDARD");
+    line("DTSTART:19700101T000000");
+    line(&format!("TZOFFSETFROM:{offset}"));
+    line(&format!("TZOFFSETTO:{offset}"));
+    line("END:STANDARD");
+    line("END:VTIMEZONE");
+    for (ts, day, part) in stars {
+        let start = ts.with_timezone(&timezone).format("%Y%m%dT%H%M%S");
+        line("BEGIN:VEVENT");
+        line(&format!("UID:{year}-{day}-{part}-{}@aocbot", member.id));
+        line(&format!("DTSTAMP:{dtstamp}"));
+        line(&format!("DTSTART;TZID={tzid}:{start}"));
+        line(&format!("DTEND;TZID={tzid}:{start}"));
+        line(&format!("SUMMARY:AoC {year} Day {day} part {part} ★"));
+        line(&format!("URL:{}", AocDay { year, day }.url()));
+        line("END:VEVENT");
+    }
+    line("END:VCALENDAR");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::fixtures::{member, YEAR};
+
+    #[test]
+    fn render() {
+        let member = member(7, "Alice", &[(2, 90, None), (1, 100, Some(200))]);
+        let now = "2024-12-03T00:00:00Z".parse().unwrap();
+        let cet = FixedOffset::east_opt(3600).unwrap();
+
+        let calendar = super::render(&member, YEAR, cet, now);
+        let lines = calendar.split("\r\n").collect::<Vec<_>>();
+        assert!(lines.contains(&"TZOFFSETTO:+0100"));
+        assert_eq!(
+            lines
+                .iter()
+                .filter_map(|l| l.strip_prefix("DTSTART;TZID=UTC+01:00:"))
+                .collect::<Vec<_>>(),
+            ["20241201T060140", "20241201T060320", "20241202T060130"]
+        );
+        assert_eq!(
+            lines
+                .iter()
+                .filter_map(|l| l.strip_prefix("SUMMARY:"))
+                .collect::<Vec<_>>(),
+            [
+                "AoC 2024 Day 1 part 1 ★",
+                "AoC 2024 Day 1 part 2 ★",
+                "AoC 2024 Day 2 part 1 ★"
+            ]
+        );
+        assert!(lines.contains(&"UID:2024-1-2-7@aocbot"));
+        assert!(lines.contains(&"DTSTAMP:20241203T000000Z"));
+        assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 3);
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+    }
+}
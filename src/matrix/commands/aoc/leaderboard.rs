@@ -1,108 +1,395 @@
-use std::fmt::Write;
+use std::{collections::HashSet, fmt::Write};
 
-use chrono::TimeZone;
+use chrono::{DateTime, Utc};
 use matrix_sdk::{
-    ruma::{api::client::error::ErrorKind, events::room::message::OriginalRoomMessageEvent},
+    ruma::{events::room::message::OriginalRoomMessageEvent, OwnedUserId},
     Room,
 };
 
 use crate::{
-    aoc::{day::AocDay, models::PrivateLeaderboardMember},
-    context::Context,
-    matrix::{
-        commands::{parser::ParsedCommand, send_error},
-        utils::{error_message, html_message, RoomExt},
+    aoc::models::{
+        disambiguated_display_names, ranked_members, DayOrder, PrivateLeaderboard,
+        PrivateLeaderboardMember,
+    },
+    config::Config,
+    context::{Context, ContextUsers},
+    matrix::commands::{
+        aoc::{
+            auto_format,
+            enrich::{mark_newcomers, Enrichment, Enrichments, Pipeline},
+            fit_rows, fmt_last_update, fmt_tagline, leaderboard_heading, prefetch, send_debug,
+            send_leaderboard, Rendered,
+        },
+        args::{
+            check_incompatible, parse_bool, parse_columns, parse_format, parse_min_stars,
+            parse_offset, parse_rows, parse_year, Incompatible, Rows, FORMAT_COLLAPSE,
+            FORMAT_COLUMNS,
+        },
+        error::{CommandError, CommandResult},
+        parser::ParsedCommand,
+    },
+    matrix::utils::Mentions,
+    utils::{
+        datetime::now,
+        fmt::{escape_html, fmt_rank},
+        image::TextTable,
     },
-    utils::{datetime::DateTimeExt, fmt::fmt_rank},
 };
 
+const INCOMPATIBLE: &[Incompatible] = &[FORMAT_COLUMNS, FORMAT_COLLAPSE];
+
 pub async fn invoke(
     event: &OriginalRoomMessageEvent,
     room: Room,
     context: &Context,
-    mut cmd: ParsedCommand<'_>,
-) -> anyhow::Result<()> {
-    let most_recent_year = AocDay::most_recent().year;
-    let year = match cmd.get_from_kwargs_or_args("year").map(|y| {
-        y.parse()
-            .ok()
-            .filter(|y| (2015..=most_recent_year).contains(y))
-    }) {
-        Some(Some(y)) => y,
-        Some(None) => return send_error(&room, event, "Failed to parse argument 'year'").await,
-        None => most_recent_year,
-    };
+    cmd: ParsedCommand<'_>,
+) -> CommandResult {
+    invoke_with_strip(event, room, context, cmd, false).await
+}
+
+/// Run the `leaderboard` command, showing a star strip of the 25 days for every member with
+/// `strip`, which is the `year` command.
+pub async fn invoke_with_strip(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+    strip: bool,
+) -> CommandResult {
+    check_incompatible(&cmd, INCOMPATIBLE)?;
+
+    let year = parse_year(cmd.get_from_kwargs("year"))?;
+
+    let rows = parse_rows(
+        cmd.get_from_kwargs("rows"),
+        context.config.aoc.leaderboard_rows,
+        context.config.aoc.min_rows,
+    )?;
+
+    let offset = parse_offset(cmd.get_from_kwargs("offset"))?;
+
+    let participation = &context.config.aoc.participation;
+    let min_stars = parse_min_stars(cmd.get_from_kwargs("min_stars"), participation.min_stars)?;
+
+    // columns select the cells of the plain text formats, which is tsv unless requested otherwise
+    let columns = parse_columns(cmd.get_from_kwargs("columns"));
+    let requested_format = cmd
+        .get_from_kwargs("format")
+        .or(columns.as_ref().map(|_| "tsv"));
+    let format = parse_format(requested_format, context.config.matrix.image_format)?;
 
-    let rows = match cmd
-        .get_from_kwargs_or_args("rows")
-        .map(|y| y.parse().ok().filter(|x| (0..=200).contains(x)))
-    {
-        Some(Some(x)) => x,
-        Some(None) => return send_error(&room, event, "Failed to parse argument 'rows'").await,
-        None => context.config.aoc.leaderboard_rows,
+    let here = parse_bool(
+        cmd.get_from_kwargs("here"),
+        "here",
+        context.config.aoc.room_members_only,
+    )?;
+
+    let params = Params {
+        year,
+        rows,
+        offset,
+        min_stars,
+        here,
+        strip,
     };
+    let collapse = parse_bool(
+        cmd.get_from_kwargs("collapse"),
+        "collapse",
+        context.config.matrix.collapse_enabled(room.room_id()),
+    )?;
+    let fresh = parse_bool(cmd.get_from_kwargs("fresh"), "fresh", false)?;
+    let debug = parse_bool(cmd.get_from_kwargs("debug"), "debug", false)?;
+
+    let started = now();
+    prefetch(&room, event, context, year, fresh)
+        .await
+        .map_err(|err| CommandError::fetch(year, err))?;
+    let mut rendered = render(context, &room, params)
+        .await
+        .map_err(|err| CommandError::fetch(year, err))?;
+    if let Some(columns) = &columns {
+        rendered.table.select_columns(columns)?;
+    }
+    let last_update = rendered.last_update;
+    let format = auto_format(
+        &context.config,
+        format,
+        requested_format.is_some(),
+        &mut rendered,
+    );
 
-    let offset = match cmd
-        .get_from_kwargs_or_args("offset")
-        .map(|y| y.parse().ok().filter(|x| (0..=200).contains(x)))
-    {
-        Some(Some(x)) => x,
-        Some(None) => return send_error(&room, event, "Failed to parse argument 'offset'").await,
-        None => 0,
+    send_leaderboard(
+        &room,
+        event,
+        context,
+        format,
+        rendered,
+        params.into(),
+        collapse,
+    )
+    .await?;
+
+    if debug {
+        send_debug(
+            &room,
+            event,
+            started,
+            last_update,
+            &context.aoc_client.star_discrepancies(year).await,
+            &[
+                ("year", year.to_string()),
+                ("rows", rows.to_string()),
+                ("offset", offset.to_string()),
+                ("min_stars", min_stars.to_string()),
+                ("here", here.to_string()),
+                ("strip", strip.to_string()),
+                ("collapse", collapse.to_string()),
+                ("fresh", fresh.to_string()),
+                ("format", format!("{format:?}").to_lowercase()),
+            ],
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params {
+    pub year: i32,
+    pub rows: Rows,
+    pub offset: usize,
+    pub min_stars: u32,
+    pub here: bool,
+    pub strip: bool,
+}
+
+pub async fn render(context: &Context, room: &Room, params: Params) -> anyhow::Result<Rendered> {
+    let (leaderboard, last_update) = context
+        .aoc_client
+        .get_private_leaderboard(params.year)
+        .await?;
+    let audience = if params.here {
+        Some(context.room_members(room).await?)
+    } else {
+        None
     };
+    context.newcomers.observe(params.year, &leaderboard).await?;
+    let users = context.users.read().await;
+    let slice = slice(
+        &context.config,
+        &users,
+        &leaderboard,
+        audience.as_deref(),
+        params,
+    );
+    let mut enrichments = Pipeline::new(&context.config, &users)
+        .enrich_all(&slice)
+        .await;
+    mark_newcomers(context, params.year, &mut enrichments).await;
+    Ok(build(
+        &context.config,
+        &users,
+        &enrichments,
+        leaderboard,
+        audience.as_deref(),
+        last_update,
+        params,
+    ))
+}
 
-    let (leaderboard, last_update) = match context.aoc_client.get_private_leaderboard(year).await {
-        Ok(resp) => resp,
-        Err(err) => match err.downcast::<reqwest::Error>() {
-            Ok(err) => {
-                if let Some(status) = err.status() {
-                    room.reply_to(
-                        event,
-                        error_message(format!(
-                            "Failed to fetch private leaderboard for {year} ({status})"
-                        )),
-                    )
-                    .await?;
-                    return Ok(());
-                } else {
-                    return Err(err.into());
-                }
-            }
-            Err(err) => return Err(err),
-        },
+/// Render the given slice of the private leaderboard as it was at the given time, or `None` if
+/// no leaderboard fetched until then was archived. Members are not marked as newcomers.
+pub async fn render_archived(
+    context: &Context,
+    params: Params,
+    ts: DateTime<Utc>,
+) -> anyhow::Result<Option<Rendered>> {
+    let Some((leaderboard, fetched)) = context
+        .aoc_client
+        .archived_leaderboard(params.year, ts)
+        .await?
+    else {
+        return Ok(None);
     };
-    let last_update = context
-        .config
-        .local_timezone
-        .from_utc_datetime(&last_update.naive_utc())
-        .format_ymd_hms_z();
+    let users = context.users.read().await;
+    let slice = slice(&context.config, &users, &leaderboard, None, params);
+    let enrichments = Pipeline::new(&context.config, &users)
+        .enrich_all(&slice)
+        .await;
+    Ok(Some(build(
+        &context.config,
+        &users,
+        &enrichments,
+        leaderboard,
+        None,
+        fetched,
+        params,
+    )))
+}
 
+/// Render the given slice of an already fetched private leaderboard, showing the members with
+/// their enrichments. `rows` and `offset` are only bounded by the number of members, so
+/// leaderboards of any size can be paged through.
+pub fn build(
+    config: &Config,
+    users: &ContextUsers,
+    enrichments: &Enrichments,
+    leaderboard: PrivateLeaderboard,
+    audience: Option<&HashSet<OwnedUserId>>,
+    last_update: DateTime<Utc>,
+    params: Params,
+) -> Rendered {
+    match params.rows {
+        Rows::Count(rows) => build_slice(
+            config,
+            users,
+            enrichments,
+            leaderboard,
+            audience,
+            last_update,
+            params,
+            rows,
+        ),
+        Rows::All => fit_rows(params.offset, |rows| {
+            build_slice(
+                config,
+                users,
+                enrichments,
+                leaderboard.clone(),
+                audience,
+                last_update,
+                params,
+                rows,
+            )
+        }),
+    }
+}
+
+/// The ranked members of the leaderboard who are shown, with the numbers of members hidden for
+/// having fewer than `min_stars` stars and for not being in the audience.
+fn ranked(
+    config: &Config,
+    users: &ContextUsers,
+    leaderboard: PrivateLeaderboard,
+    audience: Option<&HashSet<OwnedUserId>>,
+    min_stars: u32,
+) -> (Vec<(usize, PrivateLeaderboardMember)>, usize, usize) {
+    // members who are not in the room are removed before ranking
     let mut members = leaderboard.members.into_values().collect::<Vec<_>>();
-    members.sort_unstable();
+    let len = members.len();
+    if let Some(audience) = audience {
+        members.retain(|m| {
+            users
+                .by_aoc
+                .get(&m.id)
+                .and_then(|u| u.matrix.as_ref())
+                .is_some_and(|id| audience.contains(id))
+        });
+    }
+    let absent = len - members.len();
+
+    let rank_before_filter = config.aoc.participation.rank_before_filter;
+    let (members, hidden) = ranked_members(members, min_stars, rank_before_filter);
+    (members, hidden, absent)
+}
+
+/// The members in the given slice of the leaderboard, which are the ones to enrich. With
+/// `rows=all`, all members from the offset on are included, as it is not known yet how many
+/// of them fit into the message.
+fn slice(
+    config: &Config,
+    users: &ContextUsers,
+    leaderboard: &PrivateLeaderboard,
+    audience: Option<&HashSet<OwnedUserId>>,
+    params: Params,
+) -> Vec<PrivateLeaderboardMember> {
+    let (members, _, _) = ranked(
+        config,
+        users,
+        leaderboard.clone(),
+        audience,
+        params.min_stars,
+    );
+    members
+        .into_iter()
+        .skip(params.offset)
+        .take(params.rows.limit())
+        .map(|(_, m)| m)
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_slice(
+    config: &Config,
+    users: &ContextUsers,
+    enrichments: &Enrichments,
+    leaderboard: PrivateLeaderboard,
+    audience: Option<&HashSet<OwnedUserId>>,
+    last_update: DateTime<Utc>,
+    params: Params,
+    rows: usize,
+) -> Rendered {
+    let Params {
+        year,
+        offset,
+        min_stars,
+        strip,
+        ..
+    } = params;
+    let updated_at = last_update;
+    let last_update = fmt_last_update(config, last_update);
+
+    let (members, hidden, absent) = ranked(config, users, leaderboard, audience, min_stars);
+
+    let branding = &config.aoc.branding;
+    let tagline = fmt_tagline(branding);
+    let mut table = TextTable {
+        title: leaderboard_heading(branding, year, None, None),
+        header: [
+            "Rank",
+            "Local Score",
+            "Global Score",
+            "Stars",
+            "AoC Name",
+            "Matrix User",
+            "Repository",
+        ]
+        .into_iter()
+        .chain(strip.then_some("Days"))
+        .map(Into::into)
+        .collect(),
+        footer: branding
+            .tagline
+            .iter()
+            .cloned()
+            .chain([format!("Last update: {last_update}")])
+            .collect(),
+        ..Default::default()
+    };
 
     let mut leaderboard = format!(
         r#"
-<h3>Private Leaderboard (Advent of Code {year})</h3>
-<table>
-<tr> <th>Rank</th> <th>Local Score</th> <th>Global Score</th> <th>Stars</th> <th>AoC Name</th> <th>Matrix User</th> <th>Repository</th> </tr>
-"#
+<h3>{}</h3>
+{tagline}<table>
+<tr> <th>Rank</th> <th>Local Score</th> <th>Global Score</th> <th>Stars</th> <th>AoC Name</th> <th>Matrix User</th> <th>Repository</th> {}</tr>
+"#,
+        escape_html(&table.title),
+        if strip { "<th>Days</th> " } else { "" },
     );
 
-    let mut last_score = u32::MAX;
-    let mut rank = 0;
-    for (rank, member) in members
+    let rows = members
         .into_iter()
-        .enumerate()
-        .map(|(i, member)| {
-            if member.local_score != last_score {
-                last_score = member.local_score;
-                rank = i + 1;
-            }
-            (rank, member)
-        })
         .skip(offset)
         .take(rows)
-    {
+        .collect::<Vec<_>>();
+
+    let names = disambiguated_display_names(rows.iter().map(|(_, m)| m));
+    let mut compact = Vec::with_capacity(rows.len());
+    let mut mentions = Mentions::new(config.matrix.max_mentions);
+    let mut newcomers = Vec::new();
+
+    for (rank, member) in rows {
         let PrivateLeaderboardMember {
             local_score,
             global_score,
@@ -110,39 +397,74 @@ pub async fn invoke(
             ..
         } = member;
 
-        let name = member.display_name();
+        let name = &names[&member.id];
+        let html_name = escape_html(name);
+        let id = member.id;
 
-        let matrix_name = context
-            .users
-            .by_aoc
-            .get(&member.id)
-            .and_then(|u| u.matrix.as_ref())
-            .map(|m| m.matrix_to_uri().to_string())
+        let Enrichment {
+            matrix_user,
+            repo,
+            newcomer,
+        } = enrichments.get(&member.id).cloned().unwrap_or_default();
+        let (name, html_name) = if newcomer {
+            newcomers.push(id);
+            let badge = &config.aoc.newcomers.badge;
+            (
+                format!("{badge} {name}"),
+                format!("{} {html_name}", escape_html(badge)),
+            )
+        } else {
+            (name.clone(), html_name.into())
+        };
+        let matrix_name = matrix_user
+            .as_ref()
+            .map(|m| mentions.mention_member(&member, m))
             .unwrap_or_default();
+        let (repo, repo_title) = repo.map_or_else(Default::default, |r| (r.url, r.title));
+        let (html_repo, html_repo_title) = (escape_html(&repo), escape_html(&repo_title));
 
-        let repo = context
-            .users
-            .by_aoc
-            .get(&member.id)
-            .and_then(|u| u.repo.as_deref())
-            .unwrap_or_default();
-        let repo_title = context
-            .config
-            .aoc
-            .repo_rules
-            .match_and_replace(repo)
-            .map(|m| m.replacement);
-        let repo_title = repo_title.as_deref().unwrap_or(repo);
-
-        let (m, m_) = if rank <= 3 {
+        let highlight = rank <= 3;
+        let (m, m_) = if highlight {
             ("<b>", "</b>")
         } else {
             Default::default()
         };
 
+        let strip = strip.then(|| member.star_grid(year, DayOrder::Ascending, &config.aoc.grid));
+        let compact_strip = strip
+            .as_ref()
+            .map(|s| format!(" {}", escape_html(s)))
+            .unwrap_or_default();
+        compact.push(format!(
+            "{rank}. {html_name} — {local_score} ({stars}★){compact_strip}"
+        ));
+
         let rank = fmt_rank(rank);
 
-        let link_prefix = &context.config.matrix.link_prefix;
+        table.rows.push((
+            highlight,
+            [
+                rank.to_string(),
+                local_score.to_string(),
+                global_score.to_string(),
+                stars.to_string(),
+                name,
+                // the plain text formats cannot show ids without risking a notification
+                matrix_user
+                    .filter(|_| !member.no_ping)
+                    .map(|m| m.to_string())
+                    .unwrap_or_default(),
+                repo_title.clone(),
+            ]
+            .into_iter()
+            .chain(strip.clone())
+            .collect(),
+        ));
+
+        let html_strip = strip
+            .map(|s| format!("\n    <td><code>{}</code></td>", escape_html(&s)))
+            .unwrap_or_default();
+        let link_prefix = &config.matrix.link_prefix;
         write!(
             &mut leaderboard,
             r#"
@@ -151,43 +473,333 @@ pub async fn invoke(
     <td>{m}{local_score}{m_}</td>
     <td>{m}{global_score}{m_}</td>
     <td>{m}{stars}{m_}</td>
-    <td>{m}{name}{m_}</td>
+    <td>{m}<span title="AoC #{id}">{html_name}</span>{m_}</td>
     <td>{matrix_name}</td>
-    <td>{m}<a href="{link_prefix}{repo}">{repo_title}</a>{m_}</td>
+    <td>{m}<a href="{link_prefix}{html_repo}">{html_repo_title}</a>{m_}</td>{html_strip}
 </tr>
 "#
         )
         .unwrap();
     }
 
+    let hidden = match hidden {
+        0 => String::new(),
+        1 => format!(", 1 member with fewer than {min_stars} stars hidden (min_stars=0 shows all)"),
+        n => format!(
+            ", {n} members with fewer than {min_stars} stars hidden (min_stars=0 shows all)"
+        ),
+    };
+    let hidden = match absent {
+        0 => hidden,
+        1 => format!("{hidden}, 1 member not in this room hidden (here=no shows all)"),
+        n => format!("{hidden}, {n} members not in this room hidden (here=no shows all)"),
+    };
+    table.footer[0].push_str(&hidden);
+
+    let not_mentioned = mentions
+        .note()
+        .map(|note| format!(", {note}"))
+        .unwrap_or_default();
     write!(
         &mut leaderboard,
         r#"
 </table>
-<sup>Last update: {last_update}</sup>
+<sup>Last update: {last_update}{hidden}{not_mentioned}</sup>
 "#
     )
     .unwrap();
 
-    if let Err(err) = room.reply_to(event, html_message(leaderboard)).await {
-        if err
-            .as_client_api_error()
-            .and_then(|err| err.error_kind())
-            .is_some_and(|kind| matches!(kind, ErrorKind::TooLarge))
-        {
-            room.reply_to(
-                event,
-                error_message(
-                    "The requested leaderboard slice would be too large to fit in a matrix \
-                     message. Try to reduce the number of rows.",
-                ),
-            )
-            .await?;
-            return Ok(());
-        } else {
-            return Err(err.into());
+    let compact = format!(
+        "\n<h3>{}</h3>\n{tagline}<pre>{}</pre>\n<sup>Last update: {last_update}{hidden}</sup>\n",
+        escape_html(&table.title),
+        compact.join("\n"),
+    );
+
+    Rendered {
+        html: leaderboard,
+        compact,
+        table,
+        last_update: updated_at,
+        newcomers,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        aoc::fixtures::{leaderboard, member, YEAR},
+        config::tests::load_with_overrides,
+        matrix::commands::{
+            aoc::{enrich::enrich_blocking, HTML_SIZE_BUDGET},
+            args::Format,
+            parser::parse,
+        },
+        utils::serde::via_string::ViaString,
+    };
+
+    impl Params {
+        pub fn new(year: i32) -> Self {
+            Self {
+                year,
+                rows: Rows::Count(20),
+                offset: 0,
+                min_stars: 0,
+                here: false,
+                strip: false,
+            }
         }
     }
 
-    Ok(())
+    pub fn render(config: &Config, leaderboard: &PrivateLeaderboard, params: Params) -> Rendered {
+        let users = ContextUsers::new(config, &[]);
+        let enrichments = enrich_blocking(config, &users, leaderboard);
+        build(
+            config,
+            &users,
+            &enrichments,
+            leaderboard.clone(),
+            None,
+            now(),
+            params,
+        )
+    }
+
+    #[test]
+    fn incompatible() {
+        let check = |cmd| check_incompatible(&parse(cmd), INCOMPATIBLE);
+
+        assert_eq!(check("leaderboard format=table collapse"), Ok(()));
+        assert_eq!(check("leaderboard columns=rank"), Ok(()));
+        assert_eq!(
+            check("leaderboard format=TSV collapse=false"),
+            Err(
+                "Arguments 'format=TSV' and 'collapse' cannot be combined (only html \
+                 leaderboards can be collapsed)"
+                    .into()
+            )
+        );
+        assert_eq!(
+            check("leaderboard format=compact columns=rank"),
+            Err(
+                "Arguments 'format=compact' and 'columns' cannot be combined (columns only \
+                 apply to tsv and image)"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn more_than_200_members() {
+        let config = load_with_overrides([]);
+        let users = ContextUsers::new(&config, &[]);
+
+        let leaderboard = leaderboard((1..=250).map(|id| PrivateLeaderboardMember {
+            local_score: 1000 - id as u32,
+            ..member(id, &format!("Member {id}"), &[(1, 100, None)])
+        }));
+
+        let params = Params {
+            offset: 240,
+            ..Params::new(YEAR)
+        };
+        let rendered = render(&config, &leaderboard, params);
+
+        let ranks = rendered
+            .table
+            .rows
+            .iter()
+            .map(|(_, row)| row[0].clone())
+            .collect::<Vec<_>>();
+        let expected = (241..=250)
+            .map(|rank| fmt_rank(rank).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ranks, expected);
+        assert!(rendered.compact.contains("250. Member 250 — 750 (1★)"));
+
+        // only the members of the slice are enriched
+        let slice = slice(&config, &users, &leaderboard, None, params);
+        assert_eq!(
+            slice.iter().map(|m| m.id).collect::<Vec<_>>(),
+            (241..=250).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn newcomer_badge() {
+        let config = load_with_overrides([]);
+        let users = ContextUsers::new(&config, &[]);
+        let leaderboard =
+            leaderboard([member(1, "Alice", &[(1, 100, None)]), member(2, "Bob", &[])]);
+        let mut enrichments = enrich_blocking(&config, &users, &leaderboard);
+        enrichments.entry(2).or_default().newcomer = true;
+
+        let rendered = build(
+            &config,
+            &users,
+            &enrichments,
+            leaderboard,
+            None,
+            now(),
+            Params::new(YEAR),
+        );
+        assert!(rendered.html.contains(">🆕 Bob</span>"));
+        assert!(rendered.html.contains(">Alice</span>"));
+        // the plain text formats and images show the badge as well
+        assert!(rendered.table.to_tsv().contains("\t🆕 Bob\t"));
+        assert_eq!(rendered.newcomers, [2]);
+    }
+
+    #[test]
+    fn auto_format() {
+        let config = load_with_overrides(["aoc.compact_threshold = 50"]);
+        let leaderboard =
+            leaderboard((1..=60).map(|id| member(id, &format!("Member {id}"), &[(1, 100, None)])));
+        let rendered = |rows| {
+            let params = Params {
+                rows: Rows::Count(rows),
+                ..Params::new(YEAR)
+            };
+            render(&config, &leaderboard, params)
+        };
+
+        let mut small = rendered(50);
+        let format = super::auto_format(&config, Format::Table, false, &mut small);
+        assert_eq!(format, Format::Table);
+
+        let mut large = rendered(60);
+        let format = super::auto_format(&config, Format::Table, false, &mut large);
+        assert_eq!(format, Format::Compact);
+        assert!(large.compact.contains(
+            "60 rows are shown in the compact format, use format=table for the full table"
+        ));
+
+        // requested explicitly
+        let mut large = rendered(60);
+        let format = super::auto_format(&config, Format::Table, true, &mut large);
+        assert_eq!(format, Format::Table);
+    }
+
+    #[test]
+    fn here() {
+        let config = load_with_overrides([r#"users = [
+            { aoc = 1, matrix = "@alice:example.com" },
+            { aoc = 2, matrix = "@bob:example.com" },
+        ]"#]);
+        let users = ContextUsers::new(&config, &[]);
+
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, None)]),
+            member(2, "Bob", &[(1, 200, None)]),
+            member(3, "Carol", &[(1, 300, None)]),
+        ]);
+        let audience = HashSet::from(["@bob:example.com".try_into().unwrap()]);
+
+        let params = Params {
+            here: true,
+            ..Params::new(YEAR)
+        };
+        let rendered = build(
+            &config,
+            &users,
+            &enrich_blocking(&config, &users, &leaderboard),
+            leaderboard,
+            Some(&audience),
+            now(),
+            params,
+        );
+
+        assert_eq!(rendered.table.rows.len(), 1);
+        assert_eq!(rendered.table.rows[0].1[4], "Bob");
+        assert_eq!(rendered.table.rows[0].1[0], fmt_rank(1).to_string());
+        assert!(rendered
+            .html
+            .contains(", 2 members not in this room hidden (here=no shows all)"));
+    }
+
+    #[test]
+    fn opt_out() {
+        let config = load_with_overrides([
+            r#"aoc.opt_out_marker = "[noping]""#,
+            r#"users = [
+                { aoc = 1, matrix = "@alice:example.com" },
+                { aoc = 2, matrix = "@bob:example.com" },
+            ]"#,
+        ]);
+        let users = ContextUsers::new(&config, &[]);
+
+        let mut leaderboard = leaderboard([
+            member(1, "Alice [noping]", &[(1, 100, None)]),
+            member(2, "Bob", &[(1, 200, None)]),
+        ]);
+        leaderboard.apply_opt_out(config.aoc.opt_out_marker.as_deref().unwrap());
+        let alice = &leaderboard.members[&ViaString(1)];
+        assert_eq!(alice.display_name(), "Alice");
+        let matrix = users.by_aoc[&1].matrix.as_deref();
+        assert_eq!(
+            alice.matrix_mention_or_display_name_html(matrix),
+            "<b>Alice</b>"
+        );
+
+        let rendered = render(&config, &leaderboard, Params::new(YEAR));
+
+        assert!(!rendered
+            .html
+            .contains("https://matrix.to/#/@alice:example.com"));
+        assert!(rendered
+            .html
+            .contains("<td><code>@alice:example.com</code></td>"));
+        assert!(rendered
+            .html
+            .contains("https://matrix.to/#/@bob:example.com"));
+        assert!(!rendered.html.contains("noping"));
+        assert!(rendered.compact.contains("1. Alice — "));
+        assert!(!rendered.compact.contains("noping"));
+        assert_eq!(rendered.table.rows[0].1[4], "Alice");
+        assert_eq!(rendered.table.rows[0].1[5], "");
+
+        // only bob is mentioned, alice's id is left out of the plain body
+        let content = rendered.message(Format::Table);
+        assert_eq!(
+            content.mentions.as_ref().unwrap().user_ids,
+            [users.by_aoc[&2].matrix.clone().unwrap()].into()
+        );
+        assert!(!content.body().contains("@alice:example.com"));
+    }
+
+    #[test]
+    fn rows_all() {
+        let config = load_with_overrides([]);
+        let name = |id| format!("Member {id} {}", "x".repeat(100));
+
+        let leaderboard = leaderboard((1..=250).map(|id| PrivateLeaderboardMember {
+            local_score: 1000 - id as u32,
+            ..member(id, &name(id), &[(1, 100, None)])
+        }));
+
+        let params = Params {
+            rows: Rows::All,
+            offset: 10,
+            ..Params::new(YEAR)
+        };
+        let rendered = render(&config, &leaderboard, params);
+
+        let shown = rendered.table.rows.len();
+        assert!(0 < shown && shown < 240);
+        assert!(rendered.html.len() <= HTML_SIZE_BUDGET + 200);
+        let note = format!(
+            "{} more rows omitted to fit into a single message, use offset={} to show them",
+            240 - shown,
+            10 + shown
+        );
+        assert_eq!(rendered.table.footer.last(), Some(&note));
+
+        // one more row would not have fit
+        let params = Params {
+            rows: Rows::Count(shown + 1),
+            ..params
+        };
+        let rendered = render(&config, &leaderboard, params);
+        assert!(rendered.html.len() > HTML_SIZE_BUDGET);
+    }
 }
@@ -0,0 +1,306 @@
+use matrix_sdk::{
+    ruma::{events::room::message::OriginalRoomMessageEvent, OwnedUserId, UserId},
+    Room,
+};
+use reqwest::Url;
+
+use crate::{
+    aoc::{
+        day::AocDay,
+        models::{AocId, PrivateLeaderboard, PrivateLeaderboardMember},
+    },
+    config::Config,
+    context::Context,
+    i18n::Message,
+    links::{Request, REQUEST_TTL},
+    matrix::{
+        commands::{
+            aoc::{prefetch, send_fetch_error},
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{notice, RoomExt},
+    },
+    utils::fmt::escape_html,
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let id = match cmd.get_from_kwargs("id").map(|x| x.parse::<AocId>().ok()) {
+        Some(Some(id)) => id,
+        Some(None) => return send_error(&room, event, Message::InvalidArgument("id")).await,
+        None => return send_error(&room, event, Message::MissingArgument("id")).await,
+    };
+    let repo = match cmd.get_from_kwargs("repo").map(parse_repo) {
+        Some(Ok(repo)) => Some(repo),
+        Some(Err(err)) => return send_error(&room, event, err).await,
+        None => None,
+    };
+
+    // only members of the private leaderboard can be linked. While a request is pending, the
+    // leaderboard is refreshed, so that a name changed just now is seen.
+    let year = AocDay::most_recent().year;
+    let request = context
+        .links
+        .request(&event.sender)
+        .await
+        .filter(|r| r.aoc == id);
+    if let Err(err) = prefetch(&room, event, context, year, request.is_some()).await {
+        return send_fetch_error(&room, event, year, err).await;
+    }
+    let (leaderboard, _) = match context.aoc_client.get_private_leaderboard(year).await {
+        Ok(resp) => resp,
+        Err(err) => return send_fetch_error(&room, event, year, err).await,
+    };
+
+    let member = match check(&context.config, &leaderboard, &event.sender, id) {
+        Ok(member) => member,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let name = escape_html(&member.display_name()).into_owned();
+    let verified = request.as_ref().is_some_and(|r| is_verified(member, r));
+
+    let request = match request {
+        Some(request) if verified => Request {
+            repo: repo.or(request.repo.clone()),
+            ..request
+        },
+        _ => {
+            let request = context.links.add_request(&event.sender, id, repo).await?;
+            let message = format!(
+                "To prove that you own {name} (#{id}), add `{}` to your AoC name and send this \
+                 command again within {} hours. You can remove the code afterwards.",
+                request.code,
+                REQUEST_TTL.num_hours()
+            );
+            room.reply_to(event, notice(message)).await?;
+            return Ok(());
+        }
+    };
+
+    context.link(&request).await?;
+    room.reply_to(
+        event,
+        notice(format!("✅️ Linked your matrix account to {name} (#{id})")),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Link a matrix user to the member of their pending link request without verification (admin
+/// only).
+pub async fn approve(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    if !context.config.matrix.admin_ids.contains(&event.sender) {
+        return send_error(&room, event, Message::PermissionDenied).await;
+    }
+    let user = match cmd
+        .get_from_kwargs("user")
+        .map(|x| x.parse::<OwnedUserId>())
+    {
+        Some(Ok(user)) => user,
+        Some(Err(_)) => return send_error(&room, event, Message::InvalidArgument("user")).await,
+        None => return send_error(&room, event, Message::MissingArgument("user")).await,
+    };
+    let Some(request) = context.links.request(&user).await else {
+        return send_error(&room, event, format!("{user} has no pending link request")).await;
+    };
+
+    context.link(&request).await?;
+    room.reply_to(
+        event,
+        notice(format!("✅️ Linked {user} to AoC member #{}", request.aoc)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unlink(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let user = match cmd
+        .get_from_kwargs("user")
+        .map(|x| x.parse::<OwnedUserId>())
+    {
+        Some(Ok(user)) => user,
+        Some(Err(_)) => return send_error(&room, event, Message::InvalidArgument("user")).await,
+        None => event.sender.clone(),
+    };
+    let own = user == event.sender;
+    if !own && !context.config.matrix.admin_ids.contains(&event.sender) {
+        return send_error(&room, event, Message::PermissionDenied).await;
+    }
+    let account = if own {
+        "Your matrix account".into()
+    } else {
+        user.to_string()
+    };
+    if configured(&context.config, &user) {
+        let message = if own {
+            CONFIGURED.into()
+        } else {
+            format!("{user} is linked in the config")
+        };
+        return send_error(&room, event, message).await;
+    }
+    if !context.unlink(&user).await? {
+        let message = format!("{account} is not linked to an AoC account");
+        return send_error(&room, event, message).await;
+    }
+    let message = if own {
+        "✅️ Removed the link of your matrix account".into()
+    } else {
+        format!("✅️ Removed the link of {user}")
+    };
+    room.reply_to(event, notice(message)).await?;
+
+    Ok(())
+}
+
+const CONFIGURED: &str = "Your matrix account is linked in the config, ask an admin to change it";
+
+/// Whether the matrix user is linked in the config, which cannot be changed using commands.
+fn configured(config: &Config, user_id: &UserId) -> bool {
+    config
+        .users
+        .iter()
+        .any(|u| u.matrix.as_deref() == Some(user_id))
+}
+
+fn check<'a>(
+    config: &Config,
+    leaderboard: &'a PrivateLeaderboard,
+    user_id: &UserId,
+    id: AocId,
+) -> Result<&'a PrivateLeaderboardMember, String> {
+    if configured(config, user_id) {
+        return Err(CONFIGURED.into());
+    }
+    let Some(member) = leaderboard.members.values().find(|m| m.id == id) else {
+        return Err(format!(
+            "AoC member #{id} is not on the private leaderboard"
+        ));
+    };
+    // links of other users are replaced once the request is verified
+    let configured_member = config
+        .users
+        .iter()
+        .any(|u| u.aoc == Some(id) && u.matrix.as_deref().is_some_and(|m| m != user_id));
+    if configured_member {
+        return Err(format!(
+            "AoC member #{id} is linked to another matrix account in the config"
+        ));
+    }
+    Ok(member)
+}
+
+fn is_verified(member: &PrivateLeaderboardMember, request: &Request) -> bool {
+    member
+        .name
+        .as_deref()
+        .is_some_and(|name| name.contains(&request.code))
+}
+
+fn parse_repo(value: &str) -> Result<String, String> {
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(value.into()),
+        _ => Err(Message::InvalidArgumentExpected {
+            name: "repo",
+            expected: "an http(s) url",
+        }
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aoc::fixtures::{leaderboard, member},
+        config::tests::load_with_overrides,
+        context::ContextUsers,
+        links::Link,
+        utils::datetime::now,
+    };
+
+    #[test]
+    fn link() {
+        let config = load_with_overrides([
+            r#"users = [{ aoc = 1, matrix = "@alice:example.com", repo = "https://github.com/alice/aoc" }]"#,
+        ]);
+        let link = |matrix: &str, aoc| Link {
+            matrix: matrix.try_into().unwrap(),
+            aoc,
+            repo: Some(format!("https://github.com/{aoc}/aoc")),
+            ts: now(),
+        };
+        // links of configured matrix users or members are ignored
+        let users = ContextUsers::new(
+            &config,
+            &[
+                link("@bob:example.com", 2),
+                link("@alice:example.com", 3),
+                link("@mallory:example.com", 1),
+            ],
+        );
+        let matrix = |id| users.by_aoc.get(&id).and_then(|u| u.matrix.clone());
+        assert_eq!(matrix(1).unwrap(), "@alice:example.com");
+        assert_eq!(matrix(2).unwrap(), "@bob:example.com");
+        assert_eq!(matrix(3), None);
+        assert_eq!(users.resolved[&2].repo.as_ref().unwrap().title, "2/aoc");
+
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[]),
+            member(2, "Bob", &[]),
+            member(3, "Carol", &[]),
+        ]);
+        let check = |user_id: &str, id| {
+            super::check(&config, &leaderboard, user_id.try_into().unwrap(), id).map(|m| m.id)
+        };
+        assert_eq!(check("@carol:example.com", 3), Ok(3));
+        // members linked by other users can be claimed with a verified request
+        assert_eq!(check("@bob:example.com", 2), Ok(2));
+        assert_eq!(check("@carol:example.com", 2), Ok(2));
+        assert!(check("@carol:example.com", 1)
+            .unwrap_err()
+            .contains("in the config"));
+        assert!(check("@carol:example.com", 4)
+            .unwrap_err()
+            .contains("not on the private leaderboard"));
+        assert_eq!(check("@alice:example.com", 3), Err(CONFIGURED.into()));
+
+        assert!(parse_repo("https://codeberg.org/carol/aoc").is_ok());
+        assert!(parse_repo("javascript:alert(1)").is_err());
+        assert!(parse_repo("carol/aoc").is_err());
+    }
+
+    #[test]
+    fn verify() {
+        let request = Request {
+            matrix: "@bob:example.com".try_into().unwrap(),
+            aoc: 2,
+            repo: None,
+            code: "aocbot-1234abcd".into(),
+            ts: now(),
+        };
+        assert!(is_verified(
+            &member(2, "Bob aocbot-1234abcd", &[]),
+            &request
+        ));
+        assert!(!is_verified(&member(2, "Bob", &[]), &request));
+        assert!(!is_verified(&member(2, "", &[]), &request));
+    }
+}
@@ -1,6 +1,582 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use matrix_sdk::{
+    ruma::{
+        api::client::error::ErrorKind,
+        events::room::{
+            message::{
+                ImageMessageEventContent, MessageType, OriginalRoomMessageEvent,
+                RoomMessageEventContent,
+            },
+            ImageInfo,
+        },
+        UInt,
+    },
+    Room,
+};
+
+use self::reactions::View;
+use crate::{
+    aoc::{
+        client::Parts,
+        models::{AocId, StarDiscrepancy},
+    },
+    audit::{self, Outcome},
+    config::{BrandingConfig, Config},
+    context::Context,
+    i18n::Message,
+    matrix::{
+        commands::args::Format,
+        placeholder,
+        utils::{error_message, html_message, notice, with_mentions, RoomExt},
+    },
+    utils::{
+        datetime::{now, DateTimeExt},
+        fmt::{escape_html, fmt_staleness, fmt_timedelta},
+        image::TextTable,
+    },
+};
+
+pub mod boards;
 pub mod clear_cache;
 pub mod day;
+pub mod difficulty;
+pub mod digest;
+pub mod enrich;
+pub mod gap;
+pub mod history;
+pub mod ical;
 pub mod join;
 pub mod leaderboard;
+pub mod link;
+pub mod percentile;
+pub mod racing;
+pub mod random;
+pub mod reactions;
+pub mod records;
+pub mod rollup;
+pub mod snapshot;
 pub mod solutions;
+pub mod streak;
+pub mod timeline;
+pub mod unlocks;
 pub mod user;
+pub mod whois;
+pub mod winners;
+pub mod year;
+
+/// The header of yearly (`day` is `None`) and daily leaderboards, using the name of the board if
+/// it is configured.
+pub fn leaderboard_heading(
+    branding: &BrandingConfig,
+    year: i32,
+    day: Option<(u32, Parts)>,
+    title: Option<&str>,
+) -> String {
+    let title = title.map(|t| format!(": {t}")).unwrap_or_default();
+    match (&branding.name, day) {
+        (None, None) => format!("Private Leaderboard (Advent of Code {year})"),
+        (None, Some((day, parts))) => {
+            let parts = match parts {
+                Parts::Both => String::new(),
+                parts => format!("/{}", parts.name()),
+            };
+            format!("Private Leaderboard (Advent of Code {year}/{day:02}{parts}{title})")
+        }
+        (Some(name), None) => format!("{name} AoC {year}"),
+        (Some(name), Some((day, parts))) => {
+            let parts = match parts {
+                Parts::Both => String::new(),
+                parts => format!(", Part {}", parts.name()),
+            };
+            format!("{name} AoC {year} — Day {day}{parts}{title}")
+        }
+    }
+}
+
+pub fn fmt_tagline(branding: &BrandingConfig) -> String {
+    branding
+        .tagline
+        .as_ref()
+        .map(|t| format!("<p><i>{}</i></p>\n", escape_html(t)))
+        .unwrap_or_default()
+}
+
+pub fn fmt_last_update(config: &Config, last_update: DateTime<Utc>) -> String {
+    let staleness = &config.aoc.staleness;
+    let badge = fmt_staleness(
+        now() - last_update,
+        TimeDelta::seconds(staleness.fresh as _),
+        TimeDelta::seconds(staleness.stale as _),
+    );
+    let last_update = config
+        .local_timezone
+        .from_utc_datetime(&last_update.naive_utc())
+        .format_ymd_hms_z();
+    format!("{last_update} ({badge})")
+}
+
+/// Fetch the leaderboard of the given year before rendering it, refreshing it for commands using
+/// `fresh=yes`. The user is notified using a placeholder message if the fetch has to wait for the
+/// rate limit or takes noticeable time, which the response of the command replaces.
+pub async fn prefetch(
+    room: &Room,
+    event: &OriginalRoomMessageEvent,
+    context: &Context,
+    year: i32,
+    fresh: bool,
+) -> anyhow::Result<()> {
+    let client = &context.aoc_client;
+    if !fresh {
+        let fetch = client.get_private_leaderboard(year);
+        return placeholder::while_slow(room, event, "Fetching the leaderboard…", fetch)
+            .await
+            .map(drop);
+    }
+
+    let wait = client.refresh_wait(year).await;
+    let refresh = client.refresh_private_leaderboard(year);
+    if wait.is_zero() {
+        return placeholder::while_slow(room, event, "Fetching fresh data…", refresh).await;
+    }
+    let secs = wait.as_secs_f64().ceil();
+    let text = format!("Fetching fresh data, this may take up to {secs} seconds");
+    placeholder::post(room, event, &text).await;
+    refresh.await
+}
+
+pub async fn send_fetch_error(
+    room: &Room,
+    event: &OriginalRoomMessageEvent,
+    year: i32,
+    err: anyhow::Error,
+) -> anyhow::Result<()> {
+    let message = fetch_error_message(year, err)?;
+    audit::set_outcome(Outcome::Error);
+    room.reply_to(event, error_message(message)).await?;
+    Ok(())
+}
+
+/// The message shown when AoC answered a leaderboard fetch with an error status. Other errors are
+/// returned, they are not the user's concern.
+pub fn fetch_error_message(year: i32, err: anyhow::Error) -> anyhow::Result<String> {
+    let err = err.downcast::<reqwest::Error>()?;
+    let Some(status) = err.status() else {
+        return Err(err.into());
+    };
+    Ok(Message::FetchFailed {
+        year,
+        status: Some(status),
+    }
+    .into())
+}
+
+/// Maximum size of the html of a leaderboard message rendered with `rows=all`. The html is sent
+/// both as formatted and as plain text body, and events are limited to 64 KiB in total.
+pub const HTML_SIZE_BUDGET: usize = 30_000;
+
+pub struct Rendered {
+    pub html: String,
+    pub compact: String,
+    pub table: TextTable,
+    pub last_update: DateTime<Utc>,
+    /// The members shown with a newcomer badge, whose badges are counted once the leaderboard is
+    /// sent.
+    pub newcomers: Vec<AocId>,
+}
+
+impl Rendered {
+    pub fn message(self, format: Format) -> RoomMessageEventContent {
+        match format {
+            Format::Compact => html_message(self.compact),
+            Format::Table | Format::Image => html_message(self.html),
+            Format::Tsv => {
+                html_message(format!("<pre>{}</pre>", escape_html(&self.table.to_tsv())))
+            }
+        }
+    }
+
+    /// Collapse the html formats into a `<details>` element, which the matrix spec allows since
+    /// v1.10. Its summary shows the heading, which is removed from the collapsed content.
+    /// Spoilers (`data-mx-spoiler`) are no alternative, as they only apply to inline content.
+    pub fn collapse(&mut self) {
+        let summary = format!(
+            "<b>{}</b> ({} rows)",
+            escape_html(&self.table.title),
+            self.table.rows.len()
+        );
+        for html in [&mut self.html, &mut self.compact] {
+            let content = match (html.find("<h3>"), html.find("</h3>")) {
+                (Some(start), Some(end)) => {
+                    format!("{}{}", &html[..start], &html[end + "</h3>".len()..])
+                }
+                _ => html.clone(),
+            };
+            *html = format!("<details><summary>{summary}</summary>{content}</details>\n");
+        }
+    }
+}
+
+/// Render the largest slice of a leaderboard starting at `offset` that fits into a single
+/// message, using `build` to render a given number of rows. The number of omitted rows is noted
+/// below the leaderboard.
+pub fn fit_rows(offset: usize, build: impl Fn(usize) -> Rendered) -> Rendered {
+    let fits = |rendered: &Rendered| rendered.html.len() <= HTML_SIZE_BUDGET;
+
+    let full = build(usize::MAX);
+    if fits(&full) {
+        return full;
+    }
+
+    // binary search for the number of rows, `lo` always fits and `hi` never does
+    let total = full.table.rows.len();
+    let (mut lo, mut hi) = (0, total);
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if fits(&build(mid)) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    let mut rendered = build(lo);
+    let note = format!(
+        "{} more rows omitted to fit into a single message, use offset={} to show them",
+        total - lo,
+        offset + lo
+    );
+    let html = format!("<p><i>{note}</i></p>\n");
+    rendered.html.push_str(&html);
+    rendered.compact.push_str(&html);
+    rendered.table.footer.push(note);
+    rendered
+}
+
+/// Switch to the compact format if no format was requested and the leaderboard has more than
+/// `aoc.compact_threshold` rows, noting the switch below the leaderboard.
+pub fn auto_format(
+    config: &Config,
+    format: Format,
+    requested: bool,
+    rendered: &mut Rendered,
+) -> Format {
+    let rows = rendered.table.rows.len();
+    if requested || rows <= config.aoc.compact_threshold {
+        return format;
+    }
+    let note =
+        format!("{rows} rows are shown in the compact format, use format=table for the full table");
+    rendered
+        .compact
+        .push_str(&format!("<p><i>{note}</i></p>\n"));
+    Format::Compact
+}
+
+/// Reply with a leaderboard, either as html or as an image rendered from the plain text table.
+///
+/// Html leaderboards are remembered as `view`, so they can be re-rendered when users react to
+/// them. With `collapse`, they are collapsed into an expandable element. Once sent, the
+/// leaderboard counts towards clearing the badges of its newcomers.
+pub async fn send_leaderboard(
+    room: &Room,
+    event: &OriginalRoomMessageEvent,
+    context: &Context,
+    format: Format,
+    mut rendered: Rendered,
+    view: View,
+    collapse: bool,
+) -> anyhow::Result<()> {
+    if collapse {
+        rendered.collapse();
+    }
+    let newcomers = std::mem::take(&mut rendered.newcomers);
+    let content = match format {
+        Format::Table | Format::Compact | Format::Tsv => rendered.message(format),
+        Format::Image => {
+            let table = rendered.table;
+            let png = table.to_png();
+            let mut info = ImageInfo::new();
+            info.mimetype = Some(mime_guess::mime::IMAGE_PNG.to_string());
+            info.size = UInt::new(png.data.len() as u64);
+            info.width = Some(png.width.into());
+            info.height = Some(png.height.into());
+            let response = room
+                .client()
+                .media()
+                .upload(&mime_guess::mime::IMAGE_PNG, png.data, None)
+                .await?;
+            let mut image_message =
+                ImageMessageEventContent::plain(table.title, response.content_uri)
+                    .info(Box::new(info));
+            image_message.filename = Some("leaderboard.png".into());
+            with_mentions(RoomMessageEventContent::new(MessageType::Image(
+                image_message,
+            )))
+        }
+    };
+
+    // the placeholder is replaced here instead of by `reply_to`, so that the view is remembered
+    // using the id of the replaced event, which reactions refer to
+    let placeholder = match placeholder::take() {
+        Some(event_id) if placeholder::can_replace(&content) => Some(event_id),
+        Some(event_id) => {
+            placeholder::discard(room.clone(), event_id);
+            None
+        }
+        None => None,
+    };
+    let response = match &placeholder {
+        Some(event_id) => room.edit(event_id, content, true).await,
+        None => room.reply_to(event, content).await,
+    };
+    match response {
+        Ok(response) => {
+            context
+                .newcomers
+                .shown(view.year(), &newcomers, &context.config.aoc.newcomers)
+                .await?;
+            // plain text formats may have been reduced to a selection of columns
+            let view_format = matches!(format, Format::Table | Format::Compact);
+            if view_format && context.config.matrix.reactions_enabled(room.room_id()) {
+                let event_id = placeholder.unwrap_or(response.event_id);
+                context.views.insert(event_id, view, format, collapse);
+            }
+        }
+        Err(err)
+            if err
+                .as_client_api_error()
+                .and_then(|err| err.error_kind())
+                .is_some_and(|kind| matches!(kind, ErrorKind::TooLarge)) =>
+        {
+            audit::set_outcome(Outcome::TooLarge);
+            room.reply_to(event, error_message(Message::SliceTooLarge))
+                .await?;
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    Ok(())
+}
+
+/// Reply with the resolved parameters of a command and the age of the leaderboard data, which
+/// was served from the cache if it was last updated before the command started.
+pub async fn send_debug(
+    room: &Room,
+    event: &OriginalRoomMessageEvent,
+    started: DateTime<Utc>,
+    last_update: DateTime<Utc>,
+    star_discrepancies: &[StarDiscrepancy],
+    params: &[(&str, String)],
+) -> anyhow::Result<()> {
+    let params = params
+        .iter()
+        .map(|(key, value)| format!("`{key}={value}`"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let source = if last_update < started {
+        "cache"
+    } else {
+        "Advent of Code"
+    };
+    let age = fmt_timedelta(now() - last_update);
+    let mut message = format!("🔧 Resolved parameters: {params}  \nData: from {source}, {age} old");
+    if !star_discrepancies.is_empty() {
+        let members = star_discrepancies
+            .iter()
+            .map(|d| {
+                format!(
+                    "{} ({} instead of {})",
+                    escape_html(&d.name),
+                    d.stars,
+                    d.completed
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            &mut message,
+            "  \n⚠️ Star counts disagreeing with the completed days were corrected: {members}"
+        )
+        .unwrap();
+    }
+    room.reply_to(event, notice(message)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        aoc::{
+            client::Part2Start,
+            day::AocDay,
+            fixtures::{leaderboard, member, YEAR},
+            models::{ranked_members, DayOrder, PrivateLeaderboardMember},
+        },
+        config::tests::load_with_overrides,
+        context::ContextUsers,
+        matrix::commands::{
+            aoc::{enrich::enrich_blocking, rollup::Period, timeline::Paging},
+            args::Rows,
+        },
+        utils::datetime::set_now,
+    };
+
+    #[test]
+    fn renderers_escape_names() {
+        let today = AocDay { year: YEAR, day: 2 };
+        let _guard = set_now(today.unlock_datetime() + TimeDelta::hours(1));
+        let config = load_with_overrides([
+            r#"users = [{ aoc = 1, repo = "https://example.com/?a=1&b=2" }]"#,
+        ]);
+        let users = ContextUsers::new(&config, &[]);
+        let evil = PrivateLeaderboardMember {
+            local_score: 10,
+            ..member(1, "<b>Alice & Bob", &[(1, 100, Some(200)), (2, 100, None)])
+        };
+        let leaderboard = leaderboard([evil.clone(), member(2, "Carol", &[(1, 300, None)])]);
+        let enrichments = enrich_blocking(&config, &users, &leaderboard);
+        let (ranked, _) = ranked_members(leaderboard.members.values().cloned().collect(), 0, true);
+        let title = Some("<Camel> Cards");
+
+        let day = self::day::build(
+            &config,
+            &users,
+            &enrichments,
+            leaderboard.clone(),
+            title,
+            None,
+            None,
+            now(),
+            day::Params {
+                rows: Rows::All,
+                summary: true,
+                ..day::Params::new(YEAR, 1)
+            },
+        );
+        let params = self::leaderboard::Params {
+            rows: Rows::All,
+            ..self::leaderboard::Params::new(YEAR)
+        };
+        let board = self::leaderboard::tests::render(&config, &leaderboard, params);
+        let season = self::leaderboard::tests::render(
+            &config,
+            &leaderboard,
+            self::leaderboard::Params {
+                strip: true,
+                ..params
+            },
+        );
+        let winners = winners::winners(&leaderboard, YEAR, Parts::Both, Part2Start::Part1);
+        let rendered = [
+            ("day", day.html),
+            ("day compact", day.compact),
+            ("leaderboard", board.html),
+            ("leaderboard compact", board.compact),
+            ("year", season.html),
+            ("year compact", season.compact),
+            (
+                "boards",
+                boards::render(
+                    &config,
+                    YEAR,
+                    2,
+                    vec![("Ours", Some((leaderboard.clone(), now())))],
+                ),
+            ),
+            ("gap", gap::render(&ranked, 2).unwrap()),
+            (
+                "history",
+                history::render(&config, YEAR, 2, 2, leaderboard.clone(), now()),
+            ),
+            (
+                "racing",
+                racing::render(
+                    &config,
+                    &leaderboard,
+                    today,
+                    &racing::progress(&leaderboard, today.day),
+                    now(),
+                ),
+            ),
+            (
+                "random",
+                random::render(
+                    &config,
+                    &evil,
+                    AocDay { year: YEAR, day: 1 },
+                    enrichments[&1].clone(),
+                ),
+            ),
+            ("records", records::render(&leaderboard, YEAR, "now")),
+            (
+                "rollup",
+                rollup::render(&config, &leaderboard, &Period::event(YEAR), 2, now()),
+            ),
+            (
+                "solutions",
+                solutions::render(&config, &HashMap::from([(1, evil.clone())])),
+            ),
+            (
+                "streak",
+                streak::render(
+                    &config.aoc.streak,
+                    &evil.display_name(),
+                    &[(YEAR, Some(true))],
+                ),
+            ),
+            (
+                "timeline",
+                timeline::render(
+                    &leaderboard,
+                    AocDay { year: YEAR, day: 1 },
+                    title,
+                    Paging::Rows {
+                        rows: Rows::All,
+                        offset: 0,
+                    },
+                    "now",
+                ),
+            ),
+            (
+                "user",
+                user::render(
+                    &config,
+                    &users,
+                    &leaderboard,
+                    &evil,
+                    YEAR,
+                    DayOrder::Ascending,
+                    "now",
+                ),
+            ),
+            (
+                "whois",
+                whois::render(&config, Some(&evil), Some(&config.users[0])),
+            ),
+            (
+                "winners",
+                winners::render(&config, &leaderboard, YEAR, Parts::Both, &winners, now()),
+            ),
+            ("mention", evil.matrix_mention_or_display_name(None)),
+            (
+                "html mention",
+                evil.matrix_mention_or_display_name_html(None),
+            ),
+        ];
+
+        for (renderer, html) in rendered {
+            assert!(
+                html.contains("&lt;b&gt;Alice &amp; Bob"),
+                "{renderer} does not show the escaped name:\n{html}"
+            );
+            for raw in ["<b>Alice", "<Camel>", "?a=1&b=2"] {
+                assert!(!html.contains(raw), "{renderer} contains {raw:?}:\n{html}");
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use chrono::TimeDelta;
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{
+        client::{score_day, Part2Start, Parts},
+        day::AocDay,
+        models::{AocId, PrivateLeaderboard},
+    },
+    context::Context,
+    matrix::{
+        commands::{
+            aoc::{day::completion_delta, send_fetch_error},
+            args::{parse_day, parse_part2_start, parse_parts, parse_year},
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{notice, RoomExt},
+    },
+    utils::fmt::fmt_timedelta,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Standing {
+    time: TimeDelta,
+    slower: usize,
+    finishers: usize,
+}
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let users = context.users.read().await;
+    let Some(aoc_id) = users.by_matrix.get(&event.sender).and_then(|u| u.aoc) else {
+        return send_error(
+            &room,
+            event,
+            "Your matrix account is not linked to an AoC account",
+        )
+        .await;
+    };
+
+    let day = match parse_day(
+        cmd.get_from_kwargs("day"),
+        context.config.aoc.default_day_grace(),
+    ) {
+        Ok(day) => day,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let default_parts = context.config.default_parts(room.room_id());
+    let parts = match parse_parts(cmd.get_from_kwargs("p"), default_parts) {
+        Ok(parts) => parts,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let part2_start = match parse_part2_start(
+        cmd.get_from_kwargs("p2_from"),
+        context.config.aoc.part2_start,
+    ) {
+        Ok(part2_start) => part2_start,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let day = AocDay { year, day };
+
+    let (leaderboard, _) = match context.aoc_client.get_private_leaderboard(year).await {
+        Ok(resp) => resp,
+        Err(err) => return send_fetch_error(&room, event, year, err).await,
+    };
+
+    let Some(standing) = standing(&leaderboard, day, parts, part2_start, aoc_id) else {
+        return send_error(
+            &room,
+            event,
+            format!(
+                "You have not completed {} of day {} of {year} yet",
+                fmt_parts(parts),
+                day.day
+            ),
+        )
+        .await;
+    };
+
+    room.reply_to(event, notice(render(day, parts, standing)))
+        .await?;
+
+    Ok(())
+}
+
+fn standing(
+    leaderboard: &PrivateLeaderboard,
+    day: AocDay,
+    parts: Parts,
+    part2_start: Part2Start,
+    id: AocId,
+) -> Option<Standing> {
+    let required = match parts {
+        Parts::Both => 2,
+        Parts::P1 | Parts::P2 => 1,
+    };
+    let mut daily = leaderboard.clone();
+    score_day(&mut daily, day.day, parts, part2_start, false);
+    let unlock = day.unlock_datetime();
+    let times = daily
+        .members
+        .values()
+        .filter(|m| m.stars == required)
+        .filter_map(|m| {
+            Some((
+                m.id,
+                completion_delta(m, day.day, parts, part2_start, unlock)?,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let &(_, time) = times.iter().find(|&&(member, _)| member == id)?;
+    Some(Standing {
+        time,
+        slower: times.iter().filter(|&&(_, t)| t > time).count(),
+        finishers: times.len(),
+    })
+}
+
+fn fmt_parts(parts: Parts) -> &'static str {
+    match parts {
+        Parts::P1 => "part 1",
+        Parts::P2 => "part 2",
+        Parts::Both => "both parts",
+    }
+}
+
+fn render(day: AocDay, parts: Parts, standing: Standing) -> String {
+    let Standing {
+        time,
+        slower,
+        finishers,
+    } = standing;
+    let prefix = format!(
+        "You completed {} of day {} of {} in {}",
+        fmt_parts(parts),
+        day.day,
+        day.year,
+        fmt_timedelta(time)
+    );
+    let others = finishers - 1;
+    if others == 0 {
+        return format!("{prefix}, the only finisher so far");
+    }
+    let percent = slower * 100 / others;
+    let finishers = if others == 1 { "finisher" } else { "finishers" };
+    format!("{prefix}, faster than {percent}% of the {others} other {finishers}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::fixtures::{leaderboard, member, YEAR};
+
+    #[test]
+    fn percentile() {
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200))]),
+            member(2, "Bob", &[(1, 150, Some(400))]),
+            member(3, "Carol", &[(1, 90, Some(300))]),
+            member(4, "Dave", &[(1, 60, Some(900))]),
+            member(5, "Eve", &[(1, 50, None)]),
+        ]);
+        let day = AocDay { year: YEAR, day: 1 };
+        let standing = |parts, part2_start, id| standing(&leaderboard, day, parts, part2_start, id);
+
+        let alice = standing(Parts::Both, Part2Start::Part1, 1).unwrap();
+        assert_eq!(
+            alice,
+            Standing {
+                time: TimeDelta::seconds(200),
+                slower: 3,
+                finishers: 4,
+            }
+        );
+        assert_eq!(
+            render(day, Parts::Both, alice),
+            "You completed both parts of day 1 of 2024 in 3m 20s, faster than 100% of the 3 other \
+             finishers"
+        );
+        let dave = standing(Parts::Both, Part2Start::Part1, 4).unwrap();
+        assert_eq!(dave.slower, 0);
+        assert!(render(day, Parts::Both, dave).contains("faster than 0% of the 3 other"));
+
+        // part 1 only finishers count for part 1, part 2 is timed from part 1 or the unlock
+        let eve = standing(Parts::P1, Part2Start::Part1, 5).unwrap();
+        assert_eq!((eve.slower, eve.finishers), (4, 5));
+        assert_eq!(standing(Parts::Both, Part2Start::Part1, 5), None);
+        let bob = standing(Parts::P2, Part2Start::Part1, 2).unwrap();
+        assert_eq!((bob.time, bob.slower), (TimeDelta::seconds(250), 1));
+        let bob = standing(Parts::P2, Part2Start::Unlock, 2).unwrap();
+        assert_eq!((bob.time, bob.slower), (TimeDelta::seconds(400), 1));
+
+        let only = Standing {
+            time: TimeDelta::seconds(59),
+            slower: 0,
+            finishers: 1,
+        };
+        assert_eq!(
+            render(day, Parts::P1, only),
+            "You completed part 1 of day 1 of 2024 in 59s, the only finisher so far"
+        );
+    }
+}
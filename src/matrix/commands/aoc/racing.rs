@@ -0,0 +1,170 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{
+        day::AocDay,
+        models::{disambiguated_display_names, AocId, PrivateLeaderboard},
+    },
+    config::Config,
+    context::Context,
+    matrix::{
+        commands::{
+            aoc::{fmt_last_update, send_fetch_error},
+            send_error,
+        },
+        utils::{html_message, RoomExt},
+    },
+    utils::fmt::{escape_html, fmt_timedelta},
+};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct Progress {
+    racing: Vec<(AocId, DateTime<Utc>)>,
+    done: Vec<(AocId, DateTime<Utc>)>,
+}
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+) -> anyhow::Result<()> {
+    let Some(today) = AocDay::current() else {
+        return send_error(
+            &room,
+            event,
+            "Today's puzzle has not unlocked yet, the event is not running",
+        )
+        .await;
+    };
+
+    let (leaderboard, last_update) =
+        match context.aoc_client.get_private_leaderboard(today.year).await {
+            Ok(resp) => resp,
+            Err(err) => return send_fetch_error(&room, event, today.year, err).await,
+        };
+
+    let progress = progress(&leaderboard, today.day);
+    let html = render(&context.config, &leaderboard, today, &progress, last_update);
+    room.reply_to(event, html_message(html)).await?;
+
+    Ok(())
+}
+
+pub(super) fn progress(leaderboard: &PrivateLeaderboard, day: u32) -> Progress {
+    let mut progress = Progress::default();
+    for member in leaderboard.members.values() {
+        let Some(completion) = member.completion_day_level.get(&day) else {
+            continue;
+        };
+        match &completion.snd {
+            Some(p2) => progress.done.push((member.id, p2.get_star_ts)),
+            None => progress
+                .racing
+                .push((member.id, completion.fst.get_star_ts)),
+        }
+    }
+    progress.racing.sort_unstable_by_key(|&(id, ts)| (ts, id));
+    progress.done.sort_unstable_by_key(|&(id, ts)| (ts, id));
+    progress
+}
+
+pub(super) fn render(
+    config: &Config,
+    leaderboard: &PrivateLeaderboard,
+    today: AocDay,
+    progress: &Progress,
+    last_update: DateTime<Utc>,
+) -> String {
+    let names = disambiguated_display_names(leaderboard.members.values());
+    let unlock = today.unlock_datetime();
+    let list = |members: &[(AocId, DateTime<Utc>)], part: u8| {
+        members
+            .iter()
+            .map(|(id, ts)| {
+                format!(
+                    "{} (part {part} in {})",
+                    escape_html(&names[id]),
+                    fmt_timedelta(*ts - unlock)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    // the data may be several minutes behind, which matters for a live view
+    let mut out = format!(
+        "<h3>Advent of Code {}: Day {} in progress</h3>\n<p><b>Data as of {}</b></p>\n",
+        today.year,
+        today.day,
+        fmt_last_update(config, last_update)
+    );
+    if progress.racing.is_empty() {
+        out.push_str("<p>🏁 Nobody is racing part 2 right now</p>\n");
+    } else {
+        writeln!(
+            &mut out,
+            "<p>🏁 <b>{}</b> racing part 2: {}</p>",
+            progress.racing.len(),
+            list(&progress.racing, 1)
+        )
+        .unwrap();
+    }
+    if !progress.done.is_empty() {
+        writeln!(
+            &mut out,
+            "<p>✅ <b>{}</b> done: {}</p>",
+            progress.done.len(),
+            list(&progress.done, 2)
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aoc::fixtures::{leaderboard, member, YEAR},
+        config::tests::load_with_overrides,
+        utils::datetime::{now, set_now},
+    };
+
+    #[test]
+    fn render() {
+        let today = AocDay { year: YEAR, day: 2 };
+        let _guard = set_now(today.unlock_datetime() + chrono::TimeDelta::hours(1));
+        let config = load_with_overrides([]);
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200)), (2, 300, Some(900))]),
+            member(2, "Bob", &[(2, 120, None)]),
+            member(3, "Carol", &[(2, 60, None)]),
+            member(4, "Dave", &[(1, 100, None)]),
+        ]);
+
+        let progress = progress(&leaderboard, today.day);
+        let unlock = today.unlock_datetime();
+        let at = |secs| unlock + chrono::TimeDelta::seconds(secs);
+        assert_eq!(
+            progress,
+            Progress {
+                racing: vec![(3, at(60)), (2, at(120))],
+                done: vec![(1, at(900))],
+            }
+        );
+
+        let html = super::render(&config, &leaderboard, today, &progress, now());
+        assert!(html.contains("<p><b>Data as of "));
+        assert!(html.contains(
+            "<p>🏁 <b>2</b> racing part 2: Carol (part 1 in 1m 0s), Bob (part 1 in 2m 0s)</p>"
+        ));
+        assert!(html.contains("<p>✅ <b>1</b> done: Alice (part 2 in 15m 0s)</p>"));
+
+        let html = super::render(&config, &leaderboard, today, &Progress::default(), now());
+        assert!(html.contains("Nobody is racing part 2"));
+        assert!(!html.contains("done"));
+    }
+}
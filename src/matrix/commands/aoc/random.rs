@@ -0,0 +1,193 @@
+use std::hash::{BuildHasher, RandomState};
+
+use chrono::{DateTime, Utc};
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{
+        day::AocDay,
+        models::{PrivateLeaderboard, PrivateLeaderboardMember},
+    },
+    config::Config,
+    context::Context,
+    matrix::{
+        commands::{
+            aoc::{
+                enrich::{Enrichment, Pipeline},
+                send_fetch_error,
+            },
+            args::{parse_day, parse_year},
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{html_message, Mentions, RoomExt},
+    },
+    utils::fmt::{escape_html, fmt_timedelta},
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let day = match parse_day(
+        cmd.get_from_kwargs("day"),
+        context.config.aoc.default_day_grace(),
+    ) {
+        Ok(day) => day,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let day = AocDay { year, day };
+
+    let (leaderboard, _) = match context.aoc_client.get_private_leaderboard(year).await {
+        Ok(resp) => resp,
+        Err(err) => return send_fetch_error(&room, event, year, err).await,
+    };
+
+    let candidates = candidates(&leaderboard, day.day);
+    // a fresh random state is seeded randomly, so every invocation picks independently
+    let Some(member) = pick(&candidates, RandomState::new().hash_one((year, day.day))) else {
+        return send_error(
+            &room,
+            event,
+            format!(
+                "Nobody who can be spotlighted solved day {} of {year} yet",
+                day.day
+            ),
+        )
+        .await;
+    };
+
+    let users = context.users.read().await;
+    let enrichment = Pipeline::new(&context.config, &users)
+        .enrich_all([member])
+        .await
+        .remove(&member.id)
+        .unwrap_or_default();
+    let html = render(&context.config, member, day, enrichment);
+    room.reply_to(event, html_message(html)).await?;
+
+    Ok(())
+}
+
+/// The members who solved both parts of the day, by id. Anonymous members and members who opted
+/// out of mentions are never spotlighted.
+fn candidates(leaderboard: &PrivateLeaderboard, day: u32) -> Vec<&PrivateLeaderboardMember> {
+    let mut candidates = leaderboard
+        .members
+        .values()
+        .filter(|m| m.name.is_some() && !m.no_ping)
+        .filter(|m| {
+            m.completion_day_level
+                .get(&day)
+                .is_some_and(|c| c.snd.is_some())
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_unstable_by_key(|m| m.id);
+    candidates
+}
+
+fn pick<'a>(
+    candidates: &[&'a PrivateLeaderboardMember],
+    seed: u64,
+) -> Option<&'a PrivateLeaderboardMember> {
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(candidates[(seed % candidates.len() as u64) as usize])
+}
+
+pub(super) fn render(
+    config: &Config,
+    member: &PrivateLeaderboardMember,
+    day: AocDay,
+    enrichment: Enrichment,
+) -> String {
+    let unlock = day.unlock_datetime();
+    let completion = member.completion_day_level.get(&day.day);
+    let time = |ts: Option<DateTime<Utc>>| {
+        ts.map(|ts| fmt_timedelta(ts - unlock).to_string())
+            .unwrap_or_default()
+    };
+
+    let mention = enrichment
+        .matrix_user
+        .map(|m| format!(" ({})", Mentions::new(1).mention_member(member, &m)))
+        .unwrap_or_default();
+    let mut out = format!(
+        "<p>🎉 Spotlight on day {} of {}: <b>{}</b>{mention} solved both parts in <b>{}</b> \
+         (part 1 in {})!</p>\n",
+        day.day,
+        day.year,
+        escape_html(&member.display_name()),
+        time(completion.and_then(|c| Some(c.snd.as_ref()?.get_star_ts))),
+        time(completion.map(|c| c.fst.get_star_ts))
+    );
+    if let Some(repo) = enrichment.repo {
+        out.push_str(&format!(
+            "<p>📦 Take a look at their solutions: <a href=\"{}{}\">{}</a></p>\n",
+            config.matrix.link_prefix,
+            escape_html(&repo.url),
+            escape_html(&repo.title)
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aoc::fixtures::{leaderboard, member, YEAR},
+        config::tests::load_with_overrides,
+        matrix::commands::aoc::enrich::Repo,
+    };
+
+    #[test]
+    fn spotlight() {
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200))]),
+            member(2, "Bob", &[(1, 150, None)]),
+            member(3, "", &[(1, 90, Some(95))]),
+            PrivateLeaderboardMember {
+                no_ping: true,
+                ..member(4, "Dave", &[(1, 60, Some(70))])
+            },
+            member(5, "Eve", &[(1, 300, Some(3600)), (2, 10, Some(20))]),
+        ]);
+
+        // part 1 only, anonymous and opted out members are left out
+        let candidates = candidates(&leaderboard, 1);
+        let ids = candidates.iter().map(|m| m.id).collect::<Vec<_>>();
+        assert_eq!(ids, [1, 5]);
+        assert_eq!(pick(&candidates, 0).unwrap().id, 1);
+        assert_eq!(pick(&candidates, 7).unwrap().id, 5);
+        assert_eq!(pick(&super::candidates(&leaderboard, 3), 0), None);
+
+        let config = load_with_overrides([]);
+        let day = AocDay { year: YEAR, day: 1 };
+        let enrichment = Enrichment {
+            matrix_user: Some("@eve:example.com".try_into().unwrap()),
+            repo: Some(Repo {
+                url: "https://github.com/eve/aoc".into(),
+                title: "eve/aoc".into(),
+            }),
+            newcomer: false,
+        };
+        let html = render(&config, candidates[1], day, enrichment);
+        assert!(html.contains(
+            "Spotlight on day 1 of 2024: <b>Eve</b> (https://matrix.to/#/@eve:example.com) solved \
+             both parts in <b>1h 0m 0s</b> (part 1 in 5m 0s)!"
+        ));
+        assert!(html.contains(">eve/aoc</a>"));
+
+        let html = render(&config, candidates[0], day, Enrichment::default());
+        assert!(!html.contains("matrix.to"));
+        assert!(!html.contains("solutions"));
+    }
+}
@@ -0,0 +1,159 @@
+use matrix_sdk::{
+    ruma::{EventId, OwnedEventId},
+    Room,
+};
+use tracing::warn;
+
+use super::{day, leaderboard};
+use crate::{
+    aoc::{client::Parts, day::AocDay},
+    context::Context,
+    matrix::{commands::args::Format, utils::RoomExt},
+    utils::lru::{CacheStats, Lru},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Day(day::Params),
+    Leaderboard(leaderboard::Params),
+}
+
+impl View {
+    pub fn year(&self) -> i32 {
+        match self {
+            Self::Day(params) => params.year,
+            Self::Leaderboard(params) => params.year,
+        }
+    }
+}
+
+impl From<day::Params> for View {
+    fn from(params: day::Params) -> Self {
+        Self::Day(params)
+    }
+}
+
+impl From<leaderboard::Params> for View {
+    fn from(params: leaderboard::Params) -> Self {
+        Self::Leaderboard(params)
+    }
+}
+
+#[derive(Debug)]
+pub struct Views(Lru<OwnedEventId, (View, Format, bool)>);
+
+impl Views {
+    pub fn new(capacity: usize) -> Self {
+        Self(Lru::new("views", capacity))
+    }
+
+    pub fn insert(&self, event_id: OwnedEventId, view: View, format: Format, collapsed: bool) {
+        self.0.insert(event_id, (view, format, collapsed));
+    }
+
+    pub fn get(&self, event_id: &EventId) -> Option<(View, Format, bool)> {
+        self.0.get(event_id)
+    }
+
+    pub fn retain(&self, mut f: impl FnMut(&View) -> bool) -> usize {
+        self.0.retain(|_, (view, ..)| f(view))
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.0.stats()
+    }
+}
+
+/// Re-render a leaderboard that was reacted to with one of the following emojis:
+///
+/// - 1️⃣ / 2️⃣ / ⭐: show the daily leaderboard for part 1 / part 2 / both parts
+/// - 📅: show the daily leaderboard
+/// - 🏆: show the yearly leaderboard
+pub async fn handle(
+    room: &Room,
+    context: &Context,
+    event_id: &EventId,
+    key: &str,
+) -> anyhow::Result<()> {
+    if !context.config.matrix.reactions_enabled(room.room_id()) {
+        return Ok(());
+    }
+
+    let Some((view, format, collapsed)) = context.views.get(event_id) else {
+        return Ok(());
+    };
+
+    let day_params = |parts: Option<Parts>| match view {
+        View::Day(params) => day::Params {
+            parts: parts.unwrap_or(params.parts),
+            ..params
+        },
+        View::Leaderboard(params) => {
+            let most_recent = AocDay::most_recent();
+            day::Params {
+                day: if params.year == most_recent.year {
+                    most_recent.day
+                } else {
+                    25
+                },
+                year: params.year,
+                parts: parts.unwrap_or(context.config.default_parts(room.room_id())),
+                part2_start: context.config.aoc.part2_start,
+                rows: params.rows,
+                offset: params.offset,
+                summary: context.config.aoc.day_summary,
+            }
+        }
+    };
+
+    let new_view = match key.replace('\u{fe0f}', "").as_str() {
+        "1\u{20e3}" => day_params(Some(Parts::P1)).into(),
+        "2\u{20e3}" => day_params(Some(Parts::P2)).into(),
+        "⭐" => day_params(Some(Parts::Both)).into(),
+        "📅" => day_params(None).into(),
+        "🏆" => match view {
+            View::Day(params) => leaderboard::Params {
+                year: params.year,
+                rows: params.rows,
+                offset: params.offset,
+                min_stars: context.config.aoc.participation.min_stars,
+                here: context.config.aoc.room_members_only,
+                strip: false,
+            }
+            .into(),
+            view @ View::Leaderboard(_) => view,
+        },
+        _ => return Ok(()),
+    };
+    if new_view == view {
+        return Ok(());
+    }
+
+    let rendered = match new_view {
+        View::Day(params) => day::render(context, params, None)
+            .await
+            .map(|(rendered, _)| rendered),
+        View::Leaderboard(params) => leaderboard::render(context, room, params).await,
+    };
+    let mut rendered = match rendered {
+        Ok(rendered) => rendered,
+        Err(err) => {
+            warn!("Failed to re-render leaderboard {event_id}: {err:#}");
+            return Ok(());
+        }
+    };
+
+    if collapsed {
+        rendered.collapse();
+    }
+    room.edit(event_id, rendered.message(format), false).await?;
+    context
+        .views
+        .insert(event_id.to_owned(), new_view, format, collapsed);
+
+    Ok(())
+}
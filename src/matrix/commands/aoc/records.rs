@@ -0,0 +1,222 @@
+use std::{cmp::Reverse, collections::HashMap, fmt::Write};
+
+use chrono::TimeDelta;
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{
+        day::AocDay,
+        models::{AocId, PrivateLeaderboard},
+    },
+    context::Context,
+    matrix::{
+        commands::{
+            aoc::{fmt_last_update, send_fetch_error},
+            args::parse_year,
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{html_message, RoomExt},
+    },
+    utils::fmt::{escape_html, fmt_timedelta},
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    let (leaderboard, last_update) = match context.aoc_client.get_private_leaderboard(year).await {
+        Ok(resp) => resp,
+        Err(err) => return send_fetch_error(&room, event, year, err).await,
+    };
+    let last_update = fmt_last_update(&context.config, last_update);
+
+    let out = render(&leaderboard, year, &last_update);
+    room.reply_to(event, html_message(out)).await?;
+
+    Ok(())
+}
+
+pub(super) fn render(leaderboard: &PrivateLeaderboard, year: i32, last_update: &str) -> String {
+    let records = Records::compute(leaderboard, year);
+    let name = |id: AocId| {
+        leaderboard
+            .members
+            .values()
+            .find(|m| m.id == id)
+            .map(|m| escape_html(&m.display_name()).into_owned())
+            .unwrap_or_default()
+    };
+
+    let mut out = format!("\n<h3>Records (Advent of Code {year})</h3>\n<ul>\n");
+    if let Some(DayRecord { member, day, value }) = records.fastest_p1 {
+        let (name, delta) = (name(member), fmt_timedelta(value));
+        writeln!(
+            &mut out,
+            "<li>Fastest part 1: <b>{name}</b> on day {day} in <b>{delta}</b></li>"
+        )
+        .unwrap();
+    }
+    if let Some(DayRecord { member, day, value }) = records.fastest_p2 {
+        let (name, delta) = (name(member), fmt_timedelta(value));
+        writeln!(
+            &mut out,
+            "<li>Fastest part 2 after part 1: <b>{name}</b> on day {day} in <b>{delta}</b></li>"
+        )
+        .unwrap();
+    }
+    if let Some(DayRecord { member, day, value }) = records.best_day {
+        let name = name(member);
+        writeln!(
+            &mut out,
+            "<li>Largest single-day score: <b>{name}</b> with <b>{value}</b> points on day {day}</li>"
+        )
+        .unwrap();
+    }
+    if let Some((member, count)) = records.most_top3 {
+        let name = name(member);
+        writeln!(
+            &mut out,
+            "<li>Most top 3 finishes: <b>{name}</b> with <b>{count}</b></li>"
+        )
+        .unwrap();
+    }
+    if records == Records::default() {
+        out.push_str("<li>No stars have been earned yet</li>\n");
+    }
+    write!(&mut out, "</ul>\n<sup>Last update: {last_update}</sup>\n").unwrap();
+
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DayRecord<T> {
+    member: AocId,
+    day: u32,
+    value: T,
+}
+
+/// Notable records of a year. Ties are won by the earlier day, then by the lower member id.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Records {
+    /// Shortest time between puzzle unlock and part 1.
+    fastest_p1: Option<DayRecord<TimeDelta>>,
+    /// Shortest time between part 1 and part 2.
+    fastest_p2: Option<DayRecord<TimeDelta>>,
+    best_day: Option<DayRecord<u32>>,
+    most_top3: Option<(AocId, usize)>,
+}
+
+impl Records {
+    fn compute(leaderboard: &PrivateLeaderboard, year: i32) -> Self {
+        let members = leaderboard.members.len() as u32;
+
+        let mut p1 = Vec::new();
+        let mut p2 = Vec::new();
+        let mut day_scores = Vec::new();
+        let mut top3 = HashMap::<AocId, usize>::new();
+
+        for day in 1..=25 {
+            let unlock = AocDay { year, day }.unlock_datetime();
+
+            for member in leaderboard.members.values() {
+                let Some(completion) = member.completion_day_level.get(&day) else {
+                    continue;
+                };
+                let p1_ts = completion.fst.get_star_ts;
+                p1.push((p1_ts - unlock, day, member.id));
+                if let Some(snd) = &completion.snd {
+                    p2.push((snd.get_star_ts - p1_ts, day, member.id));
+                }
+            }
+
+            // local scores as awarded by AoC: the n-th star of each part is worth members - n + 1
+            let mut scores = HashMap::<AocId, u32>::new();
+            let mut ranks = [0; 2];
+            for star in leaderboard.star_events(day) {
+                let rank = &mut ranks[star.part2 as usize];
+                *scores.entry(star.member.id).or_default() += members - *rank;
+                if *rank < 3 {
+                    *top3.entry(star.member.id).or_default() += 1;
+                }
+                *rank += 1;
+            }
+            day_scores.extend(
+                scores
+                    .into_iter()
+                    .map(|(member, score)| (Reverse(score), day, member)),
+            );
+        }
+
+        let record = |(value, day, member)| DayRecord { member, day, value };
+        Self {
+            fastest_p1: p1.into_iter().min().map(record),
+            fastest_p2: p2.into_iter().min().map(record),
+            best_day: day_scores
+                .into_iter()
+                .min()
+                .map(|(Reverse(value), day, member)| DayRecord { member, day, value }),
+            most_top3: tally(top3).first().copied(),
+        }
+    }
+}
+
+/// The members of the given counts from most to least frequent, ties ordered by member id.
+pub fn tally(counts: HashMap<AocId, usize>) -> Vec<(AocId, usize)> {
+    let mut tally = counts.into_iter().collect::<Vec<_>>();
+    tally.sort_unstable_by_key(|&(member, count)| (Reverse(count), member));
+    tally
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::fixtures::{leaderboard, member, YEAR};
+
+    #[test]
+    fn compute() {
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200)), (2, 50, Some(900))]),
+            member(2, "Bob", &[(1, 150, Some(170)), (2, 40, Some(1000))]),
+            member(3, "Carol", &[(1, 300, None), (2, 60, Some(70))]),
+            member(4, "Dave", &[(2, 500, None)]),
+        ]);
+
+        let records = Records::compute(&leaderboard, YEAR);
+        assert_eq!(
+            records,
+            Records {
+                fastest_p1: Some(DayRecord {
+                    member: 2,
+                    day: 2,
+                    value: TimeDelta::seconds(40)
+                }),
+                fastest_p2: Some(DayRecord {
+                    member: 3,
+                    day: 2,
+                    value: TimeDelta::seconds(10)
+                }),
+                // Alice and Bob both earned 4 + 3 points on day 1
+                best_day: Some(DayRecord {
+                    member: 1,
+                    day: 1,
+                    value: 7
+                }),
+                // Alice and Bob finished all of their parts in the top 3
+                most_top3: Some((1, 4)),
+            }
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(Records::compute(&leaderboard([]), YEAR), Records::default());
+    }
+}
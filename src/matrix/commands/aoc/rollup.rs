@@ -0,0 +1,232 @@
+//! Rollup posts summarizing a period of the event, sent by weekly and final schedules.
+
+use std::{cmp::Reverse, collections::HashSet, fmt::Write, ops::RangeInclusive};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    aoc::{
+        day::AocDay,
+        models::{disambiguated_display_names, ranked_members, PrivateLeaderboard},
+    },
+    config::Config,
+    matrix::commands::aoc::{
+        difficulty::{self, difficulty, Metric},
+        fmt_last_update,
+        history::rank_history,
+        HTML_SIZE_BUDGET,
+    },
+    utils::fmt::{escape_html, fmt_rank, fmt_timedelta},
+};
+
+const MOVERS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Period {
+    pub year: i32,
+    pub days: RangeInclusive<u32>,
+    pub whole_event: bool,
+}
+
+impl Period {
+    pub fn week(end: AocDay) -> Self {
+        Self {
+            year: end.year,
+            days: end.day.saturating_sub(6).max(1)..=end.day,
+            whole_event: false,
+        }
+    }
+
+    pub fn event(year: i32) -> Self {
+        Self {
+            year,
+            days: 1..=25,
+            whole_event: true,
+        }
+    }
+}
+
+/// Render the rollup of the given period: stars earned, top movers, the hardest day and the
+/// current top `rows` members. Fewer members are shown if the message would get too large.
+pub fn render(
+    config: &Config,
+    leaderboard: &PrivateLeaderboard,
+    period: &Period,
+    rows: usize,
+    last_update: DateTime<Utc>,
+) -> String {
+    let mut rows = rows;
+    loop {
+        let html = render_rows(config, leaderboard, period, rows, last_update);
+        if html.len() <= HTML_SIZE_BUDGET || rows == 0 {
+            return html;
+        }
+        rows /= 2;
+    }
+}
+
+fn render_rows(
+    config: &Config,
+    leaderboard: &PrivateLeaderboard,
+    period: &Period,
+    rows: usize,
+    last_update: DateTime<Utc>,
+) -> String {
+    let Period { year, days, .. } = period;
+    let (start, end) = (*days.start(), *days.end());
+    let names = disambiguated_display_names(leaderboard.members.values());
+    let name = |id| escape_html(&names[&id]).into_owned();
+
+    let mut out = if period.whole_event {
+        format!("<h3>Advent of Code {year}: Final Rollup</h3>\n<ul>\n")
+    } else {
+        format!("<h3>Advent of Code {year}: Week in Review (Days {start}–{end})</h3>\n<ul>\n")
+    };
+
+    let since = AocDay {
+        year: *year,
+        day: start,
+    }
+    .unlock_datetime();
+    let stars = (1..=25)
+        .flat_map(|day| leaderboard.star_events(day))
+        .filter(|e| e.ts >= since)
+        .collect::<Vec<_>>();
+    let members = stars.iter().map(|e| e.member.id).collect::<HashSet<_>>();
+    writeln!(
+        &mut out,
+        "<li>⭐ <b>{}</b> stars earned by <b>{}</b> members</li>",
+        stars.len(),
+        members.len()
+    )
+    .unwrap();
+
+    // ranks before the period are only known if it does not start with the first day
+    if start > 1 {
+        let history = rank_history(leaderboard, end);
+        let mut movers = history
+            .iter()
+            .filter_map(|(&id, ranks)| {
+                let before = ranks[start as usize - 2]?;
+                let after = ranks[end as usize - 1]?;
+                (after < before).then(|| (Reverse(before - after), id, before, after))
+            })
+            .collect::<Vec<_>>();
+        movers.sort_unstable();
+        let movers = movers
+            .into_iter()
+            .take(MOVERS)
+            .map(|(Reverse(gained), id, before, after)| {
+                format!(
+                    "<b>{}</b> ▲{gained} ({} → {})",
+                    name(id),
+                    fmt_rank(before),
+                    fmt_rank(after)
+                )
+            })
+            .collect::<Vec<_>>();
+        if !movers.is_empty() {
+            writeln!(&mut out, "<li>Top movers: {}</li>", movers.join(", ")).unwrap();
+        }
+    }
+
+    // days nobody finished yet are left out, they would always be the hardest
+    let mut difficulties = days
+        .clone()
+        .map(|day| difficulty(leaderboard, AocDay { year: *year, day }, Metric::Time))
+        .filter(|d| d.median.is_some())
+        .collect::<Vec<_>>();
+    difficulty::sort(&mut difficulties, Metric::Time);
+    if let Some(hardest) = difficulties.first() {
+        writeln!(
+            &mut out,
+            "<li>Hardest day: <b>Day {}</b> (median {}, {}/{} finished)</li>",
+            hardest.day,
+            fmt_timedelta(hardest.median.unwrap_or_default()),
+            hardest.finishers,
+            hardest.participants
+        )
+        .unwrap();
+    }
+    out.push_str("</ul>\n");
+
+    let (ranked, _) = ranked_members(leaderboard.members.values().cloned().collect(), 1, true);
+    if rows > 0 && !ranked.is_empty() {
+        out.push_str("<p><b>Standings</b></p>\n<ol>\n");
+        for (_, member) in ranked.iter().take(rows) {
+            writeln!(
+                &mut out,
+                "<li><b>{}</b> — {} ({}★)</li>",
+                name(member.id),
+                member.score(),
+                member.stars
+            )
+            .unwrap();
+        }
+        out.push_str("</ol>\n");
+    }
+    writeln!(
+        &mut out,
+        "<sup>Last update: {}</sup>",
+        fmt_last_update(config, last_update)
+    )
+    .unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aoc::{
+            fixtures::{leaderboard, member, YEAR},
+            models::PrivateLeaderboardMember,
+        },
+        config::tests::load_with_overrides,
+        utils::datetime::{now, set_now},
+    };
+
+    #[test]
+    fn render() {
+        let _guard = set_now(AocDay { year: YEAR, day: 9 }.unlock_datetime());
+        let config = load_with_overrides([]);
+        let scored = |id, name, local_score, days| PrivateLeaderboardMember {
+            local_score,
+            ..member(id, name, days)
+        };
+        // Bob overtakes Alice during the second week
+        let leaderboard = leaderboard([
+            scored(
+                1,
+                "Alice",
+                10,
+                &[(1, 100, Some(200)), (8, 5000, Some(9000))],
+            ),
+            scored(
+                2,
+                "Bob",
+                12,
+                &[(1, 300, Some(400)), (7, 90, Some(100)), (8, 60, Some(70))],
+            ),
+            scored(3, "Carol", 2, &[(2, 100, None)]),
+        ]);
+
+        let week = Period::week(AocDay { year: YEAR, day: 8 });
+        assert_eq!(week.days, 2..=8);
+        let html = super::render(&config, &leaderboard, &week, 2, now());
+        assert!(html.contains("Week in Review (Days 2–8)"));
+        assert!(html.contains("<li>⭐ <b>7</b> stars earned by <b>3</b> members</li>"));
+        assert!(html.contains("<li>Top movers: <b>Bob</b> ▲1 (🥈 2nd → 🥇 1st)</li>"));
+        assert!(
+            html.contains("<li>Hardest day: <b>Day 8</b> (median 1h 15m 35s, 2/3 finished)</li>")
+        );
+        assert!(
+            html.contains("<li><b>Bob</b> — 12 (6★)</li>\n<li><b>Alice</b> — 10 (4★)</li>\n</ol>")
+        );
+
+        let html = super::render(&config, &leaderboard, &Period::event(YEAR), 2, now());
+        assert!(html.contains("Final Rollup"));
+        assert!(html.contains("<b>11</b> stars earned"));
+        assert!(!html.contains("Top movers"));
+    }
+}
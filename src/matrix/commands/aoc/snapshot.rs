@@ -0,0 +1,97 @@
+use chrono::TimeZone;
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    context::Context,
+    matrix::{
+        commands::{
+            aoc::{leaderboard, send_fetch_error},
+            args::{parse_ago, parse_year, Rows},
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{file_message, RoomExt},
+    },
+    utils::datetime::{now, DateTimeExt},
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let ago = match parse_ago(cmd.get_from_kwargs("ago")) {
+        Ok(ago) => ago,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    // the snapshot is uploaded as a file, so it is not subject to the message size limit
+    let params = leaderboard::Params {
+        year,
+        rows: Rows::Count(usize::MAX),
+        offset: 0,
+        min_stars: 0,
+        here: false,
+        strip: false,
+    };
+    let rendered = match ago {
+        Some(ago) => match leaderboard::render_archived(context, params, now() - ago).await? {
+            Some(rendered) => rendered,
+            None => {
+                return send_error(
+                    &room,
+                    event,
+                    format!("No leaderboard of {year} was archived at that time"),
+                )
+                .await
+            }
+        },
+        None => match leaderboard::render(context, &room, params).await {
+            Ok(rendered) => rendered,
+            Err(err) => return send_fetch_error(&room, event, year, err).await,
+        },
+    };
+
+    let generated_at = context
+        .config
+        .local_timezone
+        .from_utc_datetime(&now().naive_utc())
+        .format_ymd_hms_z();
+    let document = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ background: #0f0f23; color: #cccccc; font-family: monospace; }}
+a {{ color: #009900; }}
+th, td {{ padding: 0.2em 0.8em; text-align: left; }}
+</style>
+</head>
+<body>
+{html}
+<p><sup>Generated at {generated_at}</sup></p>
+</body>
+</html>
+"#,
+        title = rendered.table.title,
+        html = rendered.html,
+    );
+
+    let content = file_message(
+        &room,
+        &format!("leaderboard-{year}.html"),
+        &mime_guess::mime::TEXT_HTML_UTF_8,
+        document.into_bytes(),
+    )
+    .await?;
+    room.reply_to(event, content).await?;
+
+    Ok(())
+}
@@ -3,8 +3,13 @@ use std::{cmp::Reverse, collections::HashMap, fmt::Write};
 use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
 
 use crate::{
-    aoc::{day::AocDay, models::PrivateLeaderboardMember},
-    matrix::utils::{html_message, RoomExt},
+    aoc::{
+        day::AocDay,
+        models::{AocId, PrivateLeaderboardMember},
+    },
+    config::Config,
+    matrix::utils::{html_message, Mentions, RoomExt},
+    utils::fmt::escape_html,
     Context,
 };
 
@@ -23,6 +28,16 @@ pub async fn invoke(
         .map(|u| (u.id, u))
         .collect::<HashMap<_, _>>();
 
+    let solutions = render(&context.config, &aoc_users);
+    room.reply_to(event, html_message(solutions)).await?;
+
+    Ok(())
+}
+
+pub(super) fn render(
+    config: &Config,
+    aoc_users: &HashMap<AocId, PrivateLeaderboardMember>,
+) -> String {
     let mut solutions = String::from(
         r#"
 <h3>Advent of Code Solution Repositories</h3>
@@ -33,27 +48,18 @@ pub async fn invoke(
 
     let mut rows = Vec::new();
 
-    for user in &context.config.users {
+    for user in &config.users {
         let Some(repo) = &user.repo else { continue };
 
         let aoc_user = user.aoc.and_then(|id| aoc_users.get(&id));
 
         let name = aoc_user.map(|u| u.display_name()).unwrap_or_default();
 
-        let matrix_name = user
-            .matrix
-            .as_ref()
-            .map(|m| m.matrix_to_uri().to_string())
-            .unwrap_or_default();
+        let matrix_user = user.matrix.as_deref();
 
-        let repo_title = context
-            .config
-            .aoc
-            .repo_rules
-            .match_and_replace(repo)
-            .map(|m| m.replacement);
+        let repo_title = config.aoc.repo_title(repo);
 
-        rows.push((aoc_user, name, matrix_name, repo, repo_title));
+        rows.push((aoc_user, name, matrix_user, repo, repo_title));
     }
 
     rows.sort_unstable_by(|a, b| {
@@ -66,9 +72,20 @@ pub async fn invoke(
         key(a.0, &a.1).cmp(&key(b.0, &b.1))
     });
 
-    for (_, name, matrix_name, repo, repo_title) in rows {
-        let repo_title = repo_title.as_deref().unwrap_or(repo);
-        let link_prefix = &context.config.matrix.link_prefix;
+    // mention the users in the order they are shown
+    let mut mentions = Mentions::new(config.matrix.max_mentions);
+    for (aoc_user, name, matrix_user, repo, repo_title) in rows {
+        let matrix_name = match (aoc_user, matrix_user) {
+            (Some(member), Some(m)) => mentions.mention_member(member, m),
+            (None, Some(m)) => mentions.mention(m),
+            (_, None) => String::new(),
+        };
+        let link_prefix = &config.matrix.link_prefix;
+        let (name, repo, repo_title) = (
+            escape_html(&name),
+            escape_html(repo),
+            escape_html(&repo_title),
+        );
         write!(
             &mut solutions,
             r#"
@@ -83,8 +100,9 @@ pub async fn invoke(
     }
 
     solutions.push_str("</table>");
+    if let Some(note) = mentions.note() {
+        write!(&mut solutions, "\n<sup>{note}</sup>").unwrap();
+    }
 
-    room.reply_to(event, html_message(solutions)).await?;
-
-    Ok(())
+    solutions
 }
@@ -0,0 +1,195 @@
+use std::{fmt::Write, ops::RangeInclusive};
+
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::models::{AocId, PrivateLeaderboard},
+    config::StreakConfig,
+    context::Context,
+    i18n::Message,
+    matrix::{
+        commands::{aoc::user::find_member, parser::ParsedCommand, send_error},
+        utils::{notice, RoomExt},
+    },
+    utils::fmt::escape_html,
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let name = cmd.get_from_kwargs("user");
+    let leaderboards = context.aoc_client.get_all_private_leaderboards().await;
+
+    // the member may have left the leaderboard, so older years are searched as well
+    let users = context.users.read().await;
+    let Some(member) = leaderboards
+        .iter()
+        .rev()
+        .filter_map(|(_, leaderboard)| leaderboard.as_ref())
+        .find_map(|leaderboard| find_member(leaderboard, &users, event, name))
+    else {
+        return send_error(&room, event, Message::UserNotFound).await;
+    };
+
+    let participated = participation(&leaderboards, member.id);
+    let message = render(
+        &context.config.aoc.streak,
+        &member.display_name(),
+        &participated,
+    );
+    room.reply_to(event, notice(message)).await?;
+
+    Ok(())
+}
+
+/// Whether the given member earned at least one star in each year, or `None` if the leaderboard
+/// of the year could not be fetched.
+fn participation(
+    leaderboards: &[(i32, Option<PrivateLeaderboard>)],
+    id: AocId,
+) -> Vec<(i32, Option<bool>)> {
+    leaderboards
+        .iter()
+        .map(|(year, leaderboard)| {
+            let participated = leaderboard
+                .as_ref()
+                .map(|l| l.members.values().any(|m| m.id == id && m.stars > 0));
+            (*year, participated)
+        })
+        .collect()
+}
+
+/// The current streak (ending at the most recent year) and the longest streak of consecutive
+/// years of participation. Years that could not be fetched end a streak.
+fn streaks(
+    participated: &[(i32, Option<bool>)],
+) -> (Option<RangeInclusive<i32>>, Option<RangeInclusive<i32>>) {
+    let mut runs = Vec::<RangeInclusive<i32>>::new();
+    let mut start = None;
+    for &(year, participated) in participated {
+        match (participated == Some(true), start) {
+            (true, None) => start = Some(year),
+            (false, Some(s)) => {
+                runs.push(s..=year - 1);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    let current = participated
+        .last()
+        .zip(start)
+        .map(|(&(last, _), start)| start..=last);
+    runs.extend(current.clone());
+    // ties are resolved in favor of the most recent streak
+    let longest = runs.into_iter().max_by_key(|run| run.end() - run.start());
+    (current, longest)
+}
+
+fn fmt_streak(streak: &RangeInclusive<i32>) -> String {
+    let years = streak.end() - streak.start() + 1;
+    match years {
+        1 => format!("1 year ({})", streak.start()),
+        _ => format!("{years} years ({}–{})", streak.start(), streak.end()),
+    }
+}
+
+pub(super) fn render(
+    config: &StreakConfig,
+    name: &str,
+    participated: &[(i32, Option<bool>)],
+) -> String {
+    let name = escape_html(name);
+    let (current, longest) = streaks(participated);
+    let mut out = match (&current, &longest) {
+        (Some(current), _) => {
+            let mut out = format!(
+                "**{name}** has a participation streak of {}",
+                fmt_streak(current)
+            );
+            if current.end() - current.start() + 1 >= config.badge_years as i32 {
+                out.push(' ');
+                out.push_str(&config.badge);
+            }
+            if let Some(longest) = longest.as_ref().filter(|&l| l != current) {
+                write!(&mut out, ", their longest was {}", fmt_streak(longest)).unwrap();
+            }
+            out
+        }
+        (None, Some(longest)) => format!(
+            "**{name}** has no current participation streak, their longest was {}",
+            fmt_streak(longest)
+        ),
+        (None, None) => format!("**{name}** has not earned any stars yet"),
+    };
+
+    let failed = participated
+        .iter()
+        .filter(|(_, p)| p.is_none())
+        .map(|(year, _)| year.to_string())
+        .collect::<Vec<_>>();
+    if !failed.is_empty() {
+        write!(
+            &mut out,
+            " (the leaderboards of {} could not be fetched)",
+            failed.join(", ")
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::tests::load_with_overrides;
+
+    #[test]
+    fn render() {
+        let config = load_with_overrides([]).aoc.streak;
+        let render =
+            |participated: &[(i32, Option<bool>)]| super::render(&config, "Alice", participated);
+
+        assert_eq!(
+            render(&[
+                (2019, Some(true)),
+                (2020, Some(false)),
+                (2021, Some(true)),
+                (2022, Some(true)),
+                (2023, Some(true)),
+                (2024, Some(true)),
+            ]),
+            "**Alice** has a participation streak of 4 years (2021–2024)"
+        );
+        assert_eq!(
+            render(&[
+                (2018, Some(true)),
+                (2019, Some(true)),
+                (2020, Some(true)),
+                (2021, Some(true)),
+                (2022, Some(true)),
+            ]),
+            "**Alice** has a participation streak of 5 years (2018–2022) 🔥"
+        );
+        assert_eq!(
+            render(&[
+                (2020, Some(true)),
+                (2021, Some(true)),
+                (2022, None),
+                (2023, Some(true)),
+            ]),
+            "**Alice** has a participation streak of 1 year (2023), their longest was 2 years \
+             (2020–2021) (the leaderboards of 2022 could not be fetched)"
+        );
+        assert_eq!(
+            render(&[(2023, Some(true)), (2024, Some(false))]),
+            "**Alice** has no current participation streak, their longest was 1 year (2023)"
+        );
+        assert_eq!(
+            render(&[(2024, Some(false))]),
+            "**Alice** has not earned any stars yet"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use std::{fmt::Write, ops::Range};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{
+        day::AocDay,
+        models::{PrivateLeaderboard, StarEvent},
+    },
+    context::Context,
+    matrix::{
+        commands::{
+            aoc::{fmt_last_update, send_debug, send_fetch_error, HTML_SIZE_BUDGET},
+            args::{
+                check_incompatible, parse_bool, parse_day, parse_offset, parse_rows, parse_window,
+                parse_window_offset, parse_year, Incompatible, Rows, ROWS_WINDOW,
+            },
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{html_message, RoomExt},
+    },
+    utils::{
+        datetime::now,
+        fmt::{escape_html, fmt_timedelta},
+    },
+};
+
+const INCOMPATIBLE: &[Incompatible] = &[ROWS_WINDOW];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Paging {
+    Rows {
+        rows: Rows,
+        offset: usize,
+    },
+    Window {
+        offset: TimeDelta,
+        window: TimeDelta,
+    },
+}
+
+impl Paging {
+    /// The range of the events shown, the rows may be truncated further to fit into a message.
+    fn select(self, events: &[StarEvent<'_>], unlock: DateTime<Utc>) -> Range<usize> {
+        match self {
+            Self::Rows { rows, offset } => {
+                let start = offset.min(events.len());
+                start..start.saturating_add(rows.limit()).min(events.len())
+            }
+            Self::Window { offset, window } => {
+                let (from, to) = (unlock + offset, unlock + offset + window);
+                events.partition_point(|e| e.ts < from)..events.partition_point(|e| e.ts < to)
+            }
+        }
+    }
+}
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    if let Err(err) = check_incompatible(&cmd, INCOMPATIBLE) {
+        return send_error(&room, event, err).await;
+    }
+
+    let day = match parse_day(
+        cmd.get_from_kwargs("day"),
+        context.config.aoc.default_day_grace(),
+    ) {
+        Ok(day) => day,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    let window = match parse_window(cmd.get_from_kwargs("window")) {
+        Ok(window) => window,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    // with a time window, the offset is a duration as well
+    let paging = match window {
+        Some(window) => match parse_window_offset(cmd.get_from_kwargs("offset")) {
+            Ok(offset) => Paging::Window { offset, window },
+            Err(err) => return send_error(&room, event, err).await,
+        },
+        None => {
+            let rows = match parse_rows(
+                cmd.get_from_kwargs("rows"),
+                context.config.aoc.leaderboard_rows,
+                context.config.aoc.min_rows,
+            ) {
+                Ok(rows) => rows,
+                Err(err) => return send_error(&room, event, err).await,
+            };
+            match parse_offset(cmd.get_from_kwargs("offset")) {
+                Ok(offset) => Paging::Rows { rows, offset },
+                Err(err) => return send_error(&room, event, err).await,
+            }
+        }
+    };
+
+    let debug = match parse_bool(cmd.get_from_kwargs("debug"), "debug", false) {
+        Ok(debug) => debug,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    let started = now();
+    let (leaderboard, updated_at) = match context.aoc_client.get_private_leaderboard(year).await {
+        Ok(resp) => resp,
+        Err(err) => return send_fetch_error(&room, event, year, err).await,
+    };
+    let last_update = fmt_last_update(&context.config, updated_at);
+
+    let title = context.aoc_client.get_puzzle_title(year, day).await;
+    let out = render(
+        &leaderboard,
+        AocDay { year, day },
+        title.as_deref(),
+        paging,
+        &last_update,
+    );
+
+    room.reply_to(event, html_message(out)).await?;
+
+    if debug {
+        let paging = match paging {
+            Paging::Rows { rows, offset } => {
+                [("rows", rows.to_string()), ("offset", offset.to_string())]
+            }
+            Paging::Window { offset, window } => [
+                ("window", fmt_timedelta(window).to_string()),
+                ("offset", fmt_timedelta(offset).to_string()),
+            ],
+        };
+        let params = [("day", day.to_string()), ("year", year.to_string())]
+            .into_iter()
+            .chain(paging)
+            .collect::<Vec<_>>();
+        send_debug(
+            &room,
+            event,
+            started,
+            updated_at,
+            &context.aoc_client.star_discrepancies(year).await,
+            &params,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn render(
+    leaderboard: &PrivateLeaderboard,
+    day: AocDay,
+    title: Option<&str>,
+    paging: Paging,
+    last_update: &str,
+) -> String {
+    let AocDay { year, day } = day;
+    let unlock = AocDay { year, day }.unlock_datetime();
+    let events = leaderboard.star_events(day);
+
+    let title = match title {
+        Some(title) => format!(": {}", escape_html(title)),
+        None => String::new(),
+    };
+
+    let selected = paging.select(&events, unlock);
+    let mut out = format!(
+        r#"
+<h3>Timeline (Advent of Code {year}/{day:02}{title})</h3>
+<ol start="{}">
+"#,
+        selected.start + 1
+    );
+
+    // explicit row counts are within the size budget, other slices are truncated to fit
+    let truncate = !matches!(
+        paging,
+        Paging::Rows {
+            rows: Rows::Count(_),
+            ..
+        }
+    );
+    let mut shown = 0;
+    for event in &events[selected] {
+        let delta = fmt_timedelta(event.ts - unlock);
+        let name = escape_html(&event.member.display_name()).into_owned();
+        let stars = if event.part2 { "⭐⭐" } else { "⭐" };
+        let line = format!("<li><b>{delta}</b> {name} {stars}</li>\n");
+        if truncate && out.len() + line.len() > HTML_SIZE_BUDGET {
+            break;
+        }
+        out.push_str(&line);
+        shown += 1;
+    }
+
+    let window = match paging {
+        Paging::Window { offset, window } => format!(
+            " earned {} to {} after the unlock",
+            fmt_timedelta(offset),
+            fmt_timedelta(offset + window)
+        ),
+        Paging::Rows { .. } => String::new(),
+    };
+    write!(
+        &mut out,
+        r#"
+</ol>
+<sup>Showing {shown} of {} stars{window}. Last update: {last_update}</sup>
+"#,
+        events.len()
+    )
+    .unwrap();
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aoc::fixtures::{leaderboard, member, YEAR};
+
+    #[test]
+    fn select() {
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(4000))]),
+            member(2, "Bob", &[(1, 3600, Some(7300))]),
+        ]);
+        let events = leaderboard.star_events(1);
+        let unlock = AocDay { year: YEAR, day: 1 }.unlock_datetime();
+        let select = |paging: Paging| paging.select(&events, unlock);
+
+        let rows = |rows, offset| Paging::Rows { rows, offset };
+        assert_eq!(select(rows(Rows::Count(2), 1)), 1..3);
+        assert_eq!(select(rows(Rows::All, 0)), 0..4);
+        assert_eq!(select(rows(Rows::Count(2), 10)), 4..4);
+
+        // windows include their start but not their end
+        let window = |offset, window| Paging::Window {
+            offset: TimeDelta::seconds(offset),
+            window: TimeDelta::seconds(window),
+        };
+        assert_eq!(select(window(0, 3600)), 0..1);
+        assert_eq!(select(window(3600, 3600)), 1..3);
+        assert_eq!(select(window(7200, 3600)), 3..4);
+        assert_eq!(select(window(10800, 3600)), 4..4);
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, FixedOffset, Utc};
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::day::AocDay,
+    context::Context,
+    matrix::utils::{notice, RoomExt},
+    utils::{datetime::now, fmt::fmt_timedelta},
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+) -> anyhow::Result<()> {
+    let message = render(AocDay::next(), context.config.local_timezone, now());
+    room.reply_to(event, notice(message)).await?;
+    Ok(())
+}
+
+fn render(next: AocDay, timezone: FixedOffset, now: DateTime<Utc>) -> String {
+    let mut out = format!(
+        "**Upcoming unlocks of Advent of Code {}** (UTC{timezone})",
+        next.year
+    );
+    for day in next.day..=25 {
+        let unlock = AocDay {
+            year: next.year,
+            day,
+        }
+        .unlock_datetime();
+        write!(
+            &mut out,
+            "  \n- Day {day}: {} (in {})",
+            unlock.with_timezone(&timezone).format("%a %Y-%m-%d %H:%M"),
+            fmt_timedelta(unlock - now)
+        )
+        .unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::datetime::set_now;
+
+    #[test]
+    fn render() {
+        let timezone = "+01:00".parse().unwrap();
+        let render_at = |now: &str| {
+            let now = now.parse().unwrap();
+            let _guard = set_now(now);
+            super::render(AocDay::next(), timezone, now)
+        };
+
+        // during the event, only the days which are still locked are listed
+        assert_eq!(
+            render_at("2024-12-23T05:30:00Z"),
+            "**Upcoming unlocks of Advent of Code 2024** (UTC+01:00)  \n\
+             - Day 24: Tue 2024-12-24 06:00 (in 23h 30m 0s)  \n\
+             - Day 25: Wed 2024-12-25 06:00 (in 1d 23h 30m 0s)"
+        );
+
+        // after the last day, the next event is listed
+        let next_event = render_at("2024-12-25T12:00:00Z");
+        assert!(next_event.starts_with("**Upcoming unlocks of Advent of Code 2025**"));
+        assert!(next_event.contains("- Day 1: Mon 2025-12-01 06:00 (in 340d 17h 0m 0s)"));
+        assert_eq!(next_event.lines().count(), 26);
+    }
+}
@@ -1,53 +1,56 @@
 use std::{cmp::Reverse, fmt::Write};
 
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Utc};
 use matrix_sdk::{
-    ruma::{events::room::message::OriginalRoomMessageEvent, OwnedUserId},
+    ruma::{events::room::message::OriginalRoomMessageEvent, OwnedUserId, UserId},
     Room,
 };
 
 use crate::{
-    aoc::day::AocDay,
-    context::Context,
+    aoc::{
+        day::AocDay,
+        models::{DayOrder, PrivateLeaderboard, PrivateLeaderboardMember},
+    },
+    config::{Config, User},
+    context::{Context, ContextUsers},
+    i18n::Message,
     matrix::{
-        commands::{parser::ParsedCommand, send_error},
-        utils::{error_message, html_message, RoomExt},
+        commands::{
+            aoc::fmt_last_update,
+            args::{parse_order, parse_year},
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{html_message, Mentions, RoomExt},
     },
     utils::{
         datetime::DateTimeExt,
-        fmt::{fmt_rank, fmt_timedelta},
+        fmt::{escape_html, fmt_rank, fmt_timedelta},
     },
 };
 
-pub async fn invoke(
+/// Find the leaderboard member referred to by an AoC id or name, or by a linked matrix user (given
+/// as user id or mention). Current names take precedence over the configured aliases of renamed
+/// members. Without a name, the member linked to the sender is returned.
+pub fn find_member<'a>(
+    leaderboard: &'a PrivateLeaderboard,
+    users: &ContextUsers,
     event: &OriginalRoomMessageEvent,
-    room: Room,
-    context: &Context,
-    mut cmd: ParsedCommand<'_>,
-) -> anyhow::Result<()> {
-    let most_recent = AocDay::most_recent();
-
-    let user = cmd.get_from_kwargs_or_args("user");
-    let most_recent_year = AocDay::most_recent().year;
-    let year = match cmd.get_from_kwargs_or_args("year").map(|y| {
-        y.parse()
-            .ok()
-            .filter(|y| (2015..=most_recent_year).contains(y))
-    }) {
-        Some(Some(y)) => y,
-        Some(None) => return send_error(&room, event, "Failed to parse argument 'year'").await,
-        None => most_recent_year,
+    name: Option<&str>,
+) -> Option<&'a PrivateLeaderboardMember> {
+    let linked = |user_id: &UserId| {
+        let aoc = users.by_matrix.get(user_id)?.aoc?;
+        leaderboard.members.values().find(|m| m.id == aoc)
     };
-
-    let (leaderboard, last_update) = context.aoc_client.get_private_leaderboard(year).await?;
-
-    let last_update = context
-        .config
-        .local_timezone
-        .from_utc_datetime(&last_update.naive_utc())
-        .format_ymd_hms_z();
-
-    let Some(user) = (match user {
+    let alias = |name: &str| {
+        leaderboard.members.values().find(|m| {
+            users
+                .by_aoc
+                .get(&m.id)
+                .is_some_and(|u| u.aliases.iter().any(|a| a.to_lowercase() == name))
+        })
+    };
+    match name {
         Some(name) => leaderboard
             .members
             .values()
@@ -57,54 +60,93 @@ pub async fn invoke(
                         .as_ref()
                         .is_some_and(|n| n.to_lowercase() == name.to_lowercase())
             })
-            .or_else(|| {
-                context
-                    .users
-                    .by_matrix
-                    .get(
-                        name.parse::<OwnedUserId>()
-                            .ok()
-                            .as_ref()
-                            .or_else(|| event.content.mentions.as_ref()?.user_ids.iter().next())?,
-                    )
-                    .and_then(|u| leaderboard.members.values().find(|m| u.aoc == Some(m.id)))
-            }),
-        None => context
-            .users
-            .by_matrix
-            .get(&event.sender)
-            .and_then(|u| leaderboard.members.values().find(|m| u.aoc == Some(m.id))),
-    }) else {
-        room.reply_to(event, error_message("User not found"))
-            .await?;
-        return Ok(());
+            .or_else(|| alias(&name.to_lowercase()))
+            .or_else(|| linked(&matrix_user(name)?)),
+        None => linked(&event.sender),
+    }
+}
+
+/// The matrix user referred to by the given name, if it is a user id. The mentions of the event
+/// are not used, as a reply mentions the sender of the message it replies to.
+pub fn matrix_user(name: &str) -> Option<OwnedUserId> {
+    name.parse().ok()
+}
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let user = cmd.get_from_kwargs("user");
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
     };
 
-    let name = user.display_name();
+    let order = match parse_order(cmd.get_from_kwargs("order")) {
+        Ok(order) => order,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    let (leaderboard, last_update) = context.aoc_client.get_private_leaderboard(year).await?;
+
+    let last_update = fmt_last_update(&context.config, last_update);
+
+    let users = context.users.read().await;
+    let Some(user) = find_member(&leaderboard, &users, event, user) else {
+        return send_error(&room, event, Message::UserNotFound).await;
+    };
+
+    let out = render(
+        &context.config,
+        &users,
+        &leaderboard,
+        user,
+        year,
+        order,
+        &last_update,
+    );
+
+    room.reply_to(event, html_message(out)).await?;
+
+    Ok(())
+}
+
+pub(super) fn render(
+    config: &Config,
+    users: &ContextUsers,
+    leaderboard: &PrivateLeaderboard,
+    user: &PrivateLeaderboardMember,
+    year: i32,
+    order: DayOrder,
+    last_update: &str,
+) -> String {
+    let most_recent = AocDay::most_recent();
+    let name = escape_html(&user.display_name()).into_owned();
 
     let aoc_id = user.id;
 
-    let matrix = context
-        .users
-        .by_aoc
-        .get(&user.id)
-        .and_then(|u| u.matrix.as_ref())
-        .map(|m| m.matrix_to_uri().to_string())
-        .unwrap_or_default();
+    let resolved = users.resolved.get(&user.id);
+    let matrix = match users.by_aoc.get(&user.id) {
+        Some(User {
+            matrix: Some(matrix),
+            ..
+        }) if user.no_ping => Mentions::plain(matrix),
+        _ => resolved
+            .and_then(|u| u.matrix_uri.clone())
+            .unwrap_or_default(),
+    };
+    let (repo, repo_title) = resolved
+        .and_then(|u| u.repo.clone())
+        .map_or_else(Default::default, |r| (r.url, r.title));
+    let (repo, repo_title) = (escape_html(&repo), escape_html(&repo_title));
 
-    let repo = context
-        .users
+    let timezone = users
         .by_aoc
         .get(&user.id)
-        .and_then(|u| u.repo.as_deref())
-        .unwrap_or_default();
-    let repo_title = context
-        .config
-        .aoc
-        .repo_rules
-        .match_and_replace(repo)
-        .map(|m| m.replacement);
-    let repo_title = repo_title.as_deref().unwrap_or(repo);
+        .and_then(|u| u.timezone)
+        .unwrap_or(config.local_timezone);
 
     let stars = user.stars;
     let rank = fmt_rank(leaderboard.members.values().filter(|&o| o <= user).count());
@@ -117,9 +159,10 @@ pub async fn invoke(
         25
     };
     let max_stars = days * 2;
+    let star_grid = user.star_grid(year, order, &config.aoc.grid);
     let progress_percent = stars as f64 / max_stars as f64 * 100.0;
 
-    let link_prefix = &context.config.matrix.link_prefix;
+    let link_prefix = &config.matrix.link_prefix;
     let mut out = format!(
         r#"
 <table>
@@ -152,6 +195,8 @@ pub async fn invoke(
     </tr>
 </table>
 
+<p><code>{star_grid}</code></p>
+
 <table>
     <tr>
         <th>Day</th>
@@ -163,13 +208,7 @@ pub async fn invoke(
 
     for d in 1..=days {
         let unlock = AocDay { year, day: d }.unlock_datetime();
-        let fmt_dt = |dt: DateTime<Utc>| {
-            context
-                .config
-                .local_timezone
-                .from_utc_datetime(&dt.naive_utc())
-                .format_ymd_hms()
-        };
+        let fmt_dt = |dt: DateTime<Utc>| dt.format_ymd_hms_in(timezone, config.local_timezone);
         let p1 = user.completion_day_level.get(&d).map(|c| c.fst.get_star_ts);
         let p2 = user
             .completion_day_level
@@ -227,7 +266,5 @@ pub async fn invoke(
 
     write!(&mut out, "</table><sup>Last update: {last_update}</sup>",).unwrap();
 
-    room.reply_to(event, html_message(out)).await?;
-
-    Ok(())
+    out
 }
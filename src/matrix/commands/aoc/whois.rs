@@ -0,0 +1,141 @@
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{day::AocDay, models::PrivateLeaderboardMember},
+    config::{Config, User},
+    context::Context,
+    i18n::Message,
+    matrix::{
+        commands::{
+            aoc::user::{find_member, matrix_user},
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{html_message, Mentions, RoomExt},
+    },
+    utils::fmt::escape_html,
+};
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let name = cmd.get_from_kwargs("user");
+    let (leaderboard, _) = context
+        .aoc_client
+        .get_private_leaderboard(AocDay::most_recent().year)
+        .await?;
+
+    let users = context.users.read().await;
+    let member = find_member(&leaderboard, &users, event, name);
+    let user = match member {
+        Some(member) => users.by_aoc.get(&member.id),
+        None => {
+            let user_id = match name {
+                Some(name) => matrix_user(name),
+                None => Some(event.sender.clone()),
+            };
+            let Some(user_id) = user_id else {
+                return send_error(&room, event, Message::UserNotFound).await;
+            };
+            let Some(user) = users.by_matrix.get(&user_id) else {
+                let content = html_message(format!("{} is not linked", user_id.matrix_to_uri()));
+                room.reply_to(event, content).await?;
+                return Ok(());
+            };
+            Some(user)
+        }
+    };
+    // matrix users may be linked to members who are not on this year's leaderboard
+    let member = member.or_else(|| {
+        let aoc = user?.aoc?;
+        leaderboard.members.values().find(|m| m.id == aoc)
+    });
+
+    room.reply_to(event, html_message(render(&context.config, member, user)))
+        .await?;
+    Ok(())
+}
+
+pub(super) fn render(
+    config: &Config,
+    member: Option<&PrivateLeaderboardMember>,
+    user: Option<&User>,
+) -> String {
+    let mut aoc = match (member, user.and_then(|u| u.aoc)) {
+        (Some(member), _) => format!(
+            "<b>{}</b> (AoC #{})",
+            escape_html(&member.display_name()),
+            member.id
+        ),
+        (None, Some(aoc)) => format!("AoC #{aoc}"),
+        (None, None) => "AoC: not linked".into(),
+    };
+    if let Some(aliases) = user.map(|u| &u.aliases).filter(|a| !a.is_empty()) {
+        aoc.push_str(&format!(", formerly known as {}", aliases.join(", ")));
+    }
+    let Some(user) = user else {
+        return format!("{aoc} is not linked");
+    };
+
+    let matrix = user
+        .matrix
+        .as_ref()
+        .map(|m| match member {
+            Some(member) if member.no_ping => Mentions::plain(m),
+            _ => m.matrix_to_uri().to_string(),
+        })
+        .unwrap_or_else(|| "Matrix: not linked".into());
+    let repo = match &user.repo {
+        Some(repo) => {
+            let title = config.aoc.repo_title(repo);
+            format!(
+                r#"<a href="{}{}">{}</a>"#,
+                config.matrix.link_prefix,
+                escape_html(repo),
+                escape_html(&title)
+            )
+        }
+        None => "Repository: not linked".into(),
+    };
+    format!("{aoc} ↔ {matrix} ↔ {repo}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{aoc::fixtures::member, config::tests::load_with_overrides};
+
+    #[test]
+    fn render() {
+        let config = load_with_overrides([r#"users = [
+            { aoc = 1, matrix = "@alice:example.com", repo = "https://github.com/alice/aoc" },
+            { matrix = "@bob:example.com" },
+            { aoc = 3, aliases = ["Carl", "C."] },
+        ]"#]);
+        let alice = member(1, "Alice", &[]);
+
+        assert_eq!(
+            super::render(&config, Some(&alice), Some(&config.users[0])),
+            r#"<b>Alice</b> (AoC #1) ↔ https://matrix.to/#/@alice:example.com ↔ <a href="https://github.com/alice/aoc">alice/aoc</a>"#
+        );
+        assert_eq!(
+            super::render(&config, None, Some(&config.users[1])),
+            "AoC: not linked ↔ https://matrix.to/#/@bob:example.com ↔ Repository: not linked"
+        );
+        assert_eq!(
+            super::render(&config, Some(&member(2, "", &[])), None),
+            "<b>[anonymous user #2]</b> (AoC #2) is not linked"
+        );
+        assert_eq!(
+            super::render(
+                &config,
+                Some(&member(3, "Carol", &[])),
+                Some(&config.users[2])
+            ),
+            "<b>Carol</b> (AoC #3), formerly known as Carl, C. ↔ Matrix: not linked ↔ Repository: \
+             not linked"
+        );
+    }
+}
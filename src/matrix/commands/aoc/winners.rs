@@ -0,0 +1,211 @@
+use std::{collections::HashMap, fmt::Write};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    aoc::{
+        client::{score_day, Part2Start, Parts},
+        day::AocDay,
+        models::{disambiguated_display_names, AocId, PrivateLeaderboard},
+    },
+    config::Config,
+    context::Context,
+    matrix::{
+        commands::{
+            aoc::{day::completion_delta, fmt_last_update, records::tally, send_fetch_error},
+            args::{parse_parts, parse_year},
+            parser::ParsedCommand,
+            send_error,
+        },
+        utils::{html_message, RoomExt},
+    },
+    utils::{
+        datetime::now,
+        fmt::{escape_html, fmt_timedelta},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Winner {
+    member: AocId,
+    time: TimeDelta,
+}
+
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    let year = match parse_year(cmd.get_from_kwargs("year")) {
+        Ok(year) => year,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+    let default_parts = context.config.default_parts(room.room_id());
+    let parts = match parse_parts(cmd.get_from_kwargs("p"), default_parts) {
+        Ok(parts) => parts,
+        Err(err) => return send_error(&room, event, err).await,
+    };
+
+    let (leaderboard, last_update) = match context.aoc_client.get_private_leaderboard(year).await {
+        Ok(resp) => resp,
+        Err(err) => return send_fetch_error(&room, event, year, err).await,
+    };
+
+    let winners = winners(&leaderboard, year, parts, context.config.aoc.part2_start);
+    let html = render(
+        &context.config,
+        &leaderboard,
+        year,
+        parts,
+        &winners,
+        last_update,
+    );
+    room.reply_to(event, html_message(html)).await?;
+
+    Ok(())
+}
+
+/// The fastest finisher of the given parts of each day, or `None` if nobody finished them. Part 2
+/// is timed from `part2_start`, ties are won by the lower member id.
+pub(super) fn winners(
+    leaderboard: &PrivateLeaderboard,
+    year: i32,
+    parts: Parts,
+    part2_start: Part2Start,
+) -> Vec<Option<Winner>> {
+    let required = match parts {
+        Parts::Both => 2,
+        Parts::P1 | Parts::P2 => 1,
+    };
+    (1..=25)
+        .map(|day| {
+            let mut daily = leaderboard.clone();
+            score_day(&mut daily, day, parts, part2_start, false);
+            let unlock = AocDay { year, day }.unlock_datetime();
+            daily
+                .members
+                .values()
+                .filter(|m| m.stars == required)
+                .filter_map(|m| Some((completion_delta(m, day, parts, part2_start, unlock)?, m.id)))
+                .min()
+                .map(|(time, member)| Winner { member, time })
+        })
+        .collect()
+}
+
+pub(super) fn render(
+    config: &Config,
+    leaderboard: &PrivateLeaderboard,
+    year: i32,
+    parts: Parts,
+    winners: &[Option<Winner>],
+    last_update: DateTime<Utc>,
+) -> String {
+    let names = disambiguated_display_names(leaderboard.members.values());
+    let parts_title = match parts {
+        Parts::P1 => ", Part 1",
+        Parts::P2 => ", Part 2",
+        Parts::Both => "",
+    };
+
+    let mut out = format!(
+        "<h3>Advent of Code {year}: Daily Winners{parts_title}</h3>\n<table>\n<tr> <th>Day</th> \
+         <th>Winner</th> <th>Time</th> </tr>\n"
+    );
+    let mut wins = HashMap::<AocId, usize>::new();
+    for (day, winner) in (1..).zip(winners) {
+        let (name, time) = match winner {
+            Some(Winner { member, time }) => {
+                *wins.entry(*member).or_default() += 1;
+                (
+                    escape_html(&names[member]).into_owned(),
+                    fmt_timedelta(*time).to_string(),
+                )
+            }
+            None if AocDay { year, day }.unlock_datetime() > now() => {
+                ("<i>locked</i>".into(), "—".into())
+            }
+            None => ("<i>nobody yet</i>".into(), "—".into()),
+        };
+        writeln!(
+            &mut out,
+            "<tr> <td>{day}</td> <td>{name}</td> <td>{time}</td> </tr>"
+        )
+        .unwrap();
+    }
+    out.push_str("</table>\n");
+
+    let tally = tally(wins)
+        .into_iter()
+        .map(|(member, count)| format!("<b>{}</b> ({count})", escape_html(&names[&member])))
+        .collect::<Vec<_>>();
+    if !tally.is_empty() {
+        writeln!(&mut out, "<p>Most daily wins: {}</p>", tally.join(", ")).unwrap();
+    }
+    writeln!(
+        &mut out,
+        "<sup>Last update: {}</sup>",
+        fmt_last_update(config, last_update)
+    )
+    .unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aoc::fixtures::{leaderboard, member, YEAR},
+        config::tests::load_with_overrides,
+        utils::datetime::set_now,
+    };
+
+    #[test]
+    fn winners() {
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200)), (2, 50, Some(900))]),
+            member(2, "Bob", &[(1, 150, Some(170)), (2, 40, Some(1000))]),
+            member(3, "Carol", &[(1, 90, None), (3, 60, None)]),
+        ]);
+        let winner = |member, secs| {
+            Some(Winner {
+                member,
+                time: TimeDelta::seconds(secs),
+            })
+        };
+
+        let both = super::winners(&leaderboard, YEAR, Parts::Both, Part2Start::Part1);
+        assert_eq!(both.len(), 25);
+        assert_eq!(&both[..4], [winner(2, 170), winner(1, 900), None, None]);
+        let p1 = super::winners(&leaderboard, YEAR, Parts::P1, Part2Start::Part1);
+        assert_eq!(
+            &p1[..4],
+            [winner(3, 90), winner(2, 40), winner(3, 60), None]
+        );
+        // part 2 is timed from part 1
+        let p2 = super::winners(&leaderboard, YEAR, Parts::P2, Part2Start::Part1);
+        assert_eq!(&p2[..3], [winner(2, 20), winner(1, 850), None]);
+        let p2 = super::winners(&leaderboard, YEAR, Parts::P2, Part2Start::Unlock);
+        assert_eq!(&p2[..3], [winner(2, 170), winner(1, 900), None]);
+    }
+
+    #[test]
+    fn render() {
+        let _guard = set_now(AocDay { year: YEAR, day: 3 }.unlock_datetime());
+        let config = load_with_overrides([]);
+        let leaderboard = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200)), (2, 50, Some(3000))]),
+            member(2, "Bob", &[(1, 150, Some(170)), (2, 40, Some(1000))]),
+            member(3, "Carol", &[(3, 60, Some(70))]),
+        ]);
+        let winners = super::winners(&leaderboard, YEAR, Parts::Both, Part2Start::Part1);
+
+        let html = super::render(&config, &leaderboard, YEAR, Parts::Both, &winners, now());
+        assert!(html.contains("<tr> <td>1</td> <td>Bob</td> <td>2m 50s</td> </tr>"));
+        assert!(html.contains("<tr> <td>3</td> <td>Carol</td> <td>1m 10s</td> </tr>"));
+        assert!(html.contains("<tr> <td>4</td> <td><i>locked</i></td> <td>—</td> </tr>"));
+        assert!(html.contains("<p>Most daily wins: <b>Bob</b> (2), <b>Carol</b> (1)</p>"));
+    }
+}
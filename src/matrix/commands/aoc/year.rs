@@ -0,0 +1,98 @@
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+
+use crate::{
+    context::Context,
+    matrix::commands::{aoc::leaderboard, error::CommandResult, parser::ParsedCommand},
+};
+
+/// Show the leaderboard of a year with a star strip of the 25 days for every member. Apart from
+/// the strip, this is the `leaderboard` command.
+pub async fn invoke(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    cmd: ParsedCommand<'_>,
+) -> CommandResult {
+    leaderboard::invoke_with_strip(event, room, context, cmd, true).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        aoc::{
+            day::AocDay,
+            fixtures::{leaderboard, member, YEAR},
+            models::PrivateLeaderboardMember,
+        },
+        config::tests::load_with_overrides,
+        matrix::commands::{
+            aoc::leaderboard::{tests::render, Params},
+            args::Rows,
+        },
+        utils::datetime::set_now,
+    };
+
+    #[test]
+    fn year() {
+        let _guard = set_now(AocDay { year: YEAR, day: 3 }.unlock_datetime());
+        let config = load_with_overrides([]);
+        let leaderboard = leaderboard([
+            PrivateLeaderboardMember {
+                local_score: 10,
+                ..member(1, "Alice", &[(1, 100, Some(200)), (2, 100, None)])
+            },
+            PrivateLeaderboardMember {
+                local_score: 10,
+                ..member(2, "<Bob>", &[(1, 150, Some(300))])
+            },
+            PrivateLeaderboardMember {
+                local_score: 4,
+                ..member(3, "Carol", &[(2, 50, None)])
+            },
+        ]);
+        let params = Params {
+            strip: true,
+            ..Params::new(YEAR)
+        };
+        let render = |params| render(&config, &leaderboard, params);
+
+        // members with equal scores share a rank
+        let rendered = render(params);
+        let strip = |member: usize| rendered.table.rows[member].1[7].clone();
+        assert_eq!(
+            rendered
+                .table
+                .rows
+                .iter()
+                .map(|(_, r)| r[0].as_str())
+                .collect::<Vec<_>>(),
+            ["🥇 1st", "🥇 1st", "🥉 3rd"]
+        );
+        assert_eq!(strip(0), "★☆○·· ····· ····· ····· ·····");
+        assert_eq!(strip(2), "○☆○·· ····· ····· ····· ·····");
+        assert!(rendered
+            .html
+            .contains(r#"<span title="AoC #2">&lt;Bob&gt;</span>"#));
+        assert!(rendered
+            .compact
+            .contains("1. Alice — 10 (3★) ★☆○·· ····· ····· ····· ·····\n"));
+
+        assert!(rendered.html.contains("<th>Days</th>"));
+
+        let rendered = render(Params {
+            rows: Rows::Count(1),
+            offset: 2,
+            ..params
+        });
+        assert_eq!(rendered.table.rows.len(), 1);
+        assert_eq!(rendered.table.rows[0].1[4], "Carol");
+
+        // the leaderboard command shows no strips
+        let rendered = render(Params {
+            strip: false,
+            ..params
+        });
+        assert_eq!(rendered.table.header.len(), 7);
+        assert!(!rendered.html.contains("<th>Days</th>"));
+    }
+}
@@ -0,0 +1,495 @@
+use std::fmt;
+
+use chrono::TimeDelta;
+
+use super::parser::ParsedCommand;
+use crate::{
+    aoc::{
+        client::{Part2Start, Parts, FIRST_YEAR},
+        day::AocDay,
+        models::DayOrder,
+    },
+    config::LimitsConfig,
+    i18n::Message,
+    utils::suggest::closest,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Incompatible {
+    pub key: &'static str,
+    /// The values of `key` the rule applies to, or any value if empty.
+    pub values: &'static [&'static str],
+    pub other: &'static str,
+    pub reason: &'static str,
+}
+
+pub const PARTS_PART2_START: Incompatible = Incompatible {
+    key: "p",
+    values: &["1", "both", "1,2", "2,1"],
+    other: "p2_from",
+    reason: "the start of part 2 only applies to the part 2 leaderboard",
+};
+
+pub const ROWS_WINDOW: Incompatible = Incompatible {
+    key: "rows",
+    values: &[],
+    other: "window",
+    reason: "a time window replaces the rows",
+};
+
+pub const FORMAT_COLUMNS: Incompatible = Incompatible {
+    key: "format",
+    values: &["table", "compact"],
+    other: "columns",
+    reason: "columns only apply to tsv and image",
+};
+
+pub const FORMAT_COLLAPSE: Incompatible = Incompatible {
+    key: "format",
+    values: &["image", "tsv"],
+    other: "collapse",
+    reason: "only html leaderboards can be collapsed",
+};
+
+pub fn check_incompatible(cmd: &ParsedCommand, rules: &[Incompatible]) -> Result<(), String> {
+    for rule in rules {
+        let Some(value) = cmd.get_from_kwargs(rule.key) else {
+            continue;
+        };
+        if !rule.values.is_empty() && !rule.values.iter().any(|v| v.eq_ignore_ascii_case(value)) {
+            continue;
+        }
+        if cmd.get_from_kwargs(rule.other).is_some() {
+            let key = match rule.values {
+                [] => rule.key.to_string(),
+                _ => format!("{}={value}", rule.key),
+            };
+            return Err(Message::IncompatibleArguments {
+                key: &key,
+                other: rule.other,
+                reason: rule.reason,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+pub fn check_length(cmd: &str, limits: &LimitsConfig) -> Result<(), String> {
+    if cmd.len() > limits.max_length {
+        return Err(Message::CommandTooLong {
+            max: limits.max_length,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+pub fn check_args(cmd: &ParsedCommand, limits: &LimitsConfig) -> Result<(), String> {
+    if cmd.args.len() + cmd.kwargs.len() > limits.max_args {
+        return Err(Message::TooManyArguments {
+            max: limits.max_args,
+        }
+        .into());
+    }
+    let too_long = cmd
+        .args
+        .iter()
+        .copied()
+        .chain(cmd.kwargs.iter().flat_map(|(k, v)| [&**k, *v]))
+        .any(|x| x.len() > limits.max_arg_length);
+    if too_long {
+        return Err(Message::ArgumentTooLong {
+            max: limits.max_arg_length,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Parse a day, defaulting to the current one (or the previous one during the grace period, see
+/// [`AocDay::current_with_grace`]).
+pub fn parse_day(value: Option<&str>, grace: TimeDelta) -> Result<u32, String> {
+    match value
+        .map(|d| d.parse().ok().filter(|d| (1..=25).contains(d)))
+        .or_else(|| AocDay::current_with_grace(grace).map(|d| Some(d.day)))
+    {
+        Some(Some(d)) => Ok(d),
+        Some(None) => Err(Message::InvalidArgument("day").into()),
+        None => Err(Message::MissingArgument("day").into()),
+    }
+}
+
+/// Parse a year, either in full or as two digits (`23` is 2023), defaulting to the most recent
+/// one.
+pub fn parse_year(value: Option<&str>) -> Result<i32, String> {
+    let most_recent_year = AocDay::most_recent().year;
+    match value.map(|y| {
+        let century = if y.len() == 2 { 2000 } else { 0 };
+        y.parse::<i32>()
+            .ok()
+            .map(|y| century + y)
+            .filter(|y| (FIRST_YEAR..=most_recent_year).contains(y))
+    }) {
+        Some(Some(y)) => Ok(y),
+        Some(None) => Err(Message::InvalidArgument("year").into()),
+        None => Ok(most_recent_year),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rows {
+    Count(usize),
+    All,
+}
+
+impl Rows {
+    pub fn limit(self) -> usize {
+        match self {
+            Self::Count(rows) => rows,
+            Self::All => usize::MAX,
+        }
+    }
+}
+
+impl fmt::Display for Rows {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Count(rows) => rows.fmt(f),
+            Self::All => f.write_str("all"),
+        }
+    }
+}
+
+/// Parse the number of rows, or `all`. Fewer than `min` rows are rejected, as such a slice would
+/// look broken. There is no upper bound, as the number of rows shown is capped by the size of the
+/// leaderboard.
+pub fn parse_rows(value: Option<&str>, default: usize, min: usize) -> Result<Rows, String> {
+    match value {
+        Some(x) if x.eq_ignore_ascii_case("all") => Ok(Rows::All),
+        Some(x) => match x.parse() {
+            Ok(rows) if rows >= min => Ok(Rows::Count(rows)),
+            Ok(_) => Err(Message::InvalidArgumentExpected {
+                name: "rows",
+                expected: &format!("at least {min} or all"),
+            }
+            .into()),
+            Err(_) => Err(Message::InvalidArgumentExpected {
+                name: "rows",
+                expected: "a number or all",
+            }
+            .into()),
+        },
+        None => Ok(Rows::Count(default)),
+    }
+}
+
+pub fn parse_min_stars(value: Option<&str>, default: u32) -> Result<u32, String> {
+    match value.map(|x| x.parse().ok().filter(|x| (0..=50).contains(x))) {
+        Some(Some(x)) => Ok(x),
+        Some(None) => Err(Message::InvalidArgument("min_stars").into()),
+        None => Ok(default),
+    }
+}
+
+pub fn parse_bool(value: Option<&str>, name: &str, default: bool) -> Result<bool, String> {
+    match value.map(|x| x.to_lowercase()).as_deref() {
+        Some("yes" | "true" | "on" | "1") => Ok(true),
+        Some("no" | "false" | "off" | "0") => Ok(false),
+        Some(_) => Err(Message::InvalidArgumentExpected {
+            name,
+            expected: "yes or no",
+        }
+        .into()),
+        None => Ok(default),
+    }
+}
+
+pub fn parse_offset(value: Option<&str>) -> Result<usize, String> {
+    match value.map(|x| x.parse().ok()) {
+        Some(Some(x)) => Ok(x),
+        Some(None) => Err(Message::InvalidArgument("offset").into()),
+        None => Ok(0),
+    }
+}
+
+/// Parse a duration consisting of numbers with the units `d`, `h`, `m` and `s`, e.g. `1h30m`.
+pub fn parse_duration(value: &str) -> Option<TimeDelta> {
+    let mut total = TimeDelta::zero();
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let n = rest[..digits].parse::<i64>().ok()?;
+        let mut chars = rest[digits..].chars();
+        let unit = match chars.next()?.to_ascii_lowercase() {
+            'd' => TimeDelta::try_days(n)?,
+            'h' => TimeDelta::try_hours(n)?,
+            'm' => TimeDelta::try_minutes(n)?,
+            's' => TimeDelta::try_seconds(n)?,
+            _ => return None,
+        };
+        total = total.checked_add(&unit)?;
+        rest = chars.as_str();
+    }
+    Some(total)
+}
+
+pub fn parse_window(value: Option<&str>) -> Result<Option<TimeDelta>, String> {
+    match value.map(parse_duration) {
+        Some(Some(window)) if window > TimeDelta::zero() => Ok(Some(window)),
+        Some(_) => Err(Message::InvalidArgumentExpected {
+            name: "window",
+            expected: "a duration like 1h or 30m",
+        }
+        .into()),
+        None => Ok(None),
+    }
+}
+
+pub fn parse_ago(value: Option<&str>) -> Result<Option<TimeDelta>, String> {
+    match value.map(parse_duration) {
+        Some(Some(ago)) => Ok(Some(ago)),
+        Some(None) => Err(Message::InvalidArgumentExpected {
+            name: "ago",
+            expected: "a duration like 2d or 6h",
+        }
+        .into()),
+        None => Ok(None),
+    }
+}
+
+pub fn parse_window_offset(value: Option<&str>) -> Result<TimeDelta, String> {
+    match value.map(parse_duration) {
+        Some(Some(offset)) => Ok(offset),
+        Some(None) => Err(Message::InvalidArgumentExpected {
+            name: "offset",
+            expected: "a duration like 2h when using a window",
+        }
+        .into()),
+        None => Ok(TimeDelta::zero()),
+    }
+}
+
+pub fn parse_order(value: Option<&str>) -> Result<DayOrder, String> {
+    match value.map(|x| x.to_lowercase()).as_deref() {
+        None | Some("asc" | "ascending") => Ok(DayOrder::Ascending),
+        Some("desc" | "descending") => Ok(DayOrder::Descending),
+        Some(_) => Err(Message::InvalidArgumentExpected {
+            name: "order",
+            expected: "asc or desc",
+        }
+        .into()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Compact,
+    Image,
+    Tsv,
+}
+
+pub fn parse_format(value: Option<&str>, image_enabled: bool) -> Result<Format, String> {
+    match value.map(|f| f.to_lowercase()).as_deref() {
+        None | Some("table") => Ok(Format::Table),
+        Some("compact") => Ok(Format::Compact),
+        Some("image") if image_enabled => Ok(Format::Image),
+        Some("image") => Err(Message::ImageDisabled.into()),
+        Some("tsv") => Ok(Format::Tsv),
+        Some(_) => Err(Message::InvalidArgumentExpected {
+            name: "format",
+            expected: "table, compact, image or tsv",
+        }
+        .into()),
+    }
+}
+
+pub fn parse_columns(value: Option<&str>) -> Option<Vec<&str>> {
+    value.map(|v| {
+        v.split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .collect()
+    })
+}
+
+pub fn parse_parts(value: Option<&str>, default: Parts) -> Result<Parts, String> {
+    let Some(value) = value else {
+        return Ok(default);
+    };
+    value
+        .parse()
+        .map_err(|()| match closest(value, Parts::NAMES) {
+            Some(suggestion) => Message::InvalidArgumentSuggestion {
+                name: "p",
+                suggestion,
+            }
+            .into(),
+            None => Message::InvalidArgumentExpected {
+                name: "p",
+                expected: "1, 2, both or 1,2",
+            }
+            .into(),
+        })
+}
+
+pub fn parse_part2_start(value: Option<&str>, default: Part2Start) -> Result<Part2Start, String> {
+    match value.map(|x| x.to_lowercase()).as_deref() {
+        None => Ok(default),
+        Some("part1" | "p1" | "1") => Ok(Part2Start::Part1),
+        Some("unlock") => Ok(Part2Start::Unlock),
+        Some(_) => Err(Message::InvalidArgumentExpected {
+            name: "p2_from",
+            expected: "part1 or unlock",
+        }
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{matrix::commands::parser::parse, utils::datetime::set_now};
+
+    #[test]
+    fn incompatible() {
+        const RULES: &[Incompatible] = &[PARTS_PART2_START, ROWS_WINDOW];
+        let check = |cmd| check_incompatible(&parse(cmd), RULES);
+
+        assert_eq!(check("day p=1 rows=5 offset=3"), Ok(()));
+        assert_eq!(check("day p=2 p2_from=unlock"), Ok(()));
+        assert_eq!(
+            check("day P=Both p2_from=unlock"),
+            Err(
+                "Arguments 'p=Both' and 'p2_from' cannot be combined (the start of part 2 only \
+                 applies to the part 2 leaderboard)"
+                    .into()
+            )
+        );
+        assert_eq!(
+            check("timeline rows=5 window=1h"),
+            Err(
+                "Arguments 'rows' and 'window' cannot be combined (a time window replaces the rows)"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let limits = LimitsConfig {
+            max_length: 50,
+            max_args: 3,
+            max_arg_length: 10,
+        };
+        let check =
+            |cmd| check_length(cmd, &limits).and_then(|()| check_args(&parse(cmd), &limits));
+
+        assert_eq!(check("leaderboard 2023 rows=10 offset=5"), Ok(()));
+        let long = format!("user \"{}\"", "x".repeat(1000));
+        assert_eq!(
+            check(&long),
+            Err("Command is too long (at most 50 characters are allowed)".into())
+        );
+        assert_eq!(
+            check("day 1 2 3 4"),
+            Err("Too many arguments (at most 3 are allowed)".into())
+        );
+        let too_long = Err("Argument is too long (at most 10 characters are allowed)".into());
+        assert_eq!(check(r#"user "Alice Bob Carol""#), too_long);
+        assert_eq!(check("day offset=12345678901"), too_long);
+        assert_eq!(check("day offsetoffset=1"), too_long);
+        // an unterminated quote extends to the end of the command
+        assert_eq!(check(r#"user "Alice Bob Carol"#), too_long);
+    }
+
+    #[test]
+    fn duration() {
+        assert_eq!(parse_duration("90s"), Some(TimeDelta::seconds(90)));
+        assert_eq!(parse_duration("1h30m"), Some(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("1D2H"), Some(TimeDelta::hours(26)));
+        assert_eq!(parse_duration("0m"), Some(TimeDelta::zero()));
+        for invalid in ["", "1", "h", "1x", "1h30", "-1h", "99999999999999d"] {
+            assert_eq!(parse_duration(invalid), None, "{invalid}");
+        }
+
+        assert_eq!(parse_window(None), Ok(None));
+        assert_eq!(parse_window(Some("1h")), Ok(Some(TimeDelta::hours(1))));
+        assert!(parse_window(Some("0s")).is_err());
+        assert_eq!(parse_window_offset(Some("2h")), Ok(TimeDelta::hours(2)));
+        assert!(parse_window_offset(Some("20")).is_err());
+    }
+
+    #[test]
+    fn rows() {
+        assert_eq!(parse_rows(None, 20, 1), Ok(Rows::Count(20)));
+        assert_eq!(parse_rows(Some("250"), 20, 1), Ok(Rows::Count(250)));
+        assert_eq!(parse_rows(Some("ALL"), 20, 1), Ok(Rows::All));
+        assert!(parse_rows(Some("-1"), 20, 1).is_err());
+        assert_eq!(
+            parse_rows(Some("0"), 20, 1),
+            Err("Failed to parse argument 'rows' (expected at least 1 or all)".into())
+        );
+        assert_eq!(parse_rows(Some("1"), 20, 1), Ok(Rows::Count(1)));
+        assert!(parse_rows(Some("4"), 20, 5).is_err());
+    }
+
+    #[test]
+    fn parts() {
+        assert_eq!(parse_parts(None, Parts::Both), Ok(Parts::Both));
+        assert_eq!(parse_parts(None, Parts::P2), Ok(Parts::P2));
+        assert_eq!(parse_parts(Some("both"), Parts::P2), Ok(Parts::Both));
+        assert_eq!(parse_parts(Some("1"), Parts::Both), Ok(Parts::P1));
+        assert_eq!(parse_parts(Some("2"), Parts::Both), Ok(Parts::P2));
+        assert_eq!(parse_parts(Some("both"), Parts::Both), Ok(Parts::Both));
+        assert_eq!(parse_parts(Some("Both"), Parts::Both), Ok(Parts::Both));
+        assert_eq!(parse_parts(Some("1,2"), Parts::Both), Ok(Parts::Both));
+        assert_eq!(parse_parts(Some("2,1"), Parts::Both), Ok(Parts::Both));
+        assert_eq!(
+            parse_parts(Some("bath"), Parts::Both),
+            Err("Failed to parse argument 'p' (did you mean 'both'?)".into())
+        );
+        assert_eq!(
+            parse_parts(Some("1,2,3"), Parts::Both),
+            Err("Failed to parse argument 'p' (did you mean '1,2'?)".into())
+        );
+        assert_eq!(
+            parse_parts(Some("all of them"), Parts::Both),
+            Err("Failed to parse argument 'p' (expected 1, 2, both or 1,2)".into())
+        );
+    }
+
+    #[test]
+    fn year() {
+        let _guard = set_now("2024-12-05T12:00:00Z".parse().unwrap());
+        assert_eq!(parse_year(None), Ok(2024));
+        assert_eq!(parse_year(Some("2023")), Ok(2023));
+        assert_eq!(parse_year(Some("15")), Ok(2015));
+        assert_eq!(parse_year(Some("23")), Ok(2023));
+        assert_eq!(
+            parse_year(Some("14")),
+            Err("Failed to parse argument 'year'".into())
+        );
+        assert!(parse_year(Some("25")).is_err());
+        assert!(parse_year(Some("2014")).is_err());
+        assert!(parse_year(Some("023")).is_err());
+        assert!(parse_year(Some("+5")).is_err());
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(parse_format(None, false), Ok(Format::Table));
+        assert_eq!(parse_format(Some("table"), false), Ok(Format::Table));
+        assert_eq!(parse_format(Some("Image"), true), Ok(Format::Image));
+        assert_eq!(parse_format(Some("compact"), false), Ok(Format::Compact));
+        assert_eq!(
+            parse_format(Some("image"), false),
+            Err("Image output is disabled".into())
+        );
+        assert!(parse_format(Some("png"), true).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use std::{fmt, future::Future, time::Duration};
+
+use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+use tracing::{error, warn};
+
+use super::send_error;
+use crate::i18n::Message;
+
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub enum CommandError {
+    User(String),
+    Transient {
+        message: String,
+        source: anyhow::Error,
+    },
+    Failed {
+        message: String,
+        source: anyhow::Error,
+    },
+    Internal(anyhow::Error),
+}
+
+pub type CommandResult = Result<(), CommandError>;
+
+impl CommandError {
+    pub fn fetch(year: i32, err: anyhow::Error) -> Self {
+        // AoC answers requests with an expired session with an html page
+        if err.is::<serde_json::Error>() {
+            return Self::Failed {
+                message: Message::ReadFailed { year }.to_string(),
+                source: err,
+            };
+        }
+        let Some(reqwest_err) = err.downcast_ref::<reqwest::Error>() else {
+            return Self::Internal(err);
+        };
+        let status = reqwest_err.status();
+        let message = Message::FetchFailed { year, status }.to_string();
+        let transient = status.is_some_and(|s| s.is_server_error())
+            || reqwest_err.is_timeout()
+            || reqwest_err.is_connect();
+        if transient {
+            Self::Transient {
+                message,
+                source: err,
+            }
+        } else {
+            Self::Failed {
+                message,
+                source: err,
+            }
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User(message) => f.write_str(message),
+            Self::Transient { message, source } | Self::Failed { message, source } => {
+                write!(f, "{message}: {source}")
+            }
+            Self::Internal(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::User(message)
+    }
+}
+
+impl From<anyhow::Error> for CommandError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::Internal(err)
+    }
+}
+
+pub async fn run<F, Fut>(
+    event: &OriginalRoomMessageEvent,
+    room: &Room,
+    handler: F,
+) -> anyhow::Result<()>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = CommandResult>,
+{
+    let mut result = handler().await;
+    if let Err(err @ CommandError::Transient { .. }) = &result {
+        warn!("Command failed, retrying: {err}");
+        tokio::time::sleep(RETRY_DELAY).await;
+        result = handler().await;
+    }
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(CommandError::User(message)) => send_error(room, event, message).await,
+        Err(
+            CommandError::Transient { message, source } | CommandError::Failed { message, source },
+        ) => {
+            warn!("Command failed: {message}: {source}");
+            send_error(room, event, message).await
+        }
+        Err(CommandError::Internal(err)) => {
+            error!("Command failed: {err:#}");
+            send_error(room, event, Message::InternalError).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aoc::{
+            day::AocDay,
+            fixtures::YEAR,
+            mock::{block_on, MockAoc},
+        },
+        utils::datetime::set_now,
+    };
+
+    #[test]
+    fn fetch() {
+        let _guard = set_now(AocDay { year: YEAR, day: 3 }.unlock_datetime());
+        let fetch = |status, body: &str| {
+            let err = block_on(async {
+                let aoc = MockAoc::start().await;
+                aoc.leaderboard(YEAR, status, body).await;
+                aoc.client().await.get_private_leaderboard(YEAR).await
+            })
+            .unwrap_err();
+            CommandError::fetch(YEAR, err)
+        };
+
+        assert!(matches!(
+            fetch(502, ""),
+            CommandError::Transient { message, .. }
+                if message == "Failed to fetch private leaderboard for 2024 (502 Bad Gateway)"
+        ));
+        assert!(matches!(
+            fetch(404, ""),
+            CommandError::Failed { message, .. }
+                if message == "Failed to fetch private leaderboard for 2024 (404 Not Found)"
+        ));
+        assert!(matches!(
+            fetch(200, "<!DOCTYPE html>"),
+            CommandError::Failed { message, .. } if message.contains("session may have expired")
+        ));
+        assert!(matches!(
+            CommandError::fetch(YEAR, anyhow::anyhow!("bug")),
+            CommandError::Internal(_)
+        ));
+    }
+}
@@ -1,43 +1,612 @@
-use std::sync::Arc;
+use std::{
+    fmt::{self, Write},
+    sync::Arc,
+    time::Duration,
+};
 
-use matrix_sdk::{ruma::events::room::message::OriginalRoomMessageEvent, Room};
+use matrix_sdk::{
+    ruma::{events::room::message::OriginalRoomMessageEvent, RoomId},
+    Room,
+};
+use parser::ParsedCommand;
+use tracing::error;
 
 use crate::{
     aoc::day::AocDay,
+    audit::{self, AuditEntry, Outcome},
     config::Config,
-    matrix::utils::{error_message, message, RoomExt},
+    i18n::Message,
+    matrix::{
+        ephemeral, placeholder,
+        utils::{error_message, message, RoomExt},
+    },
+    utils::datetime::now,
     Context,
 };
 
 pub mod admin;
 pub mod aoc;
+pub mod args;
+pub mod error;
 mod parser;
 
+pub use parser::strip_trigger;
+
 pub async fn handle(
     event: &OriginalRoomMessageEvent,
     room: Room,
     context: Arc<Context>,
     cmd: &str,
 ) -> anyhow::Result<()> {
-    let cmd = parser::parse(cmd);
+    context.record_activity();
+    let limits = &context.config.matrix.limits;
+    let cmd = parser::expand_aliases(cmd, &context.config.matrix.aliases);
+    if let Err(err) = args::check_length(&cmd, limits) {
+        return send_error(&room, event, err).await;
+    }
+    let mut cmd = parser::parse(&cmd);
+
+    let Some(command) = Command::from_name(&cmd.command) else {
+        return unknown_command(event, room).await;
+    };
+    if let Err(err) = args::check_args(&cmd, limits) {
+        return send_error(&room, event, err).await;
+    }
+
+    let mut params = cmd.args.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    let mut kwargs = cmd
+        .kwargs
+        .iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>();
+    kwargs.sort_unstable();
+    params.extend(kwargs);
+
+    if let Err(err) = cmd.bind_positionals(command.positionals(&cmd)) {
+        return send_error(&room, event, err).await;
+    }
+    if let Err(err) = command.check_window(&cmd) {
+        return send_error(&room, event, err).await;
+    }
+
+    let room_id = room.room_id().to_owned();
+    let ephemeral_delay = ephemeral_delay(&context.config, command, &cmd);
+    let dispatched =
+        placeholder::with_placeholder(dispatch(event, room.clone(), &context, command, cmd));
+    let dispatched = audit::with_outcome(dispatched);
+    let (sent, (outcome, result)) = ephemeral::with_sent_events(dispatched).await;
+    if let Some(delay) = ephemeral_delay {
+        ephemeral::redact_after(room, sent, delay);
+    }
+    let outcome = if result.is_err() {
+        Outcome::Error
+    } else {
+        outcome
+    };
+
+    let entry = AuditEntry {
+        ts: now(),
+        room_id,
+        sender: event.sender.clone(),
+        command: command.name().into(),
+        params,
+        outcome,
+    };
+    if let Err(err) = context.audit_log.record(entry).await {
+        error!("Failed to record audit log entry: {err}");
+    }
+
+    result
+}
 
-    match &*cmd.command {
+/// The delay after which the responses of the command are redacted, if they are configured to be
+/// ephemeral. Commands run with `debug=yes` use the delay configured for `debug` if there is one.
+fn ephemeral_delay(config: &Config, command: Command, cmd: &ParsedCommand<'_>) -> Option<Duration> {
+    let ephemeral = &config.matrix.ephemeral;
+    let debug = args::parse_bool(cmd.get_from_kwargs("debug"), "debug", false).unwrap_or_default();
+    let secs = debug
+        .then(|| ephemeral.get("debug"))
+        .flatten()
+        .or_else(|| ephemeral.get(command.name()))?;
+    Some(Duration::from_secs(*secs))
+}
+
+async fn dispatch(
+    event: &OriginalRoomMessageEvent,
+    room: Room,
+    context: &Context,
+    command: Command,
+    cmd: ParsedCommand<'_>,
+) -> anyhow::Result<()> {
+    if !context
+        .config
+        .matrix
+        .command_enabled(room.room_id(), command.name())
+    {
+        return send_error(&room, event, Message::CommandDisabled).await;
+    }
+
+    let Some(_permit) = context.try_acquire_command_permit(room.room_id()).await else {
+        return send_error(
+            &room,
+            event,
+            "Too many commands are running in this room, please try again in a moment",
+        )
+        .await;
+    };
+
+    match command {
         // Advent of Code
-        "join" => aoc::join::invoke(event, room, &context).await,
-        "leaderboard" | "lb" => aoc::leaderboard::invoke(event, room, &context, cmd).await,
-        "day" => aoc::day::invoke(event, room, &context, cmd).await,
-        "user" => aoc::user::invoke(event, room, &context, cmd).await,
-        "solutions" | "repos" => aoc::solutions::invoke(event, room, &context).await,
-        "clear-cache" | "cc" => aoc::clear_cache::invoke(event, room, &context).await,
+        Command::Join => aoc::join::invoke(event, room, context).await,
+        Command::Leaderboard => {
+            error::run(event, &room, || {
+                aoc::leaderboard::invoke(event, room.clone(), context, cmd.clone())
+            })
+            .await
+        }
+        Command::Day => {
+            error::run(event, &room, || {
+                aoc::day::invoke(event, room.clone(), context, cmd.clone())
+            })
+            .await
+        }
+        Command::User => aoc::user::invoke(event, room, context, cmd).await,
+        Command::Whois => aoc::whois::invoke(event, room, context, cmd).await,
+        Command::Ical => aoc::ical::invoke(event, room, context, cmd).await,
+        Command::Gap => aoc::gap::invoke(event, room, context, cmd).await,
+        Command::Boards => aoc::boards::invoke(event, room, context, cmd).await,
+        Command::History => aoc::history::invoke(event, room, context, cmd).await,
+        Command::Streak => aoc::streak::invoke(event, room, context, cmd).await,
+        Command::Timeline => aoc::timeline::invoke(event, room, context, cmd).await,
+        Command::Snapshot => aoc::snapshot::invoke(event, room, context, cmd).await,
+        Command::Records => aoc::records::invoke(event, room, context, cmd).await,
+        Command::Difficulty => aoc::difficulty::invoke(event, room, context, cmd).await,
+        Command::Winners => aoc::winners::invoke(event, room, context, cmd).await,
+        Command::Racing => aoc::racing::invoke(event, room, context).await,
+        Command::Random => aoc::random::invoke(event, room, context, cmd).await,
+        Command::Percentile => aoc::percentile::invoke(event, room, context, cmd).await,
+        Command::Year => {
+            error::run(event, &room, || {
+                aoc::year::invoke(event, room.clone(), context, cmd.clone())
+            })
+            .await
+        }
+        Command::Digest => aoc::digest::invoke(event, room, context, cmd).await,
+        Command::Link => aoc::link::invoke(event, room, context, cmd).await,
+        Command::Unlink => aoc::link::unlink(event, room, context, cmd).await,
+        Command::Unlocks => aoc::unlocks::invoke(event, room, context).await,
+        Command::Solutions => aoc::solutions::invoke(event, room, context).await,
+        Command::ClearCache => aoc::clear_cache::invoke(event, room, context, cmd).await,
 
         // General
-        "ping" => ping(event, room).await,
-        "help" => help(event, room, &context.config).await,
+        Command::Ping => ping(event, room).await,
+        Command::Help => help(event, room, &context.config).await,
 
         // Administration
-        "op" => admin::op(event, room, &context.config, cmd).await,
+        Command::Op => admin::op(event, room, &context.config, cmd).await,
+        Command::Schedule => admin::schedule(event, room, context, cmd).await,
+        Command::Audit => admin::audit(event, room, context, cmd).await,
+        Command::Block => admin::block(event, room, context, cmd).await,
+        Command::Unblock => admin::unblock(event, room, context, cmd).await,
+        Command::CheckUsers => admin::check_users(event, room, context, cmd).await,
+        Command::PartialUsers => admin::partial_users(event, room, context).await,
+        Command::Config => admin::show_config(event, room, context).await,
+        Command::Status => admin::status(event, room, context).await,
+        Command::Raw => admin::raw(event, room, context, cmd).await,
+        Command::ApproveLink => aoc::link::approve(event, room, context, cmd).await,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Join,
+    Leaderboard,
+    Day,
+    User,
+    Whois,
+    Ical,
+    Gap,
+    Boards,
+    History,
+    Streak,
+    Timeline,
+    Snapshot,
+    Records,
+    Difficulty,
+    Winners,
+    Racing,
+    Random,
+    Percentile,
+    Year,
+    Digest,
+    Link,
+    Unlink,
+    Unlocks,
+    Solutions,
+    ClearCache,
+    Ping,
+    Help,
+    Op,
+    Schedule,
+    Audit,
+    Block,
+    Unblock,
+    CheckUsers,
+    PartialUsers,
+    Config,
+    Status,
+    Raw,
+    ApproveLink,
+}
 
-        _ => unknown_command(event, room).await,
+pub fn is_command_name(name: &str) -> bool {
+    Command::from_name(name).is_some_and(|c| c.name() == name)
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "join" => Self::Join,
+            "leaderboard" | "lb" => Self::Leaderboard,
+            "day" => Self::Day,
+            "user" => Self::User,
+            "whois" => Self::Whois,
+            "ical" | "ics" => Self::Ical,
+            "gap" => Self::Gap,
+            "boards" => Self::Boards,
+            "history" | "ranks" => Self::History,
+            "streak" => Self::Streak,
+            "timeline" => Self::Timeline,
+            "snapshot" => Self::Snapshot,
+            "records" | "hof" => Self::Records,
+            "difficulty" | "hardest" => Self::Difficulty,
+            "winners" | "daily-winners" => Self::Winners,
+            "racing" | "progress" => Self::Racing,
+            "random" | "spotlight" => Self::Random,
+            "percentile" | "pct" => Self::Percentile,
+            "year" | "season" => Self::Year,
+            "digest" => Self::Digest,
+            "link" => Self::Link,
+            "unlink" => Self::Unlink,
+            "unlocks" | "upcoming" => Self::Unlocks,
+            "solutions" | "repos" => Self::Solutions,
+            "clear-cache" | "cc" | "flushcache" => Self::ClearCache,
+            "ping" => Self::Ping,
+            "help" => Self::Help,
+            "op" => Self::Op,
+            "schedule" => Self::Schedule,
+            "audit" => Self::Audit,
+            "block" => Self::Block,
+            "unblock" => Self::Unblock,
+            "check-users" => Self::CheckUsers,
+            "partial-users" => Self::PartialUsers,
+            "config" => Self::Config,
+            "status" => Self::Status,
+            "raw" => Self::Raw,
+            "approve-link" => Self::ApproveLink,
+            _ => return None,
+        })
+    }
+
+    fn positionals(self, cmd: &ParsedCommand<'_>) -> &'static [&'static str] {
+        match self {
+            Self::Join
+            | Self::Racing
+            | Self::Unlocks
+            | Self::Solutions
+            | Self::Ping
+            | Self::Help
+            | Self::PartialUsers
+            | Self::Config
+            | Self::Status => &[],
+            Self::Leaderboard | Self::Year => &["year", "rows", "offset", "format"],
+            Self::Day => &["day", "year", "p", "rows", "offset", "format"],
+            Self::User | Self::Ical => &["user", "year"],
+            Self::Whois | Self::Streak | Self::Unlink | Self::ApproveLink => &["user"],
+            Self::Gap | Self::Snapshot | Self::Records | Self::CheckUsers => &["year"],
+            Self::Raw => &["year", "day"],
+            Self::ClearCache => &["board", "year"],
+            Self::Boards | Self::History => &["year", "rows"],
+            Self::Timeline => &["day", "year", "rows", "offset"],
+            Self::Difficulty => &["year", "by"],
+            Self::Winners => &["year", "p"],
+            Self::Random => &["day", "year"],
+            Self::Percentile => &["day", "year", "p"],
+            Self::Digest => &["action"],
+            Self::Link => &["id", "repo"],
+            Self::Op => &["member", "level"],
+            // the second parameter depends on the action
+            Self::Schedule => match cmd.get_from_kwargs("action").or(cmd.args.front().copied()) {
+                Some("add") => &["action", "time", "kind", "on"],
+                Some("remove") => &["action", "id"],
+                _ => &["action"],
+            },
+            Self::Audit => &["user", "n"],
+            Self::Block => &["user", "reason"],
+            Self::Unblock => &["user"],
+        }
+    }
+
+    /// Reject time windows for commands paging by rows, which would otherwise silently ignore them.
+    fn check_window(self, cmd: &ParsedCommand<'_>) -> Result<(), String> {
+        let pages_by_rows = self.positionals(cmd).contains(&"rows");
+        if self == Self::Timeline || !pages_by_rows || cmd.get_from_kwargs("window").is_none() {
+            return Ok(());
+        }
+        Err(format!(
+            "Argument 'window' is only supported by timeline, {} pages by rows (use rows and \
+             offset)",
+            self.name()
+        ))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Join => "join",
+            Self::Leaderboard => "leaderboard",
+            Self::Day => "day",
+            Self::User => "user",
+            Self::Whois => "whois",
+            Self::Ical => "ical",
+            Self::Gap => "gap",
+            Self::Boards => "boards",
+            Self::History => "history",
+            Self::Streak => "streak",
+            Self::Timeline => "timeline",
+            Self::Snapshot => "snapshot",
+            Self::Records => "records",
+            Self::Difficulty => "difficulty",
+            Self::Winners => "winners",
+            Self::Racing => "racing",
+            Self::Random => "random",
+            Self::Percentile => "percentile",
+            Self::Year => "year",
+            Self::Digest => "digest",
+            Self::Link => "link",
+            Self::Unlink => "unlink",
+            Self::Unlocks => "unlocks",
+            Self::Solutions => "solutions",
+            Self::ClearCache => "clear-cache",
+            Self::Ping => "ping",
+            Self::Help => "help",
+            Self::Op => "op",
+            Self::Schedule => "schedule",
+            Self::Audit => "audit",
+            Self::Block => "block",
+            Self::Unblock => "unblock",
+            Self::CheckUsers => "check-users",
+            Self::PartialUsers => "partial-users",
+            Self::Config => "config",
+            Self::Status => "status",
+            Self::Raw => "raw",
+            Self::ApproveLink => "approve-link",
+        }
+    }
+}
+
+const HELP_SECTIONS: [(&str, &[Command]); 3] = [
+    (
+        "Advent of Code",
+        &[
+            Command::Join,
+            Command::Leaderboard,
+            Command::Year,
+            Command::Day,
+            Command::User,
+            Command::Whois,
+            Command::Ical,
+            Command::Gap,
+            Command::Boards,
+            Command::History,
+            Command::Streak,
+            Command::Timeline,
+            Command::Snapshot,
+            Command::Records,
+            Command::Difficulty,
+            Command::Winners,
+            Command::Racing,
+            Command::Random,
+            Command::Percentile,
+            Command::Digest,
+            Command::Link,
+            Command::Unlink,
+            Command::Unlocks,
+            Command::Solutions,
+            Command::ClearCache,
+        ],
+    ),
+    ("General", &[Command::Ping, Command::Help]),
+    (
+        "Administration",
+        &[
+            Command::Schedule,
+            Command::Audit,
+            Command::Block,
+            Command::Unblock,
+            Command::CheckUsers,
+            Command::PartialUsers,
+            Command::Config,
+            Command::Status,
+            Command::ApproveLink,
+            Command::Raw,
+        ],
+    ),
+];
+
+/// The defaults of the parameters shown in the help message, which depend on the config and the
+/// room.
+struct HelpDefaults {
+    /// `=<day>` during the event, empty otherwise.
+    day: String,
+    year: i32,
+    rows: usize,
+    board_rows: usize,
+    history_rows: usize,
+    min_stars: u32,
+    /// The default parts of the room first, e.g. `2|1|both`.
+    parts: String,
+}
+
+impl Command {
+    fn keyword_params(self) -> &'static [&'static str] {
+        match self {
+            Self::Leaderboard | Self::Year => {
+                &["columns", "min_stars", "here", "collapse", "fresh"]
+            }
+            Self::Day => &["p2_from", "columns", "summary", "collapse", "fresh"],
+            Self::User => &["order"],
+            Self::Timeline => &["window"],
+            Self::Snapshot => &["ago"],
+            Self::Percentile => &["p2_from"],
+            _ => &[],
+        }
+    }
+
+    fn param_usage(self, param: &str, defaults: &HelpDefaults) -> String {
+        match (self, param) {
+            (Self::Link, "id") => "<AoC id>".into(),
+            (Self::Unblock | Self::ApproveLink, "user") => "<user>".into(),
+            (Self::Digest, "action") => "[on|off]".into(),
+            (Self::Schedule, "action") => {
+                "[list|add <HH:MM> [daily|weekly|final] [on=<weekday>]|remove <id>]".into()
+            }
+            (Self::ClearCache, "board") => "[board|all]".into(),
+            (Self::ClearCache, "year") => "[year]".into(),
+            (Self::Raw, "day") => "[day]".into(),
+            (Self::Boards, "rows") => format!("[rows={}]", defaults.board_rows),
+            (Self::History, "rows") => format!("[rows={}]", defaults.history_rows),
+            (_, "day") => format!("[day{}]", defaults.day),
+            (_, "year") => format!("[year={}]", defaults.year),
+            (_, "rows") => format!("[rows={}|all]", defaults.rows),
+            (_, "offset") => "[offset=0]".into(),
+            (_, "format") => "[format=table|compact|image|tsv]".into(),
+            (_, "p") => format!("[p={}]", defaults.parts),
+            (_, "p2_from") => "[p2_from=part1|unlock]".into(),
+            (_, "columns") => "[columns=...]".into(),
+            (_, "min_stars") => format!("[min_stars={}]", defaults.min_stars),
+            (_, "order") => "[order=asc|desc]".into(),
+            (_, "by") => "[by=time|p2|completion]".into(),
+            (_, "n") => "[n=20]".into(),
+            (_, "window") => "[window=<duration> [offset=<duration>]]".into(),
+            (_, "ago") => "[ago=<duration>]".into(),
+            (_, "here" | "collapse" | "fresh" | "summary") => format!("[{param}=yes|no]"),
+            _ => format!("[{param}]"),
+        }
+    }
+
+    fn usage(self, defaults: &HelpDefaults) -> String {
+        let cmd = parser::parse(self.name());
+        let mut usage = self.name().to_owned();
+        for param in self.positionals(&cmd).iter().chain(self.keyword_params()) {
+            write!(&mut usage, " {}", self.param_usage(param, defaults)).unwrap();
+        }
+        usage
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Join => "Request instructions to join the private leaderboard",
+            Self::Leaderboard => {
+                "Show the given slice of the private leaderboard, hiding members with fewer stars \
+                 (or not in this room)"
+            }
+            Self::Year => {
+                "Show the private leaderboard like `leaderboard`, with a star strip of the 25 days \
+                 per member"
+            }
+            Self::Day => "Show the given slice of the daily private leaderboard",
+            Self::User => "Show statistics of the given user",
+            Self::Whois => {
+                "Show the AoC member, matrix user and repository linked to the given AoC name, id \
+                 or matrix user"
+            }
+            Self::Ical => {
+                "Upload the solve times of the given user (yourself by default) as an iCalendar \
+                 file"
+            }
+            Self::Gap => {
+                "Show how many points you are behind the member ranked above you and ahead of the \
+                 one below"
+            }
+            Self::Boards => "Compare the top members of the configured private leaderboards",
+            Self::History => "Show how the ranks of the top members evolved day by day",
+            Self::Streak => {
+                "Show for how many consecutive years the given user (yourself by default) has \
+                 earned stars"
+            }
+            Self::Timeline => {
+                "Show the order in which the stars of the given day were earned, or the stars \
+                 earned in a time window after the unlock (e.g. `window=1h offset=2h`)"
+            }
+            Self::Snapshot => {
+                "Upload the full private leaderboard as an html file, optionally as archived that \
+                 long ago (e.g. `ago=1d`)"
+            }
+            Self::Records => {
+                "Show the hall of fame: fastest solves, best single day and most top 3 finishes"
+            }
+            Self::Difficulty => "Rank the days of a year from hardest to easiest",
+            Self::Winners => "Show the fastest finisher of each day and who won the most days",
+            Self::Racing => "Show who is still racing part 2 of today's puzzle and who is done",
+            Self::Random => "Spotlight a random member who solved both parts of the day",
+            Self::Percentile => {
+                "Show how many of the other finishers of the day you were faster than"
+            }
+            Self::Digest => {
+                "Subscribe to a private message summarizing your progress after finishing each day"
+            }
+            Self::Link => {
+                "Link your matrix account to your AoC member (shown on the private leaderboard \
+                 page) and optionally your solution repository, after verifying it with a code \
+                 put into your AoC name"
+            }
+            Self::Unlink => {
+                "Remove the link of your matrix account (or of the given user, admin only)"
+            }
+            Self::Unlocks => {
+                "Show when the remaining puzzles of the current (or next) event unlock"
+            }
+            Self::Solutions => "Show the list of solution repositories",
+            Self::ClearCache => {
+                "Clear the cached data of the given private leaderboard (by owner id) and year, or \
+                 of all of them (admin only)"
+            }
+            Self::Ping => "Check bot health",
+            Self::Help => "Show this help message",
+            Self::Op => "Set the power level of the given matrix user in this room (admin only)",
+            Self::Schedule => {
+                "Manage daily leaderboard posts and weekly or final rollups in this room (room \
+                 admins only)"
+            }
+            Self::Audit => "Show the most recent commands (of the given user) (admin only)",
+            Self::Block => {
+                "Ignore all commands and reactions of the given user, or list the blocked users \
+                 (admin only)"
+            }
+            Self::Unblock => "Unblock the given user (admin only)",
+            Self::CheckUsers => {
+                "Check the configured users against the members of the private leaderboard \
+                 (admin only)"
+            }
+            Self::PartialUsers => {
+                "List the configured users with a repository but no matrix user (admin only)"
+            }
+            Self::Config => {
+                "Show a summary of the loaded configuration, without secrets (admin only)"
+            }
+            Self::Status => {
+                "Show the last activity of the bot and the usage of its in-memory caches (admin \
+                 only)"
+            }
+            Self::ApproveLink => {
+                "Link the given user to the AoC member of their pending link request without \
+                 verification (admin only)"
+            }
+            Self::Raw => {
+                "Upload the cached private leaderboard exactly as received from AoC, optionally \
+                 reduced to the completions of a day (admin only)"
+            }
+        }
     }
 }
 
@@ -46,39 +615,73 @@ pub async fn help(
     room: Room,
     config: &Config,
 ) -> anyhow::Result<()> {
+    room.reply_to(event, message(render_help(config, room.room_id())))
+        .await?;
+    Ok(())
+}
+
+fn render_help(config: &Config, room_id: &RoomId) -> String {
     let prefix = &config.matrix.command_prefix;
 
-    let default_day = AocDay::current()
-        .map(|d| format!("={}", d.day))
-        .unwrap_or_default();
-    let default_year = AocDay::most_recent().year;
-    let default_rows = config.aoc.leaderboard_rows;
-    let content = format!(
+    let default_parts = config.default_parts(room_id).name();
+    let defaults = HelpDefaults {
+        day: AocDay::current_with_grace(config.aoc.default_day_grace())
+            .map(|d| format!("={}", d.day))
+            .unwrap_or_default(),
+        year: AocDay::most_recent().year,
+        rows: config.aoc.leaderboard_rows,
+        board_rows: config.aoc.boards.rows,
+        history_rows: config.aoc.history.rows,
+        min_stars: config.aoc.participation.min_stars,
+        parts: [default_parts]
+            .into_iter()
+            .chain(
+                ["1", "2", "both"]
+                    .into_iter()
+                    .filter(|&p| p != default_parts),
+            )
+            .collect::<Vec<_>>()
+            .join("|"),
+    };
+    let mut content = format!(
         r#"
 ### AoC-Bot Commands
 
-#### Advent of Code
-- `{prefix}join` - Request instructions to join the private leaderboard
-- `{prefix}leaderboard [year={default_year}] [rows={default_rows}] [offset=0]` - Show the given slice of the private leaderboard
-- `{prefix}day [day{default_day}] [year={default_year}] [p=1|2|both] [rows={default_rows}] [offset=0]` - Show the given slice of the daily private leaderboard
-- `{prefix}user [user] [year={default_year}]` - Show statistics of the given user
-- `{prefix}solutions` - Show the list of solution repositories
-- `{prefix}clear-cache` - Clear the leaderboard cache (admin only)
-
-#### General
-- `{prefix}ping` - Check bot health
-- `{prefix}help` - Show this help message
+Commands can also be sent by mentioning the bot, e.g. `@bot: help`.
+Positional arguments are assigned to the parameters in the order shown, skipping those given by name, e.g. `{prefix}day 7 2023` is `{prefix}day day=7 year=2023`.
+The `day`, `leaderboard` and `timeline` commands accept `debug=yes` to show how their parameters were resolved.
+With `format=tsv`, leaderboards are shown as tab separated values for copying into spreadsheets. `columns` selects the copied columns (e.g. `columns=rank,aoc_name,stars`).
 "#
     );
+    for (section, commands) in HELP_SECTIONS {
+        write!(&mut content, "\n#### {section}\n").unwrap();
+        for command in commands {
+            writeln!(
+                &mut content,
+                "- `{prefix}{}` - {}",
+                command.usage(&defaults),
+                command.description()
+            )
+            .unwrap();
+        }
+    }
 
-    room.reply_to(event, message(content)).await?;
-    Ok(())
+    if !config.matrix.aliases.is_empty() {
+        let mut aliases = config.matrix.aliases.iter().collect::<Vec<_>>();
+        aliases.sort_unstable();
+        content.push_str("\n#### Aliases\n");
+        for (name, expansion) in aliases {
+            writeln!(&mut content, "- `{prefix}{name}` - `{prefix}{expansion}`").unwrap();
+        }
+    }
+
+    content
 }
 
 async fn unknown_command(event: &OriginalRoomMessageEvent, room: Room) -> anyhow::Result<()> {
     room.reply_to(
         event,
-        error_message("Unknown command. Send `!help` for a list of available commands."),
+        error_message(Message::UnknownCommand { prefix: "!" }),
     )
     .await?;
     Ok(())
@@ -92,8 +695,56 @@ pub async fn ping(event: &OriginalRoomMessageEvent, room: Room) -> anyhow::Resul
 async fn send_error(
     room: &Room,
     event: &OriginalRoomMessageEvent,
-    error: impl AsRef<str>,
+    error: impl fmt::Display,
 ) -> anyhow::Result<()> {
+    audit::set_outcome(Outcome::Error);
     room.reply_to(event, error_message(error)).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::ruma::RoomId;
+
+    use super::*;
+    use crate::{config::tests::load_with_overrides, utils::datetime::set_now};
+
+    #[test]
+    fn help() {
+        let _guard = set_now("2024-12-07T12:00:00-05:00".parse().unwrap());
+        let config = load_with_overrides(["aoc.leaderboard_rows = 10", "aoc.boards.rows = 3"]);
+        let room_id = <&RoomId>::try_from("!room:example.com").unwrap();
+        let help = render_help(&config, room_id);
+        let prefix = &config.matrix.command_prefix;
+        for line in [
+            format!(
+                "- `{prefix}leaderboard [year=2024] [rows=10|all] [offset=0] \
+                 [format=table|compact|image|tsv] [columns=...] [min_stars={}] [here=yes|no] \
+                 [collapse=yes|no] [fresh=yes|no]` - Show the given slice of the private \
+                 leaderboard, hiding members with fewer stars (or not in this room)",
+                config.aoc.participation.min_stars
+            ),
+            format!("- `{prefix}timeline [day=7] [year=2024] [rows=10|all] [offset=0] [window=<duration> [offset=<duration>]]` - Show the order in which the stars of the given day were earned, or the stars earned in a time window after the unlock (e.g. `window=1h offset=2h`)"),
+            format!("- `{prefix}boards [year=2024] [rows=3]` - Compare the top members of the configured private leaderboards"),
+            format!("- `{prefix}link <AoC id> [repo]` - Link your matrix account to your AoC member (shown on the private leaderboard page) and optionally your solution repository, after verifying it with a code put into your AoC name"),
+            format!("- `{prefix}schedule [list|add <HH:MM> [daily|weekly|final] [on=<weekday>]|remove <id>]` - Manage daily leaderboard posts and weekly or final rollups in this room (room admins only)"),
+            format!("- `{prefix}ping` - Check bot health"),
+        ] {
+            assert!(help.lines().any(|l| l == line), "{line}\n{help}");
+        }
+
+        // every command but op is listed once
+        for section in HELP_SECTIONS {
+            for command in section.1 {
+                assert_ne!(*command, Command::Op);
+                let usage = format!("- `{prefix}{} ", command.name());
+                let bare = format!("- `{prefix}{}`", command.name());
+                let count = help
+                    .lines()
+                    .filter(|l| l.starts_with(&usage) || l.starts_with(&bare))
+                    .count();
+                assert_eq!(count, 1, "{}", command.name());
+            }
+        }
+    }
+}
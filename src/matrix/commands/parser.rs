@@ -1,8 +1,18 @@
 use std::{
     borrow::Cow,
     collections::{HashMap, VecDeque},
+    sync::LazyLock,
 };
 
+use matrix_sdk::ruma::UserId;
+use regex::Regex;
+
+static PILL_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^\s*<a\s+href=["']https://matrix\.to/#/([^"'?]+)[^"']*["']\s*>(.*?)</a>"#)
+        .unwrap()
+});
+static TAG_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<.*?>"#).unwrap());
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParsedCommand<'a> {
     pub command: Cow<'a, str>,
@@ -11,15 +21,88 @@ pub struct ParsedCommand<'a> {
 }
 
 impl<'a> ParsedCommand<'a> {
-    pub fn get_from_kwargs_or_args(&mut self, key: &str) -> Option<&'a str> {
-        self.kwargs
-            .get(key)
+    pub fn get_from_kwargs(&self, key: &str) -> Option<&'a str> {
+        self.kwargs.get(key).copied()
+    }
+
+    /// Bind the positional arguments to the given parameters in order, skipping the parameters
+    /// passed as keyword arguments, e.g. `day 7 2023` to `day=7 year=2023`. Fails if there are
+    /// more positional arguments than parameters.
+    pub fn bind_positionals(&mut self, params: &[&'static str]) -> Result<(), String> {
+        let mut free = params
+            .iter()
+            .filter(|&&param| !self.kwargs.contains_key(param))
             .copied()
-            .or_else(|| self.args.pop_front())
+            .collect::<VecDeque<_>>();
+        while let Some(arg) = self.args.pop_front() {
+            let Some(param) = free.pop_front() else {
+                return Err(match params {
+                    [] => format!("`{}` takes no positional arguments", self.command),
+                    _ => format!(
+                        "Too many positional arguments (`{}` takes at most {}: {})",
+                        self.command,
+                        params.len(),
+                        params.join(", ")
+                    ),
+                });
+            };
+            self.kwargs.insert(Cow::Borrowed(param), arg);
+        }
+        Ok(())
     }
 }
 
-pub fn parse(cmd: &str) -> ParsedCommand {
+/// Strip the command prefix or a mention of the bot from the plain text body of a message.
+///
+/// Returns `None` if the message is not directed at the bot. A mention may be followed by a `:`
+/// or `,` and optionally by the command prefix. If the formatted body starts with a mention
+/// pill, its (tag-stripped) link text is expected at the start of the plain text body.
+pub fn strip_trigger<'a>(
+    body: &'a str,
+    formatted: Option<&str>,
+    prefix: &str,
+    user_id: &UserId,
+) -> Option<&'a str> {
+    if let Some(cmd) = body.strip_prefix(prefix) {
+        return Some(cmd);
+    }
+
+    let body = body.trim_start();
+    let rest = body.strip_prefix(user_id.as_str()).or_else(|| {
+        let captures = PILL_REGEX.captures(formatted?)?;
+        let target = captures[1]
+            .replace("%40", "@")
+            .replace("%3A", ":")
+            .replace("%3a", ":");
+        if target != user_id.as_str() {
+            return None;
+        }
+        let text = TAG_REGEX.replace_all(&captures[2], "");
+        body.strip_prefix(text.trim())
+    })?;
+
+    let rest = rest.trim_start_matches([':', ',']).trim_start();
+    Some(rest.strip_prefix(prefix).unwrap_or(rest))
+}
+
+/// Replace a leading alias with its expansion, keeping the remaining arguments. Aliases may expand
+/// to other aliases, cycles are rejected when the config is loaded.
+pub fn expand_aliases<'a>(cmd: &'a str, aliases: &HashMap<String, String>) -> Cow<'a, str> {
+    let mut cmd = Cow::Borrowed(cmd);
+    for _ in 0..=aliases.len() {
+        let trimmed = cmd.trim_start();
+        let (name, rest) = trimmed
+            .split_once(char::is_whitespace)
+            .unwrap_or((trimmed, ""));
+        let Some(expansion) = aliases.get(&name.to_lowercase()) else {
+            break;
+        };
+        cmd = Cow::Owned(format!("{expansion} {rest}"));
+    }
+    cmd
+}
+
+pub fn parse(cmd: &str) -> ParsedCommand<'_> {
     let mut iter = cmd
         .chars()
         .scan(0, |s, c| {
@@ -104,6 +187,23 @@ pub fn parse(cmd: &str) -> ParsedCommand {
 mod tests {
     use super::*;
 
+    #[test]
+    fn aliases() {
+        let aliases = HashMap::from([
+            ("top10".into(), "leaderboard rows=10".into()),
+            ("top".into(), "top10 offset=0".into()),
+        ]);
+        assert_eq!(expand_aliases("day 5", &aliases), "day 5");
+        assert_eq!(
+            expand_aliases("Top10 2023", &aliases),
+            "leaderboard rows=10 2023"
+        );
+        assert_eq!(
+            expand_aliases(" top", &aliases).trim_end(),
+            "leaderboard rows=10 offset=0"
+        );
+    }
+
     macro_rules! test {
         ($inp:expr, $command:expr, $args:expr, $kwargs:expr) => {{
             let input = $inp;
@@ -149,4 +249,73 @@ mod tests {
         );
         test!(r#"test "x=y""#, "test", ["x=y"], []);
     }
+
+    #[test]
+    fn positionals() {
+        let bind = |cmd, params| {
+            let mut cmd = parse(cmd);
+            cmd.bind_positionals(params).map(|()| cmd)
+        };
+        let params = &["day", "year", "p"];
+
+        let cmd = bind("day 7 2023", params).unwrap();
+        assert!(cmd.args.is_empty());
+        assert_eq!(cmd.get_from_kwargs("day"), Some("7"));
+        assert_eq!(cmd.get_from_kwargs("year"), Some("2023"));
+        assert_eq!(cmd.get_from_kwargs("p"), None);
+
+        // parameters passed as keyword arguments are skipped
+        let cmd = bind("day 2023 day=7 2", params).unwrap();
+        assert_eq!(cmd.get_from_kwargs("day"), Some("7"));
+        assert_eq!(cmd.get_from_kwargs("year"), Some("2023"));
+        assert_eq!(cmd.get_from_kwargs("p"), Some("2"));
+
+        assert_eq!(
+            bind("day 7 2023 2 5", params),
+            Err("Too many positional arguments (`day` takes at most 3: day, year, p)".into())
+        );
+        assert_eq!(
+            bind("day 7 2023 year=2022 p=2", params),
+            Err("Too many positional arguments (`day` takes at most 3: day, year, p)".into())
+        );
+        assert_eq!(
+            bind("ping pong", &[]),
+            Err("`ping` takes no positional arguments".into())
+        );
+    }
+
+    #[test]
+    fn triggers() {
+        let user_id = <&UserId>::try_from("@aocbot:example.com").unwrap();
+        let strip = |body, formatted| strip_trigger(body, formatted, "!", user_id);
+
+        assert_eq!(strip("!day 3", None), Some("day 3"));
+        assert_eq!(strip("day 3", None), None);
+        assert_eq!(strip("@aocbot:example.com: day 3", None), Some("day 3"));
+        assert_eq!(strip("@aocbot:example.com !day 3", None), Some("day 3"));
+        assert_eq!(strip("@aocbot:example.com", None), Some(""));
+        assert_eq!(
+            strip(
+                "AoC Bot: day 3",
+                Some(r#"<a href="https://matrix.to/#/@aocbot:example.com">AoC Bot</a>: day 3"#)
+            ),
+            Some("day 3")
+        );
+        assert_eq!(
+            strip(
+                "AoC Bot, lb",
+                Some(
+                    r#"<a href='https://matrix.to/#/%40aocbot%3Aexample.com?via=example.com'><b>AoC Bot</b></a>, lb"#
+                )
+            ),
+            Some("lb")
+        );
+        assert_eq!(
+            strip(
+                "Someone: day 3",
+                Some(r#"<a href="https://matrix.to/#/@someone:example.com">Someone</a>: day 3"#)
+            ),
+            None
+        );
+    }
 }
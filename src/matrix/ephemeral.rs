@@ -0,0 +1,67 @@
+//! Ephemeral command responses, which are redacted after a configurable delay to keep the room
+//! tidy.
+
+use std::{cell::RefCell, future::Future, time::Duration};
+
+use matrix_sdk::{ruma::OwnedEventId, Room};
+use tracing::warn;
+
+tokio::task_local! {
+    static SENT: RefCell<Vec<OwnedEventId>>;
+}
+
+const REDACTION_REASON: &str = "Ephemeral response";
+
+pub async fn with_sent_events<F: Future>(future: F) -> (Vec<OwnedEventId>, F::Output) {
+    SENT.scope(RefCell::new(Vec::new()), async {
+        let output = future.await;
+        (SENT.with(|sent| sent.take()), output)
+    })
+    .await
+}
+
+/// Report an event sent in response to the currently running command. Does nothing outside of
+/// [`with_sent_events`].
+pub fn record_sent(event_id: OwnedEventId) {
+    let _ = SENT.try_with(|sent| sent.borrow_mut().push(event_id));
+}
+
+/// Redact the given events after `delay` in a background task, so that the command handler does
+/// not wait for it. Failed redactions are logged.
+pub fn redact_after(room: Room, event_ids: Vec<OwnedEventId>, delay: Duration) {
+    if event_ids.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        for event_id in event_ids {
+            if let Err(err) = room.redact(&event_id, Some(REDACTION_REASON), None).await {
+                warn!(room_id = %room.room_id(), %event_id, "Failed to redact ephemeral response: {err}");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sent_events() {
+        let event_id = |id: &str| OwnedEventId::try_from(id).unwrap();
+        let (sent, output) = with_sent_events(async {
+            record_sent(event_id("$a:example.com"));
+            record_sent(event_id("$b:example.com"));
+            42
+        })
+        .await;
+        assert_eq!(output, 42);
+        assert_eq!(
+            sent,
+            [event_id("$a:example.com"), event_id("$b:example.com")]
+        );
+
+        // outside of a scope
+        record_sent(event_id("$c:example.com"));
+    }
+}
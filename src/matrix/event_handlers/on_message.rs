@@ -12,7 +12,9 @@ use matrix_sdk::{
     Client, Room, RoomState,
 };
 
-use crate::{context::Context, matrix::commands};
+use tracing::error;
+
+use crate::{context::Context, i18n, matrix::commands};
 
 pub async fn handle(
     event: OriginalSyncRoomMessageEvent,
@@ -24,6 +26,10 @@ pub async fn handle(
     if room.state() != RoomState::Joined || event.sender == client.user_id().unwrap() {
         return Ok(());
     }
+    // blocked users are ignored entirely, without read receipts or responses
+    if context.blocklist.is_blocked(&event.sender).await {
+        return Ok(());
+    }
 
     tokio::spawn({
         let room = room.clone();
@@ -38,27 +44,28 @@ pub async fn handle(
         return Ok(());
     };
 
-    if content
-        .body
-        .trim()
-        .starts_with(client.user_id().unwrap().as_str())
-        || content.formatted.as_ref().is_some_and(|f| {
-            f.body.trim().starts_with(&format!(
-                r#"<a href="{}">"#,
-                client.user_id().unwrap().matrix_to_uri(),
-            ))
-        })
-    {
+    let Some(cmd) = commands::strip_trigger(
+        &content.body,
+        content.formatted.as_ref().map(|f| f.body.as_str()),
+        &context.config.matrix.command_prefix,
+        client.user_id().unwrap(),
+    ) else {
+        return Ok(());
+    };
+
+    if cmd.trim().is_empty() {
         commands::help(&event, room, &context.config).await?;
         return Ok(());
     }
 
-    let Some(cmd) = content
-        .body
-        .strip_prefix(&context.config.matrix.command_prefix)
-    else {
-        return Ok(());
-    };
+    // commands are executed concurrently, so that slow commands don't block the sync loop
+    let cmd = cmd.to_owned();
+    let locale = context.config.matrix.locale(room.room_id());
+    tokio::spawn(i18n::with_locale(locale, async move {
+        if let Err(err) = commands::handle(&event, room, context.0, &cmd).await {
+            error!("Failed to handle command '{cmd}': {err:#}");
+        }
+    }));
 
-    commands::handle(&event, room, context.0, cmd).await
+    Ok(())
 }
@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use matrix_sdk::{
+    event_handler::Ctx, ruma::events::reaction::OriginalSyncReactionEvent, Client, Room, RoomState,
+};
+
+use crate::{context::Context, matrix::commands::aoc::reactions};
+
+pub async fn handle(
+    event: OriginalSyncReactionEvent,
+    client: Client,
+    room: Room,
+    context: Ctx<Arc<Context>>,
+) -> anyhow::Result<()> {
+    if room.state() != RoomState::Joined || event.sender == client.user_id().unwrap() {
+        return Ok(());
+    }
+    if context.blocklist.is_blocked(&event.sender).await {
+        return Ok(());
+    }
+
+    let annotation = &event.content.relates_to;
+    reactions::handle(&room, &context, &annotation.event_id, &annotation.key).await
+}
@@ -1,12 +1,25 @@
-use std::{path::Path, sync::Arc};
+use std::{path::Path, sync::Arc, time::Duration};
 
 use anyhow::Context as _;
-use matrix_sdk::{config::SyncSettings, ruma::api::client::filter::FilterDefinition, Client};
+use matrix_sdk::{
+    config::SyncSettings,
+    ruma::api::client::{error::ErrorKind, filter::FilterDefinition},
+    Client,
+};
+use matrix_sdk_base::StateStoreDataKey;
+use tracing::{info, warn};
 
 use crate::context::Context;
 
-mod commands;
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(300);
+
+pub mod commands;
+pub mod ephemeral;
 mod event_handlers;
+pub mod pins;
+pub mod placeholder;
+pub mod quiet;
 pub mod utils;
 
 pub async fn create_client(homeserver_url: &str, store_path: &Path) -> anyhow::Result<Client> {
@@ -33,6 +46,7 @@ impl Bot {
                 "m.room.encrypted",
                 "m.room.encryption",
                 "m.room.member",
+                "m.reaction",
             ]
             .into_iter()
             .map(Into::into)
@@ -41,8 +55,22 @@ impl Bot {
         filter.room.rooms = None;
 
         let sync_settings = SyncSettings::new().filter(filter.into());
-        let sync_response = client.sync_once(sync_settings.clone()).await?;
-        let sync_settings = sync_settings.token(sync_response.next_batch);
+
+        // resume from the last sync response the client has stored if possible, otherwise skip all
+        // events that were sent before the bot was started
+        let token = client
+            .store()
+            .get_kv_data(StateStoreDataKey::SyncToken)
+            .await?
+            .and_then(|value| value.into_sync_token());
+        let token = match token {
+            Some(token) => {
+                info!("Resuming sync from stored token");
+                token
+            }
+            None => client.sync_once(sync_settings.clone()).await?.next_batch,
+        };
+        let sync_settings = sync_settings.token(token);
 
         Ok(Self {
             client,
@@ -50,11 +78,50 @@ impl Bot {
         })
     }
 
+    /// Sync with the homeserver until an unrecoverable error occurs.
+    ///
+    /// Sync errors are retried with exponential backoff. The client persists the token of every
+    /// successful sync response in its state store, so events are not handled twice after a
+    /// reconnect or restart.
     pub async fn start(self, context: Arc<Context>) -> anyhow::Result<()> {
-        event_handlers::add_event_handlers(&self.client, context);
+        event_handlers::add_event_handlers(&self.client, Arc::clone(&context));
+
+        let mut sync_settings = self.sync_settings;
+        let mut delay = MIN_RECONNECT_DELAY;
+        let mut reconnecting = false;
+        loop {
+            let result = tokio::select! {
+                result = self.client.sync_once(sync_settings.clone()) => result,
+                _ = context.reconnect.notified() => {
+                    warn!("Restarting sync with homeserver");
+                    continue;
+                }
+            };
+            match result {
+                Ok(response) => {
+                    if reconnecting {
+                        info!("Reconnected to homeserver");
+                        reconnecting = false;
+                    }
+                    delay = MIN_RECONNECT_DELAY;
+                    context.record_activity();
 
-        self.client.sync(self.sync_settings).await?;
+                    sync_settings = sync_settings.token(response.next_batch);
+                }
+                Err(err) => {
+                    if err
+                        .client_api_error_kind()
+                        .is_some_and(|kind| matches!(kind, ErrorKind::UnknownToken { .. }))
+                    {
+                        return Err(err).context("Matrix session is no longer valid");
+                    }
 
-        Ok(())
+                    warn!("Sync failed, reconnecting in {delay:?}: {err}");
+                    reconnecting = true;
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                }
+            }
+        }
     }
 }
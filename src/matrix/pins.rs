@@ -0,0 +1,82 @@
+//! Pinning the latest leaderboard posted by the bot, so that the current standings are easy to
+//! find in the room.
+
+use matrix_sdk::{
+    ruma::{
+        api::client::{error::ErrorKind, state::get_state_events_for_key},
+        events::{room::pinned_events::RoomPinnedEventsEventContent, StateEventType},
+        EventId, OwnedEventId,
+    },
+    Room,
+};
+
+/// Pin the given event, unpinning the previously pinned post of the bot. Events pinned by others
+/// are kept.
+pub async fn pin(
+    room: &Room,
+    event_id: &EventId,
+    previous: Option<&EventId>,
+) -> anyhow::Result<()> {
+    // the sync filter ignores room state, so the pinned events are fetched from the homeserver
+    let request = get_state_events_for_key::v3::Request::new(
+        room.room_id().to_owned(),
+        StateEventType::RoomPinnedEvents,
+        String::new(),
+    );
+    let pinned = match room.client().send(request, None).await {
+        Ok(response) => {
+            response
+                .content
+                .deserialize_as::<RoomPinnedEventsEventContent>()?
+                .pinned
+        }
+        Err(err) if err.client_api_error_kind() == Some(&ErrorKind::NotFound) => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    let updated = replace_pin(pinned.clone(), event_id, previous);
+    if updated != pinned {
+        room.send_state_event(RoomPinnedEventsEventContent::new(updated))
+            .await?;
+    }
+    Ok(())
+}
+
+fn replace_pin(
+    mut pinned: Vec<OwnedEventId>,
+    event_id: &EventId,
+    previous: Option<&EventId>,
+) -> Vec<OwnedEventId> {
+    pinned.retain(|id| id != event_id && Some(&**id) != previous);
+    pinned.push(event_id.to_owned());
+    pinned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_pin() {
+        let id = |id: &str| OwnedEventId::try_from(id).unwrap();
+        let (other, previous, new) = (
+            id("$a:example.com"),
+            id("$b:example.com"),
+            id("$c:example.com"),
+        );
+
+        assert_eq!(
+            super::replace_pin(vec![other.clone(), previous.clone()], &new, Some(&previous)),
+            [other.clone(), new.clone()]
+        );
+        assert_eq!(
+            super::replace_pin(Vec::new(), &new, None),
+            vec![new.clone()]
+        );
+        // the previous post was unpinned by someone else
+        assert_eq!(
+            super::replace_pin(vec![other.clone()], &new, Some(&previous)),
+            [other, new]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Placeholder messages posted while a command waits for a slow fetch, so that the user gets
+//! immediate feedback. The first response of the command replaces the placeholder.
+
+use std::{cell::RefCell, future::Future, pin::pin, time::Duration};
+
+use matrix_sdk::{
+    ruma::{
+        events::room::message::{MessageType, OriginalRoomMessageEvent, RoomMessageEventContent},
+        OwnedEventId,
+    },
+    Room,
+};
+use tracing::warn;
+
+use crate::matrix::utils::{notice, RoomExt};
+
+tokio::task_local! {
+    static PENDING: RefCell<Option<OwnedEventId>>;
+}
+
+const SLOW_FETCH: Duration = Duration::from_secs(1);
+
+pub async fn with_placeholder<F: Future>(future: F) -> F::Output {
+    PENDING.scope(RefCell::new(None), future).await
+}
+
+/// Post a placeholder with the given text in response to the command. Failures are only logged,
+/// the command continues without a placeholder. Does nothing outside of [`with_placeholder`].
+pub async fn post(room: &Room, event: &OriginalRoomMessageEvent, text: &str) {
+    if PENDING.try_with(|_| ()).is_err() {
+        return;
+    }
+    match room.reply_to(event, notice(format!("⏳ {text}"))).await {
+        Ok(response) => PENDING.with(|p| *p.borrow_mut() = Some(response.event_id)),
+        Err(err) => warn!(room_id = %room.room_id(), "Failed to post placeholder: {err}"),
+    }
+}
+
+pub async fn while_slow<F: Future>(
+    room: &Room,
+    event: &OriginalRoomMessageEvent,
+    text: &str,
+    future: F,
+) -> F::Output {
+    let mut future = pin!(future);
+    tokio::select! {
+        output = &mut future => return output,
+        _ = tokio::time::sleep(SLOW_FETCH) => {}
+    }
+    post(room, event, text).await;
+    future.await
+}
+
+/// Take the pending placeholder, which the caller is responsible for replacing.
+pub fn take() -> Option<OwnedEventId> {
+    PENDING.try_with(|p| p.take()).ok().flatten()
+}
+
+/// Whether the given content can replace a placeholder. Text messages cannot be edited into
+/// images or files.
+pub fn can_replace(content: &RoomMessageEventContent) -> bool {
+    matches!(
+        content.msgtype,
+        MessageType::Text(_) | MessageType::Notice(_) | MessageType::Emote(_)
+    )
+}
+
+pub fn discard(room: Room, event_id: OwnedEventId) {
+    tokio::spawn(async move {
+        if let Err(err) = room.redact(&event_id, None, None).await {
+            warn!(room_id = %room.room_id(), %event_id, "Failed to redact placeholder: {err}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::ruma::{events::room::message::ImageMessageEventContent, OwnedMxcUri};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn can_replace() {
+        assert!(super::can_replace(&notice("42")));
+        let image = ImageMessageEventContent::plain(
+            "leaderboard.png".into(),
+            OwnedMxcUri::from("mxc://example.com/abc"),
+        );
+        assert!(!super::can_replace(&RoomMessageEventContent::new(
+            MessageType::Image(image)
+        )));
+
+        // nothing was posted, also outside of a scope
+        assert_eq!(with_placeholder(async { take() }).await, None);
+        assert_eq!(take(), None);
+    }
+}
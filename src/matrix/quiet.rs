@@ -0,0 +1,131 @@
+//! Muting and quiet hours of the messages the bot sends on its own, e.g. announcements,
+//! notifications and scheduled posts. Responses to commands are always sent.
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use matrix_sdk::ruma::RoomId;
+use serde::Deserialize;
+
+use crate::{
+    config::{Config, TimeRange},
+    utils::datetime::now,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuietPolicy {
+    /// Send them when the quiet hours end.
+    Defer,
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Proactive {
+    Allowed,
+    Defer(DateTime<Utc>),
+    Drop,
+}
+
+/// Whether the bot may currently post a proactive message in the given room. Muted rooms drop
+/// their messages regardless of the quiet policy.
+pub fn check(config: &Config, room_id: &RoomId) -> Proactive {
+    let matrix = &config.matrix;
+    if matrix.muted(room_id) {
+        return Proactive::Drop;
+    }
+    let Some(quiet_hours) = matrix.quiet_hours(room_id) else {
+        return Proactive::Allowed;
+    };
+
+    let now = now();
+    let local = config.local_timezone.from_utc_datetime(&now.naive_utc());
+    if !quiet_hours.contains(local.time()) {
+        return Proactive::Allowed;
+    }
+    match matrix.quiet_policy {
+        QuietPolicy::Defer => Proactive::Defer(quiet_hours_end(config, quiet_hours, now)),
+        QuietPolicy::Drop => Proactive::Drop,
+    }
+}
+
+fn quiet_hours_end(config: &Config, quiet_hours: TimeRange, now: DateTime<Utc>) -> DateTime<Utc> {
+    let timezone = config.local_timezone;
+    let mut date = timezone.from_utc_datetime(&now.naive_utc()).date_naive();
+    loop {
+        let end = timezone
+            .from_local_datetime(&date.and_time(quiet_hours.end))
+            .unwrap()
+            .to_utc();
+        if end > now {
+            return end;
+        }
+        date += TimeDelta::days(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::tests::load_with_overrides, utils::datetime::set_now};
+
+    #[test]
+    fn check() {
+        let room_id = <&RoomId>::try_from("!room:example.com").unwrap();
+        let other_id = <&RoomId>::try_from("!other:example.com").unwrap();
+        let config = load_with_overrides([r#"
+            local_timezone = "+01:00"
+            [matrix]
+            quiet_hours = { start = "23:00", end = "07:00" }
+            rooms = [{ id = "!room:example.com", quiet_hours = { start = "12:00", end = "13:00" } }]
+            "#]);
+        let check_at = |now: &str, room_id| {
+            let _guard = set_now(now.parse().unwrap());
+            super::check(&config, room_id)
+        };
+        let defer = |until: &str| Proactive::Defer(until.parse().unwrap());
+
+        // the boundaries in local time: 23:00 is quiet, 07:00 is not
+        assert_eq!(
+            check_at("2024-12-01T21:59:59Z", other_id),
+            Proactive::Allowed
+        );
+        assert_eq!(
+            check_at("2024-12-01T22:00:00Z", other_id),
+            defer("2024-12-02T06:00:00Z")
+        );
+        assert_eq!(
+            check_at("2024-12-02T05:59:59Z", other_id),
+            defer("2024-12-02T06:00:00Z")
+        );
+        assert_eq!(
+            check_at("2024-12-02T06:00:00Z", other_id),
+            Proactive::Allowed
+        );
+
+        // the room overrides the quiet hours
+        assert_eq!(
+            check_at("2024-12-01T22:30:00Z", room_id),
+            Proactive::Allowed
+        );
+        assert_eq!(
+            check_at("2024-12-01T11:00:00Z", room_id),
+            defer("2024-12-01T12:00:00Z")
+        );
+
+        let config = load_with_overrides([r#"
+            [matrix]
+            quiet_hours = { start = "22:00", end = "08:00" }
+            quiet_policy = "drop"
+            rooms = [{ id = "!room:example.com", mute = true }]
+            "#]);
+        let check_at = |now: &str, room_id| {
+            let _guard = set_now(now.parse().unwrap());
+            super::check(&config, room_id)
+        };
+        assert_eq!(check_at("2024-12-01T22:00:00Z", other_id), Proactive::Drop);
+        assert_eq!(
+            check_at("2024-12-01T12:00:00Z", other_id),
+            Proactive::Allowed
+        );
+        assert_eq!(check_at("2024-12-01T12:00:00Z", room_id), Proactive::Drop);
+    }
+}
@@ -1,12 +1,41 @@
-use std::future::Future;
+use std::{
+    collections::BTreeSet,
+    fmt,
+    future::Future,
+    sync::LazyLock,
+    time::{Duration, SystemTime},
+};
 
 use matrix_sdk::{
     ruma::{
-        api::client::message::send_message_event,
-        events::room::message::{OriginalRoomMessageEvent, Relation, RoomMessageEventContent},
+        api::client::{
+            error::{ErrorKind, RetryAfter},
+            message::send_message_event,
+        },
+        events::{
+            room::message::{
+                FileMessageEventContent, MessageType, OriginalRoomMessageEvent, Relation,
+                ReplacementMetadata, RoomMessageEventContent,
+            },
+            Mentions as IntentionalMentions,
+        },
+        matrix_uri::MatrixId,
+        EventId, MatrixToUri, OwnedUserId, UserId,
     },
     Room,
 };
+use regex::Regex;
+use tracing::warn;
+
+use crate::{
+    aoc::models::PrivateLeaderboardMember,
+    i18n::Message,
+    matrix::{ephemeral, placeholder},
+};
+
+const RATE_LIMIT_RETRIES: usize = 3;
+const RATE_LIMIT_DEFAULT_DELAY: Duration = Duration::from_secs(5);
+const RATE_LIMIT_MAX_DELAY: Duration = Duration::from_secs(60);
 
 pub trait RoomExt {
     fn reply_to(
@@ -14,6 +43,21 @@ pub trait RoomExt {
         event: &OriginalRoomMessageEvent,
         content: RoomMessageEventContent,
     ) -> impl Future<Output = Result<send_message_event::v3::Response, matrix_sdk::Error>> + Send;
+
+    /// Replace the content of a previously sent message. With `notify`, the users mentioned by the
+    /// new content are notified as if it was a new message, which is meant for replacing
+    /// placeholders. Otherwise nobody is notified, as for re-rendering a message.
+    fn edit(
+        &self,
+        event_id: &EventId,
+        content: RoomMessageEventContent,
+        notify: bool,
+    ) -> impl Future<Output = Result<send_message_event::v3::Response, matrix_sdk::Error>> + Send;
+
+    fn send_throttled(
+        &self,
+        content: RoomMessageEventContent,
+    ) -> impl Future<Output = Result<send_message_event::v3::Response, matrix_sdk::Error>> + Send;
 }
 
 impl RoomExt for Room {
@@ -28,26 +72,243 @@ impl RoomExt for Room {
             .as_ref()
             .filter(|rl| matches!(rl, Relation::Thread(_)))
             .cloned();
-        self.send(content).await
+        // the first response of a command replaces its placeholder
+        if let Some(event_id) = placeholder::take() {
+            if placeholder::can_replace(&content) {
+                return self.edit(&event_id, content, true).await;
+            }
+            placeholder::discard(self.clone(), event_id);
+        }
+        let response = self.send(content).await?;
+        ephemeral::record_sent(response.event_id.clone());
+        Ok(response)
+    }
+
+    async fn edit(
+        &self,
+        event_id: &EventId,
+        content: RoomMessageEventContent,
+        notify: bool,
+    ) -> Result<send_message_event::v3::Response, matrix_sdk::Error> {
+        // only users who were not mentioned by the replaced message are notified
+        let previous = match notify {
+            true => IntentionalMentions::new(),
+            false => content.mentions.clone().unwrap_or_default(),
+        };
+        let metadata = ReplacementMetadata::new(event_id.to_owned(), Some(previous));
+        self.send(content.make_replacement(metadata, None)).await
+    }
+
+    async fn send_throttled(
+        &self,
+        content: RoomMessageEventContent,
+    ) -> Result<send_message_event::v3::Response, matrix_sdk::Error> {
+        let mut retries = 0;
+        loop {
+            let err = match self.send(content.clone()).await {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            let Some(ErrorKind::LimitExceeded { retry_after }) =
+                err.as_client_api_error().and_then(|err| err.error_kind())
+            else {
+                return Err(err);
+            };
+            if retries == RATE_LIMIT_RETRIES {
+                return Err(err);
+            }
+            retries += 1;
+
+            let delay = match retry_after {
+                Some(RetryAfter::Delay(delay)) => *delay,
+                Some(RetryAfter::DateTime(ts)) => {
+                    ts.duration_since(SystemTime::now()).unwrap_or_default()
+                }
+                None => RATE_LIMIT_DEFAULT_DELAY,
+            };
+            let delay = delay.min(RATE_LIMIT_MAX_DELAY);
+            warn!(room_id = %self.room_id(), ?delay, "rate limited, retrying");
+            tokio::time::sleep(delay).await;
+        }
     }
 }
 
+/// Renders mentions of matrix users, up to a maximum number per message. Further users are shown
+/// as plain text instead of pills, so that a single message does not notify dozens of people.
+pub struct Mentions {
+    max: usize,
+    mentioned: usize,
+    plain: usize,
+}
+
+impl Mentions {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            mentioned: 0,
+            plain: 0,
+        }
+    }
+
+    pub fn mention(&mut self, user_id: &UserId) -> String {
+        if self.mentioned < self.max {
+            self.mentioned += 1;
+            user_id.matrix_to_uri().to_string()
+        } else {
+            self.plain += 1;
+            Self::plain(user_id)
+        }
+    }
+
+    pub fn mention_member(
+        &mut self,
+        member: &PrivateLeaderboardMember,
+        user_id: &UserId,
+    ) -> String {
+        if member.no_ping {
+            Self::plain(user_id)
+        } else {
+            self.mention(user_id)
+        }
+    }
+
+    pub fn plain(user_id: &UserId) -> String {
+        format!("<code>{user_id}</code>")
+    }
+
+    pub fn note(&self) -> Option<String> {
+        (self.plain > 0).then(|| format!("and {} others not mentioned", self.plain))
+    }
+}
+
+/// Set the intentional mentions (`m.mentions`) of a message to the users it links to, so that
+/// exactly they are notified. The ids of users shown as plain text (see [`Mentions::plain`]) are
+/// removed from the plain body, as clients and servers without support for intentional mentions
+/// notify every user whose id is in the body.
+///
+/// Every message the bot sends is passed through this, the message constructors below do so.
+pub fn with_mentions(mut content: RoomMessageEventContent) -> RoomMessageEventContent {
+    static MATRIX_TO_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"https://matrix\.to/#/[^\s"'<>()]+"#).unwrap());
+    static PLAIN_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"<code>(@[^\s<]+)</code>").unwrap());
+
+    let (body, formatted) = match &mut content.msgtype {
+        MessageType::Text(c) => (&mut c.body, c.formatted.as_ref().map(|f| &*f.body)),
+        MessageType::Notice(c) => (&mut c.body, c.formatted.as_ref().map(|f| &*f.body)),
+        MessageType::Emote(c) => (&mut c.body, c.formatted.as_ref().map(|f| &*f.body)),
+        MessageType::Image(c) => (&mut c.body, c.formatted.as_ref().map(|f| &*f.body)),
+        MessageType::File(c) => (&mut c.body, c.formatted.as_ref().map(|f| &*f.body)),
+        _ => return content.add_mentions(IntentionalMentions::new()),
+    };
+    let user_ids = [Some(&**body), formatted]
+        .into_iter()
+        .flatten()
+        .flat_map(|text| MATRIX_TO_REGEX.find_iter(text))
+        .filter_map(|m| match MatrixToUri::parse(m.as_str()).ok()?.id() {
+            MatrixId::User(user_id) => Some(user_id.clone()),
+            _ => None,
+        })
+        .collect::<BTreeSet<OwnedUserId>>();
+    let plain = PLAIN_REGEX
+        .replace_all(body, |caps: &regex::Captures<'_>| {
+            match UserId::parse(&caps[1]) {
+                Ok(user_id) if !user_ids.contains(&user_id) => String::new(),
+                _ => caps[0].to_owned(),
+            }
+        })
+        .into_owned();
+    *body = plain;
+    content.add_mentions(IntentionalMentions::with_user_ids(user_ids))
+}
+
 pub fn message(text: impl AsRef<str> + Into<String>) -> RoomMessageEventContent {
-    RoomMessageEventContent::text_markdown(text)
+    with_mentions(RoomMessageEventContent::text_markdown(text))
 }
 
 pub fn notice(text: impl AsRef<str> + Into<String>) -> RoomMessageEventContent {
-    RoomMessageEventContent::notice_markdown(text)
+    with_mentions(RoomMessageEventContent::notice_markdown(text))
 }
 
 pub fn html_notice(text: impl Into<String> + Clone) -> RoomMessageEventContent {
-    RoomMessageEventContent::notice_html(text.clone(), text)
+    with_mentions(RoomMessageEventContent::notice_html(text.clone(), text))
 }
 
 pub fn html_message(html: impl Into<String> + Clone) -> RoomMessageEventContent {
-    RoomMessageEventContent::text_html(html.clone(), html)
+    with_mentions(RoomMessageEventContent::text_html(html.clone(), html))
 }
 
-pub fn error_message(text: impl AsRef<str>) -> RoomMessageEventContent {
-    message(format!("❌ Error: {}", text.as_ref()))
+pub fn error_message(text: impl fmt::Display) -> RoomMessageEventContent {
+    message(format!("❌ {}: {text}", Message::Error))
+}
+
+pub async fn file_message(
+    room: &Room,
+    filename: &str,
+    mime: &mime_guess::Mime,
+    data: Vec<u8>,
+) -> anyhow::Result<RoomMessageEventContent> {
+    let response = room.client().media().upload(mime, data, None).await?;
+    let file = FileMessageEventContent::plain(filename.into(), response.content_uri);
+    Ok(with_mentions(RoomMessageEventContent::new(
+        MessageType::File(file),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::ruma::OwnedUserId;
+
+    use super::*;
+
+    #[test]
+    fn mentions() {
+        let users = (0..50)
+            .map(|i| OwnedUserId::try_from(format!("@user{i}:example.com")).unwrap())
+            .collect::<Vec<_>>();
+        let mut mentions = Mentions::new(10);
+        let html = users
+            .iter()
+            .map(|u| mentions.mention(u))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        assert_eq!(html.matches("https://matrix.to/").count(), 10);
+        assert!(html.ends_with("<code>@user49:example.com</code>"));
+        assert_eq!(
+            mentions.note().as_deref(),
+            Some("and 40 others not mentioned")
+        );
+        assert_eq!(Mentions::new(10).note(), None);
+    }
+
+    #[test]
+    fn intentional_mentions() {
+        let alice = OwnedUserId::try_from("@alice:example.com").unwrap();
+        let bob = OwnedUserId::try_from("@bob:example.com").unwrap();
+        let html = format!(
+            "1. {} 2. {} 3. {}",
+            alice.matrix_to_uri(),
+            Mentions::plain(&bob),
+            Mentions::plain(&alice),
+        );
+
+        // only linked users are mentioned, ids of others are left out of the plain body
+        let content = html_message(&html);
+        assert_eq!(
+            content.mentions.unwrap().user_ids,
+            BTreeSet::from([alice.clone()])
+        );
+        let MessageType::Text(text) = &content.msgtype else {
+            panic!("not a text message");
+        };
+        assert!(!text.body.contains("@bob:example.com"));
+        assert!(text.body.contains("<code>@alice:example.com</code>"));
+        assert_eq!(text.formatted.as_ref().unwrap().body, html);
+
+        // messages without mentions explicitly mention nobody
+        let content = notice(format!("{} is not mentioned", Mentions::plain(&bob)));
+        assert!(content.mentions.as_ref().unwrap().user_ids.is_empty());
+        assert!(!content.body().contains("@bob:example.com"));
+    }
 }
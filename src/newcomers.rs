@@ -0,0 +1,208 @@
+//! Members who recently joined a private leaderboard, shown with a badge in the next renderings of
+//! its leaderboards.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::{
+    aoc::models::{AocId, PrivateLeaderboard},
+    config::NewcomersConfig,
+    utils::{datetime::now, serde::timestamp, store::Store},
+};
+
+const NEWCOMERS_STORE_KEY: &[u8] = b"newcomers";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Board {
+    known: BTreeSet<AocId>,
+    /// The members who joined after the first snapshot and still have a badge.
+    newcomers: BTreeMap<AocId, Newcomer>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Newcomer {
+    #[serde(with = "timestamp")]
+    joined: DateTime<Utc>,
+    renders: u32,
+}
+
+impl Newcomer {
+    /// Whether the badge is still shown, which is for the given number of sent leaderboards or
+    /// until the window has passed.
+    fn badged(&self, config: &NewcomersConfig, now: DateTime<Utc>) -> bool {
+        self.renders < config.renders && now - self.joined < TimeDelta::hours(config.hours as i64)
+    }
+}
+
+impl Board {
+    fn new(members: impl IntoIterator<Item = AocId>) -> Self {
+        Self {
+            known: members.into_iter().collect(),
+            newcomers: BTreeMap::new(),
+        }
+    }
+
+    fn observe(
+        &mut self,
+        members: impl IntoIterator<Item = AocId>,
+        now: DateTime<Utc>,
+    ) -> Vec<AocId> {
+        let joined = members
+            .into_iter()
+            .filter(|&id| self.known.insert(id))
+            .collect::<Vec<_>>();
+        for &id in &joined {
+            self.newcomers.insert(
+                id,
+                Newcomer {
+                    joined: now,
+                    renders: 0,
+                },
+            );
+        }
+        joined
+    }
+
+    fn badged(&self, config: &NewcomersConfig, now: DateTime<Utc>) -> Vec<AocId> {
+        self.newcomers
+            .iter()
+            .filter(|(_, n)| n.badged(config, now))
+            .map(|(&id, _)| id)
+            .collect()
+    }
+
+    /// Count a sent leaderboard showing the badges of the given members and clear the badges which
+    /// are no longer shown. Returns whether anything changed.
+    fn shown(&mut self, ids: &[AocId], config: &NewcomersConfig, now: DateTime<Utc>) -> bool {
+        let mut changed = false;
+        for id in ids {
+            if let Some(newcomer) = self.newcomers.get_mut(id) {
+                newcomer.renders += 1;
+                changed = true;
+            }
+        }
+        let before = self.newcomers.len();
+        self.newcomers.retain(|_, n| n.badged(config, now));
+        changed || self.newcomers.len() != before
+    }
+}
+
+pub struct Newcomers {
+    store: Store,
+    boards: RwLock<HashMap<i32, Board>>,
+}
+
+impl Newcomers {
+    pub async fn load(store: Store) -> anyhow::Result<Self> {
+        let boards = store
+            .get::<HashMap<i32, Board>>(NEWCOMERS_STORE_KEY)
+            .await?
+            .unwrap_or_default();
+        Ok(Self {
+            store,
+            boards: RwLock::new(boards),
+        })
+    }
+
+    /// Compare the members of the given leaderboard to the ones seen before, returning the
+    /// members who joined since. Nobody is new on the first snapshot of a year.
+    pub async fn observe(
+        &self,
+        year: i32,
+        leaderboard: &PrivateLeaderboard,
+    ) -> anyhow::Result<Vec<AocId>> {
+        let mut guard = self.boards.write().await;
+        let members = leaderboard.members.values().map(|m| m.id);
+        let joined = match guard.get_mut(&year) {
+            Some(board) => board.observe(members, now()),
+            None => {
+                guard.insert(year, Board::new(members));
+                self.store.set(NEWCOMERS_STORE_KEY, &*guard).await?;
+                return Ok(Vec::new());
+            }
+        };
+        if !joined.is_empty() {
+            info!(year, ?joined, "new leaderboard members");
+            self.store.set(NEWCOMERS_STORE_KEY, &*guard).await?;
+        }
+        Ok(joined)
+    }
+
+    pub async fn badged(&self, year: i32, config: &NewcomersConfig) -> Vec<AocId> {
+        self.boards
+            .read()
+            .await
+            .get(&year)
+            .map(|board| board.badged(config, now()))
+            .unwrap_or_default()
+    }
+
+    /// Count a sent leaderboard of the given year towards clearing the badges of the given
+    /// members. Renderings which are not sent, e.g. skipped posts, do not count.
+    pub async fn shown(
+        &self,
+        year: i32,
+        ids: &[AocId],
+        config: &NewcomersConfig,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.boards.write().await;
+        let Some(board) = guard.get_mut(&year) else {
+            return Ok(());
+        };
+        if board.shown(ids, config, now()) {
+            self.store.set(NEWCOMERS_STORE_KEY, &*guard).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newcomers() {
+        let config = NewcomersConfig {
+            badge: "🆕".into(),
+            renders: 2,
+            hours: 24,
+        };
+        let start = DateTime::UNIX_EPOCH;
+
+        // the members of the first snapshot are not new, members joining later are
+        let mut board = Board::new([1, 2]);
+        assert_eq!(board.observe([1, 2, 3], start), [3]);
+        assert_eq!(board.observe([1, 2, 3], start), [] as [AocId; 0]);
+        // members leaving and rejoining are not new
+        assert_eq!(board.observe([1, 3], start), [] as [AocId; 0]);
+        assert_eq!(board.observe([1, 2, 3], start), [] as [AocId; 0]);
+
+        // the badge is cleared after the configured number of sent leaderboards, renderings
+        // which are not sent do not count
+        assert_eq!(board.badged(&config, start), [3]);
+        assert_eq!(board.badged(&config, start), [3]);
+        assert!(board.shown(&[3], &config, start));
+        assert_eq!(board.observe([1, 2, 3, 4], start), [4]);
+        assert_eq!(board.badged(&config, start), [3, 4]);
+        assert!(board.shown(&[3, 4], &config, start));
+        assert_eq!(board.badged(&config, start), [4]);
+        assert!(board.shown(&[4], &config, start));
+        assert_eq!(board.badged(&config, start), [] as [AocId; 0]);
+        // nothing to store without badges
+        assert!(!board.shown(&[], &config, start));
+
+        // or once the window has passed
+        assert_eq!(board.observe([5], start), [5]);
+        assert_eq!(board.badged(&config, start + TimeDelta::hours(23)), [5]);
+        assert_eq!(
+            board.badged(&config, start + TimeDelta::hours(24)),
+            [] as [AocId; 0]
+        );
+        assert!(board.shown(&[], &config, start + TimeDelta::hours(24)));
+        assert!(board.newcomers.is_empty());
+    }
+}
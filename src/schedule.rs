@@ -0,0 +1,395 @@
+//! Leaderboard posts that are scheduled at runtime using the `schedule` command, or configured
+//! per room.
+
+use std::collections::HashMap;
+
+use chrono::{
+    DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc, Weekday,
+};
+use matrix_sdk::ruma::{OwnedEventId, OwnedRoomId, RoomId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, RwLock};
+
+use crate::{
+    aoc::{
+        day::AocDay,
+        models::{AocId, PrivateLeaderboard},
+    },
+    config::MatrixConfig,
+    utils::store::Store,
+};
+
+const SCHEDULES_STORE_KEY: &[u8] = b"schedules";
+const LAST_POSTS_STORE_KEY: &[u8] = b"schedule_last_posts";
+const PINNED_POSTS_STORE_KEY: &[u8] = b"schedule_pinned_posts";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: u32,
+    pub room_id: OwnedRoomId,
+    pub time: NaiveTime,
+    #[serde(default)]
+    pub kind: PostKind,
+    /// Whether the schedule is configured instead of added using the `schedule` command. The ids
+    /// of configured schedules are counted separately and they cannot be removed.
+    #[serde(skip)]
+    pub configured: bool,
+}
+
+impl Schedule {
+    pub fn configured(config: &MatrixConfig) -> Vec<Self> {
+        config
+            .rooms
+            .iter()
+            .filter_map(|room| Some((room.id.clone(), room.post.time?)))
+            .zip(1..)
+            .map(|((room_id, time), id)| Self {
+                id,
+                room_id,
+                time,
+                kind: PostKind::Daily,
+                configured: true,
+            })
+            .collect()
+    }
+
+    pub fn label(&self) -> String {
+        if self.configured {
+            format!("config #{}", self.id)
+        } else {
+            format!("#{}", self.id)
+        }
+    }
+
+    /// The first trigger of this schedule strictly after `after`.
+    pub fn next_trigger(&self, timezone: FixedOffset, after: DateTime<Utc>) -> DateTime<Utc> {
+        let local = timezone.from_utc_datetime(&after.naive_utc());
+        let mut date = local.date_naive();
+        loop {
+            let trigger = timezone
+                .from_local_datetime(&date.and_time(self.time))
+                .unwrap()
+                .to_utc();
+            if trigger > after && self.kind.posts_on(date) {
+                return trigger;
+            }
+            date += TimeDelta::days(1);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PostKind {
+    /// The daily leaderboard of the most recent day, posted every day of the event.
+    #[default]
+    Daily,
+    /// A rollup of the past week, posted on the given weekday during the event.
+    Weekly(Weekday),
+    /// A rollup of the whole event, posted once on the day after it ended.
+    Final,
+}
+
+impl PostKind {
+    /// Whether posts of this kind are due on the given local date, ignoring whether the event is
+    /// running.
+    fn posts_on(self, date: NaiveDate) -> bool {
+        match self {
+            Self::Daily => true,
+            Self::Weekly(weekday) => date.weekday() == weekday,
+            Self::Final => date.month() == 12 && date.day() == 26,
+        }
+    }
+
+    pub fn during_event(self) -> bool {
+        !matches!(self, Self::Final)
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Self::Daily => "daily".into(),
+            Self::Weekly(weekday) => format!("weekly rollup on {weekday}"),
+            Self::Final => "final rollup after the event".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostSnapshot {
+    pub day: AocDay,
+    /// Stars and local score of each member, ordered by id.
+    pub standings: Vec<(AocId, u32, u32)>,
+}
+
+impl PostSnapshot {
+    pub fn new(day: AocDay, leaderboard: &PrivateLeaderboard) -> Self {
+        let mut standings = leaderboard
+            .members
+            .values()
+            .map(|m| (m.id, m.stars, m.local_score))
+            .collect::<Vec<_>>();
+        standings.sort_unstable();
+        Self { day, standings }
+    }
+}
+
+/// Whether a scheduled post should be sent. With `dedupe`, posts showing the same standings as the
+/// last post of the room are skipped, unless the post is due at an always post time.
+pub fn should_post(
+    dedupe: bool,
+    always_post: bool,
+    last: Option<&PostSnapshot>,
+    current: &PostSnapshot,
+) -> bool {
+    !dedupe || always_post || last != Some(current)
+}
+
+pub fn parse_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value, "%H:%M").ok()
+}
+
+pub struct Schedules {
+    store: Store,
+    configured: Vec<Schedule>,
+    entries: RwLock<Vec<Schedule>>,
+    last_posts: RwLock<HashMap<OwnedRoomId, PostSnapshot>>,
+    pinned_posts: RwLock<HashMap<OwnedRoomId, OwnedEventId>>,
+    changed: Notify,
+}
+
+impl Schedules {
+    pub async fn load(store: Store, configured: Vec<Schedule>) -> anyhow::Result<Self> {
+        let entries = store
+            .get::<Vec<Schedule>>(SCHEDULES_STORE_KEY)
+            .await?
+            .unwrap_or_default();
+        let last_posts = store
+            .get::<HashMap<OwnedRoomId, PostSnapshot>>(LAST_POSTS_STORE_KEY)
+            .await?
+            .unwrap_or_default();
+        let pinned_posts = store
+            .get::<HashMap<OwnedRoomId, OwnedEventId>>(PINNED_POSTS_STORE_KEY)
+            .await?
+            .unwrap_or_default();
+        Ok(Self {
+            store,
+            configured,
+            entries: RwLock::new(entries),
+            last_posts: RwLock::new(last_posts),
+            pinned_posts: RwLock::new(pinned_posts),
+            changed: Notify::new(),
+        })
+    }
+
+    pub async fn last_post(&self, room_id: &RoomId) -> Option<PostSnapshot> {
+        self.last_posts.read().await.get(room_id).cloned()
+    }
+
+    pub async fn set_last_post(
+        &self,
+        room_id: &RoomId,
+        snapshot: PostSnapshot,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.last_posts.write().await;
+        guard.insert(room_id.to_owned(), snapshot);
+        self.store.set(LAST_POSTS_STORE_KEY, &*guard).await
+    }
+
+    pub async fn pinned_post(&self, room_id: &RoomId) -> Option<OwnedEventId> {
+        self.pinned_posts.read().await.get(room_id).cloned()
+    }
+
+    pub async fn set_pinned_post(
+        &self,
+        room_id: &RoomId,
+        event_id: OwnedEventId,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.pinned_posts.write().await;
+        guard.insert(room_id.to_owned(), event_id);
+        self.store.set(PINNED_POSTS_STORE_KEY, &*guard).await
+    }
+
+    pub async fn all(&self) -> Vec<Schedule> {
+        let entries = self.entries.read().await;
+        self.configured.iter().chain(&*entries).cloned().collect()
+    }
+
+    pub async fn by_room(&self, room_id: &RoomId) -> Vec<Schedule> {
+        let entries = self.entries.read().await;
+        self.configured
+            .iter()
+            .chain(&*entries)
+            .filter(|s| s.room_id == room_id)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn add(
+        &self,
+        room_id: OwnedRoomId,
+        time: NaiveTime,
+        kind: PostKind,
+    ) -> anyhow::Result<Schedule> {
+        let mut guard = self.entries.write().await;
+        let id = guard.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+        let schedule = Schedule {
+            id,
+            room_id,
+            time,
+            kind,
+            configured: false,
+        };
+        guard.push(schedule.clone());
+        self.store.set(SCHEDULES_STORE_KEY, &*guard).await?;
+        self.changed.notify_one();
+        Ok(schedule)
+    }
+
+    /// Remove a schedule of the given room. Returns `false` if there is no such schedule, which
+    /// includes the configured schedules.
+    pub async fn remove(&self, room_id: &RoomId, id: u32) -> anyhow::Result<bool> {
+        let mut guard = self.entries.write().await;
+        let len = guard.len();
+        guard.retain(|s| !(s.id == id && s.room_id == room_id));
+        if guard.len() == len {
+            return Ok(false);
+        }
+        self.store.set(SCHEDULES_STORE_KEY, &*guard).await?;
+        self.changed.notify_one();
+        Ok(true)
+    }
+
+    pub async fn changed(&self) {
+        self.changed.notified().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::load_with_overrides;
+
+    #[test]
+    fn next_trigger() {
+        let schedule = Schedule {
+            id: 1,
+            room_id: "!xoXcjSEJPUfQmzETtS:matrix.example.com".try_into().unwrap(),
+            time: parse_time("07:30").unwrap(),
+            kind: PostKind::Daily,
+            configured: false,
+        };
+        let cet = FixedOffset::east_opt(3600).unwrap();
+
+        for (after, expected) in [
+            ("2024-12-01T05:00:00Z", "2024-12-01T06:30:00Z"),
+            ("2024-12-01T06:30:00Z", "2024-12-02T06:30:00Z"),
+            ("2024-12-01T23:30:00Z", "2024-12-02T06:30:00Z"),
+        ] {
+            let after = after.parse().unwrap();
+            let expected = expected.parse::<DateTime<Utc>>().unwrap();
+            assert_eq!(schedule.next_trigger(cet, after), expected);
+        }
+
+        // 2024-12-01 is a sunday
+        let weekly = Schedule {
+            kind: PostKind::Weekly(Weekday::Sun),
+            ..schedule.clone()
+        };
+        let final_rollup = Schedule {
+            kind: PostKind::Final,
+            ..schedule
+        };
+        for (schedule, after, expected) in [
+            (&weekly, "2024-12-01T05:00:00Z", "2024-12-01T06:30:00Z"),
+            (&weekly, "2024-12-01T06:30:00Z", "2024-12-08T06:30:00Z"),
+            (
+                &final_rollup,
+                "2024-12-01T05:00:00Z",
+                "2024-12-26T06:30:00Z",
+            ),
+            (
+                &final_rollup,
+                "2024-12-26T06:30:00Z",
+                "2025-12-26T06:30:00Z",
+            ),
+        ] {
+            let after = after.parse().unwrap();
+            let expected = expected.parse::<DateTime<Utc>>().unwrap();
+            assert_eq!(schedule.next_trigger(cet, after), expected);
+        }
+    }
+
+    #[test]
+    fn configured() {
+        let config = load_with_overrides([
+            "matrix.rooms = [{ id = \"!a:example.com\", post = { time = \"07:00\", rows = 5 } }, \
+             { id = \"!b:example.com\" }, { id = \"!c:example.com\", post = { time = \"21:30\" } }]",
+        ]);
+        let schedules = Schedule::configured(&config.matrix);
+        let summary = schedules
+            .iter()
+            .map(|s| (s.label(), s.room_id.as_str(), s.time, s.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "config #1".into(),
+                    "!a:example.com",
+                    parse_time("07:00").unwrap(),
+                    PostKind::Daily
+                ),
+                (
+                    "config #2".into(),
+                    "!c:example.com",
+                    parse_time("21:30").unwrap(),
+                    PostKind::Daily
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_post() {
+        let snapshot = |day, stars| PostSnapshot {
+            day: AocDay { year: 2024, day },
+            standings: vec![(1, stars, 10)],
+        };
+        let last = snapshot(3, 5);
+
+        // nothing changed
+        assert!(!super::should_post(
+            true,
+            false,
+            Some(&last),
+            &snapshot(3, 5)
+        ));
+        assert!(super::should_post(
+            false,
+            false,
+            Some(&last),
+            &snapshot(3, 5)
+        ));
+        assert!(super::should_post(true, true, Some(&last), &snapshot(3, 5)));
+        // new stars or a new day
+        assert!(super::should_post(
+            true,
+            false,
+            Some(&last),
+            &snapshot(3, 6)
+        ));
+        assert!(super::should_post(
+            true,
+            false,
+            Some(&last),
+            &snapshot(4, 5)
+        ));
+        assert!(super::should_post(true, false, None, &snapshot(3, 5)));
+    }
+
+    #[test]
+    fn time() {
+        assert_eq!(parse_time("07:30"), NaiveTime::from_hms_opt(7, 30, 0));
+        assert_eq!(parse_time("24:00"), None);
+        assert_eq!(parse_time("7pm"), None);
+    }
+}
@@ -0,0 +1,92 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::TimeZone;
+use tracing::{error, info, trace};
+
+use crate::{
+    aoc::day::AocDay,
+    digest::{self, Subscription},
+    matrix::utils::message,
+    utils::datetime::now,
+    Context,
+};
+
+pub async fn start(context: Arc<Context>) -> ! {
+    loop {
+        if let Err(err) = trigger(&context).await {
+            error!("Failed to send digests: {err}");
+        }
+        tokio::time::sleep(Duration::from_secs(context.config.digest.interval)).await;
+    }
+}
+
+async fn trigger(context: &Context) -> anyhow::Result<()> {
+    if !digest::is_december() {
+        return Ok(());
+    }
+    let today = AocDay::most_recent();
+
+    let subscriptions = context.digests.all().await;
+    if subscriptions.iter().all(|s| s.last_sent == Some(today)) {
+        return Ok(());
+    }
+
+    trace!(?today, "checking for digests to send");
+    let (leaderboard, _) = context
+        .aoc_client
+        .get_private_leaderboard(today.year)
+        .await?;
+
+    for Subscription { user_id, last_sent } in subscriptions {
+        let users = context.users.read().await;
+        let Some(user) = users.by_matrix.get(&user_id) else {
+            continue;
+        };
+        let Some(aoc_id) = user.aoc else {
+            continue;
+        };
+        // days which are not finished yet are sent once they are, unless a later day is sent
+        // before that
+        let digests = digest::owed_days(last_sent, today)
+            .filter_map(|day| Some((day, digest::render(&leaderboard, aoc_id, day)?)))
+            .collect::<Vec<_>>();
+        if digests.is_empty() {
+            continue;
+        }
+
+        let timezone = user.timezone.unwrap_or(context.config.local_timezone);
+        drop(users);
+        let local_time = timezone.from_utc_datetime(&now().naive_utc()).time();
+        if context.config.digest.quiet_hours.contains(local_time) {
+            trace!(%user_id, "delaying digest because of quiet hours");
+            continue;
+        }
+
+        let client = context.room.client();
+        let room = match client.get_dm_room(&user_id) {
+            Some(room) => room,
+            None => {
+                info!(%user_id, "creating dm room for digests");
+                match client.create_dm(&user_id).await {
+                    Ok(room) => room,
+                    Err(err) => {
+                        error!(%user_id, "Failed to create dm room for digests: {err}");
+                        continue;
+                    }
+                }
+            }
+        };
+        for (day, digest) in digests {
+            if let Err(err) = room.send(message(digest)).await {
+                error!(%user_id, ?day, "Failed to send digest: {err}");
+                break;
+            }
+            if let Err(err) = context.digests.mark_sent(&user_id, day).await {
+                error!(%user_id, ?day, "Failed to remember sent digest: {err}");
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -15,6 +15,10 @@ use tracing::{error, trace, warn};
 
 use crate::{
     mastodon::{self, AttachmentType},
+    matrix::{
+        quiet::{self, Proactive},
+        utils::with_mentions,
+    },
     utils::datetime::now,
     Context,
 };
@@ -50,6 +54,13 @@ async fn trigger(context: &Context, last_id: &mut mastodon::Id) -> anyhow::Resul
         room.join().await?;
     }
 
+    // deferred posts are sent once the quiet hours end, as the last id is kept
+    let send = match quiet::check(&context.config, room.room_id()) {
+        Proactive::Allowed => true,
+        Proactive::Defer(_) => return Ok(()),
+        Proactive::Drop => false,
+    };
+
     trace!("checking for new garygrady posts");
 
     let now = now();
@@ -71,7 +82,7 @@ async fn trigger(context: &Context, last_id: &mut mastodon::Id) -> anyhow::Resul
         for post in posts.into_iter().rev() {
             *last_id = post.id.max(*last_id);
 
-            if !CONTENT_REGEX.is_match(&post.content) || post.created_at < not_before {
+            if !send || !CONTENT_REGEX.is_match(&post.content) || post.created_at < not_before {
                 continue;
             }
 
@@ -118,8 +129,8 @@ async fn trigger(context: &Context, last_id: &mut mastodon::Id) -> anyhow::Resul
                 image_message.filename = Some(filename);
                 image_message.formatted = Some(FormattedBody::html(caption));
 
-                room.send(RoomMessageEventContent::new(MessageType::Image(
-                    image_message,
+                room.send(with_mentions(RoomMessageEventContent::new(
+                    MessageType::Image(image_message),
                 )))
                 .await?;
             }
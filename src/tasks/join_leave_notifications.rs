@@ -8,7 +8,10 @@ use crate::{
         day::AocDay,
         models::{PrivateLeaderboard, PrivateLeaderboardMembers},
     },
-    matrix::utils::notice,
+    matrix::{
+        quiet::{self, Proactive},
+        utils::notice,
+    },
     Context,
 };
 
@@ -40,20 +43,30 @@ async fn trigger(
 
     let year = AocDay::most_recent().year;
 
+    // deferred notifications are sent once the quiet hours end, as the cached leaderboard is kept
+    let send = match quiet::check(&context.config, room.room_id()) {
+        Proactive::Allowed => true,
+        Proactive::Defer(_) => return Ok(()),
+        Proactive::Drop => false,
+    };
+
     trace!("checking for member leave/join events");
 
     let new_leaderboard = context.aoc_client.get_private_leaderboard(year).await?.0;
-
-    send_notifications(
-        room,
-        context,
-        leaderboard
-            .as_ref()
-            .map(|l| &l.members)
-            .unwrap_or(&Default::default()),
-        &new_leaderboard.members,
-    )
-    .await?;
+    context.newcomers.observe(year, &new_leaderboard).await?;
+
+    if send {
+        send_notifications(
+            room,
+            context,
+            leaderboard
+                .as_ref()
+                .map(|l| &l.members)
+                .unwrap_or(&Default::default()),
+            &new_leaderboard.members,
+        )
+        .await?;
+    }
 
     *leaderboard = Some(new_leaderboard);
 
@@ -93,9 +106,9 @@ async fn send_notifications(
         "sending leaderboard join/leave notifications"
     );
 
+    let users = context.users.read().await;
     for (member, joined) in notifications {
-        let matrix = context
-            .users
+        let matrix = users
             .by_aoc
             .get(&member.id)
             .and_then(|m| m.matrix.as_deref());
@@ -2,14 +2,20 @@ use std::sync::Arc;
 
 use crate::context::Context;
 
+mod digests;
 mod garygrady_posts;
 mod join_leave_notifications;
+mod scheduled_posts;
 mod solve_notifications;
 mod unlock_announcements;
+mod watchdog;
 
 pub fn start(context: Arc<Context>) {
     tokio::spawn(unlock_announcements::start(Arc::clone(&context)));
     tokio::spawn(solve_notifications::start(Arc::clone(&context)));
     tokio::spawn(join_leave_notifications::start(Arc::clone(&context)));
     tokio::spawn(garygrady_posts::start(Arc::clone(&context)));
+    tokio::spawn(scheduled_posts::start(Arc::clone(&context)));
+    tokio::spawn(digests::start(Arc::clone(&context)));
+    tokio::spawn(watchdog::start(Arc::clone(&context)));
 }
@@ -0,0 +1,318 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use matrix_sdk::{ruma::RoomId, RoomState};
+use tracing::{error, info, warn};
+
+use crate::{
+    aoc::day::AocDay,
+    audit::{AuditEntry, Outcome},
+    config::Config,
+    context::Context,
+    i18n,
+    matrix::{
+        commands::{
+            aoc::{
+                day,
+                rollup::{self, Period},
+            },
+            args::{Format, Rows},
+        },
+        pins,
+        quiet::{self, Proactive},
+        utils::{html_message, notice, RoomExt},
+    },
+    schedule::{should_post, PostKind, PostSnapshot, Schedule},
+    utils::datetime::{now, sleep_until},
+};
+
+pub async fn start(context: Arc<Context>) -> ! {
+    let timezone = context.config.local_timezone;
+    loop {
+        let schedules = context.schedules.all().await;
+        let now = now();
+        let Some(next) = schedules
+            .iter()
+            .map(|s| s.next_trigger(timezone, now))
+            .min()
+        else {
+            context.schedules.changed().await;
+            continue;
+        };
+
+        info!(?next, "waiting until next scheduled post");
+        tokio::select! {
+            _ = sleep_until(next) => {}
+            _ = context.schedules.changed() => continue,
+        }
+
+        // leaderboards and weekly rollups are only posted while the event is running
+        let running = AocDay::current().is_some();
+        let due = schedules
+            .iter()
+            .filter(|s| s.next_trigger(timezone, now) == next)
+            .filter(|s| running || !s.kind.during_event())
+            .collect::<Vec<_>>();
+        if due.is_empty() {
+            continue;
+        }
+        broadcast(&context, &due).await;
+    }
+}
+
+/// Send the given scheduled posts one after another, so that the homeserver's rate limit is not
+/// exceeded, and report the result to the admin room.
+async fn broadcast(context: &Arc<Context>, schedules: &[&Schedule]) {
+    let delay = Duration::from_secs(context.config.matrix.broadcast_delay);
+    let (mut failed, mut skipped, mut deferred) = (0, 0, 0);
+    let mut posted = false;
+    for schedule in schedules {
+        match quiet::check(&context.config, &schedule.room_id) {
+            Proactive::Allowed => {}
+            Proactive::Defer(until) => {
+                info!(
+                    ?until,
+                    "Deferred scheduled post {} because of quiet hours",
+                    schedule.label()
+                );
+                defer(context, (*schedule).clone(), until);
+                deferred += 1;
+                continue;
+            }
+            Proactive::Drop => {
+                info!(
+                    "Dropped scheduled post {} because of quiet hours",
+                    schedule.label()
+                );
+                skipped += 1;
+                continue;
+            }
+        }
+
+        if posted {
+            tokio::time::sleep(delay).await;
+        }
+
+        let locale = context.config.matrix.locale(&schedule.room_id);
+        let result = i18n::with_locale(locale, trigger(context, schedule)).await;
+        posted = result.as_ref().is_ok_and(|&posted| posted);
+        match &result {
+            Ok(true) => {}
+            Ok(false) => {
+                info!(
+                    "Skipped scheduled post {}, nothing changed",
+                    schedule.label()
+                );
+                skipped += 1;
+            }
+            Err(err) => {
+                error!(
+                    "Failed to send scheduled post {}: {err:#}",
+                    schedule.label()
+                );
+                failed += 1;
+            }
+        }
+        if let Err(err) = record(context, schedule, result.is_ok()).await {
+            error!("Failed to record audit log entry: {err}");
+        }
+    }
+
+    let succeeded = schedules.len() - failed - skipped - deferred;
+    if succeeded + failed + skipped == 0 {
+        // all posts were deferred, they are reported once they are sent
+        return;
+    }
+    info!(succeeded, skipped, deferred, failed, "sent scheduled posts");
+    if let Err(err) = report(context, succeeded, skipped, deferred, failed).await {
+        error!("Failed to report scheduled posts: {err:#}");
+    }
+}
+
+/// Send a scheduled post once the quiet hours of its room end.
+fn defer(context: &Arc<Context>, schedule: Schedule, until: DateTime<Utc>) {
+    let context = Arc::clone(context);
+    tokio::spawn(async move {
+        sleep_until(until).await;
+        // the schedule may have been removed in the meantime
+        if context.schedules.all().await.contains(&schedule) {
+            broadcast(&context, &[&schedule]).await;
+        }
+    });
+}
+
+async fn report(
+    context: &Context,
+    succeeded: usize,
+    skipped: usize,
+    deferred: usize,
+    failed: usize,
+) -> anyhow::Result<()> {
+    let Some(room_id) = &context.config.matrix.admin_room_id else {
+        return Ok(());
+    };
+    let room = context
+        .room
+        .client()
+        .get_room(room_id)
+        .filter(|room| room.state() == RoomState::Joined)
+        .ok_or_else(|| anyhow!("not a member of admin room {room_id}"))?;
+    let status = if failed == 0 { "✅️" } else { "⚠️" };
+    let skipped = match skipped {
+        0 => String::new(),
+        n => format!(", {n} skipped"),
+    };
+    let deferred = match deferred {
+        0 => String::new(),
+        n => format!(", {n} deferred"),
+    };
+    room.send_throttled(notice(format!(
+        "{status} Scheduled posts: {succeeded} succeeded{skipped}{deferred}, {failed} failed"
+    )))
+    .await?;
+    Ok(())
+}
+
+async fn record(context: &Context, schedule: &Schedule, success: bool) -> anyhow::Result<()> {
+    if !context.config.matrix.audit.bot_actions {
+        return Ok(());
+    }
+    let Some(user_id) = context.room.client().user_id().map(ToOwned::to_owned) else {
+        return Ok(());
+    };
+    let entry = AuditEntry {
+        ts: now(),
+        room_id: schedule.room_id.clone(),
+        sender: user_id,
+        command: "scheduled-post".into(),
+        params: vec![schedule.label()],
+        outcome: if success {
+            Outcome::Success
+        } else {
+            Outcome::Error
+        },
+    };
+    context.audit_log.record(entry).await
+}
+
+/// Send a scheduled post. Returns `false` if it was skipped because nothing changed since the last
+/// post in the room.
+async fn trigger(context: &Context, schedule: &Schedule) -> anyhow::Result<bool> {
+    let room = context
+        .room
+        .client()
+        .get_room(&schedule.room_id)
+        .filter(|room| room.state() == RoomState::Joined)
+        .ok_or_else(|| anyhow!("not a member of scheduled room {}", schedule.room_id))?;
+
+    let most_recent = AocDay::most_recent();
+    let year = most_recent.year;
+    let (leaderboard, last_update) = context.aoc_client.get_private_leaderboard(year).await?;
+
+    // rollups summarize a whole period, so they are neither deduplicated nor pinned
+    let period = match schedule.kind {
+        PostKind::Daily => None,
+        PostKind::Weekly(_) => Some(Period::week(most_recent)),
+        PostKind::Final => Some(Period::event(year)),
+    };
+    if let Some(period) = period {
+        let html = rollup::render(
+            &context.config,
+            &leaderboard,
+            &period,
+            context.config.aoc.rollup_rows,
+            last_update,
+        );
+        room.send_throttled(html_message(html)).await?;
+        return Ok(true);
+    }
+
+    let snapshot = PostSnapshot::new(most_recent, &leaderboard);
+    let last = context.schedules.last_post(&schedule.room_id).await;
+    let dedupe = context
+        .config
+        .matrix
+        .dedupe_posts_enabled(&schedule.room_id);
+    let always_post = context.config.matrix.always_post == Some(schedule.time);
+    if !should_post(dedupe, always_post, last.as_ref(), &snapshot) {
+        return Ok(false);
+    }
+
+    let params = post_params(&context.config, &schedule.room_id, most_recent);
+    let (mut rendered, _) = day::render(context, params, None).await?;
+    let newcomers = std::mem::take(&mut rendered.newcomers);
+    let response = room.send_throttled(rendered.message(Format::Table)).await?;
+    context
+        .newcomers
+        .shown(params.year, &newcomers, &context.config.aoc.newcomers)
+        .await?;
+    context
+        .schedules
+        .set_last_post(&schedule.room_id, snapshot)
+        .await?;
+
+    if context.config.matrix.pin_posts_enabled(&schedule.room_id) {
+        let previous = context.schedules.pinned_post(&schedule.room_id).await;
+        // failing to pin (e.g. without permission) does not fail the post
+        match pins::pin(&room, &response.event_id, previous.as_deref()).await {
+            Ok(()) => {
+                context
+                    .schedules
+                    .set_pinned_post(&schedule.room_id, response.event_id)
+                    .await?;
+            }
+            Err(err) => warn!(
+                room_id = %schedule.room_id,
+                "Failed to pin scheduled post {}: {err:#}",
+                schedule.label()
+            ),
+        }
+    }
+
+    Ok(true)
+}
+
+fn post_params(config: &Config, room_id: &RoomId, day: AocDay) -> day::Params {
+    let post = config.matrix.post(room_id);
+    day::Params {
+        day: day.day,
+        year: day.year,
+        parts: post
+            .and_then(|p| p.parts)
+            .unwrap_or_else(|| config.default_parts(room_id)),
+        part2_start: config.aoc.part2_start,
+        rows: Rows::Count(
+            post.and_then(|p| p.rows)
+                .unwrap_or(config.aoc.leaderboard_rows),
+        ),
+        offset: post.and_then(|p| p.offset).unwrap_or(0),
+        summary: config.aoc.day_summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{aoc::client::Parts, config::tests::load_with_overrides};
+
+    #[test]
+    fn post_params() {
+        let config = load_with_overrides([
+            "aoc.leaderboard_rows = 20",
+            r#"aoc.default_parts = "2""#,
+            "matrix.rooms = [{ id = \"!a:example.com\", post = { rows = 5, offset = 10 } }, \
+             { id = \"!b:example.com\", default_parts = \"1\", post = { parts = \"both\" } }]",
+        ]);
+        let day = AocDay { year: 2024, day: 3 };
+        let params = |room_id: &str| {
+            let params = super::post_params(&config, room_id.try_into().unwrap(), day);
+            (params.rows, params.parts, params.offset)
+        };
+
+        assert_eq!(params("!a:example.com"), (Rows::Count(5), Parts::P2, 10));
+        // the parts of the post take precedence over the default parts of the room
+        assert_eq!(params("!b:example.com"), (Rows::Count(20), Parts::Both, 0));
+        assert_eq!(params("!c:example.com"), (Rows::Count(20), Parts::P2, 0));
+    }
+}
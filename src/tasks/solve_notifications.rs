@@ -1,22 +1,42 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use chrono::{DateTime, TimeZone, Utc};
 use matrix_sdk::{Room, RoomState};
+use serde::{Deserialize, Serialize};
 use tracing::{error, trace, warn};
 
 use crate::{
     aoc::{
         day::AocDay,
-        models::{PrivateLeaderboard, PrivateLeaderboardMember, PrivateLeaderboardMembers},
+        models::{AocId, PrivateLeaderboard, PrivateLeaderboardMember, PrivateLeaderboardMembers},
+    },
+    context::ContextUsers,
+    matrix::{
+        quiet::{self, Proactive},
+        utils::html_notice,
     },
-    matrix::utils::html_notice,
     utils::{
         datetime::{now, DateTimeExt},
         fmt::{fmt_rank, fmt_timedelta},
+        serde::timestamp,
     },
     Context,
 };
 
+const SOLVE_NOTIFICATIONS_STORE_KEY: &[u8] = b"solve_notifications";
+
+type Watermarks = HashMap<i32, Watermark>;
+
+/// Position of a star in the order of announcements, which are sent at most once, even across
+/// restarts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct Watermark {
+    #[serde(with = "timestamp")]
+    ts: DateTime<Utc>,
+    member: AocId,
+    part2: bool,
+}
+
 pub async fn start(context: Arc<Context>) -> ! {
     let mut year = AocDay::most_recent().year;
     let mut leaderboard = context
@@ -25,11 +45,31 @@ pub async fn start(context: Arc<Context>) -> ! {
         .await
         .map(|(lb, _)| lb);
 
+    let interval = Duration::from_secs(context.config.solves.interval);
     loop {
         if let Err(err) = trigger(&context, &mut year, &mut leaderboard).await {
             error!("Failed to check for new puzzle solves: {err}");
         }
-        tokio::time::sleep(Duration::from_secs(10)).await;
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// The room receiving the announcements, the main room unless configured otherwise. Rooms the bot
+/// is not a member of are joined, including configured rooms it has never seen.
+async fn announcement_room(context: &Context) -> anyhow::Result<Room> {
+    let room_id = context
+        .config
+        .solves
+        .room_id
+        .as_deref()
+        .unwrap_or(context.room.room_id());
+    let client = context.room.client();
+    match client.get_room(room_id) {
+        Some(room) if room.state() == RoomState::Joined => Ok(room),
+        _ => {
+            warn!("not a member of target room {room_id}");
+            Ok(client.join_room_by_id(room_id).await?)
+        }
     }
 }
 
@@ -38,11 +78,7 @@ async fn trigger(
     year: &mut i32,
     leaderboard: &mut Option<PrivateLeaderboard>,
 ) -> anyhow::Result<()> {
-    let room = &context.room;
-    if room.state() != RoomState::Joined {
-        warn!("not a member of target room {}", room.room_id());
-        room.join().await?;
-    }
+    let room = &announcement_room(context).await?;
 
     let current_year = AocDay::most_recent().year;
     if current_year != *year {
@@ -50,6 +86,13 @@ async fn trigger(
         *year = current_year;
     }
 
+    // deferred notifications are sent once the quiet hours end, as the cached leaderboard is kept
+    let send = match quiet::check(&context.config, room.room_id()) {
+        Proactive::Allowed => true,
+        Proactive::Defer(_) => return Ok(()),
+        Proactive::Drop => false,
+    };
+
     trace!(year, "checking for new puzzle solves");
 
     let new_leaderboard = context.aoc_client.get_private_leaderboard(*year).await?.0;
@@ -63,6 +106,7 @@ async fn trigger(
             .map(|l| &l.members)
             .unwrap_or(&Default::default()),
         &new_leaderboard.members,
+        send,
     )
     .await?;
 
@@ -77,7 +121,42 @@ async fn send_notifications(
     year: i32,
     old_leaderboard: &PrivateLeaderboardMembers,
     new_leaderboard: &PrivateLeaderboardMembers,
+    send: bool,
 ) -> anyhow::Result<()> {
+    let store = &context.store;
+    let mut watermarks = store
+        .get::<Watermarks>(SOLVE_NOTIFICATIONS_STORE_KEY)
+        .await?
+        .unwrap_or_default();
+    let notifications = unsent(
+        notifications(year, old_leaderboard, new_leaderboard),
+        watermarks.get(&year).copied(),
+    );
+
+    trace!(?notifications, "sending puzzle solve notifications");
+    let users = context.users.read().await;
+    for notification in notifications {
+        // dropped notifications are marked as sent as well
+        if send {
+            room.send(html_notice(notification.to_string(context, &users)))
+                .await?;
+        }
+        watermarks.insert(year, notification.watermark());
+        store
+            .set(SOLVE_NOTIFICATIONS_STORE_KEY, &watermarks)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Notifications of the stars earned between the two leaderboards in the last 24 hours, in the
+/// order they were earned.
+fn notifications<'a>(
+    year: i32,
+    old_leaderboard: &PrivateLeaderboardMembers,
+    new_leaderboard: &'a PrivateLeaderboardMembers,
+) -> Vec<Notification<'a>> {
     let mut notifications = Vec::new();
     for (id, member) in new_leaderboard {
         let Some(old_member) = old_leaderboard.get(id) else {
@@ -132,15 +211,18 @@ async fn send_notifications(
 
     let now = now();
     notifications.retain(|n| now <= n.ts + Duration::from_secs(24 * 3600));
-    notifications.sort_unstable_by_key(|n| n.ts);
-
-    trace!(?notifications, "sending puzzle solve notifications");
-    for notification in notifications {
-        room.send(html_notice(notification.to_string(context)))
-            .await?;
-    }
+    notifications.sort_unstable_by_key(|n| n.watermark());
+    notifications
+}
 
-    Ok(())
+fn unsent(
+    notifications: Vec<Notification<'_>>,
+    watermark: Option<Watermark>,
+) -> Vec<Notification<'_>> {
+    notifications
+        .into_iter()
+        .filter(|n| watermark.is_none_or(|w| n.watermark() > w))
+        .collect()
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -153,7 +235,15 @@ struct Notification<'a> {
 }
 
 impl Notification<'_> {
-    fn to_string(self, context: &Context) -> String {
+    fn watermark(self) -> Watermark {
+        Watermark {
+            ts: self.ts,
+            member: self.member.id,
+            part2: self.part2,
+        }
+    }
+
+    fn to_string(self, context: &Context, users: &ContextUsers) -> String {
         let Self {
             member,
             part2,
@@ -162,8 +252,7 @@ impl Notification<'_> {
             rank,
         } = self;
 
-        let matrix = context
-            .users
+        let matrix = users
             .by_aoc
             .get(&member.id)
             .and_then(|m| m.matrix.as_deref());
@@ -205,3 +294,61 @@ impl Notification<'_> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aoc::fixtures::{leaderboard, member, YEAR},
+        utils::datetime::set_now,
+    };
+
+    #[test]
+    fn restart() {
+        let _guard =
+            set_now(AocDay { year: YEAR, day: 1 }.unlock_datetime() + Duration::from_secs(3600));
+        let ids = |notifications: &[Notification]| {
+            notifications
+                .iter()
+                .map(|n| (n.member.id, n.part2))
+                .collect::<Vec<_>>()
+        };
+
+        let cached = leaderboard([
+            member(1, "Alice", &[]),
+            member(2, "Bob", &[]),
+            member(3, "Carol", &[]),
+        ]);
+        let first = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200))]),
+            member(2, "Bob", &[(1, 150, None)]),
+            member(3, "Carol", &[]),
+        ]);
+        let pending = unsent(notifications(YEAR, &cached.members, &first.members), None);
+        assert_eq!(ids(&pending), [(1, false), (2, false), (1, true)]);
+
+        // only the first two notifications were sent before the restart, and the leaderboard
+        // cache is still older than that
+        let watermark = rmp_serde::from_slice::<Watermark>(
+            &rmp_serde::to_vec(&pending[1].watermark()).unwrap(),
+        )
+        .unwrap();
+        let second = leaderboard([
+            member(1, "Alice", &[(1, 100, Some(200))]),
+            member(2, "Bob", &[(1, 150, None)]),
+            member(3, "Carol", &[(1, 300, None)]),
+        ]);
+        let pending = unsent(
+            notifications(YEAR, &cached.members, &second.members),
+            Some(watermark),
+        );
+        assert_eq!(ids(&pending), [(1, true), (3, false)]);
+
+        let watermark = pending.last().unwrap().watermark();
+        let pending = unsent(
+            notifications(YEAR, &cached.members, &second.members),
+            Some(watermark),
+        );
+        assert!(pending.is_empty());
+    }
+}
@@ -4,7 +4,13 @@ use matrix_sdk::RoomState;
 use tracing::{error, info, warn};
 
 use crate::{
-    aoc::day::AocDay, context::Context, matrix::utils::message, utils::datetime::sleep_until,
+    aoc::day::AocDay,
+    context::Context,
+    matrix::{
+        quiet::{self, Proactive},
+        utils::message,
+    },
+    utils::datetime::sleep_until,
 };
 
 pub async fn start(context: Arc<Context>) -> ! {
@@ -27,6 +33,22 @@ async fn trigger(context: &Context, day: AocDay) -> anyhow::Result<()> {
         room.join().await?;
     }
 
+    match quiet::check(&context.config, room.room_id()) {
+        Proactive::Allowed => {}
+        Proactive::Defer(until) => {
+            info!(
+                ?day,
+                ?until,
+                "delaying unlock announcement because of quiet hours"
+            );
+            sleep_until(until).await;
+        }
+        Proactive::Drop => {
+            info!(?day, "dropping unlock announcement because of quiet hours");
+            return Ok(());
+        }
+    }
+
     let url = day.url();
     let AocDay { year, day } = day;
     let link_prefix = &context.config.matrix.link_prefix;
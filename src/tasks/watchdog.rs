@@ -0,0 +1,77 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use matrix_sdk::RoomState;
+use tracing::{error, warn};
+
+use crate::{
+    context::Context,
+    matrix::utils::{notice, RoomExt},
+    utils::{datetime::now, fmt::fmt_timedelta},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub async fn start(context: Arc<Context>) {
+    let config = &context.config.watchdog;
+    if config.timeout == 0 {
+        return;
+    }
+    let timeout = TimeDelta::seconds(config.timeout as _);
+
+    // only alert once per period of inactivity
+    let mut alerted = None::<DateTime<Utc>>;
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+
+        let now = now();
+        let last_activity = context.last_activity();
+        let local_time = context
+            .config
+            .local_timezone
+            .from_utc_datetime(&now.naive_utc())
+            .time();
+        if now - last_activity < timeout
+            || !config.active_hours.contains(local_time)
+            || alerted == Some(last_activity)
+        {
+            continue;
+        }
+        alerted = Some(last_activity);
+
+        let inactive = fmt_timedelta(now - last_activity).to_string();
+        warn!(%inactive, "no activity, the bot may be stuck");
+        if let Err(err) = alert(&context, &inactive).await {
+            error!("Failed to send watchdog alert: {err:#}");
+        }
+        if config.reconnect {
+            context.reconnect.notify_one();
+        }
+    }
+}
+
+async fn alert(context: &Context, inactive: &str) -> anyhow::Result<()> {
+    let Some(room_id) = &context.config.matrix.admin_room_id else {
+        return Ok(());
+    };
+    let Some(room) = context
+        .room
+        .client()
+        .get_room(room_id)
+        .filter(|room| room.state() == RoomState::Joined)
+    else {
+        warn!("not a member of admin room {room_id}");
+        return Ok(());
+    };
+
+    let reconnect = if context.config.watchdog.reconnect {
+        ", restarting the sync with the homeserver"
+    } else {
+        ""
+    };
+    room.send_throttled(notice(format!(
+        "⚠️ The bot may be stuck: no sync responses or commands were handled for {inactive}{reconnect}"
+    )))
+    .await?;
+    Ok(())
+}
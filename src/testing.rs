@@ -0,0 +1,203 @@
+//! A harness for the integration tests in `tests/`, which runs commands of the bot the way they
+//! are run for messages in its room, against a mock AoC server and a mock homeserver.
+
+use std::{future::Future, sync::Arc, thread, time::Duration};
+
+use matrix_sdk::{
+    matrix_auth::{MatrixSession, MatrixSessionTokens},
+    ruma::events::room::message::OriginalRoomMessageEvent,
+    Client, SessionMeta,
+};
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path, path_regex},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::{
+    aoc::{archive::Archive, client::AocClient, sessions::Sessions},
+    config,
+    context::{Context, ContextGarygrady},
+    i18n, mastodon,
+    matrix::commands,
+    utils::store::Store,
+};
+
+/// The stack size of the thread running a test, as commands need more than the default stack of
+/// test threads in debug builds.
+const STACK_SIZE: usize = 16 * 1024 * 1024;
+
+const BOT_USER_ID: &str = "@aocbot:matrix.example.com";
+const SENDER: &str = "@alice:matrix.example.com";
+
+pub struct Harness {
+    homeserver: MockServer,
+    context: Arc<Context>,
+}
+
+#[derive(Debug)]
+pub struct Reply {
+    pub body: String,
+    pub formatted_body: Option<String>,
+}
+
+impl Harness {
+    /// Log into the AoC server at the given url with a single session, and into a mock
+    /// homeserver on which the bot has joined the configured room. The config is the default one
+    /// and the stores are kept in memory.
+    pub async fn start(aoc_url: &str) -> anyhow::Result<Self> {
+        let homeserver = MockServer::start().await;
+        let mut config = config::load_with_defaults(config::REQUIRED.into_iter(), [].into_iter())?;
+        config.aoc.base_url = aoc_url.into();
+        config.matrix.homeserver = homeserver.uri();
+        let room_id = config.matrix.room_id.clone();
+
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/versions"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "versions": ["v1.11"] })),
+            )
+            .mount(&homeserver)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/v3/sync"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "next_batch": "s1",
+                "rooms": { "join": { room_id.as_str(): {} } },
+            })))
+            .mount(&homeserver)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/_matrix/client/v3/keys/upload"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "one_time_key_counts": {} })),
+            )
+            .mount(&homeserver)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"/state/m\.room\.encryption/?$"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+                "errcode": "M_NOT_FOUND",
+                "error": "Event not found",
+            })))
+            .mount(&homeserver)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path_regex(r"/send/[^/]+/[^/]+$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$sent" })))
+            .mount(&homeserver)
+            .await;
+
+        let client = Client::builder()
+            .homeserver_url(&config.matrix.homeserver)
+            .build()
+            .await?;
+        client
+            .matrix_auth()
+            .restore_session(MatrixSession {
+                meta: SessionMeta {
+                    user_id: BOT_USER_ID.try_into()?,
+                    device_id: "DEVICE".into(),
+                },
+                tokens: MatrixSessionTokens {
+                    access_token: "token".into(),
+                    refresh_token: None,
+                },
+            })
+            .await?;
+        client.sync_once(Default::default()).await?;
+        let room = client
+            .get_room(&room_id)
+            .ok_or_else(|| anyhow::anyhow!("Room {room_id} was not joined"))?;
+
+        let store = Store::new(client);
+        let aoc_client = AocClient::new(
+            Sessions::new(&["session".into()], &config.aoc.base_url)?,
+            Duration::from_secs(config.aoc.default_cache_ttl),
+            Default::default(),
+            Duration::from_secs(config.aoc.fresh_interval),
+            config.caches.other_leaderboards,
+            config.caches.puzzle_titles,
+            config.aoc.opt_out_marker.clone(),
+            Archive::open_in_memory(config.aoc.archive_snapshots)?,
+            store.clone(),
+        )
+        .await?;
+        let garygrady = ContextGarygrady {
+            server: "https://mastodon.example.com".parse()?,
+            user_id: mastodon::Id(1),
+        };
+        let context = Context::new(config, store, room, aoc_client, garygrady).await?;
+        Ok(Self {
+            homeserver,
+            context: Arc::new(context),
+        })
+    }
+
+    /// Run the given command, without the command prefix, as sent by a user into the room and
+    /// return the messages the bot sent in reply.
+    pub async fn command(&self, cmd: &str) -> anyhow::Result<Vec<Reply>> {
+        let room = self.context.room.clone();
+        let prefix = &self.context.config.matrix.command_prefix;
+        let event = serde_json::from_value::<OriginalRoomMessageEvent>(json!({
+            "type": "m.room.message",
+            "event_id": "$command",
+            "room_id": room.room_id(),
+            "sender": SENDER,
+            "origin_server_ts": 0,
+            "content": { "msgtype": "m.text", "body": format!("{prefix}{cmd}") },
+        }))?;
+
+        let sent_before = self.sent_messages().await.len();
+        let locale = self.context.config.matrix.locale(room.room_id());
+        // boxed, so that the futures of tests stay small
+        Box::pin(i18n::with_locale(
+            locale,
+            commands::handle(&event, room, Arc::clone(&self.context), cmd),
+        ))
+        .await?;
+        Ok(self
+            .sent_messages()
+            .await
+            .into_iter()
+            .skip(sent_before)
+            .collect())
+    }
+
+    async fn sent_messages(&self) -> Vec<Reply> {
+        let requests = self
+            .homeserver
+            .received_requests()
+            .await
+            .unwrap_or_default();
+        requests
+            .into_iter()
+            .filter(|r| r.url.path().contains("/send/m.room.message/"))
+            .filter_map(|r| serde_json::from_slice::<Value>(&r.body).ok())
+            .map(|content| Reply {
+                body: content["body"].as_str().unwrap_or_default().into(),
+                formatted_body: content["formatted_body"].as_str().map(Into::into),
+            })
+            .collect()
+    }
+}
+
+pub fn block_on<F: Future + Send>(future: F) -> F::Output
+where
+    F::Output: Send,
+{
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap()
+                    .block_on(future)
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    })
+}
@@ -47,7 +47,12 @@ pub async fn sleep_until(datetime: DateTime<Utc>) {
 
 pub trait DateTimeExt {
     fn format_ymd_hms_z(self) -> impl std::fmt::Display;
-    fn format_ymd_hms(self) -> impl std::fmt::Display;
+    /// Format in the given `zone`, including the offset only if it differs from `default_zone`.
+    fn format_ymd_hms_in(
+        self,
+        zone: FixedOffset,
+        default_zone: FixedOffset,
+    ) -> impl std::fmt::Display;
 }
 
 impl<Tz> DateTimeExt for DateTime<Tz>
@@ -59,7 +64,40 @@ where
         self.format("%Y-%m-%d %H:%M:%S %:z")
     }
 
-    fn format_ymd_hms(self) -> impl std::fmt::Display {
-        self.format("%Y-%m-%d %H:%M:%S")
+    fn format_ymd_hms_in(
+        self,
+        zone: FixedOffset,
+        default_zone: FixedOffset,
+    ) -> impl std::fmt::Display {
+        let fmt = if zone == default_zone {
+            "%Y-%m-%d %H:%M:%S"
+        } else {
+            "%Y-%m-%d %H:%M:%S %:z"
+        };
+        zone.from_utc_datetime(&self.naive_utc()).format(fmt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_ymd_hms_in() {
+        let dt = "2024-12-01T05:13:37Z".parse::<DateTime<Utc>>().unwrap();
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let cet = FixedOffset::east_opt(3600).unwrap();
+        assert_eq!(
+            dt.format_ymd_hms_in(utc, utc).to_string(),
+            "2024-12-01 05:13:37"
+        );
+        assert_eq!(
+            dt.format_ymd_hms_in(cet, cet).to_string(),
+            "2024-12-01 06:13:37"
+        );
+        assert_eq!(
+            dt.format_ymd_hms_in(EST, cet).to_string(),
+            "2024-12-01 00:13:37 -05:00"
+        );
     }
 }
@@ -1,4 +1,7 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter},
+};
 
 use chrono::TimeDelta;
 
@@ -39,6 +42,66 @@ pub fn fmt_timedelta(td: TimeDelta) -> impl Display {
     })
 }
 
+/// A badge showing the age of cached data. Data fetched within the last minute is just fresh.
+pub fn fmt_staleness(age: TimeDelta, fresh: TimeDelta, stale: TimeDelta) -> impl Display {
+    DisplayWith(move |f| {
+        if age < fresh.min(TimeDelta::minutes(1)) {
+            write!(f, "🟢 fresh")
+        } else if age < fresh {
+            write!(f, "🟢 fresh, {} old", fmt_timedelta_coarse(age))
+        } else if age < stale {
+            write!(f, "🟡 {} old", fmt_timedelta_coarse(age))
+        } else {
+            write!(f, "🔴 {}+ stale", fmt_timedelta_coarse(stale))
+        }
+    })
+}
+
+/// Escape the characters with a special meaning in html text and quoted attribute values.
+/// Strings controlled by members (e.g. names and repository urls) have to be escaped before they
+/// are interpolated into html.
+pub fn escape_html(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"']) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;"),
+    )
+}
+
+/// Shorten a text to at most `max_length` characters, ending with an ellipsis. The host of urls is
+/// always kept, even if it is longer.
+pub fn truncate_url(text: &str, max_length: usize) -> Cow<'_, str> {
+    if text.chars().count() <= max_length {
+        return Cow::Borrowed(text);
+    }
+    let host_end = text.find("://").map_or(0, |i| {
+        let start = i + 3;
+        text[start..].find('/').map_or(text.len(), |j| start + j)
+    });
+    let keep = max_length
+        .saturating_sub(1)
+        .max(text[..host_end].chars().count());
+    Cow::Owned(text.chars().take(keep).chain(['…']).collect())
+}
+
+fn fmt_timedelta_coarse(td: TimeDelta) -> impl Display {
+    DisplayWith(move |f| {
+        if td.num_days() >= 1 {
+            write!(f, "{}d", td.num_days())
+        } else if td.num_hours() >= 1 {
+            write!(f, "{}h", td.num_hours())
+        } else if td.num_minutes() >= 1 {
+            write!(f, "{}m", td.num_minutes())
+        } else {
+            write!(f, "{}s", td.num_seconds())
+        }
+    })
+}
+
 struct DisplayWith<F>(F)
 where
     F: Fn(&mut Formatter) -> std::fmt::Result;
@@ -51,3 +114,47 @@ where
         (self.0)(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staleness() {
+        let fmt = |secs| {
+            fmt_staleness(
+                TimeDelta::seconds(secs),
+                TimeDelta::minutes(5),
+                TimeDelta::hours(1),
+            )
+            .to_string()
+        };
+        assert_eq!(fmt(0), "🟢 fresh");
+        assert_eq!(fmt(59), "🟢 fresh");
+        assert_eq!(fmt(60), "🟢 fresh, 1m old");
+        assert_eq!(fmt(299), "🟢 fresh, 4m old");
+        assert_eq!(fmt(300), "🟡 5m old");
+        assert_eq!(fmt(15 * 60 + 42), "🟡 15m old");
+        assert_eq!(fmt(3599), "🟡 59m old");
+        assert_eq!(fmt(3600), "🔴 1h+ stale");
+        assert_eq!(fmt(5 * 86400), "🔴 1h+ stale");
+    }
+
+    #[test]
+    fn truncate_url() {
+        assert_eq!(super::truncate_url("alice/aoc", 10), "alice/aoc");
+        assert_eq!(
+            super::truncate_url("https://example.com/alice/aoc", 24),
+            "https://example.com/ali…"
+        );
+        // the host is kept
+        assert_eq!(
+            super::truncate_url("https://git.example.com/alice/aoc", 10),
+            "https://git.example.com…"
+        );
+        assert_eq!(
+            super::truncate_url("gitlab:group/sub/aoc", 10),
+            "gitlab:gr…"
+        );
+    }
+}
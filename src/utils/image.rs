@@ -0,0 +1,256 @@
+//! Raster rendering of text tables, used for posting leaderboards as images.
+
+use std::{io::Cursor, sync::LazyLock};
+
+use ab_glyph::{FontRef, PxScale};
+use image::{ImageFormat, Rgb, RgbImage};
+use imageproc::{
+    drawing::{draw_filled_rect_mut, draw_text_mut, text_size},
+    rect::Rect,
+};
+
+use crate::i18n::Message;
+
+/// DejaVu Sans, which covers most scripts used in names, see `assets/fonts/LICENSE`.
+static REGULAR: LazyLock<FontRef<'static>> = LazyLock::new(|| {
+    FontRef::try_from_slice(include_bytes!("../../assets/fonts/DejaVuSans.ttf")).unwrap()
+});
+static BOLD: LazyLock<FontRef<'static>> = LazyLock::new(|| {
+    FontRef::try_from_slice(include_bytes!("../../assets/fonts/DejaVuSans-Bold.ttf")).unwrap()
+});
+
+const BACKGROUND: Rgb<u8> = Rgb([0x0f, 0x0f, 0x23]);
+const TEXT: Rgb<u8> = Rgb([0xcc, 0xcc, 0xcc]);
+const HEADER: Rgb<u8> = Rgb([0x00, 0xcc, 0x00]);
+const HIGHLIGHT: Rgb<u8> = Rgb([0xff, 0xff, 0x66]);
+const GRID: Rgb<u8> = Rgb([0x33, 0x33, 0x40]);
+
+const FONT_SIZE: f32 = 18.0;
+const LINE_HEIGHT: u32 = 28;
+const GRID_WIDTH: u32 = 2;
+const CELL_PADDING: u32 = 12;
+const MARGIN: u32 = 16;
+
+#[derive(Debug, Default)]
+pub struct TextTable {
+    pub title: String,
+    pub header: Vec<String>,
+    pub rows: Vec<(bool, Vec<String>)>,
+    pub footer: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Png {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TextTable {
+    /// Keep only the given columns, in the given order. Columns are named by their lowercase
+    /// header with spaces replaced by underscores, e.g. `local_score`.
+    pub fn select_columns(&mut self, columns: &[&str]) -> Result<(), String> {
+        let names = self
+            .header
+            .iter()
+            .map(|h| h.to_lowercase().replace(' ', "_"))
+            .collect::<Vec<_>>();
+        let indices = columns
+            .iter()
+            .map(|c| names.iter().position(|n| n.eq_ignore_ascii_case(c)))
+            .collect::<Option<Vec<_>>>()
+            .filter(|indices| !indices.is_empty())
+            .ok_or_else(|| {
+                String::from(Message::InvalidArgumentExpected {
+                    name: "columns",
+                    expected: &names.join(", "),
+                })
+            })?;
+
+        let select = |cells: &[String]| indices.iter().map(|&i| cells[i].clone()).collect();
+        self.header = select(&self.header);
+        for (_, cells) in &mut self.rows {
+            *cells = select(cells);
+        }
+        Ok(())
+    }
+
+    /// The header and rows as tab separated values. Tabs and line breaks within cells are
+    /// replaced with spaces, so that every row stays on a single line.
+    pub fn to_tsv(&self) -> String {
+        let line = |cells: &[String]| {
+            cells
+                .iter()
+                .map(|c| c.replace(['\t', '\n', '\r'], " "))
+                .collect::<Vec<_>>()
+                .join("\t")
+        };
+        let mut out = line(&self.header);
+        for (_, cells) in &self.rows {
+            out.push('\n');
+            out.push_str(&line(cells));
+        }
+        out
+    }
+
+    pub fn to_png(&self) -> Png {
+        let rows = [(true, &self.header)]
+            .into_iter()
+            .chain(self.rows.iter().map(|(highlight, row)| (*highlight, row)))
+            .collect::<Vec<_>>();
+        let columns = rows.iter().map(|(_, row)| row.len()).max().unwrap_or(0);
+        let widths = (0..columns)
+            .map(|i| {
+                rows.iter()
+                    .filter_map(|(bold, row)| Some(text_width(row.get(i)?, *bold)))
+                    .max()
+                    .unwrap_or(0)
+                    + 2 * CELL_PADDING
+            })
+            .collect::<Vec<_>>();
+
+        let table_width = widths.iter().sum::<u32>();
+        let footer_width = self.footer.iter().map(|l| text_width(l, false)).max();
+        let width = 2 * MARGIN
+            + table_width
+                .max(text_width(&self.title, true))
+                .max(footer_width.unwrap_or(0));
+        let lines = rows.len() + 1 + self.footer.len(); // title, header, rows and footer
+        let height = 2 * MARGIN + lines as u32 * LINE_HEIGHT;
+
+        let mut canvas = RgbImage::from_pixel(width, height, BACKGROUND);
+
+        let mut y = MARGIN;
+        draw_line(&mut canvas, MARGIN, y, &self.title, HIGHLIGHT, true);
+        y += LINE_HEIGHT;
+
+        for (i, (highlight, row)) in rows.into_iter().enumerate() {
+            let color = match (i, highlight) {
+                (0, _) => HEADER,
+                (_, true) => HIGHLIGHT,
+                (_, false) => TEXT,
+            };
+            let grid = Rect::at(MARGIN as _, (y + LINE_HEIGHT - GRID_WIDTH) as _)
+                .of_size(table_width.max(1), GRID_WIDTH);
+            draw_filled_rect_mut(&mut canvas, grid, GRID);
+            let mut x = MARGIN;
+            for (cell, width) in row.iter().zip(&widths) {
+                draw_line(&mut canvas, x + CELL_PADDING, y, cell, color, highlight);
+                x += width;
+            }
+            y += LINE_HEIGHT;
+        }
+
+        for line in &self.footer {
+            draw_line(&mut canvas, MARGIN, y, line, TEXT, false);
+            y += LINE_HEIGHT;
+        }
+
+        let mut data = Vec::new();
+        canvas
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .expect("encoding a png in memory cannot fail");
+        Png {
+            data,
+            width,
+            height,
+        }
+    }
+}
+
+fn font(bold: bool) -> &'static FontRef<'static> {
+    if bold {
+        &BOLD
+    } else {
+        &REGULAR
+    }
+}
+
+/// Emoji are not part of the font, the ones used by default are replaced by similar text.
+fn printable(text: &str) -> String {
+    text.replace('⭐', "★").replace('🆕', "NEW")
+}
+
+fn text_width(text: &str, bold: bool) -> u32 {
+    text_size(PxScale::from(FONT_SIZE), font(bold), &printable(text)).0
+}
+
+fn draw_line(canvas: &mut RgbImage, x: u32, y: u32, text: &str, color: Rgb<u8>, bold: bool) {
+    let y = y + (LINE_HEIGHT - FONT_SIZE as u32) / 2;
+    draw_text_mut(
+        canvas,
+        color,
+        x as _,
+        y as _,
+        PxScale::from(FONT_SIZE),
+        font(bold),
+        &printable(text),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use ab_glyph::Font;
+
+    use super::*;
+
+    #[test]
+    fn render_table() {
+        let table = TextTable {
+            title: "Title".into(),
+            header: vec!["A".into(), "Long header".into()],
+            rows: vec![
+                (true, vec!["1".into(), "x".into()]),
+                (false, vec!["2".into(), "Zoë Ølsen ⭐".into()]),
+            ],
+            footer: vec!["Footer".into()],
+        };
+        let png = table.to_png();
+
+        assert_eq!(&png.data[..8], b"\x89PNG\r\n\x1a\n");
+        let decoded = image::load_from_memory_with_format(&png.data, ImageFormat::Png)
+            .unwrap()
+            .to_rgb8();
+        assert_eq!(decoded.dimensions(), (png.width, png.height));
+        let names = text_width("Long header", true).max(text_width("Zoë Ølsen ⭐", false));
+        assert_eq!(
+            png.width,
+            2 * MARGIN + 4 * CELL_PADDING + text_width("A", true) + names
+        );
+        assert_eq!(png.height, 2 * MARGIN + 5 * LINE_HEIGHT);
+
+        // non-ascii names and the star strips of the year command are drawn with their glyphs,
+        // not as the glyph of a missing character
+        let glyph = |c| REGULAR.glyph_id(c).0;
+        assert!(['ë', 'Ø', '★', '☆', '○', '·']
+            .into_iter()
+            .all(|c| glyph(c) != 0));
+        assert!(decoded.pixels().any(|&p| p == HIGHLIGHT));
+    }
+
+    #[test]
+    fn tsv() {
+        let mut table = TextTable {
+            header: vec!["Rank".into(), "AoC Name".into(), "Stars".into()],
+            rows: vec![
+                (false, vec!["1".into(), "Tab\tbed".into(), "10".into()]),
+                (false, vec!["2".into(), "Multi\r\nline".into(), "8".into()]),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            table.to_tsv(),
+            "Rank\tAoC Name\tStars\n1\tTab bed\t10\n2\tMulti  line\t8"
+        );
+
+        table.select_columns(&["stars", "AOC_NAME"]).unwrap();
+        assert_eq!(
+            table.to_tsv(),
+            "Stars\tAoC Name\n10\tTab bed\n8\tMulti  line"
+        );
+        assert_eq!(
+            table.select_columns(&["rank"]),
+            Err("Failed to parse argument 'columns' (expected stars, aoc_name)".into())
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::{borrow::Borrow, fmt, hash::Hash, num::NonZeroUsize, sync::Mutex};
+
+use ::lru::LruCache;
+use tracing::debug;
+
+/// A map holding at most `capacity` entries, evicting the least recently used entry when it is
+/// full. The entries are behind a lock which is never held across an await point, so the cache
+/// can be shared without an async lock.
+#[derive(Debug)]
+pub struct Lru<K: Hash + Eq, V> {
+    name: &'static str,
+    capacity: usize,
+    inner: Mutex<Inner<K, V>>,
+}
+
+#[derive(Debug)]
+struct Inner<K: Hash + Eq, V> {
+    /// `None` if the capacity is zero, which disables the cache.
+    entries: Option<LruCache<K, V>>,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl<K: Hash + Eq, V: Clone> Lru<K, V> {
+    pub fn new(name: &'static str, capacity: usize) -> Self {
+        Self {
+            name,
+            capacity,
+            inner: Mutex::new(Inner {
+                entries: NonZeroUsize::new(capacity).map(LruCache::new),
+                hits: 0,
+                misses: 0,
+                evictions: 0,
+            }),
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        let value = inner.entries.as_mut().and_then(|e| e.get(key).cloned());
+        match value {
+            Some(_) => inner.hits += 1,
+            None => inner.misses += 1,
+        }
+        value
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(entries) = inner.entries.as_mut() else {
+            return;
+        };
+        let evicts = !entries.contains(&key) && entries.len() == entries.cap().get();
+        entries.put(key, value);
+        if evicts {
+            inner.evictions += 1;
+            debug!(
+                cache = self.name,
+                evictions = inner.evictions,
+                "evicted least recently used entry"
+            );
+        }
+    }
+
+    /// All entries, least recently used first, without marking them as used.
+    pub fn entries(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+    {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .entries
+            .iter()
+            .flat_map(|e| e.iter().rev())
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    pub fn retain(&self, mut f: impl FnMut(&K, &V) -> bool) -> usize
+    where
+        K: Clone,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(entries) = inner.entries.as_mut() else {
+            return 0;
+        };
+        let removed = entries
+            .iter()
+            .filter(|(k, v)| !f(k, v))
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+        for key in &removed {
+            entries.pop(key);
+        }
+        removed.len()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock().unwrap();
+        CacheStats {
+            name: self.name,
+            len: inner.entries.as_ref().map_or(0, LruCache::len),
+            capacity: self.capacity,
+            hits: inner.hits,
+            misses: inner.misses,
+            evictions: inner.evictions,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub name: &'static str,
+    pub len: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}/{} ({} hits, {} misses, {} evicted)",
+            self.name, self.len, self.capacity, self.hits, self.misses, self.evictions
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eviction() {
+        let lru = Lru::new("test", 2);
+        lru.insert(1, "a");
+        lru.insert(2, "b");
+        // 1 is used more recently than 2 now
+        assert_eq!(lru.get(&1), Some("a"));
+        lru.insert(3, "c");
+        assert_eq!(lru.get(&2), None);
+        assert_eq!(lru.get(&1), Some("a"));
+        assert_eq!(lru.get(&3), Some("c"));
+        assert_eq!(lru.entries(), [(1, "a"), (3, "c")]);
+        assert_eq!(
+            lru.stats().to_string(),
+            "test 2/2 (3 hits, 1 misses, 1 evicted)"
+        );
+
+        // replacing an entry does not evict others
+        lru.insert(3, "d");
+        assert_eq!(lru.get(&3), Some("d"));
+        assert_eq!(lru.get(&1), Some("a"));
+        assert_eq!(lru.stats().evictions, 1);
+
+        // removed entries are not counted as evicted
+        assert_eq!(lru.retain(|&k, _| k != 1), 1);
+        assert_eq!(lru.entries(), [(3, "d")]);
+        assert_eq!(lru.stats().evictions, 1);
+
+        let disabled = Lru::new("test", 0);
+        disabled.insert(1, "a");
+        assert_eq!(disabled.get(&1), None);
+        assert_eq!(disabled.stats().len, 0);
+    }
+}
@@ -1,5 +1,12 @@
 pub mod datetime;
 pub mod fmt;
+pub mod image;
+pub mod lru;
 pub mod regex_set_replacer;
 pub mod serde;
+pub mod single_flight;
+#[cfg(test)]
+pub mod snapshot;
+pub mod stats;
 pub mod store;
+pub mod suggest;
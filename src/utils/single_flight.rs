@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::OnceCell;
+
+/// Coalesces concurrent calls with the same key, so that only one of them does the work and the
+/// others share its result.
+pub struct SingleFlight<K, T> {
+    in_flight: Mutex<HashMap<K, Arc<OnceCell<T>>>>,
+}
+
+impl<K: Eq + Hash + Clone, T: Clone> SingleFlight<K, T> {
+    pub fn new() -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Run `f`, unless a call with the same key is already in flight, in which case its result is
+    /// awaited instead. Errors are not shared, the next waiting caller runs `f` itself.
+    pub async fn run<F, Fut, E>(&self, key: K, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let cell = Arc::clone(
+            self.in_flight
+                .lock()
+                .unwrap()
+                .entry(key.clone())
+                .or_default(),
+        );
+        let result = cell.get_or_try_init(f).await.cloned();
+
+        // later calls start a new flight
+        let mut guard = self.in_flight.lock().unwrap();
+        if guard.get(&key).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+            guard.remove(&key);
+        }
+        result
+    }
+}
+
+impl<K: Eq + Hash + Clone, T: Clone> Default for SingleFlight<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn coalesce() {
+        let flight = Arc::new(SingleFlight::<i32, String>::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let tasks = (0..10)
+            .map(|_| {
+                let (flight, calls) = (Arc::clone(&flight), Arc::clone(&calls));
+                tokio::spawn(async move {
+                    flight
+                        .run(2024, || async {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            Ok::<_, ()>("leaderboard".to_string())
+                        })
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), Ok("leaderboard".into()));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // the flight is over, so the next call runs again
+        let result = flight.run(2024, || async { Ok::<_, ()>("new".into()) });
+        assert_eq!(result.await, Ok("new".into()));
+        assert!(flight.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let flight = SingleFlight::<i32, u32>::new();
+        assert_eq!(
+            flight.run(2024, || async { Err("failed") }).await,
+            Err("failed")
+        );
+        assert_eq!(flight.run(2024, || async { Ok::<_, ()>(1) }).await, Ok(1));
+    }
+}
@@ -0,0 +1,26 @@
+//! Minimal snapshot testing. Set `UPDATE_SNAPSHOTS=1` to (re)write the expected output.
+
+use std::path::Path;
+
+#[track_caller]
+pub fn assert_snapshot(path: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "Failed to read snapshot {} ({err}), run with UPDATE_SNAPSHOTS=1 to create it",
+            path.display()
+        )
+    });
+    assert_eq!(
+        actual,
+        expected,
+        "snapshot {} does not match, run with UPDATE_SNAPSHOTS=1 to update it",
+        path.display()
+    );
+}
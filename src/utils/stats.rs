@@ -0,0 +1,32 @@
+use chrono::TimeDelta;
+
+pub fn mean(values: &[TimeDelta]) -> Option<TimeDelta> {
+    let count = i32::try_from(values.len()).ok().filter(|&n| n > 0)?;
+    Some(values.iter().sum::<TimeDelta>() / count)
+}
+
+pub fn median(values: &mut [TimeDelta]) -> Option<TimeDelta> {
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    match values.len() {
+        0 => None,
+        n if n % 2 == 0 => Some((values[mid - 1] + values[mid]) / 2),
+        _ => Some(values[mid]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_and_median() {
+        let mut values = [30, 10, 20, 100].map(TimeDelta::seconds);
+        assert_eq!(mean(&values), Some(TimeDelta::seconds(40)));
+        assert_eq!(median(&mut values), Some(TimeDelta::seconds(25)));
+        assert_eq!(median(&mut values[..3]), Some(TimeDelta::seconds(20)));
+
+        assert_eq!(mean(&[]), None);
+        assert_eq!(median(&mut []), None);
+    }
+}
@@ -0,0 +1,128 @@
+//! Run the `leaderboard` command against a mock AoC server, covering the http layer, the parsing
+//! of the responses and the replies users get when the server misbehaves.
+
+use aocbot::testing::{block_on, Harness, Reply};
+use wiremock::{
+    matchers::{header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const LEADERBOARD_2024: &str = include_str!("fixtures/leaderboard_2024.json");
+
+/// Start a server which identifies the session as the owner of private leaderboard 1 and answers
+/// the given number of requests of its 2024 leaderboard with the given response.
+async fn server(leaderboard: ResponseTemplate, requests: u64) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/leaderboard/private"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string(
+                "<p>Others can join it using the code <code>1-42ff1337</code>.</p>",
+            ),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/2024/leaderboard/private/view/1.json"))
+        .and(header("cookie", "session=session"))
+        .respond_with(leaderboard)
+        .expect(requests)
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Run `leaderboard 2024` against the given response and return the replies of the bot.
+fn leaderboard(response: ResponseTemplate, requests: u64) -> Vec<Reply> {
+    block_on(async {
+        let server = server(response, requests).await;
+        let harness = Harness::start(&server.uri()).await.unwrap();
+        harness.command("leaderboard 2024").await.unwrap()
+    })
+}
+
+/// The text of the only reply, which is an error.
+fn error(response: ResponseTemplate, requests: u64) -> String {
+    match leaderboard(response, requests).as_slice() {
+        [reply] => reply.body.clone(),
+        replies => panic!("expected a single reply: {replies:?}"),
+    }
+}
+
+#[test]
+fn render() {
+    let replies = leaderboard(
+        ResponseTemplate::new(200).set_body_string(LEADERBOARD_2024),
+        1,
+    );
+    let [reply] = replies.as_slice() else {
+        panic!("expected a single reply: {replies:?}");
+    };
+    let html = reply.formatted_body.as_deref().unwrap();
+
+    assert!(html.contains("<h3>Private Leaderboard (Advent of Code 2024)</h3>"));
+    // Bob's star count is corrected to his completed days
+    let rows = html
+        .split("<tr>")
+        .skip(2)
+        .map(|row| {
+            row.lines()
+                .filter_map(|l| l.trim().strip_prefix("<td><b>")?.strip_suffix("</b></td>"))
+                .take(5)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rows,
+        [
+            [
+                "🥇 1st",
+                "8",
+                "0",
+                "3",
+                r#"<span title="AoC #1">Alice</span>"#
+            ],
+            [
+                "🥈 2nd",
+                "4",
+                "0",
+                "2",
+                r#"<span title="AoC #3">[anonymous user #3]</span>"#
+            ],
+            [
+                "🥉 3rd",
+                "3",
+                "0",
+                "1",
+                r#"<span title="AoC #2">Bob</span>"#
+            ],
+        ]
+    );
+}
+
+#[test]
+fn unauthorized() {
+    assert_eq!(
+        error(ResponseTemplate::new(401), 1),
+        "❌ Error: Failed to fetch private leaderboard for 2024 (401 Unauthorized)"
+    );
+}
+
+#[test]
+fn server_error() {
+    // retried once before the error is reported
+    assert_eq!(
+        error(ResponseTemplate::new(500), 2),
+        "❌ Error: Failed to fetch private leaderboard for 2024 (500 Internal Server Error)"
+    );
+}
+
+#[test]
+fn malformed() {
+    // AoC answers requests with an expired session with an html page
+    let response = ResponseTemplate::new(200).set_body_string("<!DOCTYPE html>");
+    assert_eq!(
+        error(response, 1),
+        "❌ Error: Failed to read private leaderboard for 2024 (the AoC session may have expired)"
+    );
+}